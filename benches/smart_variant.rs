@@ -0,0 +1,56 @@
+//! Benchmarks for passing a large `SmartVariant::Text` argument.
+//!
+//! Run with `cargo bench --bench smart_variant`.
+
+#![cfg_attr(not(windows), allow(unused))]
+
+use std::convert::TryFrom;
+use std::hint::black_box;
+use std::time::Instant;
+
+#[cfg(windows)]
+use rusty_winapi::smart_variant::{AutoVariant, SmartVariant};
+
+const ITERATIONS: u32 = 100;
+
+fn measure<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+
+    println!("{:<40} {:>12?} per iteration", name, elapsed / ITERATIONS);
+}
+
+#[cfg(not(windows))]
+fn main() {
+    println!("benchmarks need Windows");
+}
+
+#[cfg(windows)]
+fn main() {
+    // ~4 MiB of SQL-like text.
+    let text: String =
+        "SELECT Field1, Field2 FROM Catalog.Goods WHERE Code = &Code;\n".repeat(70_000);
+    println!("text argument size: {} bytes", text.len());
+
+    let param = SmartVariant::Text(text.as_str().into());
+
+    measure("SmartVariant::clone (Text)", || {
+        black_box(param.clone());
+    });
+
+    measure("String::clone (baseline)", || {
+        black_box(text.clone());
+    });
+
+    measure("SmartVariant -> AutoVariant (BSTR)", || {
+        black_box(AutoVariant::try_from(param.clone()).unwrap());
+    });
+
+    let params = vec![param.clone(); 8];
+    measure("clone 8 params (invoke argument prep)", || {
+        black_box(params.to_vec());
+    });
+}
@@ -0,0 +1,196 @@
+//! `#[com_client]` expansion.
+
+use proc_macro::{Delimiter, Group, Span, TokenStream, TokenTree};
+
+use crate::{is_ident, is_punct, pascal_case, split_commas, split_items, string_literal, Error};
+
+/// Trait method invoked by the generated implementation.
+struct Method {
+    /// Tokens from `fn` to the return type.
+    signature: Vec<TokenTree>,
+    member: String,
+    flags: &'static str,
+    params: Vec<String>,
+}
+
+pub fn expand(item: TokenStream) -> Result<TokenStream, Error> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let position = tokens
+        .iter()
+        .position(|x| is_ident(x, "trait"))
+        .ok_or_else(|| {
+            (
+                Span::call_site(),
+                String::from("#[com_client] applies to traits"),
+            )
+        })?;
+    let name = match tokens.get(position + 1) {
+        Some(TokenTree::Ident(x)) => x.to_string(),
+        _ => return Err((Span::call_site(), String::from("expected trait name"))),
+    };
+    if let Some(x) = tokens.get(position + 2).filter(|x| is_punct(x, '<')) {
+        return Err((
+            x.span(),
+            String::from("#[com_client] traits can't be generic"),
+        ));
+    }
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Brace => x,
+        _ => return Err((Span::call_site(), String::from("expected trait body"))),
+    };
+
+    let mut items = TokenStream::new();
+    let mut methods = Vec::new();
+    for (item, attrs) in split_items(body.stream(), &["com"]) {
+        if let Some(method) = parse_method(&item, &attrs)? {
+            methods.push(method);
+        }
+        items.extend(item);
+    }
+
+    let mut result: TokenStream = tokens.into_iter().collect();
+    let mut body = Group::new(Delimiter::Brace, items);
+    body.set_span(Span::call_site());
+    result.extend(vec![TokenTree::from(body)]);
+
+    let mut generated = format!(
+        "impl<D: ::rusty_winapi::smart_idispatch::SmartIDispatch + ?Sized> {} for D {{",
+        name
+    );
+    for method in methods {
+        let signature: TokenStream = method.signature.into_iter().collect();
+        generated += &format!(
+            "{} {{ ::rusty_winapi::com_client::invoke(self, {:?}, ::rusty_winapi::com_client::{}, &[{}]) }}",
+            signature,
+            method.member,
+            method.flags,
+            method
+                .params
+                .iter()
+                .map(|x| format!("::rusty_winapi::smart_variant::SmartVariant::from({})", x))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    generated += "}";
+    result.extend(
+        generated
+            .parse::<TokenStream>()
+            .map_err(|e| (Span::call_site(), e.to_string()))?,
+    );
+
+    Ok(result)
+}
+
+/// Parses trait item, `None` if it's a method with a default body.
+fn parse_method(item: &[TokenTree], attrs: &[Group]) -> Result<Option<Method>, Error> {
+    let span = item.first().map_or_else(Span::call_site, TokenTree::span);
+    let start = match item.iter().position(|x| is_ident(x, "fn")) {
+        Some(x) => x,
+        None => {
+            return Err((
+                span,
+                String::from("#[com_client] traits can contain only methods"),
+            ))
+        }
+    };
+    if !item.last().is_some_and(|x| is_punct(x, ';')) {
+        return match attrs.first() {
+            Some(x) => Err((
+                x.span(),
+                String::from("#[com] applies to methods without body"),
+            )),
+            None => Ok(None),
+        };
+    }
+
+    let name = match item.get(start + 1) {
+        Some(TokenTree::Ident(x)) => x.to_string(),
+        _ => return Err((span, String::from("expected method name"))),
+    };
+    let params = match item.get(start + 2) {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Parenthesis => x,
+        Some(x) if is_punct(x, '<') => {
+            return Err((
+                x.span(),
+                String::from("#[com_client] methods can't be generic"),
+            ))
+        }
+        _ => return Err((span, String::from("expected method parameters"))),
+    };
+    if !item.get(start + 3).is_some_and(|x| is_punct(x, '-')) {
+        return Err((
+            params.span(),
+            String::from("#[com_client] methods must return ComResult<_>"),
+        ));
+    }
+
+    let mut params = split_commas(params.stream()).into_iter();
+    match params.next() {
+        Some(ref x)
+            if x.len() == 3
+                && is_punct(&x[0], '&')
+                && is_ident(&x[1], "mut")
+                && is_ident(&x[2], "self") => {}
+        _ => return Err((span, String::from("#[com_client] methods take `&mut self`"))),
+    }
+    let params = params
+        .map(|x| match (x.first(), x.get(1)) {
+            (Some(TokenTree::Ident(name)), Some(colon)) if is_punct(colon, ':') => {
+                Ok(name.to_string())
+            }
+            _ => Err((
+                x.first().map_or(span, TokenTree::span),
+                String::from("expected `name: Type` parameter"),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut member = pascal_case(&name);
+    let mut flags = "DISPATCH_METHOD";
+    for attr in attrs {
+        let args = match attr.stream().into_iter().nth(1) {
+            Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Parenthesis => x,
+            _ => return Err((attr.span(), String::from("expected #[com(...)]"))),
+        };
+        for arg in split_commas(args.stream()) {
+            match arg.as_slice() {
+                [TokenTree::Ident(key), eq, TokenTree::Literal(value)]
+                    if key.to_string() == "name" && is_punct(eq, '=') =>
+                {
+                    member = string_literal(value).ok_or_else(|| {
+                        (value.span(), String::from("expected member name string"))
+                    })?;
+                }
+                [TokenTree::Ident(kind)] => {
+                    flags = match kind.to_string().as_str() {
+                        "method" => "DISPATCH_METHOD",
+                        "get" => "DISPATCH_PROPERTYGET",
+                        "put" => "DISPATCH_PROPERTYPUT",
+                        "put_ref" => "DISPATCH_PROPERTYPUTREF",
+                        _ => {
+                            return Err((
+                                kind.span(),
+                                String::from("expected method, get, put or put_ref"),
+                            ))
+                        }
+                    }
+                }
+                _ => {
+                    let span = arg.first().map_or(args.span(), TokenTree::span);
+                    return Err((
+                        span,
+                        String::from("expected `name = \"...\"`, method, get, put or put_ref"),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(Some(Method {
+        signature: item[start..item.len() - 1].to_vec(),
+        member,
+        flags,
+        params,
+    }))
+}
@@ -0,0 +1,210 @@
+//! `#[dispatch_server]` expansion.
+
+use proc_macro::{Delimiter, Group, Span, TokenStream, TokenTree};
+
+use crate::{is_ident, is_punct, pascal_case, split_commas, split_items, string_literal, Error};
+
+/// Member method of the impl block.
+struct Member {
+    method: String,
+    name: String,
+    /// `DISPATCH_*` flag of the member kind.
+    flag: &'static str,
+    /// Types of parameters.
+    params: Vec<String>,
+}
+
+pub fn expand(item: TokenStream) -> Result<TokenStream, Error> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let position = tokens
+        .iter()
+        .position(|x| is_ident(x, "impl"))
+        .ok_or_else(|| {
+            (
+                Span::call_site(),
+                String::from("#[dispatch_server] applies to impl blocks"),
+            )
+        })?;
+    if let Some(x) = tokens.get(position + 1).filter(|x| is_punct(x, '<')) {
+        return Err((
+            x.span(),
+            String::from("#[dispatch_server] impl blocks can't be generic"),
+        ));
+    }
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Brace => x,
+        _ => return Err((Span::call_site(), String::from("expected impl body"))),
+    };
+    let self_type: TokenStream = tokens[position + 1..].iter().cloned().collect();
+    if tokens[position + 1..].iter().any(|x| is_ident(x, "for")) {
+        return Err((
+            Span::call_site(),
+            String::from("#[dispatch_server] applies to inherent impl blocks"),
+        ));
+    }
+
+    let mut items = TokenStream::new();
+    let mut members = Vec::new();
+    for (item, attrs) in split_items(body.stream(), &["method", "get", "put"]) {
+        if let Some(attr) = attrs.first() {
+            members.push(parse_member(&item, attr)?);
+        }
+        items.extend(item);
+    }
+
+    let mut result: TokenStream = tokens.into_iter().collect();
+    let mut body = Group::new(Delimiter::Brace, items);
+    body.set_span(Span::call_site());
+    result.extend(vec![TokenTree::from(body)]);
+
+    // DISPIDs from 1 in order of declaration, property get and put of the same name share DISPID.
+    let mut names: Vec<String> = Vec::new();
+    for member in &members {
+        if !names.iter().any(|x| x.eq_ignore_ascii_case(&member.name)) {
+            names.push(member.name.clone());
+        }
+    }
+
+    let mut generated = format!(
+        "impl ::rusty_winapi::server::dispatch::DispatchServer for {} {{
+            fn get_ids_of_names(
+                &self,
+                names: &[::std::string::String],
+            ) -> ::std::vec::Vec<::std::option::Option<::winapi::um::oaidl::DISPID>> {{
+                const MEMBERS: &[&str] = &{:?};
+                names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| match i {{
+                        0 => ::rusty_winapi::server::dispatch::member_dispid(MEMBERS, x),
+                        _ => ::std::option::Option::None,
+                    }})
+                    .collect()
+            }}
+
+            #[allow(unused_variables, unused_mut)]
+            fn invoke(
+                &self,
+                dispid: ::winapi::um::oaidl::DISPID,
+                flags: ::winapi::shared::minwindef::WORD,
+                args: ::std::vec::Vec<::rusty_winapi::smart_variant::SmartVariant>,
+            ) -> ::std::result::Result<
+                ::rusty_winapi::smart_variant::SmartVariant,
+                ::rusty_winapi::error::DispatchError,
+            > {{",
+        self_type, names
+    );
+    for member in members {
+        let dispid = names
+            .iter()
+            .position(|x| x.eq_ignore_ascii_case(&member.name))
+            .unwrap()
+            + 1;
+        let args = (0..member.params.len())
+            .map(|i| format!("arg{}", i))
+            .collect::<Vec<_>>();
+        let conversions: String = args
+            .iter()
+            .zip(&member.params)
+            .enumerate()
+            .map(|(index, (arg, ty))| {
+                format!(
+                    "let {}: {} = ::std::convert::TryFrom::try_from(args.next().unwrap())
+                        .map_err(|_| ::rusty_winapi::error::DispatchError::type_mismatch({}))?;",
+                    arg, ty, index
+                )
+            })
+            .collect();
+        generated += &format!(
+            "if dispid == {} && flags & ::winapi::um::oleauto::{} != 0 {{
+                if args.len() != {} {{
+                    return ::std::result::Result::Err(::winapi::shared::winerror::DISP_E_BADPARAMCOUNT.into());
+                }}
+                let mut args = args.into_iter();
+                {}
+                return ::rusty_winapi::server::dispatch::IntoDispatchResult::into_dispatch_result(
+                    self.{}({}),
+                );
+            }}",
+            dispid,
+            member.flag,
+            args.len(),
+            conversions,
+            member.method,
+            args.join(", ")
+        );
+    }
+    generated +=
+        "::std::result::Result::Err(::winapi::shared::winerror::DISP_E_MEMBERNOTFOUND.into()) } }";
+    result.extend(
+        generated
+            .parse::<TokenStream>()
+            .map_err(|e| (Span::call_site(), e.to_string()))?,
+    );
+
+    Ok(result)
+}
+
+/// Parses method with `#[method(...)]`, `#[get(...)]` or `#[put(...)]` attribute.
+fn parse_member(item: &[TokenTree], attr: &Group) -> Result<Member, Error> {
+    let span = attr.span();
+    let mut attr = attr.stream().into_iter();
+    let kind = attr.next().map(|x| x.to_string()).unwrap_or_default();
+    let flag = match kind.as_str() {
+        "method" => "DISPATCH_METHOD",
+        "get" => "DISPATCH_PROPERTYGET",
+        _ => "DISPATCH_PROPERTYPUT",
+    };
+
+    let start = item
+        .iter()
+        .position(|x| is_ident(x, "fn"))
+        .ok_or_else(|| (span, format!("#[{}] applies to methods", kind)))?;
+    let method = match item.get(start + 1) {
+        Some(TokenTree::Ident(x)) => x.to_string(),
+        _ => return Err((span, String::from("expected method name"))),
+    };
+    let params = match item.get(start + 2) {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Parenthesis => x,
+        _ => return Err((span, format!("#[{}] methods can't be generic", kind))),
+    };
+
+    let mut params = split_commas(params.stream()).into_iter();
+    match params.next() {
+        Some(ref x) if x.len() == 2 && is_punct(&x[0], '&') && is_ident(&x[1], "self") => {}
+        _ => return Err((span, format!("#[{}] methods take `&self`", kind))),
+    }
+    let params = params
+        .map(|x| match (x.first(), x.get(1)) {
+            (Some(TokenTree::Ident(_)), Some(colon)) if is_punct(colon, ':') => {
+                Ok(x[2..].iter().cloned().collect::<TokenStream>().to_string())
+            }
+            _ => Err((
+                x.first().map_or(span, TokenTree::span),
+                String::from("expected `name: Type` parameter"),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // #[get] or #[get("Name")]
+    let args: Vec<TokenTree> = match attr.next() {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Parenthesis => {
+            x.stream().into_iter().collect()
+        }
+        None => Vec::new(),
+        Some(x) => return Err((x.span(), format!("expected #[{}(...)]", kind))),
+    };
+    let name = match args.as_slice() {
+        [] => pascal_case(&method),
+        [TokenTree::Literal(x)] => string_literal(x)
+            .ok_or_else(|| (x.span(), String::from("expected member name string")))?,
+        _ => return Err((span, format!("expected #[{0}] or #[{0}(\"Name\")]", kind))),
+    };
+
+    Ok(Member {
+        method,
+        name,
+        flag,
+        params,
+    })
+}
@@ -0,0 +1,184 @@
+//! `#[event_sink]` expansion.
+
+use proc_macro::{Delimiter, Group, Span, TokenStream, TokenTree};
+
+use crate::{is_ident, is_punct, pascal_case, split_commas, split_items, string_literal, Error};
+
+/// Event handled by a method.
+enum Event {
+    Name(String),
+    DispId(String),
+}
+
+/// Handler method of the impl block.
+struct Handler {
+    name: String,
+    event: Event,
+    /// Types of parameters.
+    params: Vec<String>,
+}
+
+pub fn expand(item: TokenStream) -> Result<TokenStream, Error> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let position = tokens
+        .iter()
+        .position(|x| is_ident(x, "impl"))
+        .ok_or_else(|| {
+            (
+                Span::call_site(),
+                String::from("#[event_sink] applies to impl blocks"),
+            )
+        })?;
+    if let Some(x) = tokens.get(position + 1).filter(|x| is_punct(x, '<')) {
+        return Err((
+            x.span(),
+            String::from("#[event_sink] impl blocks can't be generic"),
+        ));
+    }
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Brace => x,
+        _ => return Err((Span::call_site(), String::from("expected impl body"))),
+    };
+    let self_type: TokenStream = tokens[position + 1..].iter().cloned().collect();
+    if tokens[position + 1..].iter().any(|x| is_ident(x, "for")) {
+        return Err((
+            Span::call_site(),
+            String::from("#[event_sink] applies to inherent impl blocks"),
+        ));
+    }
+
+    let mut items = TokenStream::new();
+    let mut handlers = Vec::new();
+    for (item, attrs) in split_items(body.stream(), &["event"]) {
+        if let Some(attr) = attrs.first() {
+            handlers.push(parse_handler(&item, attr)?);
+        }
+        items.extend(item);
+    }
+
+    let mut result: TokenStream = tokens.into_iter().collect();
+    let mut body = Group::new(Delimiter::Brace, items);
+    body.set_span(Span::call_site());
+    result.extend(vec![TokenTree::from(body)]);
+
+    let mut generated = format!(
+        "impl ::rusty_winapi::server::event_sink::EventHandlers for {} {{
+            fn register(
+                self: ::std::rc::Rc<Self>,
+                builder: ::rusty_winapi::server::event_sink::EventSinkBuilder,
+            ) -> ::rusty_winapi::server::event_sink::EventSinkBuilder {{",
+        self_type
+    );
+    for handler in handlers {
+        let (method, event) = match handler.event {
+            Event::Name(x) => ("on", format!("{:?}", x)),
+            Event::DispId(x) => ("on_dispid", x),
+        };
+        let args = (0..handler.params.len())
+            .map(|i| format!("arg{}", i))
+            .collect::<Vec<_>>();
+        let conversions: String = args
+            .iter()
+            .zip(&handler.params)
+            .map(|(arg, ty)| {
+                format!(
+                    "let {}: {} = match ::rusty_winapi::server::event_sink::event_arg(args.next()) {{
+                        ::std::option::Option::Some(x) => x,
+                        ::std::option::Option::None => return,
+                    }};",
+                    arg, ty
+                )
+            })
+            .collect();
+        generated += &format!(
+            "let builder = {{
+                let this = ::std::rc::Rc::clone(&self);
+                builder.{}({}, move |args| {{
+                    let mut args = args.into_iter();
+                    {}
+                    let _ = this.{}({});
+                }})
+            }};",
+            method,
+            event,
+            conversions,
+            handler.name,
+            args.join(", ")
+        );
+    }
+    generated += "builder } }";
+    result.extend(
+        generated
+            .parse::<TokenStream>()
+            .map_err(|e| (Span::call_site(), e.to_string()))?,
+    );
+
+    Ok(result)
+}
+
+/// Parses method with `#[event(...)]` attribute.
+fn parse_handler(item: &[TokenTree], attr: &Group) -> Result<Handler, Error> {
+    let span = attr.span();
+    let start = item
+        .iter()
+        .position(|x| is_ident(x, "fn"))
+        .ok_or_else(|| (span, String::from("#[event] applies to methods")))?;
+    let name = match item.get(start + 1) {
+        Some(TokenTree::Ident(x)) => x.to_string(),
+        _ => return Err((span, String::from("expected method name"))),
+    };
+    let params = match item.get(start + 2) {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Parenthesis => x,
+        _ => return Err((span, String::from("#[event] methods can't be generic"))),
+    };
+
+    let mut params = split_commas(params.stream()).into_iter();
+    match params.next() {
+        Some(ref x) if x.len() == 2 && is_punct(&x[0], '&') && is_ident(&x[1], "self") => {}
+        _ => return Err((span, String::from("#[event] methods take `&self`"))),
+    }
+    let params = params
+        .map(|x| match (x.first(), x.get(1)) {
+            (Some(TokenTree::Ident(_)), Some(colon)) if is_punct(colon, ':') => {
+                Ok(x[2..].iter().cloned().collect::<TokenStream>().to_string())
+            }
+            _ => Err((
+                x.first().map_or(span, TokenTree::span),
+                String::from("expected `name: Type` parameter"),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // #[event], #[event("Name")] or #[event(dispid = 1)]
+    let args: Vec<TokenTree> = match attr.stream().into_iter().nth(1) {
+        Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Parenthesis => {
+            x.stream().into_iter().collect()
+        }
+        None => Vec::new(),
+        Some(x) => return Err((x.span(), String::from("expected #[event(...)]"))),
+    };
+    let event = match args.as_slice() {
+        [] => Event::Name(pascal_case(&name)),
+        [TokenTree::Literal(x)] => Event::Name(
+            string_literal(x)
+                .ok_or_else(|| (x.span(), String::from("expected event name string")))?,
+        ),
+        [TokenTree::Ident(key), eq, rest @ ..]
+            if key.to_string() == "dispid" && is_punct(eq, '=') && !rest.is_empty() =>
+        {
+            Event::DispId(rest.iter().cloned().collect::<TokenStream>().to_string())
+        }
+        _ => {
+            return Err((
+                span,
+                String::from("expected #[event], #[event(\"Name\")] or #[event(dispid = ...)]"),
+            ))
+        }
+    };
+
+    Ok(Handler {
+        name,
+        event,
+        params,
+    })
+}
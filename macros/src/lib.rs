@@ -0,0 +1,178 @@
+//! Procedural macros of rusty_winapi, use them through the `rusty_winapi` re-exports.
+//!
+//! Token streams are parsed by hand, the crate has no dependencies besides `proc_macro`.
+
+extern crate proc_macro;
+
+mod com_client;
+mod dispatch_server;
+mod event_sink;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// Implements a trait of late-bound automation members for all `SmartIDispatch` types, see `rusty_winapi::com_client`.
+#[proc_macro_attribute]
+pub fn com_client(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if let Some(x) = attr.into_iter().next() {
+        return compile_error(x.span(), "#[com_client] takes no arguments");
+    }
+    com_client::expand(item).unwrap_or_else(|(span, message)| compile_error(span, &message))
+}
+
+/// Implements `DispatchServer` for the type of an impl block, its methods marked `#[method]`, `#[get]` or `#[put]`
+/// become automation members, see `rusty_winapi::server::dispatch`.
+#[proc_macro_attribute]
+pub fn dispatch_server(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if let Some(x) = attr.into_iter().next() {
+        return compile_error(x.span(), "#[dispatch_server] takes no arguments");
+    }
+    dispatch_server::expand(item).unwrap_or_else(|(span, message)| compile_error(span, &message))
+}
+
+/// Implements `EventHandlers` for the type of an impl block, its methods marked `#[event]` handle events, see
+/// `rusty_winapi::server::event_sink`.
+#[proc_macro_attribute]
+pub fn event_sink(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if let Some(x) = attr.into_iter().next() {
+        return compile_error(x.span(), "#[event_sink] takes no arguments");
+    }
+    event_sink::expand(item).unwrap_or_else(|(span, message)| compile_error(span, &message))
+}
+
+/// Error of macro expansion, reported at the span.
+type Error = (Span, String);
+
+fn compile_error(span: Span, message: &str) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::from(literal).into());
+    group.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut semicolon = Punct::new(';', Spacing::Alone);
+    semicolon.set_span(span);
+    vec![
+        TokenTree::from(Ident::new("compile_error", span)),
+        bang.into(),
+        group.into(),
+        semicolon.into(),
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn is_ident(x: &TokenTree, name: &str) -> bool {
+    match x {
+        TokenTree::Ident(x) => x.to_string() == name,
+        _ => false,
+    }
+}
+
+fn is_punct(x: &TokenTree, ch: char) -> bool {
+    match x {
+        TokenTree::Punct(x) => x.as_char() == ch,
+        _ => false,
+    }
+}
+
+/// Splits body of a trait or impl block into items (with their attributes but `#[<attr>(...)]` of `names`) and
+/// those attributes of each item.
+fn split_items(body: TokenStream, names: &[&str]) -> Vec<(Vec<TokenTree>, Vec<Group>)> {
+    let mut result = Vec::new();
+    let mut rest = body.into_iter().peekable();
+    while rest.peek().is_some() {
+        let mut item = Vec::new();
+        let mut attrs = Vec::new();
+        while let Some(x) = rest.next() {
+            let end = is_punct(&x, ';')
+                || match &x {
+                    TokenTree::Group(g) => g.delimiter() == Delimiter::Brace,
+                    _ => false,
+                };
+            if is_punct(&x, '#') {
+                if let Some(TokenTree::Group(g)) = rest.peek() {
+                    if g.stream()
+                        .into_iter()
+                        .next()
+                        .is_some_and(|x| names.iter().any(|name| is_ident(&x, name)))
+                    {
+                        attrs.push(g.clone());
+                        rest.next();
+                        continue;
+                    }
+                }
+            }
+            item.push(x);
+            if end {
+                break;
+            }
+        }
+        result.push((item, attrs));
+    }
+    result
+}
+
+/// Value of a string literal without escapes, `None` for other literals.
+fn string_literal(x: &Literal) -> Option<String> {
+    let x = x.to_string();
+    if x.len() >= 2 && x.starts_with('"') && x.ends_with('"') {
+        Some(x[1..x.len() - 1].to_string())
+    } else {
+        None
+    }
+}
+
+/// Splits `tokens` by commas outside of angle brackets (which aren't token groups).
+fn split_commas(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut result = vec![Vec::new()];
+    let mut depth = 0;
+    let mut arrow = false;
+    for x in tokens {
+        match &x {
+            TokenTree::Punct(p) if p.as_char() == ',' && depth == 0 => {
+                result.push(Vec::new());
+                continue;
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+            // `->` of fn pointer types closes nothing.
+            TokenTree::Punct(p) if p.as_char() == '>' && !arrow => depth -= 1,
+            _ => {}
+        }
+        arrow = match &x {
+            TokenTree::Punct(p) => p.as_char() == '-' && p.spacing() == Spacing::Joint,
+            _ => false,
+        };
+        result.last_mut().unwrap().push(x);
+    }
+    if result.last().is_some_and(Vec::is_empty) {
+        result.pop();
+    }
+    result
+}
+
+/// Automation name of a Rust member: `pool_size` becomes `PoolSize`, raw identifiers lose `r#`.
+fn pascal_case(name: &str) -> String {
+    let name = name.trim_start_matches("r#");
+    name.split('_')
+        .map(|x| {
+            let mut chars = x.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pascal_case() {
+        assert_eq!("Connect", pascal_case("connect"));
+        assert_eq!("PoolSize", pascal_case("pool_size"));
+        assert_eq!("Type", pascal_case("r#type"));
+        assert_eq!("Item", pascal_case("_item"));
+    }
+}
@@ -0,0 +1,757 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Fluent builder unifying all ways to create a COM object.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activate::Activate;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let excel = Activate::<IDispatch>::new()
+//!     .progid("Excel.Application")
+//!     .create()
+//!     .expect("Excel.Application");
+//! ```
+
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+
+use winapi::shared::guiddef::{CLSID, GUID, IID, REFIID};
+use winapi::shared::minwindef::{BOOL, DWORD, LPVOID};
+use winapi::shared::ntdef::{HRESULT, LCID, LONG, ULONG};
+use winapi::shared::rpcdce::*;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror;
+use winapi::shared::wtypes::BSTR;
+use winapi::shared::wtypesbase::{COAUTHIDENTITY, COAUTHINFO};
+use winapi::um::combaseapi::{
+    CoCreateInstance, CoCreateInstanceEx, CoGetClassObject, CoSetProxyBlanket,
+};
+use winapi::um::objidl::BIND_OPTS;
+use winapi::um::objidlbase::{COSERVERINFO, EOAC_NONE, MULTI_QI};
+use winapi::um::unknwnbase::{IClassFactory, IClassFactoryVtbl, IUnknown, LPUNKNOWN};
+use winapi::{Interface, RIDL};
+
+use crate::auto_bstr::AutoBSTR;
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::cls_ctx::ClsCtx;
+use crate::config::Config;
+use crate::error::{ActivationError, ComResult};
+use crate::safe::clsid::CLSIDFromProgID;
+use crate::safe::guid::Guid;
+use crate::safe::moniker::bind_to_object_with;
+use crate::smart_iobjectsafety::{IObjectSafety, SmartIObjectSafety};
+use crate::smart_iunknown::SmartIUnknown;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LICINFO {
+    pub cbLicInfo: LONG,
+    pub fRuntimeKeyAvail: BOOL,
+    pub fLicVerified: BOOL,
+}
+
+RIDL! {#[uuid(0xb196b28f, 0xbab4, 0x101a, 0xb6, 0x9c, 0x00, 0xaa, 0x00, 0x34, 0x1d, 0x07)]
+interface IClassFactory2(IClassFactory2Vtbl): IClassFactory(IClassFactoryVtbl) {
+    fn GetLicInfo(
+        pLicInfo: *mut LICINFO,
+    ) -> HRESULT,
+    fn RequestLicKey(
+        dwReserved: DWORD,
+        pBstrKey: *mut BSTR,
+    ) -> HRESULT,
+    fn CreateInstanceLic(
+        pUnkOuter: LPUNKNOWN,
+        pUnkReserved: LPUNKNOWN,
+        riid: REFIID,
+        bstrKey: BSTR,
+        ppvObj: *mut LPVOID,
+    ) -> HRESULT,
+}}
+
+crate::impl_interface_vtbl!(IClassFactory2 => IClassFactory2Vtbl);
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BIND_OPTS3 {
+    pub cbStruct: DWORD,
+    pub grfFlags: DWORD,
+    pub grfMode: DWORD,
+    pub dwTickCountDeadline: DWORD,
+    pub dwTrackFlags: DWORD,
+    pub dwClassContext: DWORD,
+    pub locale: LCID,
+    pub pServerInfo: *mut COSERVERINFO,
+    pub hwnd: HWND,
+}
+
+/// Parameters of [CoSetProxyBlanket] applied to a proxy after activation.
+///
+/// [CoSetProxyBlanket]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cosetproxyblanket
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SecurityBlanket {
+    pub authn_svc: DWORD,
+    pub authz_svc: DWORD,
+    pub authn_level: DWORD,
+    pub imp_level: DWORD,
+    pub capabilities: DWORD,
+}
+
+impl Default for SecurityBlanket {
+    fn default() -> Self {
+        SecurityBlanket {
+            authn_svc: RPC_C_AUTHN_DEFAULT,
+            authz_svc: RPC_C_AUTHZ_DEFAULT,
+            authn_level: RPC_C_AUTHN_LEVEL_DEFAULT,
+            imp_level: RPC_C_IMP_LEVEL_IMPERSONATE,
+            capabilities: EOAC_NONE,
+        }
+    }
+}
+
+impl SecurityBlanket {
+    /// Sets authentication service.
+    pub fn authn_service(mut self, authn_service: AuthnService) -> Self {
+        self.authn_svc = authn_service.into();
+        self
+    }
+
+    /// Sets authentication level.
+    pub fn authn_level(mut self, authn_level: AuthnLevel) -> Self {
+        self.authn_level = authn_level.into();
+        self
+    }
+
+    /// Sets impersonation level.
+    pub fn imp_level(mut self, imp_level: ImpLevel) -> Self {
+        self.imp_level = imp_level.into();
+        self
+    }
+}
+
+/// Authentication service (`RPC_C_AUTHN_*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthnService {
+    None,
+    WinNT,
+    Kerberos,
+    Negotiate,
+    Default,
+}
+
+impl From<AuthnService> for DWORD {
+    fn from(x: AuthnService) -> Self {
+        match x {
+            AuthnService::None => RPC_C_AUTHN_NONE,
+            AuthnService::WinNT => RPC_C_AUTHN_WINNT,
+            AuthnService::Kerberos => RPC_C_AUTHN_GSS_KERBEROS,
+            AuthnService::Negotiate => RPC_C_AUTHN_GSS_NEGOTIATE,
+            AuthnService::Default => RPC_C_AUTHN_DEFAULT,
+        }
+    }
+}
+
+/// Authentication level (`RPC_C_AUTHN_LEVEL_*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthnLevel {
+    Default,
+    None,
+    Connect,
+    Call,
+    Packet,
+    PacketIntegrity,
+    PacketPrivacy,
+}
+
+impl From<AuthnLevel> for DWORD {
+    fn from(x: AuthnLevel) -> Self {
+        match x {
+            AuthnLevel::Default => RPC_C_AUTHN_LEVEL_DEFAULT,
+            AuthnLevel::None => RPC_C_AUTHN_LEVEL_NONE,
+            AuthnLevel::Connect => RPC_C_AUTHN_LEVEL_CONNECT,
+            AuthnLevel::Call => RPC_C_AUTHN_LEVEL_CALL,
+            AuthnLevel::Packet => RPC_C_AUTHN_LEVEL_PKT,
+            AuthnLevel::PacketIntegrity => RPC_C_AUTHN_LEVEL_PKT_INTEGRITY,
+            AuthnLevel::PacketPrivacy => RPC_C_AUTHN_LEVEL_PKT_PRIVACY,
+        }
+    }
+}
+
+/// Impersonation level (`RPC_C_IMP_LEVEL_*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImpLevel {
+    Default,
+    Anonymous,
+    Identify,
+    Impersonate,
+    Delegate,
+}
+
+impl From<ImpLevel> for DWORD {
+    fn from(x: ImpLevel) -> Self {
+        match x {
+            ImpLevel::Default => RPC_C_IMP_LEVEL_DEFAULT,
+            ImpLevel::Anonymous => RPC_C_IMP_LEVEL_ANONYMOUS,
+            ImpLevel::Identify => RPC_C_IMP_LEVEL_IDENTIFY,
+            ImpLevel::Impersonate => RPC_C_IMP_LEVEL_IMPERSONATE,
+            ImpLevel::Delegate => RPC_C_IMP_LEVEL_DELEGATE,
+        }
+    }
+}
+
+/// Identity is passed as UTF-16 strings.
+const SEC_WINNT_AUTH_IDENTITY_UNICODE: ULONG = 2;
+
+/// Explicit credentials (COAUTHIDENTITY) to authenticate as, instead of the process token.
+///
+/// Password buffer is zeroed on drop.
+pub struct AuthIdentity {
+    user: Vec<u16>,
+    domain: Vec<u16>,
+    password: Vec<u16>,
+    identity: COAUTHIDENTITY,
+}
+
+// Raw pointers of COAUTHIDENTITY point into owned buffers only.
+unsafe impl Send for AuthIdentity {}
+unsafe impl Sync for AuthIdentity {}
+
+impl AuthIdentity {
+    pub fn new(user: &str, domain: &str, password: &str) -> Self {
+        let mut user: Vec<u16> = user.encode_utf16().collect();
+        let mut domain: Vec<u16> = domain.encode_utf16().collect();
+        let mut password: Vec<u16> = password.encode_utf16().collect();
+
+        let identity = COAUTHIDENTITY {
+            User: user.as_mut_ptr(),
+            UserLength: user.len() as ULONG,
+            Domain: domain.as_mut_ptr(),
+            DomainLength: domain.len() as ULONG,
+            Password: password.as_mut_ptr(),
+            PasswordLength: password.len() as ULONG,
+            Flags: SEC_WINNT_AUTH_IDENTITY_UNICODE,
+        };
+
+        AuthIdentity {
+            user,
+            domain,
+            password,
+            identity,
+        }
+    }
+
+    /// User name.
+    pub fn user(&self) -> String {
+        String::from_utf16_lossy(&self.user)
+    }
+
+    /// Domain name.
+    pub fn domain(&self) -> String {
+        String::from_utf16_lossy(&self.domain)
+    }
+
+    /// Pointer to COAUTHIDENTITY, valid while `self` is alive.
+    pub fn as_ptr(&self) -> *const COAUTHIDENTITY {
+        &self.identity
+    }
+}
+
+impl Drop for AuthIdentity {
+    fn drop(&mut self) {
+        for x in self.password.iter_mut() {
+            unsafe { std::ptr::write_volatile(x, 0) };
+        }
+    }
+}
+
+/// Class to be activated.
+#[derive(Clone)]
+pub enum ClassId {
+    Clsid(CLSID),
+    ProgId(String),
+}
+
+type PostCreationStep<T> = Box<dyn FnOnce(&mut AutoCOMInterface<T>) -> ComResult<()>>;
+
+/// Builder of a new COM object instance, wrapped into `AutoCOMInterface<T>`.
+///
+/// Depending on options, object is created via CoCreateInstance, CoCreateInstanceEx (remote server)
+/// or IClassFactory2::CreateInstanceLic (license key), then post-creation steps are applied in order.
+pub struct Activate<T: Interface> {
+    class: Option<ClassId>,
+    cls_context: DWORD,
+    server: Option<String>,
+    license_key: Option<String>,
+    outer: LPUNKNOWN,
+    security_blanket: Option<SecurityBlanket>,
+    credentials: Option<&'static AuthIdentity>,
+    steps: Vec<PostCreationStep<T>>,
+    _interface: PhantomData<T>,
+}
+
+impl<T: Interface + 'static> Activate<T> {
+    /// Starts a new activation with server context from global [`Config`].
+    ///
+    /// [`Config`]: ../config/struct.Config.html
+    pub fn new() -> Self {
+        Activate {
+            class: None,
+            cls_context: Config::global().cls_context(),
+            server: None,
+            license_key: None,
+            outer: std::ptr::null_mut(),
+            security_blanket: None,
+            credentials: None,
+            steps: Vec::new(),
+            _interface: PhantomData,
+        }
+    }
+
+    /// Class to activate by CLSID.
+    pub fn clsid(mut self, clsid: &CLSID) -> Self {
+        self.class = Some(ClassId::Clsid(*clsid));
+        self
+    }
+
+    /// Class to activate by ProgID, e.g. `"Excel.Application"`.
+    pub fn progid(mut self, progid: &str) -> Self {
+        self.class = Some(ClassId::ProgId(progid.into()));
+        self
+    }
+
+    /// Server context, [`ClsCtx`] or raw `CLSCTX_*` flags.
+    ///
+    /// [`ClsCtx`]: ../cls_ctx/struct.ClsCtx.html
+    pub fn cls_context<C: Into<ClsCtx>>(mut self, cls_context: C) -> Self {
+        self.cls_context = cls_context.into().bits();
+        self
+    }
+
+    /// Remote machine name to activate object on.
+    pub fn server(mut self, server: &str) -> Self {
+        self.server = Some(server.into());
+        self
+    }
+
+    /// Runtime license key, object is created via IClassFactory2::CreateInstanceLic.
+    pub fn license_key(mut self, license_key: &str) -> Self {
+        self.license_key = Some(license_key.into());
+        self
+    }
+
+    /// Controlling IUnknown of an aggregate.
+    pub fn outer(mut self, outer: LPUNKNOWN) -> Self {
+        self.outer = outer;
+        self
+    }
+
+    /// Security blanket set on the proxy after creation.
+    pub fn security_blanket(mut self, security_blanket: SecurityBlanket) -> Self {
+        self.security_blanket = Some(security_blanket);
+        self
+    }
+
+    /// Explicit credentials to activate remote object with and to set on its proxy.
+    ///
+    /// COM keeps the identity pointer for subsequent calls through the proxy, so it must outlive the object.
+    pub fn credentials(mut self, credentials: &'static AuthIdentity) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Marks interface `T` of a created object safe for scripting via IObjectSafety.
+    pub fn safe_for_scripting(self) -> Self {
+        self.then(|x| {
+            x.query_interface::<IObjectSafety>()?
+                .set_safe_for_scripting::<T>()
+        })
+    }
+
+    /// Adds a custom post-creation step, e.g. additional QueryInterface or initialization call.
+    pub fn then<F>(mut self, step: F) -> Self
+    where
+        F: FnOnce(&mut AutoCOMInterface<T>) -> ComResult<()> + 'static,
+    {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Creates an object instance and applies post-creation steps.
+    ///
+    /// # Errors
+    ///
+    /// * If class isn't specified, returns `RustyWinapiError::Com(E_INVALIDARG)`.
+    /// * If ProgID isn't registered, returns `ActivationError::ProgIdNotFound`.
+    /// * Otherwise returns error of a failed step.
+    pub fn create(self) -> ComResult<AutoCOMInterface<T>> {
+        let clsid = match &self.class {
+            Some(ClassId::Clsid(x)) => *x,
+            Some(ClassId::ProgId(x)) => {
+                CLSIDFromProgID(x).map_err(|hresult| ActivationError::ProgIdNotFound {
+                    progid: x.clone(),
+                    hresult,
+                })?
+            }
+            None => return Err(winerror::E_INVALIDARG.into()),
+        };
+
+        let blanket = self.security_blanket.unwrap_or_default();
+        let mut pvoid: LPVOID = std::ptr::null_mut();
+        let start = crate::trace::start();
+        let hresult = with_server_info(
+            self.server.as_deref(),
+            &blanket,
+            self.credentials,
+            |pserver_info| self.activate(&clsid, pserver_info, &mut pvoid),
+        )
+        .unwrap_or_else(|e| e);
+        crate::trace::create_instance(&clsid, &T::uuidof(), hresult, start);
+
+        if !winerror::SUCCEEDED(hresult) {
+            return Err(hresult.into());
+        }
+
+        let mut result: AutoCOMInterface<T> =
+            TryFrom::try_from(pvoid as *mut T).map_err(|_| winerror::E_POINTER)?;
+
+        if self.security_blanket.is_some() || self.credentials.is_some() {
+            set_proxy_blanket(result.as_iunknown_ptr(), &blanket, self.credentials)?;
+        }
+
+        for step in self.steps {
+            step(&mut result)?;
+        }
+
+        Ok(result)
+    }
+
+    fn activate(
+        &self,
+        clsid: &CLSID,
+        pserver_info: *mut COSERVERINFO,
+        pvoid: &mut LPVOID,
+    ) -> Result<HRESULT, HRESULT> {
+        let hresult = match &self.license_key {
+            Some(key) => {
+                let factory = create_class_factory2(clsid, self.cls_context, pserver_info)?;
+                let key = AutoBSTR::try_from(key.as_str()).map_err(|_| winerror::E_OUTOFMEMORY)?;
+                unsafe {
+                    factory.as_inner().CreateInstanceLic(
+                        self.outer,
+                        std::ptr::null_mut(),
+                        &T::uuidof(),
+                        key.as_bstr().as_raw(),
+                        pvoid,
+                    )
+                }
+            }
+            None if self.server.is_some() => {
+                let mut mqi = MULTI_QI {
+                    pIID: &T::uuidof(),
+                    pItf: std::ptr::null_mut(),
+                    hr: 0,
+                };
+                let hresult = unsafe {
+                    CoCreateInstanceEx(
+                        clsid,
+                        self.outer,
+                        self.cls_context,
+                        pserver_info,
+                        1,
+                        &mut mqi,
+                    )
+                };
+                *pvoid = mqi.pItf as LPVOID;
+                if winerror::SUCCEEDED(hresult) {
+                    mqi.hr
+                } else {
+                    hresult
+                }
+            }
+            None => Config::global().retry_policy().run(|| unsafe {
+                CoCreateInstance(clsid, self.outer, self.cls_context, &T::uuidof(), pvoid)
+            }),
+        };
+
+        Ok(hresult)
+    }
+}
+
+impl<T: Interface + 'static> Default for Activate<T> {
+    fn default() -> Self {
+        Activate::new()
+    }
+}
+
+/// Tuple of interfaces requested at once by [`create_instance_multi`].
+///
+/// Implemented for tuples of up to 6 interfaces.
+///
+/// [`create_instance_multi`]: fn.create_instance_multi.html
+pub trait InterfaceSet {
+    /// Tuple of per-interface results.
+    type Output;
+
+    /// IIDs of the interfaces in order.
+    fn iids() -> Vec<IID>;
+
+    /// Takes ownership of interface pointers returned by CoCreateInstanceEx.
+    ///
+    /// # Safety
+    ///
+    /// `results` must be filled by CoCreateInstanceEx for IIDs returned by [`iids`] in the same order.
+    ///
+    /// [`iids`]: #tymethod.iids
+    unsafe fn from_results(results: &[MULTI_QI]) -> Self::Output;
+}
+
+macro_rules! impl_interface_set {
+    ($($interface:ident: $index:tt),+) => {
+        impl<$($interface: Interface),+> InterfaceSet for ($($interface,)+) {
+            type Output = ($(ComResult<AutoCOMInterface<$interface>>,)+);
+
+            fn iids() -> Vec<IID> {
+                vec![$($interface::uuidof()),+]
+            }
+
+            unsafe fn from_results(results: &[MULTI_QI]) -> Self::Output {
+                ($(multi_qi_result::<$interface>(&results[$index]),)+)
+            }
+        }
+    };
+}
+
+impl_interface_set!(A: 0);
+impl_interface_set!(A: 0, B: 1);
+impl_interface_set!(A: 0, B: 1, C: 2);
+impl_interface_set!(A: 0, B: 1, C: 2, D: 3);
+impl_interface_set!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_interface_set!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+fn multi_qi_result<T: Interface>(result: &MULTI_QI) -> ComResult<AutoCOMInterface<T>> {
+    if winerror::SUCCEEDED(result.hr) {
+        TryFrom::try_from(result.pItf as *mut T).map_err(|_| winerror::E_POINTER.into())
+    } else {
+        Err(result.hr.into())
+    }
+}
+
+/// Creates an object of class `clsid` in an elevated (administrator) local server, via the elevation moniker
+/// `Elevation:Administrator!new:{clsid}`.
+///
+/// UAC consent dialog is shown as a child of `hwnd`, pass NULL if there is no window. Class must be registered for
+/// elevation (`Elevation\Enabled` registry value) and have a `LocalizedString`.
+///
+/// # Errors
+///
+/// * If the user declined elevation, returns `HRESULT_FROM_WIN32(ERROR_CANCELLED)`.
+/// * If class isn't registered for elevation, returns `CO_E_ELEVATION_DISABLED`.
+/// * Otherwise returns HRESULT of CoGetObject.
+pub fn create_instance_elevated<T: Interface>(
+    clsid: &CLSID,
+    hwnd: HWND,
+) -> ComResult<AutoCOMInterface<T>> {
+    let display_name = format!("Elevation:Administrator!new:{}", Guid::from(*clsid));
+
+    let mut options: BIND_OPTS3 = unsafe { std::mem::zeroed() };
+    options.cbStruct = std::mem::size_of::<BIND_OPTS3>() as DWORD;
+    options.dwClassContext = ClsCtx::LOCAL_SERVER.bits();
+    options.hwnd = hwnd;
+
+    // BIND_OPTS3 extends BIND_OPTS, cbStruct tells CoGetObject the actual size.
+    let options = unsafe { &mut *(&mut options as *mut BIND_OPTS3 as *mut BIND_OPTS) };
+    Ok(bind_to_object_with::<T>(&display_name, Some(options))?)
+}
+
+/// Creates an object and queries several interfaces of it in a single round trip via CoCreateInstanceEx.
+///
+/// Saves a round trip per interface on DCOM. Each interface gets its own result, e.g. `E_NOINTERFACE` if the object
+/// doesn't implement it. `server` and `credentials` are the same as of [`Activate`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use rusty_winapi::activate::create_instance_multi;
+/// use winapi::um::combaseapi::CLSCTX_ALL;
+/// use winapi::um::oaidl::{IDispatch, ITypeInfo};
+/// use winapi::um::unknwnbase::IUnknown;
+///
+/// # let clsid = unsafe { std::mem::zeroed() };
+/// let (unknown, dispatch, type_info) =
+///     create_instance_multi::<(IUnknown, IDispatch, ITypeInfo)>(&clsid, CLSCTX_ALL, Some("server"), None)
+///         .unwrap();
+/// ```
+///
+/// # Errors
+///
+/// * If no interface is available, returns HRESULT of CoCreateInstanceEx.
+/// * Per-interface results hold HRESULT of failed query or of failed CoSetProxyBlanket.
+///
+/// [`Activate`]: struct.Activate.html
+pub fn create_instance_multi<S: InterfaceSet, C: Into<ClsCtx>>(
+    clsid: &CLSID,
+    cls_context: C,
+    server: Option<&str>,
+    credentials: Option<&'static AuthIdentity>,
+) -> ComResult<S::Output> {
+    let cls_context = cls_context.into().bits();
+    let iids = S::iids();
+    let mut results: Vec<MULTI_QI> = iids
+        .iter()
+        .map(|x| MULTI_QI {
+            pIID: x,
+            pItf: std::ptr::null_mut(),
+            hr: 0,
+        })
+        .collect();
+
+    let blanket = SecurityBlanket::default();
+    let hresult = with_server_info(server, &blanket, credentials, |pserver_info| unsafe {
+        CoCreateInstanceEx(
+            clsid,
+            std::ptr::null_mut(),
+            cls_context,
+            pserver_info,
+            results.len() as DWORD,
+            results.as_mut_ptr(),
+        )
+    });
+
+    if !winerror::SUCCEEDED(hresult) {
+        return Err(hresult.into());
+    }
+
+    if credentials.is_some() {
+        for x in results.iter_mut() {
+            if !winerror::SUCCEEDED(x.hr) || x.pItf.is_null() {
+                continue;
+            }
+
+            if let Err(hresult) = set_proxy_blanket(x.pItf, &blanket, credentials) {
+                unsafe { (*x.pItf).Release() };
+                x.pItf = std::ptr::null_mut();
+                x.hr = hresult;
+            }
+        }
+    }
+
+    Ok(unsafe { S::from_results(&results) })
+}
+
+/// Runs `f` with COSERVERINFO of `server` (NULL if there is none), passing `identity` to the server in COAUTHINFO.
+pub(crate) fn with_server_info<R, F>(
+    server: Option<&str>,
+    blanket: &SecurityBlanket,
+    identity: Option<&AuthIdentity>,
+    f: F,
+) -> R
+where
+    F: FnOnce(*mut COSERVERINFO) -> R,
+{
+    let server = match server {
+        Some(x) => x,
+        None => return f(std::ptr::null_mut()),
+    };
+
+    let mut name: Vec<u16> = server.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut auth_info = COAUTHINFO {
+        // Activation needs a concrete authentication service, NTLM is always available.
+        dwAuthnSvc: if blanket.authn_svc == RPC_C_AUTHN_DEFAULT {
+            RPC_C_AUTHN_WINNT
+        } else {
+            blanket.authn_svc
+        },
+        dwAuthzSvc: if blanket.authz_svc == RPC_C_AUTHZ_DEFAULT {
+            RPC_C_AUTHZ_NONE
+        } else {
+            blanket.authz_svc
+        },
+        pwszServerPrincName: std::ptr::null_mut(),
+        dwAuthnLevel: blanket.authn_level,
+        dwImpersonationLevel: blanket.imp_level,
+        pAuthIdentityData: identity.map_or(std::ptr::null_mut(), |x| x.as_ptr() as *mut _),
+        dwCapabilities: blanket.capabilities,
+    };
+    let mut server_info = COSERVERINFO {
+        dwReserved1: 0,
+        pwszName: name.as_mut_ptr(),
+        pAuthInfo: if identity.is_some() {
+            &mut auth_info
+        } else {
+            std::ptr::null_mut()
+        },
+        dwReserved2: 0,
+    };
+
+    f(&mut server_info)
+}
+
+fn create_class_factory2(
+    clsid: &CLSID,
+    cls_context: DWORD,
+    server_info: *mut COSERVERINFO,
+) -> Result<AutoCOMInterface<IClassFactory2>, HRESULT> {
+    let mut pvoid: LPVOID = std::ptr::null_mut();
+    let hresult = unsafe {
+        CoGetClassObject(
+            clsid,
+            cls_context,
+            server_info as LPVOID,
+            &IClassFactory2::uuidof(),
+            &mut pvoid,
+        )
+    };
+
+    if winerror::SUCCEEDED(hresult) {
+        TryFrom::try_from(pvoid as *mut IClassFactory2).map_err(|_| winerror::E_POINTER)
+    } else {
+        Err(hresult)
+    }
+}
+
+pub(crate) fn set_proxy_blanket(
+    proxy: LPUNKNOWN,
+    blanket: &SecurityBlanket,
+    identity: Option<&AuthIdentity>,
+) -> Result<(), HRESULT> {
+    let identity = identity.map_or(std::ptr::null_mut(), |x| x.as_ptr() as LPVOID);
+    let hresult = unsafe {
+        CoSetProxyBlanket(
+            proxy,
+            blanket.authn_svc,
+            blanket.authz_svc,
+            std::ptr::null_mut(),
+            blanket.authn_level,
+            blanket.imp_level,
+            identity,
+            blanket.capabilities,
+        )
+    };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(())
+    } else {
+        Err(hresult)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::um::oaidl::IDispatch;
+
+    #[test]
+    fn test_InterfaceSet_iids() {
+        let iids = <(IUnknown, IDispatch)>::iids();
+        assert_eq!(2, iids.len());
+        assert!(winapi::shared::guiddef::IsEqualGUID(
+            &IUnknown::uuidof(),
+            &iids[0]
+        ));
+        assert!(winapi::shared::guiddef::IsEqualGUID(
+            &IDispatch::uuidof(),
+            &iids[1]
+        ));
+    }
+}
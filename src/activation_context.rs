@@ -0,0 +1,169 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Registration-free COM via activation contexts.
+//!
+//! A side-by-side manifest may describe COM classes of in-process servers (`<comClass>` elements of `<file>`), so they
+//! can be created without registry entries while the manifest's activation context is active on the thread.
+//! [`ActivationContext`] loads such a manifest, activates it for a scope and creates objects within it.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activation_context::ActivationContext;
+//! use rusty_winapi::cls_ctx::ClsCtx;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! # let clsid = unsafe { std::mem::zeroed() };
+//! let context = ActivationContext::from_manifest(r"C:\App\MyServer.sxs.manifest").unwrap();
+//! let object = context
+//!     .create_instance::<IDispatch, _>(&clsid, ClsCtx::INPROC_SERVER)
+//!     .unwrap();
+//! ```
+//!
+//! [`ActivationContext`]: struct.ActivationContext.html
+
+use std::marker::PhantomData;
+
+use winapi::shared::basetsd::ULONG_PTR;
+use winapi::shared::guiddef::CLSID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::{HANDLE, HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+use winapi::um::winbase::{
+    ActivateActCtx, CreateActCtxW, DeactivateActCtx, ReleaseActCtx, ACTCTXW,
+};
+use winapi::Interface;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::cls_ctx::ClsCtx;
+use crate::safe::clsid::to_wide;
+
+/// `lpAssemblyDirectory` of ACTCTXW is valid.
+const ACTCTX_FLAG_ASSEMBLY_DIRECTORY_VALID: DWORD = 0x004;
+
+/// Activation context created from a side-by-side manifest, released on drop.
+pub struct ActivationContext {
+    handle: HANDLE,
+}
+
+// Activation context handle is process-wide, activation itself is per thread (see `ActivationContextGuard`).
+unsafe impl Send for ActivationContext {}
+unsafe impl Sync for ActivationContext {}
+
+impl ActivationContext {
+    /// Creates activation context from a manifest file, DLLs are looked up in the manifest's directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns HRESULT of failed CreateActCtx, e.g. `HRESULT_FROM_WIN32(ERROR_SXS_CANT_GEN_ACTCTX)` for malformed
+    /// manifest.
+    pub fn from_manifest(path: &str) -> Result<Self, HRESULT> {
+        Self::create(path, None)
+    }
+
+    /// Creates activation context from a manifest file, DLLs are looked up in `assembly_directory`.
+    pub fn from_manifest_in(path: &str, assembly_directory: &str) -> Result<Self, HRESULT> {
+        Self::create(path, Some(assembly_directory))
+    }
+
+    fn create(path: &str, assembly_directory: Option<&str>) -> Result<Self, HRESULT> {
+        let path = to_wide(path);
+        let assembly_directory = assembly_directory.map(to_wide);
+
+        let mut actctx: ACTCTXW = unsafe { std::mem::zeroed() };
+        actctx.cbSize = std::mem::size_of::<ACTCTXW>() as ULONG;
+        actctx.lpSource = path.as_ptr();
+        if let Some(x) = &assembly_directory {
+            actctx.dwFlags |= ACTCTX_FLAG_ASSEMBLY_DIRECTORY_VALID;
+            actctx.lpAssemblyDirectory = x.as_ptr();
+        }
+
+        let handle = unsafe { CreateActCtxW(&actctx) };
+        if handle == INVALID_HANDLE_VALUE {
+            Err(last_error())
+        } else {
+            Ok(ActivationContext { handle })
+        }
+    }
+
+    /// Activates context on the current thread until the returned guard is dropped.
+    ///
+    /// Guards must be dropped in reverse order of activation, which scoping guarantees.
+    pub fn activate(&self) -> Result<ActivationContextGuard<'_>, HRESULT> {
+        let mut cookie: ULONG_PTR = 0;
+        if unsafe { ActivateActCtx(self.handle, &mut cookie) } == 0 {
+            return Err(last_error());
+        }
+
+        Ok(ActivationContextGuard {
+            cookie,
+            _context: PhantomData,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Runs `f` with the context activated on the current thread.
+    pub fn with<F, R>(&self, f: F) -> Result<R, HRESULT>
+    where
+        F: FnOnce() -> R,
+    {
+        let _guard = self.activate()?;
+        Ok(f())
+    }
+
+    /// Creates an object of a class described by the manifest, see [`AutoCOMInterface::create_instance`].
+    ///
+    /// [`AutoCOMInterface::create_instance`]: ../auto_com_interface/struct.AutoCOMInterface.html#method.create_instance
+    pub fn create_instance<T: Interface, C: Into<ClsCtx>>(
+        &self,
+        clsid: &CLSID,
+        cls_context: C,
+    ) -> Result<AutoCOMInterface<T>, HRESULT> {
+        Ok(self.with(|| {
+            AutoCOMInterface::<T>::create_instance(clsid, std::ptr::null_mut(), cls_context)
+        })??)
+    }
+}
+
+impl Drop for ActivationContext {
+    fn drop(&mut self) {
+        unsafe { ReleaseActCtx(self.handle) };
+    }
+}
+
+/// Activation of [`ActivationContext`] on the current thread, deactivated on drop.
+///
+/// [`ActivationContext`]: struct.ActivationContext.html
+pub struct ActivationContextGuard<'a> {
+    cookie: ULONG_PTR,
+    _context: PhantomData<&'a ActivationContext>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'a> Drop for ActivationContextGuard<'a> {
+    fn drop(&mut self) {
+        unsafe { DeactivateActCtx(0, self.cookie) };
+    }
+}
+
+fn last_error() -> HRESULT {
+    winerror::HRESULT_FROM_WIN32(unsafe { GetLastError() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ActivationContext_missing_manifest() {
+        let hresult = ActivationContext::from_manifest(r"C:\no\such.manifest")
+            .map(|_| ())
+            .unwrap_err();
+        assert!(
+            hresult == winerror::HRESULT_FROM_WIN32(winerror::ERROR_FILE_NOT_FOUND)
+                || hresult == winerror::HRESULT_FROM_WIN32(winerror::ERROR_PATH_NOT_FOUND)
+        );
+    }
+}
@@ -0,0 +1,114 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Agile references to COM interfaces (Windows 8.1+).
+//!
+//! An interface pointer is valid only in the apartment where it was obtained. [`AgileRef`] created via
+//! [RoGetAgileReference] may be stored anywhere (it's `Send + Sync`) and resolved to an interface pointer valid in
+//! the apartment of the calling thread.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::agile_ref::AgileRef;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create().unwrap();
+//! let shared = Arc::new(AgileRef::new(&excel).unwrap());
+//!
+//! std::thread::spawn(move || {
+//!     let excel = shared.resolve().unwrap();
+//! });
+//! ```
+//!
+//! [`AgileRef`]: struct.AgileRef.html
+//! [RoGetAgileReference]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-rogetagilereference
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use winapi::shared::minwindef::LPVOID;
+use winapi::shared::winerror;
+use winapi::um::combaseapi::{RoGetAgileReference, AGILEREFERENCE_DEFAULT};
+use winapi::um::objidlbase::IAgileReference;
+use winapi::Interface;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+
+/// Apartment-independent reference to interface `T` of a COM object.
+pub struct AgileRef<T: Interface> {
+    reference: AutoCOMInterface<IAgileReference>,
+    _interface: PhantomData<T>,
+}
+
+// IAgileReference is designed to be used from any apartment.
+unsafe impl<T: Interface> Send for AgileRef<T> {}
+unsafe impl<T: Interface> Sync for AgileRef<T> {}
+
+impl<T: Interface> AgileRef<T> {
+    /// Creates an agile reference to the interface.
+    ///
+    /// # Errors
+    ///
+    /// * If wrapper is empty, returns `E_POINTER`.
+    /// * Otherwise returns HRESULT of RoGetAgileReference, e.g. `CO_E_NOT_SUPPORTED` for objects which
+    ///   aggregate the free-threaded marshaler.
+    pub fn new(interface: &AutoCOMInterface<T>) -> ComResult<Self> {
+        if interface.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        let mut reference: *mut IAgileReference = std::ptr::null_mut();
+        let hresult = unsafe {
+            RoGetAgileReference(
+                AGILEREFERENCE_DEFAULT,
+                &T::uuidof(),
+                interface.as_iunknown_ptr(),
+                &mut reference,
+            )
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            NonNull::new(reference)
+                .map(|x| AgileRef {
+                    reference: unsafe { AutoCOMInterface::from_raw(x.as_ptr()) },
+                    _interface: PhantomData,
+                })
+                .ok_or_else(|| winerror::E_POINTER.into())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Returns interface pointer valid in the apartment of the calling thread.
+    pub fn resolve(&self) -> ComResult<AutoCOMInterface<T>> {
+        let mut pvoid: LPVOID = std::ptr::null_mut();
+        let hresult = unsafe { self.reference.as_inner().Resolve(&T::uuidof(), &mut pvoid) };
+
+        if winerror::SUCCEEDED(hresult) {
+            NonNull::new(pvoid as *mut T)
+                .map(|x| unsafe { AutoCOMInterface::from_raw(x.as_ptr()) })
+                .ok_or_else(|| winerror::E_POINTER.into())
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RustyWinapiError;
+    use winapi::um::oaidl::IDispatch;
+
+    #[test]
+    fn test_AgileRef_null() {
+        let empty = AutoCOMInterface::<IDispatch>::default();
+        assert_eq!(
+            Some(RustyWinapiError::Com(winerror::E_POINTER)),
+            AgileRef::new(&empty).err()
+        );
+    }
+}
@@ -0,0 +1,394 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Asynchronous late-bound calls via a dedicated STA worker, behind `async` feature.
+//!
+//! [`AsyncDispatch`] is a `Send + Sync` handle of an automation object living on a [`StaThread`]. Its calls are
+//! queued to the worker and return futures, so automation composes with async services without blocking executor
+//! threads. Futures are executor-agnostic, they don't depend on any runtime.
+//!
+//! Only values without interface pointers cross threads: arguments with interfaces are rejected with
+//! `E_INVALIDARG`, results with interfaces fail with `DISP_E_TYPEMISMATCH`. Use [`call_object_async`] to get a handle
+//! of an object returned by a call.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::async_dispatch::AsyncDispatch;
+//! use rusty_winapi::error::ComResult;
+//! use rusty_winapi::sta_thread::StaThread;
+//! use rusty_winapi::smart_variant::SmartVariant;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! async fn excel_version() -> ComResult<SmartVariant> {
+//!     let sta = Arc::new(StaThread::new()?);
+//!     let excel = AsyncDispatch::create(sta, || {
+//!         Activate::<IDispatch>::new().progid("Excel.Application").create()
+//!     })
+//!     .await?;
+//!
+//!     excel.get_async("Version").await
+//! }
+//! ```
+//!
+//! [`AsyncDispatch`]: struct.AsyncDispatch.html
+//! [`StaThread`]: ../sta_thread/struct.StaThread.html
+//! [`call_object_async`]: struct.AsyncDispatch.html#method.call_object_async
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::oaidl::IDispatch;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::{ComResult, RustyWinapiError};
+use crate::error_info::ErrorInfo;
+use crate::smart_idispatch::SmartIDispatch;
+use crate::smart_variant::SmartVariant;
+use crate::sta_thread::StaThread;
+
+thread_local! {
+    /// Objects owned by handles, living on the current worker thread.
+    static OBJECTS: RefCell<HashMap<u64, AutoCOMInterface<IDispatch>>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_OBJECT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Result of a late-bound call, same as of [`SmartIDispatch`] methods.
+///
+/// [`SmartIDispatch`]: ../smart_idispatch/trait.SmartIDispatch.html
+pub type DispatchResult<T> = ComResult<T>;
+
+struct Shared<R> {
+    value: Option<R>,
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// Future of a result of a closure run on a worker thread.
+///
+/// Resolves to `Err(RustyWinapiError::Com(RPC_E_DISCONNECTED))` if closure panicked or worker thread has gone.
+pub struct StaFuture<R>(Arc<Mutex<Shared<R>>>);
+
+/// Completes [`StaFuture`], or fails it on drop without a value.
+struct Completer<R>(Arc<Mutex<Shared<R>>>);
+
+fn oneshot<R>() -> (Completer<R>, StaFuture<R>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        value: None,
+        done: false,
+        waker: None,
+    }));
+
+    (Completer(shared.clone()), StaFuture(shared))
+}
+
+impl<R> Completer<R> {
+    fn complete(self, value: R) {
+        if let Ok(mut x) = self.0.lock() {
+            x.value = Some(value);
+        }
+    }
+}
+
+impl<R> Drop for Completer<R> {
+    fn drop(&mut self) {
+        let waker = match self.0.lock() {
+            Ok(mut x) => {
+                x.done = true;
+                x.waker.take()
+            }
+            Err(_) => None,
+        };
+
+        if let Some(x) = waker {
+            x.wake();
+        }
+    }
+}
+
+impl<R> Future for StaFuture<R> {
+    type Output = ComResult<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = match self.0.lock() {
+            Ok(x) => x,
+            Err(_) => return Poll::Ready(Err(winerror::RPC_E_DISCONNECTED.into())),
+        };
+
+        if let Some(x) = shared.value.take() {
+            Poll::Ready(Ok(x))
+        } else if shared.done {
+            Poll::Ready(Err(winerror::RPC_E_DISCONNECTED.into()))
+        } else {
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl StaThread {
+    /// Submits a closure to be run on the worker thread, returns future of its result.
+    pub fn spawn_async<F, R>(&self, f: F) -> StaFuture<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (completer, future) = oneshot();
+        // Receiver isn't needed, result is delivered by completer.
+        let _ = self.spawn(move || completer.complete(f()));
+
+        future
+    }
+}
+
+/// Value without interface pointers, safe to pass between threads.
+struct Transferable(SmartVariant);
+
+// Checked by Transferable::new() and Transferable::from_result().
+unsafe impl Send for Transferable {}
+
+impl Transferable {
+    fn is_transferable(x: &SmartVariant) -> bool {
+        !matches!(
+            x,
+            SmartVariant::IDispatch(_)
+                | SmartVariant::IUnknown(_)
+                | SmartVariant::Variant(_)
+                | SmartVariant::Array(_)
+                | SmartVariant::ByRef(_)
+        )
+    }
+
+    fn new(params: &[SmartVariant]) -> ComResult<Vec<Transferable>> {
+        match params
+            .iter()
+            .position(|x| !Transferable::is_transferable(x))
+        {
+            Some(i) => Err(Transferable::error(
+                winerror::E_INVALIDARG,
+                &params[i],
+                i as u32,
+            )),
+            None => Ok(params.iter().cloned().map(Transferable).collect()),
+        }
+    }
+
+    fn from_result(x: SmartVariant) -> ComResult<Transferable> {
+        if Transferable::is_transferable(&x) {
+            Ok(Transferable(x))
+        } else {
+            Err(Transferable::error(winerror::DISP_E_TYPEMISMATCH, &x, 0))
+        }
+    }
+
+    fn error(hresult: HRESULT, x: &SmartVariant, index: u32) -> RustyWinapiError {
+        RustyWinapiError::Dispatch {
+            hresult,
+            info: ErrorInfo {
+                scode: hresult,
+                description: format!("{} can't be passed to another thread", x.summary()),
+                ..Default::default()
+            },
+            arg_err: index,
+        }
+    }
+}
+
+/// `Send + Sync` handle of an automation object living on an STA worker, with asynchronous late-bound calls.
+///
+/// Object is released on the worker thread when the handle is dropped.
+pub struct AsyncDispatch {
+    sta: Arc<StaThread>,
+    id: u64,
+}
+
+impl AsyncDispatch {
+    /// Creates an object on the worker thread by `factory` and returns its handle.
+    pub fn create<F>(sta: Arc<StaThread>, factory: F) -> impl Future<Output = ComResult<Self>>
+    where
+        F: FnOnce() -> ComResult<AutoCOMInterface<IDispatch>> + Send + 'static,
+    {
+        let id = NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed);
+        let future = sta.spawn_async(move || {
+            factory().map(|x| {
+                OBJECTS.with(|objects| objects.borrow_mut().insert(id, x));
+            })
+        });
+
+        async move {
+            future.await??;
+            Ok(AsyncDispatch { sta, id })
+        }
+    }
+
+    /// Worker thread the object lives on.
+    #[inline]
+    pub fn sta(&self) -> &Arc<StaThread> {
+        &self.sta
+    }
+
+    /// Runs `f` with the object on the worker thread.
+    pub fn with<F, R>(&self, f: F) -> StaFuture<ComResult<R>>
+    where
+        F: FnOnce(&mut AutoCOMInterface<IDispatch>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let id = self.id;
+        self.sta.spawn_async(move || {
+            OBJECTS.with(|objects| match objects.borrow_mut().get_mut(&id) {
+                Some(x) => Ok(f(x)),
+                None => Err(winerror::RPC_E_DISCONNECTED.into()),
+            })
+        })
+    }
+
+    /// Calls a method asynchronously, see [`SmartIDispatch::call`].
+    ///
+    /// [`SmartIDispatch::call`]: ../smart_idispatch/trait.SmartIDispatch.html#method.call
+    pub fn call_async(
+        &self,
+        method: &str,
+        params: &[SmartVariant],
+    ) -> impl Future<Output = DispatchResult<SmartVariant>> {
+        let method = method.to_string();
+        let params = Transferable::new(params);
+        self.dispatch(move |x| {
+            let params: Vec<SmartVariant> = params?.into_iter().map(|x| x.0).collect();
+            x.call(&method, &params)
+        })
+    }
+
+    /// Gets a property value asynchronously, see [`SmartIDispatch::get`].
+    ///
+    /// [`SmartIDispatch::get`]: ../smart_idispatch/trait.SmartIDispatch.html#method.get
+    pub fn get_async(&self, property: &str) -> impl Future<Output = DispatchResult<SmartVariant>> {
+        let property = property.to_string();
+        self.dispatch(move |x| x.get(&property))
+    }
+
+    /// Sets a property value asynchronously, see [`SmartIDispatch::put`].
+    ///
+    /// [`SmartIDispatch::put`]: ../smart_idispatch/trait.SmartIDispatch.html#method.put
+    pub fn put_async(
+        &self,
+        property: &str,
+        value: SmartVariant,
+    ) -> impl Future<Output = DispatchResult<SmartVariant>> {
+        let property = property.to_string();
+        let value = Transferable::new(std::slice::from_ref(&value));
+        self.dispatch(move |x| {
+            let value = value?.pop().map(|x| x.0).unwrap_or(SmartVariant::Empty);
+            x.put(&property, value)
+        })
+    }
+
+    /// Calls a method returning an object asynchronously, returns handle of the object.
+    pub fn call_object_async(
+        &self,
+        method: &str,
+        params: &[SmartVariant],
+    ) -> impl Future<Output = DispatchResult<AsyncDispatch>> {
+        let sta = self.sta.clone();
+        let child = NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed);
+        let method = method.to_string();
+        let params = Transferable::new(params);
+        let future = self.with(move |x| {
+            let params: Vec<SmartVariant> = params?.into_iter().map(|x| x.0).collect();
+            let result = x.call(&method, &params)?;
+            match AutoCOMInterface::<IDispatch>::try_from(result) {
+                Ok(object) => {
+                    OBJECTS.with(|objects| objects.borrow_mut().insert(child, object));
+                    Ok(())
+                }
+                Err(e) => Err(e.into()),
+            }
+        });
+
+        async move {
+            flatten(future.await)?;
+            Ok(AsyncDispatch { sta, id: child })
+        }
+    }
+
+    fn dispatch<F>(&self, f: F) -> impl Future<Output = DispatchResult<SmartVariant>>
+    where
+        F: FnOnce(&mut AutoCOMInterface<IDispatch>) -> DispatchResult<SmartVariant>
+            + Send
+            + 'static,
+    {
+        let future = self.with(move |x| f(x).and_then(Transferable::from_result));
+        async move { flatten(future.await).map(|x| x.0) }
+    }
+}
+
+impl Drop for AsyncDispatch {
+    fn drop(&mut self) {
+        let id = self.id;
+        let _ = self.sta.spawn(move || {
+            OBJECTS.with(|objects| objects.borrow_mut().remove(&id));
+        });
+    }
+}
+
+fn flatten<T>(x: ComResult<ComResult<DispatchResult<T>>>) -> DispatchResult<T> {
+    x??
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hresult::HResult;
+    use std::task::{RawWaker, RawWakerVTable};
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    #[test]
+    fn test_StaFuture() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (completer, mut future) = oneshot::<i32>();
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        completer.complete(42);
+        assert_eq!(Poll::Ready(Ok(42)), Pin::new(&mut future).poll(&mut cx));
+
+        let (completer, mut future) = oneshot::<i32>();
+        drop(completer);
+        assert_eq!(
+            Poll::Ready(Err(RustyWinapiError::Com(winerror::RPC_E_DISCONNECTED))),
+            Pin::new(&mut future).poll(&mut cx)
+        );
+    }
+
+    #[test]
+    fn test_Transferable() {
+        assert!(Transferable::new(&[SmartVariant::Int4(1)]).is_ok());
+        assert_eq!(
+            Some(HResult::E_INVALIDARG),
+            Transferable::new(&[
+                SmartVariant::Empty,
+                SmartVariant::IDispatch(AutoCOMInterface::default())
+            ])
+            .err()
+            .map(|x| x.hresult())
+        );
+    }
+}
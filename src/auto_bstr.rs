@@ -14,6 +14,8 @@
 
 use std::cell::Cell;
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::marker::PhantomData;
 
 use winapi::shared::ntdef::{NULL, PVOID};
 use winapi::shared::wtypes::BSTR;
@@ -43,6 +45,29 @@ impl AutoBSTR {
     pub fn as_mut_ptr(&mut self) -> *mut BSTR {
         self.0.as_ptr()
     }
+
+    /// Borrows UTF-16 characters of a string (without terminating NULL) as a slice, or `None` if BSTR is NULL.
+    pub fn as_u16_slice(&self) -> Option<&[u16]> {
+        let bstr = self.0.get();
+        if bstr.is_null() {
+            None
+        } else {
+            unsafe {
+                Some(std::slice::from_raw_parts(
+                    bstr,
+                    SysStringLen(bstr) as usize,
+                ))
+            }
+        }
+    }
+
+    /// Borrows AutoBSTR instance as a non-owning [`BStr`] view.
+    ///
+    /// [`BStr`]: struct.BStr.html
+    #[inline]
+    pub fn as_bstr(&self) -> BStr<'_> {
+        unsafe { BStr::from_raw(self.0.get()) }
+    }
 }
 
 impl Default for AutoBSTR {
@@ -62,8 +87,7 @@ impl TryFrom<&str> for AutoBSTR {
 
     /// Try to convert string slice into UTF-16 encoded string, and transform it to new BSTR instance.
     fn try_from(x: &str) -> Result<Self, Self::Error> {
-        let utf16_buf: Vec<u16> = x.encode_utf16().collect();
-        Ok(AutoBSTR(Cell::new(SysAllocStringLen(&utf16_buf)?)))
+        Ok(AutoBSTR(Cell::new(SysAllocStringFromStr(x)?)))
     }
 }
 
@@ -81,12 +105,9 @@ impl From<AutoBSTR> for String {
     /// Convert from AutoBSTR instance into UTF-8 encoded Rust String.
     #[inline]
     fn from(x: AutoBSTR) -> Self {
-        let bstr = x.0.get();
-
-        if bstr == std::ptr::null_mut() {
-            "".into()
-        } else {
-            String::from_utf16_lossy(x.try_into().unwrap())
+        match x.as_u16_slice() {
+            Some(x) => String::from_utf16_lossy(x),
+            None => "".into(),
         }
     }
 }
@@ -109,23 +130,101 @@ impl From<AutoBSTR> for BSTR {
     }
 }
 
-impl<'a> TryFrom<AutoBSTR> for &'a [u16] {
-    type Error = ();
+/// Borrowed non-owning view of a BSTR string, relates to [`AutoBSTR`] like `&str` relates to [`String`].
+///
+/// NULL BSTR is a valid empty string by BSTR semantics, so is a NULL `BStr`.
+///
+/// [`AutoBSTR`]: struct.AutoBSTR.html
+/// [`String`]: https://doc.rust-lang.org/std/string/struct.String.html
+#[derive(Clone, Copy)]
+pub struct BStr<'a>(BSTR, PhantomData<&'a [u16]>);
 
-    /// AutoBSTR instance into [u16] slice reference
-    fn try_from(x: AutoBSTR) -> Result<&'a [u16], Self::Error> {
-        let bstr = x.0.get();
-        if bstr != std::ptr::null_mut() {
-            unsafe {
-                Ok(std::slice::from_raw_parts(
-                    bstr,
-                    SysStringLen(bstr) as usize,
-                ))
-            }
+impl<'a> BStr<'a> {
+    /// Wraps existing BSTR instance into BStr view without responsibility to free memory.
+    ///
+    /// # Safety
+    ///
+    /// `bstr` must be NULL or a valid BSTR instance which stays alive and unchanged during lifetime `'a`.
+    #[inline]
+    pub unsafe fn from_raw(bstr: BSTR) -> Self {
+        BStr(bstr, PhantomData)
+    }
+
+    /// Returns underlying BSTR pointer, still owned by someone else.
+    #[inline]
+    pub fn as_raw(&self) -> BSTR {
+        self.0
+    }
+
+    /// Returns the length of a string in UTF-16 characters, not including the terminating NULL character.
+    #[inline]
+    pub fn len(&self) -> usize {
+        SysStringLen(self.0) as usize
+    }
+
+    /// Returns `true` if a string is NULL or has zero length.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns UTF-16 characters of a string (without terminating NULL) as a slice.
+    pub fn as_slice(&self) -> &'a [u16] {
+        if self.0.is_null() {
+            &[]
         } else {
-            Err(())
+            unsafe { std::slice::from_raw_parts(self.0, self.len()) }
         }
     }
+
+    /// Converts into UTF-8 encoded Rust String, replacing invalid UTF-16 with U+FFFD.
+    #[inline]
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
+}
+
+impl<'a> From<&'a AutoBSTR> for BStr<'a> {
+    #[inline]
+    fn from(x: &'a AutoBSTR) -> Self {
+        x.as_bstr()
+    }
+}
+
+impl TryFrom<BStr<'_>> for AutoBSTR {
+    type Error = super::safe::bstr::SysAllocError;
+
+    /// Try to copy borrowed string into a new BSTR instance.
+    #[inline]
+    fn try_from(x: BStr<'_>) -> Result<Self, Self::Error> {
+        Ok(AutoBSTR(Cell::new(SysAllocStringLen(x.as_slice())?)))
+    }
+}
+
+impl PartialEq for BStr<'_> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl PartialEq<str> for BStr<'_> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_slice().iter().copied().eq(other.encode_utf16())
+    }
+}
+
+impl fmt::Debug for BStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl fmt::Display for BStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_string_lossy(), f)
+    }
 }
 
 #[cfg(test)]
@@ -154,4 +253,30 @@ mod tests {
         let bstr: BSTR = auto_bstr.into();
         assert_eq!(0xA5A5A5A5 as BSTR, bstr);
     }
+
+    #[test]
+    fn test_as_u16_slice() {
+        let auto_bstr: AutoBSTR = TEST_LINE.try_into().unwrap();
+        let utf16: Vec<u16> = TEST_LINE.encode_utf16().collect();
+        assert_eq!(Some(utf16.as_slice()), auto_bstr.as_u16_slice());
+
+        assert_eq!(None, AutoBSTR::default().as_u16_slice());
+    }
+
+    #[test]
+    fn test_BStr() {
+        let auto_bstr: AutoBSTR = TEST_LINE.try_into().unwrap();
+        let bstr = auto_bstr.as_bstr();
+        assert_eq!(TEST_LINE.encode_utf16().count(), bstr.len());
+        assert_eq!(TEST_LINE, bstr.to_string_lossy());
+        assert!(bstr == *TEST_LINE);
+
+        let copy = AutoBSTR::try_from(bstr).unwrap();
+        assert!(copy.as_bstr() == bstr);
+        assert_ne!(copy.as_bstr().as_raw(), bstr.as_raw());
+
+        let null = AutoBSTR::default();
+        assert!(null.as_bstr().is_empty());
+        assert_eq!(0, null.as_bstr().as_slice().len());
+    }
 }
@@ -4,20 +4,27 @@
 //!
 
 use std::cell::Cell;
-use std::convert::{AsRef, AsMut, TryFrom, TryInto};
+use std::convert::{AsMut, AsRef, TryFrom, TryInto};
 use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
 
-use winapi::shared::guiddef::{IID_NULL, REFCLSID, REFIID};
+use winapi::shared::guiddef::{IsEqualGUID, CLSID, IID_NULL, REFCLSID, REFIID};
 use winapi::shared::minwindef::{DWORD, LPVOID, PUINT, UINT, WORD};
 use winapi::shared::ntdef::{HRESULT, INT, PULONG, ULONG};
 use winapi::shared::winerror;
 use winapi::shared::wtypes::{BSTR, DATE, VARIANT_BOOL};
-use winapi::um::combaseapi::{CoCreateInstance, CoGetClassObject, CLSCTX_ALL};
+use winapi::um::combaseapi::{
+    CoCreateInstance, CoGetClassObject, CoGetInterfaceAndReleaseStream,
+    CoMarshalInterThreadInterfaceInStream, CoReleaseMarshalData, CLSCTX_ALL,
+};
 use winapi::um::oaidl::{
     IDispatch, IDispatchVtbl, DISPID, DISPID_NEWENUM, DISPPARAMS, EXCEPINFO, LPDISPATCH, LPVARIANT,
     SAFEARRAY, VARIANT,
 };
+use winapi::um::objidlbase::IStream;
 use winapi::um::oleauto::{
     SysStringLen, VariantClear, VariantInit, DISPATCH_METHOD, DISPATCH_PROPERTYGET,
     DISPATCH_PROPERTYPUT,
@@ -26,61 +33,300 @@ use winapi::um::unknwnbase::{IClassFactory, IClassFactoryVtbl, IUnknown, IUnknow
 use winapi::um::winnt::{LOCALE_USER_DEFAULT, LONG, LPCSTR, LPSTR, WCHAR};
 use winapi::{Class, Interface, RIDL};
 
+use crate::activate::{set_proxy_blanket, Activate, AuthIdentity, SecurityBlanket};
+use crate::cls_ctx::ClsCtx;
+use crate::config::Config;
+use crate::error::{ActivationError, ComResult, ConversionError, RustyWinapiError};
+use crate::safe::clsid::CLSIDFromProgID;
+use crate::smart_iunknown::SmartIUnknown;
 use crate::smart_variant::*;
 
-pub struct AutoCOMInterface<T: Interface>(*mut T);
+const NULL_INTERFACE_MESSAGE: &str = "Access to COM interface by uninitialized pointer!";
+
+/// Owning wrapper of a COM interface pointer, releases it on drop.
+///
+/// Wrapper may be empty (see [`Default`]), accessors which can't return an error panic on empty wrapper instead of
+/// dereferencing NULL, use `try_*` counterparts to check it.
+///
+/// [`Default`]: #impl-Default
+#[repr(transparent)]
+pub struct AutoCOMInterface<T: Interface>(Option<NonNull<T>>);
 
 impl<T: Interface> AutoCOMInterface<T> {
+    /// Returns `true` if wrapper doesn't hold any interface pointer.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Returns held interface pointer, still owned by the wrapper, or NULL if wrapper is empty.
+    #[inline]
+    pub fn as_raw(&self) -> *mut T {
+        match self.0 {
+            Some(x) => x.as_ptr(),
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    /// Returns held interface pointer as `LPUNKNOWN`, or NULL if wrapper is empty.
     pub fn as_iunknown_ptr(&self) -> LPUNKNOWN {
-        unsafe { self.0 as LPUNKNOWN }
+        match self.0 {
+            Some(x) => x.as_ptr() as LPUNKNOWN,
+            None => std::ptr::null_mut(),
+        }
     }
 
     pub fn as_iunknown(&self) -> &IUnknown {
-        debug_assert!(
-            self.0 != std::ptr::null_mut(),
-            "Access to COM interface by uninitialized pointer!"
-        );
-        unsafe { &*(self.0 as *const IUnknown) }
+        self.try_as_iunknown().expect(NULL_INTERFACE_MESSAGE)
     }
 
     pub fn as_iunknown_mut(&mut self) -> &mut IUnknown {
-        debug_assert!(
-            self.0 != std::ptr::null_mut(),
-            "Access to COM interface by uninitialized pointer!"
-        );
-        unsafe { &mut *(self.0 as *mut IUnknown) }
+        self.try_as_iunknown_mut().expect(NULL_INTERFACE_MESSAGE)
     }
 
     pub fn as_inner(&self) -> &T {
-        debug_assert!(
-            self.0 != std::ptr::null_mut(),
-            "Access to COM interface by uninitialized pointer!"
-        );
-        unsafe { &*self.0 }
+        self.try_as_inner().expect(NULL_INTERFACE_MESSAGE)
     }
 
     pub fn as_inner_mut(&mut self) -> &mut T {
-        debug_assert!(
-            self.0 != std::ptr::null_mut(),
-            "Access to COM interface by uninitialized pointer!"
-        );
-        unsafe { &mut *self.0 }
+        self.try_as_inner_mut().expect(NULL_INTERFACE_MESSAGE)
     }
 
-    pub fn unwrap(&mut self) -> *mut T {
-        let result = self.0;
-        self.0 = std::ptr::null_mut();
+    pub fn try_as_iunknown(&self) -> Option<&IUnknown> {
+        self.0.map(|x| unsafe { &*(x.as_ptr() as *const IUnknown) })
+    }
+
+    pub fn try_as_iunknown_mut(&mut self) -> Option<&mut IUnknown> {
+        self.0
+            .map(|x| unsafe { &mut *(x.as_ptr() as *mut IUnknown) })
+    }
+
+    pub fn try_as_inner(&self) -> Option<&T> {
+        self.0.map(|x| unsafe { &*x.as_ptr() })
+    }
+
+    pub fn try_as_inner_mut(&mut self) -> Option<&mut T> {
+        self.0.map(|x| unsafe { &mut *x.as_ptr() })
+    }
+
+    /// Returns a new owning reference to IUnknown of the same interface pointer (AddRef, no QueryInterface).
+    ///
+    /// Every COM interface derives from IUnknown, so it's always statically valid. Note that it isn't an identity
+    /// IUnknown of the object, use `query_interface::<IUnknown>()` for identity comparisons.
+    pub fn to_iunknown(&self) -> AutoCOMInterface<IUnknown> {
+        if let Some(x) = self.try_as_iunknown() {
+            unsafe { x.AddRef() };
+        }
+
+        AutoCOMInterface::wrap(self.0.map(|x| x.cast()))
+    }
+
+    /// Reference count of the interface observed by an AddRef/Release probe, `None` for an empty wrapper.
+    ///
+    /// The count is only a hint: it's the count of the proxy for out-of-process objects, other threads may change it
+    /// concurrently and objects aren't obliged to return exact values. Useful to diagnose leaks and double releases.
+    pub fn approx_ref_count(&self) -> Option<ULONG> {
+        self.try_as_iunknown().map(|x| unsafe {
+            x.AddRef();
+            x.Release()
+        })
+    }
+
+    /// Drops wrapper believed to be the last owner of the object.
+    ///
+    /// If [`Config::ref_count_assertions`] is enabled, debug builds panic when Release reports remaining references.
+    ///
+    /// [`Config::ref_count_assertions`]: ../config/struct.Config.html#method.ref_count_assertions
+    pub fn drop_last(mut self) {
+        if let Some(x) = self.try_as_iunknown() {
+            #[cfg(feature = "leak-debug")]
+            crate::leak_debug::release(x as *const IUnknown as usize);
+            let count = unsafe { x.Release() };
+            self.0 = None;
+
+            debug_assert!(
+                count == 0 || !Config::global().ref_count_assertions(),
+                "{} believed to be the last owner still has {} references",
+                std::any::type_name::<T>(),
+                count
+            );
+        }
+    }
+
+    /// Queries object for another interface `U`, see [`SmartIUnknown::query_interface`].
+    ///
+    /// [`SmartIUnknown::query_interface`]: ../smart_iunknown/trait.SmartIUnknown.html#method.query_interface
+    #[inline]
+    pub fn cast<U: Interface>(&self) -> ComResult<AutoCOMInterface<U>> {
+        SmartIUnknown::query_interface::<U>(self)
+    }
+
+    /// Wraps an owned reference, all wrappers holding a pointer are made here.
+    #[inline]
+    fn wrap(x: Option<NonNull<T>>) -> Self {
+        #[cfg(feature = "leak-debug")]
+        if let Some(x) = x {
+            crate::leak_debug::acquire(x.as_ptr() as usize, std::any::type_name::<T>());
+        }
+
+        AutoCOMInterface(x)
+    }
+
+    /// Wraps existing interface pointer with responsibility to release it on drop, without AddRef.
+    ///
+    /// NULL pointer produces an empty wrapper.
+    ///
+    /// # Safety
+    ///
+    /// `x` must be NULL or a valid interface pointer whose reference is owned by the caller and handed over.
+    #[inline]
+    pub unsafe fn from_raw(x: *mut T) -> Self {
+        AutoCOMInterface::wrap(NonNull::new(x))
+    }
+
+    /// Consumes wrapper and returns held interface pointer without Release, like `Box::into_raw`.
+    ///
+    /// Caller becomes responsible to Release the pointer, e.g. by wrapping it back with [`from_raw`].
+    ///
+    /// [`from_raw`]: #method.from_raw
+    #[inline]
+    pub fn into_raw(mut self) -> *mut T {
+        self.take_raw()
+    }
+
+    /// Takes held interface pointer out of wrapper, leaving it empty. Reference count isn't changed,
+    /// caller becomes responsible to Release the pointer.
+    #[inline]
+    pub fn take_raw(&mut self) -> *mut T {
+        match self.0.take() {
+            Some(x) => {
+                #[cfg(feature = "leak-debug")]
+                crate::leak_debug::release(x.as_ptr() as usize);
+                x.as_ptr()
+            }
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    /// Marshals held interface into a stream for one-shot hand-off to another apartment (thread).
+    ///
+    /// Returned token is `Send`, pass it to a target thread and call [`unmarshal_from_stream`] there. It's a lighter
+    /// alternative to the Global Interface Table when interface is needed in the other apartment only once.
+    ///
+    /// [`unmarshal_from_stream`]: #method.unmarshal_from_stream
+    pub fn marshal_to_stream(&self) -> ComResult<MarshaledInterface<T>> {
+        if self.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        let mut stream: *mut IStream = std::ptr::null_mut();
+        let hresult = unsafe {
+            CoMarshalInterThreadInterfaceInStream(
+                &<T as winapi::Interface>::uuidof(),
+                self.as_iunknown_ptr(),
+                &mut stream,
+            )
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            NonNull::new(stream)
+                .map(|x| MarshaledInterface {
+                    stream: x,
+                    _interface: PhantomData,
+                })
+                .ok_or_else(|| winerror::E_POINTER.into())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Unmarshals interface from a token made by [`marshal_to_stream`] into the current apartment.
+    ///
+    /// Token is consumed and its stream released whether unmarshaling succeeds or not.
+    ///
+    /// [`marshal_to_stream`]: #method.marshal_to_stream
+    pub fn unmarshal_from_stream(token: MarshaledInterface<T>) -> ComResult<AutoCOMInterface<T>> {
+        let stream = token.stream.as_ptr();
+        std::mem::forget(token);
+
+        let mut pvoid: LPVOID = std::ptr::null_mut();
+        let hresult = unsafe {
+            CoGetInterfaceAndReleaseStream(stream, &<T as winapi::Interface>::uuidof(), &mut pvoid)
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            NonNull::new(pvoid as *mut T)
+                .map(|x| AutoCOMInterface::wrap(Some(x)))
+                .ok_or_else(|| winerror::E_POINTER.into())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Sets authentication parameters of the held proxy via CoSetProxyBlanket, e.g. for WMI or remote DCOM servers.
+    ///
+    /// COM keeps the `identity` pointer for subsequent calls through the proxy, so it must outlive the proxy.
+    ///
+    /// # Errors
+    ///
+    /// * If wrapper is empty, returns `E_POINTER`.
+    /// * If interface isn't a proxy, returns `E_NOINTERFACE`.
+    /// * Otherwise returns HRESULT of CoSetProxyBlanket.
+    pub fn set_security_blanket(
+        &self,
+        blanket: &SecurityBlanket,
+        identity: Option<&'static AuthIdentity>,
+    ) -> ComResult<()> {
+        if self.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        Ok(set_proxy_blanket(
+            self.as_iunknown_ptr(),
+            blanket,
+            identity,
+        )?)
+    }
+
+    /// Returns vtable of the held interface, tied to lifetime of the wrapper.
+    ///
+    /// Intended for direct slot calls in performance-sensitive code, slots still must be called with
+    /// the interface pointer as the first argument.
+    ///
+    /// # Panics
+    ///
+    /// Panics if wrapper is empty.
+    pub fn vtbl(&self) -> &T::Vtbl
+    where
+        T: InterfaceVtbl,
+    {
+        self.try_vtbl().expect(NULL_INTERFACE_MESSAGE)
+    }
+
+    /// Returns vtable of the held interface, or `None` if wrapper is empty.
+    pub fn try_vtbl(&self) -> Option<&T::Vtbl>
+    where
+        T: InterfaceVtbl,
+    {
+        // Every interface pointer points to a pointer to its vtable.
+        self.0
+            .map(|x| unsafe { &**(x.as_ptr() as *const *const T::Vtbl) })
+    }
 
-        result
+    #[deprecated(note = "renamed to `take_raw`, or use `into_raw` to consume the wrapper")]
+    #[inline]
+    pub fn unwrap(&mut self) -> *mut T {
+        self.take_raw()
     }
 
-    pub fn get_class_object(
+    pub fn get_class_object<C: Into<ClsCtx>>(
         rclsid: REFCLSID,
-        dwClsContext: DWORD,
+        dwClsContext: C,
         pvReserved: LPVOID,
-    ) -> Result<AutoCOMInterface<T>, HRESULT> {
+    ) -> ComResult<AutoCOMInterface<T>> {
+        let dwClsContext = dwClsContext.into().bits();
         let mut pvoid: LPVOID = std::ptr::null_mut();
-        let hresult = unsafe {
+        let hresult = Config::global().retry_policy().run(|| unsafe {
             CoGetClassObject(
                 rclsid,
                 dwClsContext,
@@ -88,22 +334,37 @@ impl<T: Interface> AutoCOMInterface<T> {
                 &<T as winapi::Interface>::uuidof(),
                 &mut pvoid,
             )
-        };
+        });
 
         if winerror::SUCCEEDED(hresult) {
-            Ok(AutoCOMInterface(pvoid as *mut T))
+            NonNull::new(pvoid as *mut T)
+                .map(|x| AutoCOMInterface::wrap(Some(x)))
+                .ok_or_else(|| winerror::E_POINTER.into())
         } else {
-            Err(hresult)
+            Err(hresult.into())
         }
     }
 
-    pub fn create_instance(
+    /// Creates an object of class `rclsid` and returns its interface `T`, via CoCreateInstance.
+    ///
+    /// Non-NULL `pUnkOuter` creates an object aggregated by it, then `T` must be IUnknown (the inner, non-delegating
+    /// IUnknown is returned), otherwise `CLASS_E_NOAGGREGATION` is returned without calling COM. See also
+    /// [`create_aggregated`].
+    ///
+    /// [`create_aggregated`]: #method.create_aggregated
+    pub fn create_instance<C: Into<ClsCtx>>(
         rclsid: REFCLSID,
         pUnkOuter: LPUNKNOWN,
-        dwClsContext: DWORD,
-    ) -> Result<AutoCOMInterface<T>, HRESULT> {
+        dwClsContext: C,
+    ) -> ComResult<AutoCOMInterface<T>> {
+        if !pUnkOuter.is_null() && !IsEqualGUID(&T::uuidof(), &IUnknown::uuidof()) {
+            return Err(winerror::CLASS_E_NOAGGREGATION.into());
+        }
+
+        let dwClsContext = dwClsContext.into().bits();
         let mut pvoid: LPVOID = std::ptr::null_mut();
-        let hresult = unsafe {
+        let start = crate::trace::start();
+        let hresult = Config::global().retry_policy().run(|| unsafe {
             CoCreateInstance(
                 rclsid,
                 pUnkOuter,
@@ -111,28 +372,207 @@ impl<T: Interface> AutoCOMInterface<T> {
                 &<T as winapi::Interface>::uuidof(),
                 &mut pvoid,
             )
-        };
+        });
+        crate::trace::create_instance(rclsid, &<T as winapi::Interface>::uuidof(), hresult, start);
 
         if winerror::SUCCEEDED(hresult) {
-            Ok(AutoCOMInterface(pvoid as *mut T))
+            NonNull::new(pvoid as *mut T)
+                .map(|x| AutoCOMInterface::wrap(Some(x)))
+                .ok_or_else(|| winerror::E_POINTER.into())
         } else {
-            Err(hresult)
+            Err(hresult.into())
+        }
+    }
+
+    /// Creates an object by its ProgID, e.g. `"Excel.Application"` or `"V83.COMConnector"`.
+    ///
+    /// # Errors
+    ///
+    /// * If ProgID isn't registered, returns [`ActivationError::ProgIdNotFound`].
+    /// * Otherwise returns [`ActivationError::Activation`] with HRESULT of CoCreateInstance.
+    ///
+    /// Both are wrapped into `RustyWinapiError::Activation`.
+    ///
+    /// [`ActivationError::ProgIdNotFound`]: ../error/enum.ActivationError.html#variant.ProgIdNotFound
+    /// [`ActivationError::Activation`]: ../error/enum.ActivationError.html#variant.Activation
+    pub fn create_instance_by_progid<C: Into<ClsCtx>>(
+        progid: &str,
+        dwClsContext: C,
+    ) -> ComResult<AutoCOMInterface<T>> {
+        let clsid = CLSIDFromProgID(progid).map_err(|hresult| ActivationError::ProgIdNotFound {
+            progid: progid.into(),
+            hresult,
+        })?;
+
+        Self::create_instance(&clsid, std::ptr::null_mut(), dwClsContext)
+            .map_err(|e| ActivationError::Activation(e.hresult().into()).into())
+    }
+
+    /// Creates an object on a remote machine `hostname` via CoCreateInstanceEx.
+    ///
+    /// If `credentials` are given, they are passed to the server for activation and set on the returned proxy, so
+    /// they must outlive it. Otherwise the process token is used.
+    ///
+    /// # Errors
+    ///
+    /// * If activation failed, returns HRESULT of CoCreateInstanceEx, e.g. `E_ACCESSDENIED` or
+    ///   `HRESULT_FROM_WIN32(RPC_S_SERVER_UNAVAILABLE)` when the machine can't be reached.
+    /// * If the object doesn't support interface `T`, returns `E_NOINTERFACE`.
+    /// * Otherwise returns HRESULT of CoSetProxyBlanket.
+    pub fn create_instance_remote<C: Into<ClsCtx>>(
+        clsid: &CLSID,
+        hostname: &str,
+        credentials: Option<&'static AuthIdentity>,
+        dwClsContext: C,
+    ) -> ComResult<AutoCOMInterface<T>>
+    where
+        T: 'static,
+    {
+        let activate = Activate::<T>::new()
+            .clsid(clsid)
+            .cls_context(dwClsContext)
+            .server(hostname);
+
+        match credentials {
+            Some(x) => Ok(activate.credentials(x).create()?),
+            None => Ok(activate.create()?),
         }
     }
 }
 
+/// Interface marshaled by [`AutoCOMInterface::marshal_to_stream`], waiting to be unmarshaled in another apartment.
+///
+/// Dropping a token without unmarshaling releases marshal data, so the object isn't kept alive by it.
+///
+/// [`AutoCOMInterface::marshal_to_stream`]: struct.AutoCOMInterface.html#method.marshal_to_stream
+pub struct MarshaledInterface<T: Interface> {
+    stream: NonNull<IStream>,
+    _interface: PhantomData<T>,
+}
+
+// Marshal data stream is free-threaded and exists exactly to be passed between apartments.
+unsafe impl<T: Interface> Send for MarshaledInterface<T> {}
+
+impl<T: Interface> MarshaledInterface<T> {
+    /// Unmarshals interface into the current apartment, same as [`AutoCOMInterface::unmarshal_from_stream`].
+    ///
+    /// [`AutoCOMInterface::unmarshal_from_stream`]: struct.AutoCOMInterface.html#method.unmarshal_from_stream
+    #[inline]
+    pub fn unmarshal(self) -> ComResult<AutoCOMInterface<T>> {
+        AutoCOMInterface::unmarshal_from_stream(self)
+    }
+}
+
+impl<T: Interface> Drop for MarshaledInterface<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let stream = self.stream.as_ptr();
+            CoReleaseMarshalData(stream);
+            (*stream).Release();
+        }
+    }
+}
+
+/// Binds interface to its vtable struct, allows [`AutoCOMInterface::vtbl`].
+///
+/// Use [`impl_interface_vtbl!`] to implement it for interfaces declared with `RIDL!`.
+///
+/// # Safety
+///
+/// `Vtbl` must be the vtable struct of the interface, e.g. `IDispatchVtbl` for `IDispatch`.
+///
+/// [`AutoCOMInterface::vtbl`]: struct.AutoCOMInterface.html#method.vtbl
+/// [`impl_interface_vtbl!`]: ../macro.impl_interface_vtbl.html
+pub unsafe trait InterfaceVtbl: Interface {
+    type Vtbl;
+}
+
+/// Implements [`InterfaceVtbl`] for interfaces, e.g. `impl_interface_vtbl!(IMyDual => IMyDualVtbl);`.
+///
+/// [`InterfaceVtbl`]: auto_com_interface/trait.InterfaceVtbl.html
+#[macro_export]
+macro_rules! impl_interface_vtbl {
+    ($($interface:ty => $vtbl:ty),* $(,)?) => {
+        $(
+            unsafe impl $crate::auto_com_interface::InterfaceVtbl for $interface {
+                type Vtbl = $vtbl;
+            }
+        )*
+    };
+}
+
+impl_interface_vtbl!(
+    IUnknown => IUnknownVtbl,
+    IDispatch => IDispatchVtbl,
+    IClassFactory => IClassFactoryVtbl,
+);
+
+impl AutoCOMInterface<IUnknown> {
+    /// Creates an object of class `clsid` aggregated by `outer` and returns its inner (non-delegating) IUnknown.
+    ///
+    /// Outer object keeps the inner IUnknown while alive and hands out interfaces of the inner object obtained with
+    /// QueryInterface of it, their IUnknown methods delegate to `outer`. Inner object doesn't hold a reference to
+    /// `outer`.
+    ///
+    /// # Errors
+    ///
+    /// * If `outer` is NULL, returns `E_POINTER`.
+    /// * If class doesn't support aggregation, returns `CLASS_E_NOAGGREGATION`.
+    pub fn create_aggregated<C: Into<ClsCtx>>(
+        clsid: &CLSID,
+        outer: LPUNKNOWN,
+        cls_context: C,
+    ) -> ComResult<Self> {
+        if outer.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        Self::create_instance(clsid, outer, cls_context)
+    }
+}
+
 impl<T: Interface> Default for AutoCOMInterface<T> {
     fn default() -> Self {
-        AutoCOMInterface::<T>(std::ptr::null_mut())
+        AutoCOMInterface::<T>(None)
+    }
+}
+
+impl<T: Interface> Clone for AutoCOMInterface<T> {
+    /// Returns a new owning reference to the same interface pointer (AddRef).
+    fn clone(&self) -> Self {
+        if let Some(x) = self.try_as_iunknown() {
+            unsafe { x.AddRef() };
+        }
+
+        AutoCOMInterface::wrap(self.0)
+    }
+}
+
+impl<T: Interface> fmt::Debug for AutoCOMInterface<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AutoCOMInterface<{}>({:p})",
+            std::any::type_name::<T>(),
+            self.as_raw()
+        )
     }
 }
 
 impl<T: Interface> Drop for AutoCOMInterface<T> {
     fn drop(&mut self) {
-        if self.0 != std::ptr::null_mut() {
-            unsafe {
-                self.as_iunknown().Release();
-            }
+        if let Some(x) = self.try_as_iunknown() {
+            #[cfg(feature = "leak-debug")]
+            crate::leak_debug::release(x as *const IUnknown as usize);
+            let count = unsafe { x.Release() };
+
+            // Counts wrapped below zero mean the object was released more times than it was referenced.
+            debug_assert!(
+                count < 0x8000_0000 || !Config::global().ref_count_assertions(),
+                "{} released more times than referenced, Release returned 0x{:X}",
+                std::any::type_name::<T>(),
+                count
+            );
         }
     }
 }
@@ -141,25 +581,38 @@ impl<T: Interface> Deref for AutoCOMInterface<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { &*self.0 }
+        self.as_inner()
     }
 }
 
 impl<T: Interface> DerefMut for AutoCOMInterface<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { &mut *self.0 }
+        self.as_inner_mut()
     }
 }
 
 impl<T: Interface> AsRef<T> for AutoCOMInterface<T> {
-    fn as_ref(&self) ->&T {
-        unsafe{ &*self.0 }
+    fn as_ref(&self) -> &T {
+        self.as_inner()
     }
 }
 
 impl<T: Interface> AsMut<T> for AutoCOMInterface<T> {
     fn as_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.0 }
+        self.as_inner_mut()
+    }
+}
+
+impl<T: Interface, U: Interface> PartialEq<AutoCOMInterface<U>> for AutoCOMInterface<T> {
+    /// COM identity comparison, see [`SmartIUnknown::is_same_object`]. Empty wrappers are equal to each other only.
+    ///
+    /// [`SmartIUnknown::is_same_object`]: ../smart_iunknown/trait.SmartIUnknown.html#method.is_same_object
+    fn eq(&self, other: &AutoCOMInterface<U>) -> bool {
+        match (self.is_null(), other.is_null()) {
+            (true, true) => true,
+            (false, false) => self.is_same_object(other),
+            _ => false,
+        }
     }
 }
 
@@ -167,36 +620,43 @@ impl<T: Interface> TryFrom<*mut T> for AutoCOMInterface<T> {
     type Error = &'static str;
 
     fn try_from(x: *mut T) -> Result<Self, Self::Error> {
-        if x != std::ptr::null_mut() {
-            Ok(AutoCOMInterface(x))
-        } else {
-            Err("Can't wrap uninitialized COM interface pointer in AutoCOMInterface!")
+        match NonNull::new(x) {
+            Some(x) => Ok(AutoCOMInterface::wrap(Some(x))),
+            None => Err("Can't wrap uninitialized COM interface pointer in AutoCOMInterface!"),
         }
     }
 }
 
 impl TryFrom<SmartVariant> for AutoCOMInterface<IUnknown> {
-    type Error = &'static str;
+    type Error = ConversionError;
 
-    /// Try to convert string slice into UTF-16 encoded string, and transform it to new BSTR instance.
+    /// Try to take owned IUnknown out of SmartVariant.
     #[inline]
     fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
         match x {
-            SmartVariant::IUnknown(x) => unsafe { AutoCOMInterface::try_from(x) },
-            _ => Err("SmartVartiant doesn't contains pointer to IUnknown!"),
+            SmartVariant::IUnknown(x) if !x.is_null() => Ok(x),
+            x => Err(ConversionError::new(
+                x.summary(),
+                "AutoCOMInterface<IUnknown>",
+            )),
         }
     }
 }
 
 impl TryFrom<SmartVariant> for AutoCOMInterface<IDispatch> {
-    type Error = &'static str;
+    type Error = ConversionError;
 
-    /// Try to convert string slice into UTF-16 encoded string, and transform it to new BSTR instance.
+    /// Try to take owned IDispatch out of SmartVariant, see also [`SmartVariant::into_dispatch`].
+    ///
+    /// [`SmartVariant::into_dispatch`]: ../smart_variant/enum.SmartVariant.html#method.into_dispatch
     #[inline]
     fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
         match x {
-            SmartVariant::IDispatch(x) => unsafe { AutoCOMInterface::try_from(x) },
-            _ => Err("SmartVartiant doesn't contains pointer to IDispatch!"),
+            SmartVariant::IDispatch(x) if !x.is_null() => Ok(x),
+            x => Err(ConversionError::new(
+                x.summary(),
+                "AutoCOMInterface<IDispatch>",
+            )),
         }
     }
 }
@@ -205,6 +665,8 @@ impl TryFrom<SmartVariant> for AutoCOMInterface<IDispatch> {
 mod tests {
     use super::*;
     use crate::auto_bstr::*;
+    use crate::com_apartment::ComApartment;
+    use crate::hresult::HResult;
     use std::convert::TryInto;
 
     // 1C ComConnector (comcntr.dll) class
@@ -222,15 +684,86 @@ mod tests {
     }}
     pub type LPV8COMCONNECTOR = *mut IV8COMConnector;
 
+    #[test]
+    fn test_AutoCOMInterface_null() {
+        let mut empty = AutoCOMInterface::<IDispatch>::default();
+        assert!(empty.is_null());
+        assert!(empty.try_as_inner().is_none());
+        assert!(empty.try_as_iunknown_mut().is_none());
+        assert_eq!(std::ptr::null_mut(), empty.as_iunknown_ptr());
+        assert!(empty == AutoCOMInterface::<IUnknown>::default());
+        assert!(AutoCOMInterface::<IDispatch>::try_from(std::ptr::null_mut()).is_err());
+        assert_eq!(
+            Some(HResult::E_POINTER),
+            empty.marshal_to_stream().err().map(|x| x.hresult())
+        );
+        assert_eq!(
+            Some(HResult::E_POINTER),
+            empty
+                .set_security_blanket(&Default::default(), None)
+                .err()
+                .map(|x| x.hresult())
+        );
+        assert!(unsafe { AutoCOMInterface::<IDispatch>::from_raw(std::ptr::null_mut()) }.is_null());
+        assert!(empty.try_vtbl().is_none());
+        assert_eq!(std::ptr::null_mut(), empty.into_raw());
+    }
+
+    #[test]
+    fn test_AutoCOMInterface_approx_ref_count() {
+        use crate::server::dispatch::DispatchServer;
+        use crate::server::dynamic_object::DynamicObject;
+
+        assert_eq!(
+            None,
+            AutoCOMInterface::<IDispatch>::default().approx_ref_count()
+        );
+        let dispatch = DynamicObject::new().into_dispatch();
+        assert_eq!(Some(1), dispatch.approx_ref_count());
+        let unknown = dispatch.to_iunknown();
+        assert_eq!(Some(2), dispatch.approx_ref_count());
+        drop(unknown);
+        dispatch.drop_last();
+    }
+
+    #[test]
+    fn test_AutoCOMInterface_create_instance_by_progid() {
+        let _com = ComApartment::init_mta().unwrap();
+        match AutoCOMInterface::<IDispatch>::create_instance_by_progid("No.Such.ProgId", CLSCTX_ALL)
+        {
+            Err(RustyWinapiError::Activation(ActivationError::ProgIdNotFound {
+                progid, ..
+            })) => assert_eq!("No.Such.ProgId", progid),
+            _ => panic!("ProgIdNotFound expected"),
+        }
+    }
+
+    #[test]
+    fn test_AutoCOMInterface_create_aggregated() {
+        let _com = ComApartment::init_mta().unwrap();
+        let clsid = <V8COMConnectorClass as Class>::uuidof();
+        assert_eq!(
+            Some(HResult::E_POINTER),
+            AutoCOMInterface::create_aggregated(&clsid, std::ptr::null_mut(), CLSCTX_ALL)
+                .err()
+                .map(|x| x.hresult())
+        );
+
+        // Rejected before CoCreateInstance is called, outer is never dereferenced.
+        let v8cc = AutoCOMInterface::<IV8COMConnector>::create_instance(
+            &clsid,
+            std::ptr::NonNull::dangling().as_ptr(),
+            CLSCTX_ALL,
+        );
+        assert_eq!(
+            Some(HResult::CLASS_E_NOAGGREGATION),
+            v8cc.err().map(|x| x.hresult())
+        );
+    }
+
     // #[test]
     fn test_AutoCOMInterface_create_instance() {
-        let hr = unsafe {
-            winapi::um::combaseapi::CoInitializeEx(
-                winapi::shared::ntdef::NULL,
-                winapi::um::objbase::COINIT_MULTITHREADED,
-            )
-        };
-        assert!(winerror::SUCCEEDED(hr));
+        let _com = ComApartment::init_mta().unwrap();
 
         let v8cc = AutoCOMInterface::<IV8COMConnector>::create_instance(
             &<V8COMConnectorClass as Class>::uuidof(),
@@ -251,7 +784,5 @@ mod tests {
         assert!(winapi::shared::winerror::SUCCEEDED(hr));
 
         let conn1Cdb: AutoCOMInterface<IDispatch> = conn1Cdb.try_into().unwrap();
-
-        unsafe { winapi::um::combaseapi::CoUninitialize() };
     }
 }
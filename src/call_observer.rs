@@ -0,0 +1,131 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Hooks observing IDispatch::Invoke calls, e.g. to export latency and error counters into a metrics system.
+//!
+//! An observer is installed process-wide with [`set_global_observer`] or for calls through one object with
+//! [`SmartIDispatch::observed`], which takes precedence over the global one. Observers are called synchronously on
+//! the calling thread, keep them cheap.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use rusty_winapi::call_observer::{set_global_observer, CallObserver, InvokeCall};
+//! use rusty_winapi::hresult::HResult;
+//!
+//! #[derive(Default)]
+//! struct Failures(AtomicUsize);
+//!
+//! impl CallObserver for Failures {
+//!     fn on_invoke_end(&self, call: &InvokeCall, elapsed: Duration, result: HResult) {
+//!         if result.ok().is_err() {
+//!             self.0.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! set_global_observer(Some(Arc::new(Failures::default())));
+//! ```
+//!
+//! [`set_global_observer`]: fn.set_global_observer.html
+//! [`SmartIDispatch::observed`]: ../smart_idispatch/trait.SmartIDispatch.html#method.observed
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use winapi::shared::minwindef::WORD;
+use winapi::shared::ntdef::LCID;
+use winapi::um::oaidl::DISPID;
+
+use crate::hresult::HResult;
+
+static GLOBAL_OBSERVER: RwLock<Option<Arc<dyn CallObserver>>> = RwLock::new(None);
+
+/// Invoke call reported to a [`CallObserver`].
+///
+/// [`CallObserver`]: trait.CallObserver.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InvokeCall {
+    pub dispid: DISPID,
+    /// `DISPATCH_*` flags.
+    pub flags: WORD,
+    pub lcid: LCID,
+    /// Number of arguments including named ones.
+    pub arg_count: usize,
+    pub named_arg_count: usize,
+}
+
+/// Receiver of Invoke call events.
+pub trait CallObserver: Send + Sync {
+    /// Called before Invoke is made.
+    fn on_invoke_start(&self, call: &InvokeCall) {}
+
+    /// Called after Invoke returns with its duration, including retries of rejected calls, and result.
+    fn on_invoke_end(&self, call: &InvokeCall, elapsed: Duration, result: HResult);
+}
+
+/// Installs process-wide observer, `None` removes it.
+pub fn set_global_observer(observer: Option<Arc<dyn CallObserver>>) {
+    match GLOBAL_OBSERVER.write() {
+        Ok(mut x) => *x = observer,
+        Err(x) => *x.into_inner() = observer,
+    }
+}
+
+/// Returns process-wide observer, if it's installed.
+pub fn global_observer() -> Option<Arc<dyn CallObserver>> {
+    match GLOBAL_OBSERVER.read() {
+        Ok(x) => x.clone(),
+        Err(x) => x.into_inner().clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+    use crate::smart_idispatch::SmartIDispatch;
+    use crate::smart_variant::SmartVariant;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(bool, WORD, usize)>>);
+
+    impl CallObserver for Recorder {
+        fn on_invoke_start(&self, call: &InvokeCall) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((true, call.flags, call.arg_count));
+        }
+
+        fn on_invoke_end(&self, call: &InvokeCall, elapsed: Duration, result: HResult) {
+            assert_eq!(HResult(0), result);
+            self.0
+                .lock()
+                .unwrap()
+                .push((false, call.flags, call.arg_count));
+        }
+    }
+
+    #[test]
+    fn test_CallObserver_observed() {
+        let recorder = Arc::new(Recorder::default());
+        let mut dispatch = DynamicObject::new()
+            .with("Count", SmartVariant::Int4(1))
+            .into_dispatch();
+
+        dispatch
+            .observed(recorder.clone())
+            .put("Count", SmartVariant::Int4(2))
+            .unwrap();
+        assert_eq!(
+            vec![(true, 4, 1), (false, 4, 1)],
+            *recorder.0.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,263 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Cancellation of pending out-of-process calls.
+//!
+//! A call to a stuck server blocks the calling thread forever. A watchdog thread may abort it with
+//! [`cancel_call`], given the id of the blocked thread, provided that the blocked thread enabled cancellation
+//! with [`CallCancellation`] before making the call. Cancelled call fails with `RPC_E_CALL_CANCELED`, see
+//! [`is_cancelled`]. [`CallCancelHandle`] does the same through the ICancelMethodCalls object of the blocked thread,
+//! and [`with_timeout`] aborts calls of a closure which don't complete in time.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use rusty_winapi::cancel::{cancel_call, current_thread_id, is_cancelled, CallCancellation};
+//!
+//! let (tx, rx) = std::sync::mpsc::channel();
+//! let worker = std::thread::spawn(move || {
+//!     let _cancellation = CallCancellation::enable().unwrap();
+//!     tx.send(current_thread_id()).unwrap();
+//!     // ... long out-of-process call, failing with RPC_E_CALL_CANCELED if cancelled ...
+//! });
+//!
+//! let thread_id = rx.recv().unwrap();
+//! std::thread::sleep(Duration::from_secs(30));
+//! let _ = cancel_call(thread_id, Duration::from_secs(0));
+//! ```
+//!
+//! A hung server call aborted after a timeout:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::cancel::with_timeout;
+//! use rusty_winapi::smart_idispatch::SmartIDispatch;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let mut server = Activate::<IDispatch>::new().progid("My.Server").create().unwrap();
+//! let result = with_timeout(Duration::from_secs(30), || server.call("LongOperation", &[])).unwrap();
+//! ```
+//!
+//! [`cancel_call`]: fn.cancel_call.html
+//! [`CallCancelHandle`]: struct.CallCancelHandle.html
+//! [`with_timeout`]: fn.with_timeout.html
+//! [`CallCancellation`]: struct.CallCancellation.html
+//! [`is_cancelled`]: fn.is_cancelled.html
+
+use std::marker::PhantomData;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::{HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::um::combaseapi::{
+    CoCancelCall, CoDisableCallCancellation, CoEnableCallCancellation, CoGetCancelObject,
+};
+use winapi::um::objidlbase::ICancelMethodCalls;
+use winapi::um::processthreadsapi::GetCurrentThreadId;
+use winapi::Interface;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+
+/// Enables cancellation of synchronous calls made by the current thread, disables it on drop.
+///
+/// Guard isn't `Send`, since cancellation is enabled per thread. Guards may be nested.
+pub struct CallCancellation(PhantomData<*mut ()>);
+
+impl CallCancellation {
+    /// Enables call cancellation on the current thread.
+    pub fn enable() -> ComResult<Self> {
+        let hresult = unsafe { CoEnableCallCancellation(std::ptr::null_mut()) };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(CallCancellation(PhantomData))
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+impl Drop for CallCancellation {
+    fn drop(&mut self) {
+        unsafe {
+            CoDisableCallCancellation(std::ptr::null_mut());
+        }
+    }
+}
+
+/// Returns id of the current thread, to be passed to a watchdog which may call [`cancel_call`].
+///
+/// [`cancel_call`]: fn.cancel_call.html
+pub fn current_thread_id() -> DWORD {
+    unsafe { GetCurrentThreadId() }
+}
+
+/// Requests cancellation of a pending outbound call made by the thread `thread_id`.
+///
+/// `timeout` is how long the caller is willing to wait for the server to acknowledge cancellation, before the
+/// call returns to the client with `RPC_E_CALL_CANCELED` anyway.
+///
+/// # Errors
+///
+/// * If thread has no pending call, returns `CO_E_CANCEL_DISABLED` or `RPC_E_CALL_COMPLETE`.
+/// * Otherwise returns HRESULT of CoCancelCall.
+pub fn cancel_call(thread_id: DWORD, timeout: Duration) -> ComResult<()> {
+    let timeout = timeout.as_secs().min(ULONG::MAX as u64) as ULONG;
+    let hresult = unsafe { CoCancelCall(thread_id, timeout) };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(())
+    } else {
+        Err(hresult.into())
+    }
+}
+
+/// Handle of a thread whose pending outbound call may be cancelled from any other thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallCancelHandle {
+    thread_id: DWORD,
+}
+
+impl CallCancelHandle {
+    /// Handle of the current thread. Cancellation must be enabled with [`CallCancellation`] before the call.
+    ///
+    /// [`CallCancellation`]: struct.CallCancellation.html
+    pub fn current() -> Self {
+        CallCancelHandle {
+            thread_id: current_thread_id(),
+        }
+    }
+
+    /// Id of the thread.
+    #[inline]
+    pub fn thread_id(&self) -> DWORD {
+        self.thread_id
+    }
+
+    /// Cancels the pending call via ICancelMethodCalls, waiting up to `timeout` for the server to acknowledge.
+    ///
+    /// # Errors
+    ///
+    /// * If thread has no pending call, returns HRESULT of CoGetCancelObject, e.g. `RPC_E_CALL_COMPLETE`.
+    /// * Otherwise returns HRESULT of ICancelMethodCalls::Cancel.
+    pub fn cancel(&self, timeout: Duration) -> ComResult<()> {
+        let mut cancel: *mut ICancelMethodCalls = std::ptr::null_mut();
+        let hresult = unsafe {
+            CoGetCancelObject(
+                self.thread_id,
+                &ICancelMethodCalls::uuidof(),
+                &mut cancel as *mut *mut ICancelMethodCalls as *mut _,
+            )
+        };
+        if !winerror::SUCCEEDED(hresult) {
+            return Err(hresult.into());
+        }
+        if cancel.is_null() {
+            return Err(winerror::RPC_E_CALL_COMPLETE.into());
+        }
+
+        let cancel = unsafe { AutoCOMInterface::from_raw(cancel) };
+        let timeout = timeout.as_secs().min(ULONG::MAX as u64) as ULONG;
+        let hresult = unsafe { cancel.as_inner().Cancel(timeout) };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Starts a watchdog cancelling the pending call once `timeout` elapses, unless the watchdog is dropped before.
+    pub fn cancel_after(&self, timeout: Duration) -> CancelWatchdog {
+        let handle = *self;
+        let (stop, stopped) = channel::<()>();
+        let thread = std::thread::spawn(move || match stopped.recv_timeout(timeout) {
+            Err(RecvTimeoutError::Timeout) => handle.cancel(Duration::from_secs(0)).is_ok(),
+            _ => false,
+        });
+
+        CancelWatchdog {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Watchdog thread started by [`CallCancelHandle::cancel_after`], stopped on drop.
+///
+/// [`CallCancelHandle::cancel_after`]: struct.CallCancelHandle.html#method.cancel_after
+pub struct CancelWatchdog {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<bool>>,
+}
+
+impl CancelWatchdog {
+    /// Stops the watchdog, returns `true` if it has cancelled a call.
+    pub fn stop(mut self) -> bool {
+        self.join()
+    }
+
+    fn join(&mut self) -> bool {
+        if let Some(x) = self.stop.take() {
+            let _ = x.send(());
+        }
+
+        match self.thread.take() {
+            Some(x) => x.join().unwrap_or(false),
+            None => false,
+        }
+    }
+}
+
+impl Drop for CancelWatchdog {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/// Runs `f` on the current thread with call cancellation enabled, cancelling its pending call after `timeout`.
+///
+/// Calls cancelled by timeout fail with `RustyWinapiError::Cancelled`, which `f` is expected to return.
+///
+/// # Errors
+///
+/// Returns error of failed CoEnableCallCancellation.
+pub fn with_timeout<F, R>(timeout: Duration, f: F) -> ComResult<R>
+where
+    F: FnOnce() -> R,
+{
+    let _cancellation = CallCancellation::enable()?;
+    let _watchdog = CallCancelHandle::current().cancel_after(timeout);
+    Ok(f())
+}
+
+/// Returns `true` if a call failed because it was cancelled.
+#[inline]
+pub fn is_cancelled(hresult: HRESULT) -> bool {
+    hresult == winerror::RPC_E_CALL_CANCELED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_cancelled() {
+        assert!(is_cancelled(winerror::RPC_E_CALL_CANCELED));
+        assert!(!is_cancelled(winerror::RPC_E_CALL_REJECTED));
+    }
+
+    #[test]
+    fn test_CallCancelHandle_cancel_after() {
+        let _com = crate::com_apartment::ComApartment::init_mta().unwrap();
+        assert_eq!(current_thread_id(), CallCancelHandle::current().thread_id());
+
+        let watchdog = CallCancelHandle::current().cancel_after(Duration::from_secs(60));
+        assert!(!watchdog.stop());
+        assert_eq!(Ok(42), with_timeout(Duration::from_secs(60), || 42));
+    }
+}
@@ -0,0 +1,212 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Publishing class factories of a local (EXE) server.
+//!
+//! An EXE server registers a class factory per class it serves with CoRegisterClassObject when it's started by COM
+//! (with `-Embedding` argument) and revokes them before exit. [`ClassObjectRegistration`] revokes its registration on
+//! drop. Classes are usually registered suspended and then made available all at once by
+//! [`resume_class_objects`], so clients can't connect to a half-initialized server.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::auto_com_interface::AutoCOMInterface;
+//! use rusty_winapi::class_object::{
+//!     resume_class_objects, ClassObjectRegistration, REGCLS_MULTIPLEUSE, REGCLS_SUSPENDED,
+//! };
+//! use winapi::um::unknwnbase::IClassFactory;
+//!
+//! # fn my_factory() -> AutoCOMInterface<IClassFactory> { unimplemented!() }
+//! # let clsid = unsafe { std::mem::zeroed() };
+//! let factory = my_factory();
+//! let _registration =
+//!     ClassObjectRegistration::local_server(&factory, &clsid, REGCLS_MULTIPLEUSE | REGCLS_SUSPENDED)
+//!         .unwrap();
+//! resume_class_objects().unwrap();
+//! // ... pump messages until the last object is released ...
+//! ```
+//!
+//! [`ClassObjectRegistration`]: struct.ClassObjectRegistration.html
+//! [`resume_class_objects`]: fn.resume_class_objects.html
+
+use std::marker::PhantomData;
+
+use winapi::shared::guiddef::CLSID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::ULONG;
+use winapi::shared::winerror;
+use winapi::um::combaseapi::{
+    CoAddRefServerProcess, CoRegisterClassObject, CoReleaseServerProcess, CoResumeClassObjects,
+    CoRevokeClassObject, CoSuspendClassObjects,
+};
+use winapi::Interface;
+
+pub use winapi::um::combaseapi::{
+    REGCLS_AGILE, REGCLS_MULTIPLEUSE, REGCLS_MULTI_SEPARATE, REGCLS_SINGLEUSE, REGCLS_SURROGATE,
+    REGCLS_SUSPENDED,
+};
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::cls_ctx::ClsCtx;
+use crate::error::ComResult;
+
+/// Registration of a class object, revoked on drop.
+///
+/// Registration isn't `Send`, it should be revoked in the apartment which registered it.
+pub struct ClassObjectRegistration {
+    cookie: Option<DWORD>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl ClassObjectRegistration {
+    /// Registers `factory` (usually IClassFactory) as the class object of `clsid` via CoRegisterClassObject.
+    ///
+    /// `flags` are `REGCLS_*` values.
+    ///
+    /// # Errors
+    ///
+    /// * If wrapper is empty, returns `E_POINTER`.
+    /// * Otherwise returns HRESULT of CoRegisterClassObject, e.g. `CO_E_OBJISREG` if class is already registered.
+    pub fn register<T: Interface, C: Into<ClsCtx>>(
+        factory: &AutoCOMInterface<T>,
+        clsid: &CLSID,
+        cls_context: C,
+        flags: DWORD,
+    ) -> ComResult<Self> {
+        if factory.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        let mut cookie: DWORD = 0;
+        let hresult = unsafe {
+            CoRegisterClassObject(
+                clsid,
+                factory.as_iunknown_ptr(),
+                cls_context.into().bits(),
+                flags,
+                &mut cookie,
+            )
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(ClassObjectRegistration {
+                cookie: Some(cookie),
+                _not_send: PhantomData,
+            })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Registers `factory` for out-of-process clients (`CLSCTX_LOCAL_SERVER`).
+    pub fn local_server<T: Interface>(
+        factory: &AutoCOMInterface<T>,
+        clsid: &CLSID,
+        flags: DWORD,
+    ) -> ComResult<Self> {
+        Self::register(factory, clsid, ClsCtx::LOCAL_SERVER, flags)
+    }
+
+    /// Registration cookie.
+    pub fn cookie(&self) -> DWORD {
+        self.cookie.unwrap_or(0)
+    }
+
+    /// Revokes registration, unlike drop reports failure.
+    pub fn revoke(mut self) -> ComResult<()> {
+        self.revoke_registration()
+    }
+
+    fn revoke_registration(&mut self) -> ComResult<()> {
+        let hresult = match self.cookie.take() {
+            Some(x) => unsafe { CoRevokeClassObject(x) },
+            None => winerror::S_OK,
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(())
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+impl Drop for ClassObjectRegistration {
+    fn drop(&mut self) {
+        let _ = self.revoke_registration();
+    }
+}
+
+/// Makes class objects registered with `REGCLS_SUSPENDED` available to clients, via CoResumeClassObjects.
+pub fn resume_class_objects() -> ComResult<()> {
+    let hresult = unsafe { CoResumeClassObjects() };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(())
+    } else {
+        Err(hresult.into())
+    }
+}
+
+/// Stops serving activation requests for all registered class objects, via CoSuspendClassObjects.
+///
+/// Server calls it when its reference count drops to zero, before shutdown.
+pub fn suspend_class_objects() -> ComResult<()> {
+    let hresult = unsafe { CoSuspendClassObjects() };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(())
+    } else {
+        Err(hresult.into())
+    }
+}
+
+/// Increments the global per-process reference count, returns the new count.
+#[inline]
+pub fn add_ref_server_process() -> ULONG {
+    unsafe { CoAddRefServerProcess() }
+}
+
+/// Decrements the global per-process reference count, returns the new count.
+///
+/// When it becomes zero, class objects are suspended automatically and server should exit.
+#[inline]
+pub fn release_server_process() -> ULONG {
+    unsafe { CoReleaseServerProcess() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_apartment::ComApartment;
+    use crate::error::RustyWinapiError;
+    use crate::safe::guid::Guid;
+    use winapi::um::unknwnbase::IUnknown;
+
+    #[test]
+    fn test_ClassObjectRegistration_register() {
+        let _com = ComApartment::init_mta().unwrap();
+        let clsid: CLSID = Guid::generate().unwrap().into();
+        assert_eq!(
+            Some(RustyWinapiError::Com(winerror::E_POINTER)),
+            ClassObjectRegistration::register(
+                &AutoCOMInterface::<IUnknown>::default(),
+                &clsid,
+                ClsCtx::INPROC_SERVER,
+                REGCLS_MULTIPLEUSE
+            )
+            .err()
+        );
+
+        let object = crate::running_object::running_object_table().unwrap();
+        let registration = ClassObjectRegistration::register(
+            &object,
+            &clsid,
+            ClsCtx::INPROC_SERVER,
+            REGCLS_MULTIPLEUSE,
+        )
+        .unwrap();
+        assert_ne!(0, registration.cookie());
+        registration.revoke().unwrap();
+    }
+}
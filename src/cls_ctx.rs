@@ -0,0 +1,203 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Typed server context flags (CLSCTX).
+//!
+//! [`ClsCtx`] is a bit set of `CLSCTX_*` values accepted wherever a server context is expected. Raw `DWORD` values
+//! convert into it, so existing code passing `CLSCTX_ALL` and the like keeps working.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::auto_com_interface::AutoCOMInterface;
+//! use rusty_winapi::cls_ctx::ClsCtx;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! # let clsid = unsafe { std::mem::zeroed() };
+//! let object = AutoCOMInterface::<IDispatch>::create_instance(
+//!     &clsid,
+//!     std::ptr::null_mut(),
+//!     ClsCtx::LOCAL_SERVER | ClsCtx::ENABLE_CLOAKING,
+//! );
+//! ```
+//!
+//! [`ClsCtx`]: struct.ClsCtx.html
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::wtypesbase::*;
+use winapi::um::combaseapi::{CLSCTX_ALL, CLSCTX_INPROC, CLSCTX_SERVER};
+
+/// Set of `CLSCTX_*` flags.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ClsCtx(DWORD);
+
+impl ClsCtx {
+    pub const INPROC_SERVER: ClsCtx = ClsCtx(CLSCTX_INPROC_SERVER);
+    pub const INPROC_HANDLER: ClsCtx = ClsCtx(CLSCTX_INPROC_HANDLER);
+    pub const LOCAL_SERVER: ClsCtx = ClsCtx(CLSCTX_LOCAL_SERVER);
+    pub const REMOTE_SERVER: ClsCtx = ClsCtx(CLSCTX_REMOTE_SERVER);
+    pub const NO_CODE_DOWNLOAD: ClsCtx = ClsCtx(CLSCTX_NO_CODE_DOWNLOAD);
+    pub const NO_CUSTOM_MARSHAL: ClsCtx = ClsCtx(CLSCTX_NO_CUSTOM_MARSHAL);
+    pub const ENABLE_CODE_DOWNLOAD: ClsCtx = ClsCtx(CLSCTX_ENABLE_CODE_DOWNLOAD);
+    pub const NO_FAILURE_LOG: ClsCtx = ClsCtx(CLSCTX_NO_FAILURE_LOG);
+    pub const DISABLE_AAA: ClsCtx = ClsCtx(CLSCTX_DISABLE_AAA);
+    pub const ENABLE_AAA: ClsCtx = ClsCtx(CLSCTX_ENABLE_AAA);
+    pub const FROM_DEFAULT_CONTEXT: ClsCtx = ClsCtx(CLSCTX_FROM_DEFAULT_CONTEXT);
+    pub const ACTIVATE_32_BIT_SERVER: ClsCtx = ClsCtx(CLSCTX_ACTIVATE_32_BIT_SERVER);
+    pub const ACTIVATE_64_BIT_SERVER: ClsCtx = ClsCtx(CLSCTX_ACTIVATE_64_BIT_SERVER);
+    pub const ENABLE_CLOAKING: ClsCtx = ClsCtx(CLSCTX_ENABLE_CLOAKING);
+    pub const APPCONTAINER: ClsCtx = ClsCtx(CLSCTX_APPCONTAINER);
+    pub const ACTIVATE_AAA_AS_IU: ClsCtx = ClsCtx(CLSCTX_ACTIVATE_AAA_AS_IU);
+
+    /// In-process server or handler.
+    pub const INPROC: ClsCtx = ClsCtx(CLSCTX_INPROC);
+    /// In-process, local or remote server.
+    pub const SERVER: ClsCtx = ClsCtx(CLSCTX_SERVER);
+    /// Any context.
+    pub const ALL: ClsCtx = ClsCtx(CLSCTX_ALL);
+
+    /// Empty set.
+    #[inline]
+    pub const fn empty() -> Self {
+        ClsCtx(0)
+    }
+
+    /// Flags from a raw value, unknown bits are kept as is.
+    #[inline]
+    pub const fn from_bits(bits: DWORD) -> Self {
+        ClsCtx(bits)
+    }
+
+    /// Raw value to pass to WinAPI.
+    #[inline]
+    pub const fn bits(self) -> DWORD {
+        self.0
+    }
+
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all flags of `other` are set.
+    #[inline]
+    pub const fn contains(self, other: ClsCtx) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, other: ClsCtx) {
+        self.0 |= other.0;
+    }
+
+    #[inline]
+    pub fn remove(&mut self, other: ClsCtx) {
+        self.0 &= !other.0;
+    }
+}
+
+impl From<DWORD> for ClsCtx {
+    fn from(x: DWORD) -> Self {
+        ClsCtx(x)
+    }
+}
+
+impl From<ClsCtx> for DWORD {
+    fn from(x: ClsCtx) -> Self {
+        x.0
+    }
+}
+
+impl BitOr for ClsCtx {
+    type Output = ClsCtx;
+
+    fn bitor(self, other: ClsCtx) -> ClsCtx {
+        ClsCtx(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for ClsCtx {
+    fn bitor_assign(&mut self, other: ClsCtx) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for ClsCtx {
+    type Output = ClsCtx;
+
+    fn bitand(self, other: ClsCtx) -> ClsCtx {
+        ClsCtx(self.0 & other.0)
+    }
+}
+
+impl Not for ClsCtx {
+    type Output = ClsCtx;
+
+    fn not(self) -> ClsCtx {
+        ClsCtx(!self.0)
+    }
+}
+
+impl fmt::Debug for ClsCtx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [(ClsCtx, &str); 16] = [
+            (ClsCtx::INPROC_SERVER, "INPROC_SERVER"),
+            (ClsCtx::INPROC_HANDLER, "INPROC_HANDLER"),
+            (ClsCtx::LOCAL_SERVER, "LOCAL_SERVER"),
+            (ClsCtx::REMOTE_SERVER, "REMOTE_SERVER"),
+            (ClsCtx::NO_CODE_DOWNLOAD, "NO_CODE_DOWNLOAD"),
+            (ClsCtx::NO_CUSTOM_MARSHAL, "NO_CUSTOM_MARSHAL"),
+            (ClsCtx::ENABLE_CODE_DOWNLOAD, "ENABLE_CODE_DOWNLOAD"),
+            (ClsCtx::NO_FAILURE_LOG, "NO_FAILURE_LOG"),
+            (ClsCtx::DISABLE_AAA, "DISABLE_AAA"),
+            (ClsCtx::ENABLE_AAA, "ENABLE_AAA"),
+            (ClsCtx::FROM_DEFAULT_CONTEXT, "FROM_DEFAULT_CONTEXT"),
+            (ClsCtx::ACTIVATE_32_BIT_SERVER, "ACTIVATE_32_BIT_SERVER"),
+            (ClsCtx::ACTIVATE_64_BIT_SERVER, "ACTIVATE_64_BIT_SERVER"),
+            (ClsCtx::ENABLE_CLOAKING, "ENABLE_CLOAKING"),
+            (ClsCtx::APPCONTAINER, "APPCONTAINER"),
+            (ClsCtx::ACTIVATE_AAA_AS_IU, "ACTIVATE_AAA_AS_IU"),
+        ];
+
+        let mut rest = *self;
+        let mut names = Vec::new();
+        for (flag, name) in NAMES.iter() {
+            if self.contains(*flag) {
+                names.push(name.to_string());
+                rest.remove(*flag);
+            }
+        }
+        if !rest.is_empty() {
+            names.push(format!("{:#x}", rest.0));
+        }
+
+        if names.is_empty() {
+            write!(f, "ClsCtx(empty)")
+        } else {
+            write!(f, "ClsCtx({})", names.join(" | "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ClsCtx_flags() {
+        let mut ctx = ClsCtx::LOCAL_SERVER | ClsCtx::ENABLE_CLOAKING;
+        assert!(ctx.contains(ClsCtx::LOCAL_SERVER));
+        assert!(!ctx.contains(ClsCtx::INPROC_SERVER));
+        assert_eq!(CLSCTX_LOCAL_SERVER | CLSCTX_ENABLE_CLOAKING, ctx.bits());
+
+        ctx.remove(ClsCtx::ENABLE_CLOAKING);
+        assert_eq!(ClsCtx::from(CLSCTX_LOCAL_SERVER), ctx);
+        assert!(ClsCtx::ALL.contains(ClsCtx::SERVER));
+        assert_eq!(
+            "ClsCtx(INPROC_SERVER | 0x40)",
+            format!("{:?}", ClsCtx::from_bits(0x41))
+        );
+    }
+}
@@ -0,0 +1,107 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! COM initialization of the current thread.
+//!
+//! Every thread must join an apartment before using COM, [`ComApartment`] guard is the entry point for it:
+//! it calls CoInitializeEx on creation and balances it with CoUninitialize on drop.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::com_apartment::ComApartment;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let _com = ComApartment::init_sta().expect("COM initialization");
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create();
+//! ```
+//!
+//! [`ComApartment`]: struct.ComApartment.html
+
+use std::marker::PhantomData;
+
+use winapi::shared::winerror;
+use winapi::um::combaseapi::{CoInitializeEx, CoUninitialize};
+use winapi::um::objbase::{COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED};
+
+use crate::config::{Apartment, Config};
+use crate::error::ComResult;
+
+/// Keeps the current thread initialized for COM, uninitializes it on drop.
+///
+/// Guard isn't `Send`, since initialization is per thread. COM objects must be released before the guard is
+/// dropped.
+pub struct ComApartment {
+    apartment: Apartment,
+    already_initialized: bool,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl ComApartment {
+    /// Initializes the current thread as a single-threaded apartment.
+    #[inline]
+    pub fn init_sta() -> ComResult<Self> {
+        ComApartment::init(Apartment::SingleThreaded)
+    }
+
+    /// Joins the current thread to the multithreaded apartment.
+    #[inline]
+    pub fn init_mta() -> ComResult<Self> {
+        ComApartment::init(Apartment::MultiThreaded)
+    }
+
+    /// Initializes the current thread with apartment model preferred by global [`Config`].
+    ///
+    /// [`Config`]: ../config/struct.Config.html
+    #[inline]
+    pub fn init_default() -> ComResult<Self> {
+        ComApartment::init(Config::global().apartment())
+    }
+
+    /// Initializes the current thread with the given apartment model.
+    ///
+    /// Repeated initialization with the same model succeeds, see [`already_initialized`].
+    ///
+    /// # Errors
+    ///
+    /// * If thread is already initialized with another model, returns `RustyWinapiError::Com(RPC_E_CHANGED_MODE)`.
+    /// * Otherwise returns HRESULT of CoInitializeEx as `RustyWinapiError`.
+    ///
+    /// [`already_initialized`]: #method.already_initialized
+    pub fn init(apartment: Apartment) -> ComResult<Self> {
+        let coinit = match apartment {
+            Apartment::SingleThreaded => COINIT_APARTMENTTHREADED,
+            Apartment::MultiThreaded => COINIT_MULTITHREADED,
+        };
+        let hresult = unsafe { CoInitializeEx(std::ptr::null_mut(), coinit) };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(ComApartment {
+                apartment,
+                already_initialized: hresult == winerror::S_FALSE,
+                _not_send: PhantomData,
+            })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Apartment model of the thread.
+    #[inline]
+    pub fn apartment(&self) -> Apartment {
+        self.apartment
+    }
+
+    /// Returns `true` if thread was already initialized with the same model (CoInitializeEx returned `S_FALSE`).
+    #[inline]
+    pub fn already_initialized(&self) -> bool {
+        self.already_initialized
+    }
+}
+
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        // Successful initialization, S_FALSE included, must be balanced.
+        unsafe { CoUninitialize() };
+    }
+}
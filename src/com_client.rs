@@ -0,0 +1,100 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Typed facades of late-bound automation objects declared as Rust traits with [`#[com_client]`](../attr.com_client.html).
+//!
+//! The attribute implements the trait for every [`SmartIDispatch`] type. Each method without a default body must
+//! take `&mut self` and return `ComResult<T>`, it invokes the member by name (Rust name in PascalCase unless
+//! `#[com(name = "...")]` says otherwise) with arguments converted by `SmartVariant::from`. The result is
+//! converted as an out-parameter ([`OutParam`]): `SmartVariant` as is, an interface, a [`VariantType`] coerced or
+//! `()` discarding it. Members are methods by default, `#[com(get)]`, `#[com(put)]` and `#[com(put_ref)]` make them
+//! property accessors, the last parameter of a put being the value.
+//!
+//! Errors are the same as of [`SmartIDispatch::call_with_flags`], with the member context.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::com_client;
+//! use rusty_winapi::prelude::*;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! #[com_client]
+//! trait V8COMConnector {
+//!     fn connect(&mut self, conn_string: &str) -> ComResult<AutoCOMInterface<IDispatch>>;
+//!
+//!     #[com(name = "PoolCapacity", get)]
+//!     fn pool_capacity(&mut self) -> ComResult<u32>;
+//!
+//!     #[com(name = "PoolCapacity", put)]
+//!     fn set_pool_capacity(&mut self, value: u32) -> ComResult<()>;
+//! }
+//!
+//! let mut connector = Activate::<IDispatch>::new().progid("V83.COMConnector").create()?;
+//! connector.set_pool_capacity(10)?;
+//! let connection = connector.connect("File=\"C:\\Base\";")?;
+//! # Ok::<(), RustyWinapiError>(())
+//! ```
+//!
+//! [`SmartIDispatch`]: ../smart_idispatch/trait.SmartIDispatch.html
+//! [`SmartIDispatch::call_with_flags`]: ../smart_idispatch/trait.SmartIDispatch.html#method.call_with_flags
+//! [`OutParam`]: ../smart_idispatch/trait.OutParam.html
+//! [`VariantType`]: ../smart_variant/trait.VariantType.html
+
+use winapi::shared::minwindef::WORD;
+
+use crate::error::ComResult;
+use crate::smart_idispatch::{OutParam, SmartIDispatch};
+use crate::smart_variant::SmartVariant;
+
+#[doc(hidden)]
+pub use winapi::um::oleauto::{
+    DISPATCH_METHOD, DISPATCH_PROPERTYGET, DISPATCH_PROPERTYPUT, DISPATCH_PROPERTYPUTREF,
+};
+
+/// Invokes `member` on behalf of a `#[com_client]` method.
+#[doc(hidden)]
+pub fn invoke<D: SmartIDispatch + ?Sized, R: OutParam>(
+    dispatch: &mut D,
+    member: &str,
+    flags: WORD,
+    params: &[SmartVariant],
+) -> ComResult<R> {
+    let result = dispatch.call_with_flags(member, flags, params)?;
+    Ok(R::from_out_param(result)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_client;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+
+    #[com_client]
+    trait Counter {
+        /// Current value.
+        #[com(get)]
+        fn count(&mut self) -> ComResult<i32>;
+
+        #[com(name = "Count", put)]
+        fn set_count(&mut self, value: i32) -> ComResult<()>;
+
+        fn raw_count(&mut self) -> ComResult<SmartVariant>;
+
+        fn doubled(&mut self) -> ComResult<i32> {
+            Ok(self.count()? * 2)
+        }
+    }
+
+    #[test]
+    fn test_com_client() {
+        let object = DynamicObject::new().with("Count", SmartVariant::Int4(1));
+        let mut dispatch = object.clone().into_dispatch();
+
+        dispatch.set_count(2).unwrap();
+        assert_eq!(2, dispatch.count().unwrap());
+        assert_eq!(4, dispatch.doubled().unwrap());
+        assert_eq!(Some(SmartVariant::Int4(2)), object.get("Count"));
+        assert!(dispatch.raw_count().is_err());
+    }
+}
@@ -0,0 +1,161 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Self-diagnostics of COM environment of the current thread and process.
+//!
+//! Most failures of automation code are caused by environment setup mistakes: COM isn't initialized on the thread,
+//! an object is used from a wrong apartment, a message filter isn't installed for a busy server. [`report`] collects
+//! what can be detected, so it can be printed on failure.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::com_diagnostics;
+//!
+//! println!("{}", com_diagnostics::report());
+//! ```
+//!
+//! [`report`]: fn.report.html
+
+use std::fmt;
+
+use winapi::shared::minwindef::LPVOID;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::combaseapi::CoGetApartmentType;
+use winapi::um::objidlbase::{
+    APTTYPE, APTTYPEQUALIFIER, APTTYPE_MAINSTA, APTTYPE_MTA, APTTYPE_NA, APTTYPE_STA,
+};
+use winapi::um::unknwnbase::IUnknown;
+
+use crate::ffi::CoRegisterMessageFilter;
+
+/// Apartment state of a thread.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApartmentState {
+    NotInitialized,
+    SingleThreaded,
+    MainSingleThreaded,
+    MultiThreaded,
+    Neutral,
+    Unknown(HRESULT),
+}
+
+impl fmt::Display for ApartmentState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApartmentState::NotInitialized => write!(f, "COM is not initialized"),
+            ApartmentState::SingleThreaded => write!(f, "STA"),
+            ApartmentState::MainSingleThreaded => write!(f, "main STA"),
+            ApartmentState::MultiThreaded => write!(f, "MTA"),
+            ApartmentState::Neutral => write!(f, "NA"),
+            ApartmentState::Unknown(x) => write!(f, "unknown (0x{:08X})", x),
+        }
+    }
+}
+
+/// Snapshot of COM environment state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiagnosticsReport {
+    /// Apartment of the current thread.
+    pub apartment: ApartmentState,
+    /// Whether CoInitializeSecurity was called, `None` if it can't be determined.
+    ///
+    /// COM provides no way to query it without initializing security.
+    pub security_initialized: Option<bool>,
+    /// Whether a message filter is registered on the current thread (meaningful for STA only).
+    pub message_filter_registered: bool,
+    /// Number of interface references owned by live wrappers, `None` without the `leak-debug` feature.
+    pub live_interfaces: Option<usize>,
+}
+
+impl fmt::Display for DiagnosticsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "COM diagnostics:")?;
+        writeln!(f, "  apartment: {}", self.apartment)?;
+        match self.security_initialized {
+            Some(x) => writeln!(f, "  security initialized: {}", x)?,
+            None => writeln!(f, "  security initialized: unknown")?,
+        }
+        writeln!(
+            f,
+            "  message filter registered: {}",
+            self.message_filter_registered
+        )?;
+        match self.live_interfaces {
+            Some(x) => write!(f, "  live interfaces: {}", x),
+            None => write!(f, "  live interfaces: not tracked"),
+        }
+    }
+}
+
+/// Collects COM environment state of the current thread and process.
+pub fn report() -> DiagnosticsReport {
+    DiagnosticsReport {
+        apartment: apartment_state(),
+        security_initialized: None,
+        message_filter_registered: message_filter_registered(),
+        #[cfg(feature = "leak-debug")]
+        live_interfaces: Some(crate::leak_debug::ref_counts().balance()),
+        #[cfg(not(feature = "leak-debug"))]
+        live_interfaces: None,
+    }
+}
+
+/// Returns apartment state of the current thread.
+pub fn apartment_state() -> ApartmentState {
+    let mut apt_type: APTTYPE = 0;
+    let mut apt_qualifier: APTTYPEQUALIFIER = 0;
+    let hresult = unsafe { CoGetApartmentType(&mut apt_type, &mut apt_qualifier) };
+
+    match hresult {
+        winerror::CO_E_NOTINITIALIZED => ApartmentState::NotInitialized,
+        x if !winerror::SUCCEEDED(x) => ApartmentState::Unknown(x),
+        _ => match apt_type {
+            APTTYPE_STA => ApartmentState::SingleThreaded,
+            APTTYPE_MAINSTA => ApartmentState::MainSingleThreaded,
+            APTTYPE_MTA => ApartmentState::MultiThreaded,
+            APTTYPE_NA => ApartmentState::Neutral,
+            _ => ApartmentState::Unknown(hresult),
+        },
+    }
+}
+
+/// Returns `true` if a message filter is registered on the current thread.
+///
+/// Temporarily revokes the filter to get it, then registers it back.
+pub fn message_filter_registered() -> bool {
+    let mut previous: LPVOID = std::ptr::null_mut();
+    let hresult = unsafe { CoRegisterMessageFilter(std::ptr::null_mut(), &mut previous) };
+    if !winerror::SUCCEEDED(hresult) || previous.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let mut revoked: LPVOID = std::ptr::null_mut();
+        CoRegisterMessageFilter(previous, &mut revoked);
+        // CoRegisterMessageFilter returned the previous filter with a reference held for us.
+        (*(previous as *mut IUnknown)).Release();
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_Display() {
+        let report = DiagnosticsReport {
+            apartment: ApartmentState::SingleThreaded,
+            security_initialized: None,
+            message_filter_registered: false,
+            live_interfaces: Some(3),
+        };
+
+        assert_eq!(
+            "COM diagnostics:\n  apartment: STA\n  security initialized: unknown\n  message filter registered: false\n  live interfaces: 3",
+            report.to_string()
+        );
+    }
+}
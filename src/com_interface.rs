@@ -0,0 +1,474 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Declarations of COM interfaces and classes with [`com_interface!`], without winapi's `RIDL!`.
+//!
+//! Interfaces are declared with GUID strings and Rust parameter types. Each method gets its vtable entry with ABI
+//! types of the parameters ([`ComIn::Abi`], e.g. BSTR for `&str`) and a safe method converting arguments, checking
+//! HRESULT and converting the `[out, retval]` value ([`ComOut`]) of methods declaring a result. Interfaces get
+//! `winapi::Interface`, [`InterfaceVtbl`] and, if derived from IDispatch, [`DispatchInterface`] implementations, so
+//! they work with [`AutoCOMInterface`] and smart traits.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::com_interface;
+//! use rusty_winapi::prelude::*;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! com_interface! {
+//!     /// 1C ComConnector (comcntr.dll) class.
+//!     #[uuid("181E893D-73A4-4722-B61D-D604B3D67D47")]
+//!     pub class V8COMConnector;
+//!
+//!     #[uuid("ba4e52bd-dcb2-4bf7-bb29-84c1ca456a8f")]
+//!     pub interface IV8COMConnector(IV8COMConnectorVtbl): IDispatch {
+//!         fn Connect(&self, connect_string: &str) -> AutoCOMInterface<IDispatch>;
+//!     }
+//! }
+//!
+//! let connector = Activate::<IV8COMConnector>::new().clsid(<V8COMConnector as winapi::Class>::uuidof()).create()?;
+//! let connection = connector.Connect("File=\"C:\\Base\";")?;
+//! # Ok::<(), RustyWinapiError>(())
+//! ```
+//!
+//! [`com_interface!`]: ../macro.com_interface.html
+//! [`ComIn::Abi`]: trait.ComIn.html#associatedtype.Abi
+//! [`ComOut`]: trait.ComOut.html
+//! [`InterfaceVtbl`]: ../auto_com_interface/trait.InterfaceVtbl.html
+//! [`DispatchInterface`]: ../smart_idispatch/trait.DispatchInterface.html
+//! [`AutoCOMInterface`]: ../auto_com_interface/struct.AutoCOMInterface.html
+
+use std::convert::{TryFrom, TryInto};
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::winerror;
+use winapi::shared::wtypes::{BSTR, VARIANT_BOOL};
+use winapi::um::oaidl::VARIANT;
+use winapi::Interface;
+
+use crate::auto_bstr::AutoBSTR;
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+use crate::smart_variant::{AutoVariant, SmartVariant};
+
+/// Parses GUID string `"xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"`, optionally in braces, at compile time if used in
+/// a constant.
+///
+/// # Panics
+///
+/// Panics (fails compilation of a constant) if the string isn't a GUID.
+pub const fn guid(s: &str) -> GUID {
+    let s = s.as_bytes();
+    let (start, len) = match s.len() {
+        38 if s[0] == b'{' && s[37] == b'}' => (1, 36),
+        36 => (0, 36),
+        _ => panic!("GUID must be 36 characters long, 38 with braces"),
+    };
+
+    let mut digits = [0u8; 32];
+    let mut count = 0;
+    let mut i = 0;
+    while i < len {
+        let c = s[start + i];
+        if i == 8 || i == 13 || i == 18 || i == 23 {
+            if c != b'-' {
+                panic!("GUID groups must be separated by '-'");
+            }
+        } else {
+            digits[count] = match c {
+                b'0'..=b'9' => c - b'0',
+                b'a'..=b'f' => c - b'a' + 10,
+                b'A'..=b'F' => c - b'A' + 10,
+                _ => panic!("GUID must consist of hexadecimal digits"),
+            };
+            count += 1;
+        }
+        i += 1;
+    }
+
+    let mut data4 = [0u8; 8];
+    let mut j = 0;
+    while j < 8 {
+        data4[j] = digits[16 + 2 * j] << 4 | digits[17 + 2 * j];
+        j += 1;
+    }
+
+    GUID {
+        Data1: hex(&digits, 0, 8) as u32,
+        Data2: hex(&digits, 8, 4) as u16,
+        Data3: hex(&digits, 12, 4) as u16,
+        Data4: data4,
+    }
+}
+
+const fn hex(digits: &[u8; 32], start: usize, len: usize) -> u64 {
+    let mut result = 0u64;
+    let mut i = 0;
+    while i < len {
+        result = result << 4 | digits[start + i] as u64;
+        i += 1;
+    }
+    result
+}
+
+/// Parameter type of [`com_interface!`] methods.
+///
+/// Argument is converted into `Holder` owning resources (e.g. BSTR) for the duration of the call, the vtable gets
+/// its `Abi` value.
+///
+/// [`com_interface!`]: ../macro.com_interface.html
+pub trait ComIn {
+    type Abi;
+    type Holder;
+
+    fn into_holder(self) -> ComResult<Self::Holder>;
+    fn abi(holder: &Self::Holder) -> Self::Abi;
+}
+
+/// Result type of [`com_interface!`] methods, converted from the `[out, retval]` value the method returns.
+///
+/// [`com_interface!`]: ../macro.com_interface.html
+pub trait ComOut: Sized {
+    type Abi;
+
+    /// Takes ownership of `abi`.
+    ///
+    /// # Safety
+    ///
+    /// `abi` must be an `[out]` value set by a successful call, or zero-initialized.
+    unsafe fn from_abi(abi: Self::Abi) -> ComResult<Self>;
+}
+
+macro_rules! impl_com_primitive {
+    ($($type:ty),*) => {
+        $(
+            impl ComIn for $type {
+                type Abi = $type;
+                type Holder = $type;
+
+                fn into_holder(self) -> ComResult<Self::Holder> {
+                    Ok(self)
+                }
+
+                fn abi(holder: &Self::Holder) -> Self::Abi {
+                    *holder
+                }
+            }
+
+            impl ComOut for $type {
+                type Abi = $type;
+
+                unsafe fn from_abi(abi: Self::Abi) -> ComResult<Self> {
+                    Ok(abi)
+                }
+            }
+        )*
+    };
+}
+
+impl_com_primitive!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+impl ComIn for bool {
+    type Abi = VARIANT_BOOL;
+    type Holder = bool;
+
+    fn into_holder(self) -> ComResult<Self::Holder> {
+        Ok(self)
+    }
+
+    fn abi(holder: &Self::Holder) -> Self::Abi {
+        if *holder {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+impl ComOut for bool {
+    type Abi = VARIANT_BOOL;
+
+    unsafe fn from_abi(abi: Self::Abi) -> ComResult<Self> {
+        Ok(abi != 0)
+    }
+}
+
+impl ComIn for &str {
+    type Abi = BSTR;
+    type Holder = AutoBSTR;
+
+    fn into_holder(self) -> ComResult<Self::Holder> {
+        Ok(AutoBSTR::try_from(self)?)
+    }
+
+    fn abi(holder: &Self::Holder) -> Self::Abi {
+        holder.as_bstr().as_raw()
+    }
+}
+
+impl ComOut for String {
+    type Abi = BSTR;
+
+    unsafe fn from_abi(abi: Self::Abi) -> ComResult<Self> {
+        Ok(String::from(AutoBSTR::from(abi)))
+    }
+}
+
+/// `[in] VARIANT` by value, the callee doesn't take ownership.
+impl ComIn for SmartVariant {
+    type Abi = VARIANT;
+    type Holder = AutoVariant;
+
+    fn into_holder(self) -> ComResult<Self::Holder> {
+        Ok(AutoVariant::try_from_smart(self)?)
+    }
+
+    fn abi(holder: &Self::Holder) -> Self::Abi {
+        unsafe { std::ptr::read(holder.as_ptr()) }
+    }
+}
+
+impl ComIn for &SmartVariant {
+    type Abi = VARIANT;
+    type Holder = AutoVariant;
+
+    fn into_holder(self) -> ComResult<Self::Holder> {
+        self.clone().into_holder()
+    }
+
+    fn abi(holder: &Self::Holder) -> Self::Abi {
+        SmartVariant::abi(holder)
+    }
+}
+
+impl ComOut for SmartVariant {
+    type Abi = VARIANT;
+
+    unsafe fn from_abi(abi: Self::Abi) -> ComResult<Self> {
+        Ok(SmartVariant::try_from(abi)?)
+    }
+}
+
+/// `[in]` interface pointer, the callee AddRefs it if it keeps it.
+impl<'a, T: Interface> ComIn for &'a AutoCOMInterface<T> {
+    type Abi = *mut T;
+    type Holder = &'a AutoCOMInterface<T>;
+
+    fn into_holder(self) -> ComResult<Self::Holder> {
+        Ok(self)
+    }
+
+    fn abi(holder: &Self::Holder) -> Self::Abi {
+        holder.as_inner() as *const T as *mut T
+    }
+}
+
+/// `[out, retval]` interface pointer, NULL fails with `E_POINTER`.
+impl<T: Interface> ComOut for AutoCOMInterface<T> {
+    type Abi = *mut T;
+
+    unsafe fn from_abi(abi: Self::Abi) -> ComResult<Self> {
+        abi.try_into().map_err(|_| winerror::E_POINTER.into())
+    }
+}
+
+/// Declares COM classes and interfaces, see [module documentation](com_interface/index.html).
+///
+/// Interface methods take `&self` and parameters of [`ComIn`] types. Method with a result type `T` ([`ComOut`])
+/// has an `[out, retval]` parameter in its vtable entry and returns `ComResult<T>`, method without one returns
+/// `ComResult<()>`. Vtable entries follow the parent interface's ones in declaration order.
+///
+/// [`ComIn`]: com_interface/trait.ComIn.html
+/// [`ComOut`]: com_interface/trait.ComOut.html
+#[macro_export]
+macro_rules! com_interface {
+    () => {};
+    (
+        $(#[doc = $doc:expr])*
+        #[uuid($uuid:literal)]
+        $vis:vis class $name:ident;
+        $($rest:tt)*
+    ) => {
+        $(#[doc = $doc])*
+        $vis enum $name {}
+
+        impl ::winapi::Class for $name {
+            #[inline]
+            fn uuidof() -> ::winapi::shared::guiddef::GUID {
+                const UUID: ::winapi::shared::guiddef::GUID = $crate::com_interface::guid($uuid);
+                UUID
+            }
+        }
+
+        $crate::com_interface!($($rest)*);
+    };
+    (
+        $(#[doc = $doc:expr])*
+        #[uuid($uuid:literal)]
+        $vis:vis interface $name:ident($vtbl:ident): $parent:ident {
+            $($methods:tt)*
+        }
+        $($rest:tt)*
+    ) => {
+        $crate::com_interface!(@methods [$(#[doc = $doc])*] $uuid $vis $name $vtbl $parent [] [] $($methods)*);
+        $crate::com_interface!(@dispatch $name $parent);
+        $crate::com_interface!($($rest)*);
+    };
+
+    (@methods $attrs:tt $uuid:literal $vis:vis $name:ident $vtbl:ident $parent:ident [$($fields:tt)*] [$($impls:tt)*]
+        $(#[doc = $mdoc:expr])*
+        fn $method:ident(&self $(, $param:ident: $type:ty)* $(,)?) -> $ret:ty;
+        $($rest:tt)*
+    ) => {
+        $crate::com_interface!(@methods $attrs $uuid $vis $name $vtbl $parent
+            [
+                $($fields)*
+                pub $method: unsafe extern "system" fn(
+                    This: *mut $name,
+                    $($param: <$type as $crate::com_interface::ComIn>::Abi,)*
+                    retval: *mut <$ret as $crate::com_interface::ComOut>::Abi,
+                ) -> ::winapi::shared::ntdef::HRESULT,
+            ]
+            [
+                $($impls)*
+                $(#[doc = $mdoc])*
+                pub fn $method(&self $(, $param: $type)*) -> $crate::error::ComResult<$ret> {
+                    $(let $param = <$type as $crate::com_interface::ComIn>::into_holder($param)?;)*
+                    let mut retval: <$ret as $crate::com_interface::ComOut>::Abi = unsafe { ::std::mem::zeroed() };
+                    let hresult = unsafe {
+                        ((*self.lpVtbl).$method)(
+                            self as *const Self as *mut Self,
+                            $(<$type as $crate::com_interface::ComIn>::abi(&$param),)*
+                            &mut retval,
+                        )
+                    };
+                    if ::winapi::shared::winerror::SUCCEEDED(hresult) {
+                        unsafe { <$ret as $crate::com_interface::ComOut>::from_abi(retval) }
+                    } else {
+                        Err(hresult.into())
+                    }
+                }
+            ]
+            $($rest)*
+        );
+    };
+    (@methods $attrs:tt $uuid:literal $vis:vis $name:ident $vtbl:ident $parent:ident [$($fields:tt)*] [$($impls:tt)*]
+        $(#[doc = $mdoc:expr])*
+        fn $method:ident(&self $(, $param:ident: $type:ty)* $(,)?);
+        $($rest:tt)*
+    ) => {
+        $crate::com_interface!(@methods $attrs $uuid $vis $name $vtbl $parent
+            [
+                $($fields)*
+                pub $method: unsafe extern "system" fn(
+                    This: *mut $name,
+                    $($param: <$type as $crate::com_interface::ComIn>::Abi,)*
+                ) -> ::winapi::shared::ntdef::HRESULT,
+            ]
+            [
+                $($impls)*
+                $(#[doc = $mdoc])*
+                pub fn $method(&self $(, $param: $type)*) -> $crate::error::ComResult<()> {
+                    $(let $param = <$type as $crate::com_interface::ComIn>::into_holder($param)?;)*
+                    let hresult = unsafe {
+                        ((*self.lpVtbl).$method)(
+                            self as *const Self as *mut Self,
+                            $(<$type as $crate::com_interface::ComIn>::abi(&$param),)*
+                        )
+                    };
+                    if ::winapi::shared::winerror::SUCCEEDED(hresult) {
+                        Ok(())
+                    } else {
+                        Err(hresult.into())
+                    }
+                }
+            ]
+            $($rest)*
+        );
+    };
+    (@methods [$($attrs:tt)*] $uuid:literal $vis:vis $name:ident $vtbl:ident $parent:ident [$($fields:tt)*] [$($impls:tt)*]) => {
+        #[repr(C)]
+        $vis struct $vtbl {
+            pub parent: <$parent as $crate::auto_com_interface::InterfaceVtbl>::Vtbl,
+            $($fields)*
+        }
+
+        $($attrs)*
+        #[repr(C)]
+        $vis struct $name {
+            pub lpVtbl: *const $vtbl,
+        }
+
+        #[allow(non_snake_case)]
+        impl $name {
+            $($impls)*
+        }
+
+        impl ::std::ops::Deref for $name {
+            type Target = $parent;
+
+            #[inline]
+            fn deref(&self) -> &$parent {
+                unsafe { &*(self as *const $name as *const $parent) }
+            }
+        }
+
+        impl ::winapi::Interface for $name {
+            #[inline]
+            fn uuidof() -> ::winapi::shared::guiddef::GUID {
+                const UUID: ::winapi::shared::guiddef::GUID = $crate::com_interface::guid($uuid);
+                UUID
+            }
+        }
+
+        $crate::impl_interface_vtbl!($name => $vtbl);
+    };
+
+    (@dispatch $name:ident IDispatch) => {
+        unsafe impl $crate::smart_idispatch::DispatchInterface for $name {}
+    };
+    (@dispatch $name:ident $parent:ident) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::shared::guiddef::IsEqualGUID;
+    use winapi::um::oaidl::IDispatch;
+    use winapi::Class;
+
+    crate::com_interface! {
+        #[uuid("{181E893D-73A4-4722-B61D-D604B3D67D47}")]
+        class V8COMConnector;
+
+        /// 1C ComConnector interface.
+        #[uuid("ba4e52bd-dcb2-4bf7-bb29-84c1ca456a8f")]
+        interface IV8COMConnector(IV8COMConnectorVtbl): IDispatch {
+            fn Connect(&self, connect_string: &str) -> AutoCOMInterface<IDispatch>;
+            fn SetPoolSize(&self, size: u32, strict: bool);
+        }
+    }
+
+    #[test]
+    fn test_guid() {
+        const GUID: GUID = guid("ba4e52bd-dcb2-4bf7-bb29-84c1ca456a8f");
+        assert_eq!(0xba4e52bd, GUID.Data1);
+        assert_eq!(0xdcb2, GUID.Data2);
+        assert_eq!(0x4bf7, GUID.Data3);
+        assert_eq!([0xbb, 0x29, 0x84, 0xc1, 0xca, 0x45, 0x6a, 0x8f], GUID.Data4);
+    }
+
+    #[test]
+    fn test_com_interface() {
+        assert!(IsEqualGUID(
+            &IV8COMConnector::uuidof(),
+            &guid("BA4E52BD-DCB2-4BF7-BB29-84C1CA456A8F")
+        ));
+        assert_eq!(0x181E893D, V8COMConnector::uuidof().Data1);
+        assert_eq!(
+            std::mem::size_of::<winapi::um::oaidl::IDispatchVtbl>()
+                + 2 * std::mem::size_of::<usize>(),
+            std::mem::size_of::<IV8COMConnectorVtbl>()
+        );
+    }
+}
@@ -0,0 +1,299 @@
+#![allow(non_camel_case_types, non_snake_case)]
+
+//! Crate-wide configuration defaults.
+//!
+//! [`Config`] consolidates defaults which otherwise would be passed to every call: LCID used for name
+//! resolution and invocation, retry policy for busy servers, tracing verbosity, string coercion strictness,
+//! preferred apartment model and use of IDispatchEx. Process-global configuration is used unless overridden
+//! explicitly.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use rusty_winapi::config::{Config, RetryPolicy};
+//!
+//! Config::builder()
+//!     .lcid(0x0419)
+//!     .retry_policy(RetryPolicy::new(3, Duration::from_millis(100)))
+//!     .build()
+//!     .set_global();
+//!
+//! assert_eq!(0x0419, Config::global().lcid());
+//! ```
+//!
+//! [`Config`]: struct.Config.html
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::{HRESULT, LCID};
+use winapi::shared::winerror;
+use winapi::um::combaseapi::CLSCTX_ALL;
+use winapi::um::winnt::LOCALE_USER_DEFAULT;
+
+use crate::cls_ctx::ClsCtx;
+
+static GLOBAL_CONFIG: RwLock<Option<Config>> = RwLock::new(None);
+
+/// Preferred COM apartment model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Apartment {
+    /// Single-threaded apartment (COINIT_APARTMENTTHREADED).
+    SingleThreaded,
+    /// Multithreaded apartment (COINIT_MULTITHREADED).
+    MultiThreaded,
+}
+
+/// Verbosity of diagnostic output produced by the crate.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum TraceLevel {
+    Off,
+    Errors,
+    Calls,
+}
+
+/// How many times and how often a call rejected by a busy server is repeated.
+///
+/// Calls are repeated only when they fail with `RPC_E_CALL_REJECTED` or `RPC_E_SERVERCALL_RETRYLATER`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy with the given number of retries after the first failed attempt.
+    pub fn new(attempts: u32, delay: Duration) -> Self {
+        RetryPolicy { attempts, delay }
+    }
+
+    /// Policy without any retries.
+    pub fn none() -> Self {
+        RetryPolicy::new(0, Duration::from_millis(0))
+    }
+
+    /// Returns `true` if a call failed with this HRESULT is worth repeating.
+    pub fn is_retryable(hresult: HRESULT) -> bool {
+        hresult == winerror::RPC_E_CALL_REJECTED || hresult == winerror::RPC_E_SERVERCALL_RETRYLATER
+    }
+
+    /// Runs `f` repeating it according to the policy while it returns a retryable HRESULT.
+    pub fn run<F: FnMut() -> HRESULT>(&self, mut f: F) -> HRESULT {
+        let mut hresult = f();
+        for _ in 0..self.attempts {
+            if !RetryPolicy::is_retryable(hresult) {
+                break;
+            }
+            std::thread::sleep(self.delay);
+            hresult = f();
+        }
+
+        hresult
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::none()
+    }
+}
+
+/// Crate-wide defaults consumed by dispatch and activation layers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    lcid: LCID,
+    cls_context: DWORD,
+    retry_policy: RetryPolicy,
+    trace_level: TraceLevel,
+    strict_string_coercion: bool,
+    apartment: Apartment,
+    dispatch_ex: bool,
+    ref_count_assertions: bool,
+}
+
+impl Config {
+    /// Starts building a new configuration from defaults.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder(Config::default())
+    }
+
+    /// Returns a copy of process-global configuration.
+    pub fn global() -> Config {
+        match GLOBAL_CONFIG.read() {
+            Ok(x) => x.clone().unwrap_or_default(),
+            Err(x) => x.into_inner().clone().unwrap_or_default(),
+        }
+    }
+
+    /// Replaces process-global configuration with this one.
+    pub fn set_global(self) {
+        match GLOBAL_CONFIG.write() {
+            Ok(mut x) => *x = Some(self),
+            Err(x) => *x.into_inner() = Some(self),
+        }
+    }
+
+    /// Locale used for GetIDsOfNames and Invoke, `LOCALE_USER_DEFAULT` by default.
+    #[inline]
+    pub fn lcid(&self) -> LCID {
+        self.lcid
+    }
+
+    /// Server context used for activation, `CLSCTX_ALL` by default.
+    #[inline]
+    pub fn cls_context(&self) -> DWORD {
+        self.cls_context
+    }
+
+    /// Retry policy for calls rejected by busy servers, no retries by default.
+    #[inline]
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Verbosity of diagnostic output, `TraceLevel::Off` by default.
+    #[inline]
+    pub fn trace_level(&self) -> TraceLevel {
+        self.trace_level
+    }
+
+    /// Whether [`SmartVariant::coerce`] converts values to/from strings only if they are strings already, `false` by
+    /// default.
+    ///
+    /// [`SmartVariant::coerce`]: ../smart_variant/enum.SmartVariant.html#method.coerce
+    #[inline]
+    pub fn strict_string_coercion(&self) -> bool {
+        self.strict_string_coercion
+    }
+
+    /// Preferred apartment model, `Apartment::MultiThreaded` by default.
+    #[inline]
+    pub fn apartment(&self) -> Apartment {
+        self.apartment
+    }
+
+    /// Whether names are resolved and members invoked via IDispatchEx when object implements it, `false` by default.
+    ///
+    /// IDispatchEx resolves names case-sensitively and sees members added at runtime (JScript objects, HTML DOM),
+    /// every call then makes an extra QueryInterface.
+    #[inline]
+    pub fn dispatch_ex(&self) -> bool {
+        self.dispatch_ex
+    }
+
+    /// Whether debug builds check reference counts returned by Release of interface wrappers, `false` by default.
+    ///
+    /// See [`AutoCOMInterface::drop_last`].
+    ///
+    /// [`AutoCOMInterface::drop_last`]: ../auto_com_interface/struct.AutoCOMInterface.html#method.drop_last
+    #[inline]
+    pub fn ref_count_assertions(&self) -> bool {
+        self.ref_count_assertions
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            lcid: LOCALE_USER_DEFAULT,
+            cls_context: CLSCTX_ALL,
+            retry_policy: RetryPolicy::none(),
+            trace_level: TraceLevel::Off,
+            strict_string_coercion: false,
+            apartment: Apartment::MultiThreaded,
+            dispatch_ex: false,
+            ref_count_assertions: false,
+        }
+    }
+}
+
+/// Builder of [`Config`].
+///
+/// [`Config`]: struct.Config.html
+#[derive(Clone, Debug)]
+pub struct ConfigBuilder(Config);
+
+impl ConfigBuilder {
+    pub fn lcid(mut self, lcid: LCID) -> Self {
+        self.0.lcid = lcid;
+        self
+    }
+
+    pub fn cls_context<C: Into<ClsCtx>>(mut self, cls_context: C) -> Self {
+        self.0.cls_context = cls_context.into().bits();
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.0.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn trace_level(mut self, trace_level: TraceLevel) -> Self {
+        self.0.trace_level = trace_level;
+        self
+    }
+
+    pub fn strict_string_coercion(mut self, strict: bool) -> Self {
+        self.0.strict_string_coercion = strict;
+        self
+    }
+
+    pub fn apartment(mut self, apartment: Apartment) -> Self {
+        self.0.apartment = apartment;
+        self
+    }
+
+    pub fn dispatch_ex(mut self, dispatch_ex: bool) -> Self {
+        self.0.dispatch_ex = dispatch_ex;
+        self
+    }
+
+    pub fn ref_count_assertions(mut self, enabled: bool) -> Self {
+        self.0.ref_count_assertions = enabled;
+        self
+    }
+
+    pub fn build(self) -> Config {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_RetryPolicy_run() {
+        let mut calls = 0;
+        let hresult = RetryPolicy::new(2, Duration::from_millis(0)).run(|| {
+            calls += 1;
+            winerror::RPC_E_CALL_REJECTED
+        });
+        assert_eq!(winerror::RPC_E_CALL_REJECTED, hresult);
+        assert_eq!(3, calls);
+
+        let mut calls = 0;
+        let hresult = RetryPolicy::new(2, Duration::from_millis(0)).run(|| {
+            calls += 1;
+            winerror::E_FAIL
+        });
+        assert_eq!(winerror::E_FAIL, hresult);
+        assert_eq!(1, calls);
+    }
+
+    #[test]
+    fn test_Config_builder() {
+        let config = Config::builder()
+            .lcid(0x0419)
+            .apartment(Apartment::SingleThreaded)
+            .build();
+        assert_eq!(0x0419, config.lcid());
+        assert_eq!(Apartment::SingleThreaded, config.apartment());
+        assert_eq!(RetryPolicy::none(), config.retry_policy());
+        assert!(!config.dispatch_ex());
+        assert!(Config::builder().dispatch_ex(true).build().dispatch_ex());
+    }
+}
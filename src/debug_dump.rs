@@ -0,0 +1,174 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Diagnostic formatting of VARIANT and DISPPARAMS structures.
+//!
+//! Renders exactly what goes on the wire: VARIANT type names (with VT_ARRAY/VT_BYREF modifiers), values,
+//! and mapping of named arguments to their DISPIDs.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::debug_dump::debug_dump_variant;
+//! use std::convert::TryFrom;
+//! use rusty_winapi::smart_variant::{AutoVariant, SmartVariant};
+//! use winapi::um::oaidl::VARIANT;
+//!
+//! let variant: VARIANT = AutoVariant::try_from(SmartVariant::Int4(42)).unwrap().into();
+//! assert_eq!("VT_I4 42", debug_dump_variant(&variant));
+//! ```
+
+use std::fmt::Write;
+
+use winapi::shared::wtypes::*;
+use winapi::um::oaidl::{DISPPARAMS, VARIANT};
+
+use crate::auto_bstr::BStr;
+use crate::smart_variant::vt_name;
+
+/// Renders VARIANT type tag with modifiers, e.g. `VT_BYREF|VT_I4`.
+pub fn debug_dump_vartype(vt: VARTYPE) -> String {
+    let mut result = String::new();
+    if vt & VT_ARRAY as VARTYPE != 0 {
+        result.push_str("VT_ARRAY|");
+    }
+    if vt & VT_BYREF as VARTYPE != 0 {
+        result.push_str("VT_BYREF|");
+    }
+    result.push_str(vt_name((vt & VT_TYPEMASK as VARTYPE) as VARENUM));
+
+    result
+}
+
+/// Renders VARIANT type and value without taking ownership of it, e.g. `VT_BSTR "abc"`.
+pub fn debug_dump_variant(variant: &VARIANT) -> String {
+    unsafe {
+        let vt = variant.n1.n2().vt;
+        let data = &variant.n1.n2().n3;
+        let name = debug_dump_vartype(vt);
+
+        if vt & (VT_ARRAY | VT_BYREF) as VARTYPE != 0 {
+            return format!("{} {:p}", name, *data.byref());
+        }
+
+        match vt as VARENUM {
+            VT_EMPTY | VT_NULL => name,
+            VT_I2 => format!("{} {}", name, data.iVal()),
+            VT_I4 => format!("{} {}", name, data.lVal()),
+            VT_R4 => format!("{} {}", name, data.fltVal()),
+            VT_R8 => format!("{} {}", name, data.dblVal()),
+            VT_CY => format!("{} {}", name, data.cyVal().int64),
+            VT_DATE => format!("{} {}", name, data.date()),
+            VT_BSTR => format!("{} {:?}", name, BStr::from_raw(*data.bstrVal())),
+            VT_DISPATCH => format!("{} {:p}", name, *data.pdispVal()),
+            VT_ERROR => format!("{} 0x{:08X}", name, data.scode()),
+            VT_BOOL => format!("{} {}", name, *data.boolVal() != 0),
+            VT_VARIANT => format!("{} {:p}", name, *data.pvarVal()),
+            VT_UNKNOWN => format!("{} {:p}", name, *data.punkVal()),
+            VT_I1 => format!("{} {}", name, data.cVal()),
+            VT_UI1 => format!("{} {}", name, data.bVal()),
+            VT_UI2 => format!("{} {}", name, data.uiVal()),
+            VT_UI4 => format!("{} {}", name, data.ulVal()),
+            VT_I8 => format!("{} {}", name, data.llVal()),
+            VT_UI8 => format!("{} {}", name, data.ullVal()),
+            VT_INT => format!("{} {}", name, data.intVal()),
+            VT_UINT => format!("{} {}", name, data.uintVal()),
+            _ => format!("{} 0x{:016X}", name, data.ullVal()),
+        }
+    }
+}
+
+/// Renders DISPPARAMS as a multi-line tree of arguments in caller's (not reversed) order,
+/// named arguments are marked with their DISPIDs.
+///
+/// # Safety
+///
+/// `params` must point to valid `cArgs` VARIANTs and `cNamedArgs` DISPIDs, as required by IDispatch::Invoke.
+pub unsafe fn debug_dump(params: &DISPPARAMS) -> String {
+    let mut result = format!(
+        "DISPPARAMS {{ cArgs: {}, cNamedArgs: {} }}",
+        params.cArgs, params.cNamedArgs
+    );
+
+    let args: &[VARIANT] = if params.rgvarg.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(params.rgvarg, params.cArgs as usize)
+    };
+    let named: &[i32] = if params.rgdispidNamedArgs.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(params.rgdispidNamedArgs, params.cNamedArgs as usize)
+    };
+
+    // Named arguments go first in rgvarg, positional ones follow in reversed order.
+    for (i, arg) in args.iter().enumerate().rev() {
+        let _ = match named.get(i) {
+            Some(dispid) => write!(
+                result,
+                "\n  [{}] named (DISPID {}): {}",
+                i,
+                dispid,
+                debug_dump_variant(arg)
+            ),
+            None => write!(
+                result,
+                "\n  [{}] arg #{}: {}",
+                i,
+                args.len() - 1 - i,
+                debug_dump_variant(arg)
+            ),
+        };
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smart_variant::{AutoVariant, SmartVariant};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_debug_dump() {
+        let mut args: Vec<VARIANT> = vec![
+            AutoVariant::try_from(SmartVariant::Bool(true))
+                .unwrap()
+                .into(),
+            AutoVariant::try_from(SmartVariant::Text("abc".into()))
+                .unwrap()
+                .into(),
+            AutoVariant::try_from(SmartVariant::Int4(42))
+                .unwrap()
+                .into(),
+        ];
+        let mut named = vec![-3];
+        let params = DISPPARAMS {
+            rgvarg: args.as_mut_ptr(),
+            rgdispidNamedArgs: named.as_mut_ptr(),
+            cArgs: 3,
+            cNamedArgs: 1,
+        };
+
+        assert_eq!(
+            "DISPPARAMS { cArgs: 3, cNamedArgs: 1 }\n  [2] arg #0: VT_I4 42\n  [1] arg #1: VT_BSTR \"abc\"\n  [0] named (DISPID -3): VT_BOOL true",
+            unsafe { debug_dump(&params) }
+        );
+
+        for x in args {
+            drop(AutoVariant::from(x));
+        }
+    }
+
+    #[test]
+    fn test_debug_dump_vartype() {
+        assert_eq!(
+            "VT_BYREF|VT_I4",
+            debug_dump_vartype((VT_BYREF | VT_I4) as VARTYPE)
+        );
+        assert_eq!(
+            "VT_ARRAY|VT_VARIANT",
+            debug_dump_vartype((VT_ARRAY | VT_VARIANT) as VARTYPE)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Early-bound calls of dual interfaces through their vtables.
+//!
+//! [`EarlyBound`] wraps an automation object and, if the object's type info describes a dual interface, calls its
+//! members directly through the vtable (by `oVft` offset of FUNCDESC, with DispCallFunc) instead of
+//! IDispatch::Invoke: no DISPPARAMS marshaling and no argument coercion by the server, which is noticeable in
+//! chatty automation loops. Members the vtable can't serve (inherited from another interface, with named or
+//! omitted arguments, records or pointers in parameters) fall back to late binding, as do objects without type
+//! info or dual interface.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::early_bound::EarlyBound;
+//! use rusty_winapi::smart_idispatch::SmartIDispatch;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create().unwrap();
+//! let mut excel = EarlyBound::new(excel);
+//! for _ in 0..1000 {
+//!     excel.get("Ready").unwrap();
+//! }
+//! ```
+//!
+//! [`EarlyBound`]: struct.EarlyBound.html
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::{UINT, WORD};
+use winapi::shared::ntdef::LCID;
+use winapi::shared::winerror;
+use winapi::shared::wtypes::*;
+use winapi::um::oaidl::{
+    IDispatch, ITypeInfo, CC_STDCALL, DISPID, PARAMFLAG_FRETVAL, TKIND_DISPATCH, TKIND_ENUM,
+    TYPEFLAG_FDUAL, VARIANT,
+};
+use winapi::um::unknwnbase::IUnknown;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::{ComResult, RustyWinapiError};
+use crate::error_info::{clear_error_info, get_error_info, set_last_error_info};
+use crate::ffi::DispCallFunc;
+use crate::smart_idispatch::SmartIDispatch;
+use crate::smart_itypeinfo::{ElementType, SmartITypeInfo};
+use crate::smart_iunknown::SmartIUnknown;
+use crate::smart_variant::{AutoVariant, SmartVariant};
+
+/// Automation object calling members of its dual interface through the vtable, see
+/// [module documentation](index.html).
+pub struct EarlyBound {
+    dispatch: AutoCOMInterface<IDispatch>,
+    vtable: Option<Vtable>,
+}
+
+/// Vtable interface of the dual interface with slots of its members, resolved at first call.
+struct Vtable {
+    interface: AutoCOMInterface<IUnknown>,
+    type_info: AutoCOMInterface<ITypeInfo>,
+    slots: HashMap<(DISPID, WORD), Option<Slot>>,
+}
+
+/// Member callable through the vtable.
+struct Slot {
+    offset: usize,
+    /// VARTYPEs of `[in]` parameters.
+    params: Vec<VARTYPE>,
+    /// VARTYPE of `[out, retval]` parameter.
+    retval: Option<VARTYPE>,
+}
+
+impl EarlyBound {
+    /// Wraps `dispatch`, looking up its dual interface by the type info. Objects without one are called late-bound.
+    pub fn new(dispatch: AutoCOMInterface<IDispatch>) -> Self {
+        let vtable = Vtable::of(&dispatch);
+        EarlyBound { dispatch, vtable }
+    }
+
+    /// Returns `true` if members are called through the vtable (where possible).
+    pub fn is_early_bound(&self) -> bool {
+        self.vtable.is_some()
+    }
+
+    /// Unwraps the object.
+    pub fn into_inner(self) -> AutoCOMInterface<IDispatch> {
+        self.dispatch
+    }
+}
+
+impl From<AutoCOMInterface<IDispatch>> for EarlyBound {
+    fn from(x: AutoCOMInterface<IDispatch>) -> Self {
+        EarlyBound::new(x)
+    }
+}
+
+impl Vtable {
+    fn of(dispatch: &AutoCOMInterface<IDispatch>) -> Option<Self> {
+        let type_info = dispatch.get_type_info(0, dispatch.lcid()).ok()?;
+        let iid = {
+            let attr = type_info.type_attr().ok()?;
+            if attr.typekind != TKIND_DISPATCH || attr.wTypeFlags & TYPEFLAG_FDUAL as WORD == 0 {
+                return None;
+            }
+            attr.guid
+        };
+        let type_info = type_info.impl_type_info(UINT::MAX).ok()?;
+
+        let mut pvoid = std::ptr::null_mut();
+        let hresult = unsafe { dispatch.as_iunknown().QueryInterface(&iid, &mut pvoid) };
+        if winerror::FAILED(hresult) {
+            return None;
+        }
+
+        Some(Vtable {
+            interface: (pvoid as *mut IUnknown).try_into().ok()?,
+            type_info,
+            slots: HashMap::new(),
+        })
+    }
+}
+
+impl Slot {
+    /// Finds function `dispid` of invoke kind in `flags` (INVOKEKIND bits are the same as DISPATCH_ flags).
+    fn find(type_info: &AutoCOMInterface<ITypeInfo>, dispid: DISPID, flags: WORD) -> Option<Self> {
+        for i in 0..type_info.type_attr().ok()?.cFuncs {
+            let func = type_info.func_desc(i.into()).ok()?;
+            if func.memid != dispid || func.invkind & flags as u32 == 0 {
+                continue;
+            }
+            if func.cParamsOpt != 0 || func.result() != ElementType::Base(VT_HRESULT as VARTYPE) {
+                return None;
+            }
+
+            let mut params = Vec::new();
+            let mut retval = None;
+            let descs = func.param_descs();
+            for (j, (desc, param)) in descs.iter().zip(func.params()).enumerate() {
+                let param_flags = unsafe { desc.u.paramdesc().wParamFlags } as u32;
+                match param {
+                    ElementType::Ptr(x)
+                        if param_flags & PARAMFLAG_FRETVAL != 0 && j + 1 == descs.len() =>
+                    {
+                        retval = Some(vartype(type_info, &x)?);
+                    }
+                    x => params.push(vartype(type_info, &x)?),
+                }
+            }
+
+            return Some(Slot {
+                offset: func.oVft as usize,
+                params,
+                retval,
+            });
+        }
+
+        None
+    }
+
+    /// Calls the slot, `None` if it doesn't take `params` as is.
+    fn call(
+        &self,
+        interface: &AutoCOMInterface<IUnknown>,
+        lcid: LCID,
+        params: &[SmartVariant],
+    ) -> Option<ComResult<SmartVariant>> {
+        if params.len() != self.params.len() {
+            return None;
+        }
+
+        let mut args = Vec::with_capacity(params.len() + 1);
+        for (i, (x, vt)) in params.iter().zip(&self.params).enumerate() {
+            let x = match AutoVariant::try_from_smart(x.clone()) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e.into())),
+            };
+            let x = match *vt as u32 {
+                VT_VARIANT => Ok(x),
+                vt if x.vtype() == vt => Ok(x),
+                vt => x.change_type(vt, lcid),
+            };
+            match x {
+                Ok(x) => args.push(x),
+                Err(_) => return Some(Err(RustyWinapiError::TypeMismatch { argument: Some(i) })),
+            }
+        }
+
+        let mut vts: Vec<VARTYPE> = self.params.clone();
+        let mut retval = AutoVariant::new();
+        let mut out = AutoVariant::new();
+        if let Some(vt) = self.retval {
+            *out.vtype_mut() = VT_BYREF as VARTYPE | vt;
+            unsafe {
+                *out.data_mut().byref_mut() = match vt as u32 {
+                    VT_VARIANT => retval.as_mut_ptr() as *mut c_void,
+                    _ => retval.data_mut() as *mut _ as *mut c_void,
+                };
+            }
+            vts.push(VT_BYREF as VARTYPE | vt);
+        }
+        let mut pargs: Vec<*mut VARIANT> = args
+            .iter_mut()
+            .map(|x| x.as_mut_ptr())
+            .chain(self.retval.map(|_| out.as_mut_ptr()))
+            .collect();
+
+        let mut result = AutoVariant::new();
+        clear_error_info();
+        let hresult = unsafe {
+            DispCallFunc(
+                interface.as_iunknown_ptr() as *mut c_void,
+                self.offset,
+                CC_STDCALL,
+                VT_I4 as VARTYPE,
+                pargs.len() as UINT,
+                vts.as_mut_ptr(),
+                pargs.as_mut_ptr(),
+                result.as_mut_ptr(),
+            )
+        };
+        // `out` refers to `retval`, it owns nothing.
+        *out.vtype_mut() = VT_EMPTY as VARTYPE;
+
+        let hresult = match hresult {
+            x if winerror::FAILED(x) => x,
+            _ => unsafe { *result.data().lVal() },
+        };
+        if winerror::FAILED(hresult) {
+            let mut info = get_error_info().unwrap_or_default();
+            info.scode = hresult;
+            set_last_error_info(Some(info.clone()));
+            return Some(Err(RustyWinapiError::from_dispatch(hresult, info, 0)));
+        }
+
+        if let Some(vt) = self.retval.filter(|&x| x as u32 != VT_VARIANT) {
+            *retval.vtype_mut() = vt;
+        }
+        Some(SmartVariant::try_from(retval).map_err(RustyWinapiError::from))
+    }
+}
+
+/// VARTYPE of a parameter passed by value, `None` if it can't be passed as VARIANT.
+fn vartype(type_info: &AutoCOMInterface<ITypeInfo>, x: &ElementType) -> Option<VARTYPE> {
+    match x {
+        ElementType::Base(vt) => match *vt as u32 {
+            VT_I1 | VT_UI1 | VT_I2 | VT_UI2 | VT_I4 | VT_UI4 | VT_I8 | VT_UI8 | VT_INT
+            | VT_UINT | VT_R4 | VT_R8 | VT_DATE | VT_BSTR | VT_BOOL | VT_ERROR | VT_VARIANT
+            | VT_DISPATCH | VT_UNKNOWN => Some(*vt),
+            _ => None,
+        },
+        ElementType::UserDefined(href) => {
+            let referenced = type_info.ref_type_info(*href).ok()?;
+            let attr = referenced.type_attr().ok()?;
+            match attr.typekind {
+                TKIND_ENUM => Some(VT_I4 as VARTYPE),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+impl SmartIUnknown for EarlyBound {
+    fn as_iunknown(&self) -> &IUnknown {
+        self.dispatch.as_iunknown()
+    }
+
+    fn as_iunknown_mut(&mut self) -> &mut IUnknown {
+        self.dispatch.as_iunknown_mut()
+    }
+}
+
+impl SmartIDispatch for EarlyBound {
+    fn as_idispatch(&self) -> &IDispatch {
+        self.dispatch.as_inner()
+    }
+
+    fn as_idispatch_mut(&mut self) -> &mut IDispatch {
+        self.dispatch.as_inner_mut()
+    }
+
+    fn invoke_named(
+        &mut self,
+        member_dispid: DISPID,
+        lcid: LCID,
+        flags: WORD,
+        params: &[SmartVariant],
+        named: &[(DISPID, SmartVariant)],
+    ) -> ComResult<SmartVariant> {
+        if let (Some(vtable), true) = (&mut self.vtable, named.is_empty()) {
+            let Vtable {
+                interface,
+                type_info,
+                slots,
+            } = vtable;
+            let slot = slots
+                .entry((member_dispid, flags))
+                .or_insert_with(|| Slot::find(type_info, member_dispid, flags));
+            if let Some(result) = slot.as_ref().and_then(|x| x.call(interface, lcid, params)) {
+                return result;
+            }
+        }
+        self.dispatch
+            .invoke_named(member_dispid, lcid, flags, params, named)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+
+    #[test]
+    fn test_EarlyBound_fallback() {
+        let object = DynamicObject::new().with("Count", SmartVariant::Int4(1));
+        let mut early = EarlyBound::new(object.clone().into_dispatch());
+        assert!(!early.is_early_bound());
+
+        early.put("Count", SmartVariant::Int4(2)).unwrap();
+        assert_eq!(SmartVariant::Int4(2), early.get("Count").unwrap());
+        assert_eq!(Some(SmartVariant::Int4(2)), object.get("Count"));
+    }
+}
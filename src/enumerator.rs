@@ -0,0 +1,355 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Iterators over COM enumerators: IEnumVARIANT of automation collections, IEnumString, IEnumUnknown and
+//! IEnumConnectionPoints.
+//!
+//! [`EnumVariant`] walks `_NewEnum` of a collection (`For Each` in VBScript), [`EnumString`] yields strings freeing
+//! them with CoTaskMemFree (e.g. of `IBindCtx::EnumObjectParam` and autocomplete sources), and
+//! [`EnumUnknown`] yields owned objects (e.g. of `IOleContainer::EnumObjects`). Each item is a `ComResult`, an
+//! iterator stops after a failed `Next`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::enumerator::EnumVariant;
+//! use rusty_winapi::prelude::*;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let mut excel = Activate::<IDispatch>::new().progid("Excel.Application").create()?;
+//! let mut workbooks: AutoCOMInterface<IDispatch> = excel.get("Workbooks")?.try_into()?;
+//! for workbook in EnumVariant::of(&mut workbooks)? {
+//!     let mut workbook: AutoCOMInterface<IDispatch> = workbook?.try_into()?;
+//!     println!("{:?}", workbook.get("Name")?);
+//! }
+//! # Ok::<(), RustyWinapiError>(())
+//! ```
+//!
+//! [`EnumVariant`]: struct.EnumVariant.html
+//! [`EnumString`]: struct.EnumString.html
+//! [`EnumUnknown`]: struct.EnumUnknown.html
+
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
+
+use winapi::shared::ntdef::{HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::shared::wtypesbase::LPOLESTR;
+use winapi::um::oaidl::{DISPID_NEWENUM, VARIANT};
+use winapi::um::objidlbase::{IEnumString, IEnumUnknown};
+use winapi::um::oleauto::{DISPATCH_METHOD, DISPATCH_PROPERTYGET};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::RIDL;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::{ComResult, RustyWinapiError};
+use crate::safe::clsid::take_co_task_string;
+use crate::smart_iconnectionpoint::{IConnectionPoint, IEnumConnectionPoints};
+use crate::smart_idispatch::SmartIDispatch;
+use crate::smart_variant::{AutoVariant, SmartVariant};
+
+// Not declared in `winapi` 0.3.
+RIDL! {#[uuid(0x00020404, 0x0000, 0x0000, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46)]
+interface IEnumVARIANT(IEnumVARIANTVtbl): IUnknown(IUnknownVtbl) {
+    fn Next(
+        celt: ULONG,
+        rgVar: *mut VARIANT,
+        pCeltFetched: *mut ULONG,
+    ) -> HRESULT,
+    fn Skip(
+        celt: ULONG,
+    ) -> HRESULT,
+    fn Reset() -> HRESULT,
+    fn Clone(
+        ppEnum: *mut *mut IEnumVARIANT,
+    ) -> HRESULT,
+}}
+
+crate::impl_interface_vtbl!(IEnumVARIANT => IEnumVARIANTVtbl);
+
+/// Number of elements [`EnumVariant`] requests per IEnumVARIANT::Next call by default.
+///
+/// [`EnumVariant`]: struct.EnumVariant.html
+pub const DEFAULT_BATCH_SIZE: ULONG = 32;
+
+/// Iterator over IEnumVARIANT, requests elements in batches and buffers them.
+///
+/// Batches save a round trip per element with out-of-process collections, e.g. rows of Excel ranges or 1C query
+/// results. Fetched elements are owned by the iterator until yielded. Elements of types [`SmartVariant`] can't hold
+/// are yielded as `RustyWinapiError::Conversion` errors, following elements are still yielded.
+///
+/// [`SmartVariant`]: ../smart_variant/enum.SmartVariant.html
+pub struct EnumVariant {
+    inner: AutoCOMInterface<IEnumVARIANT>,
+    done: bool,
+    batch_size: ULONG,
+    buffer: VecDeque<ComResult<SmartVariant>>,
+}
+
+/// Iterator over IEnumString.
+pub struct EnumString {
+    inner: AutoCOMInterface<IEnumString>,
+    done: bool,
+}
+
+/// Iterator over IEnumUnknown.
+pub struct EnumUnknown {
+    inner: AutoCOMInterface<IEnumUnknown>,
+    done: bool,
+}
+
+/// Iterator over IEnumConnectionPoints, see [`SmartIConnectionPointContainer::connection_points`].
+///
+/// [`SmartIConnectionPointContainer::connection_points`]: ../smart_iconnectionpoint/trait.SmartIConnectionPointContainer.html#method.connection_points
+pub struct EnumConnectionPoints {
+    inner: AutoCOMInterface<IEnumConnectionPoints>,
+    done: bool,
+}
+
+macro_rules! impl_enumerator {
+    ($($name:ident => $interface:ident),+) => {
+        $(
+            impl $name {
+                /// Wraps enumerator, iteration continues from its current position.
+                pub fn new(inner: AutoCOMInterface<$interface>) -> Self {
+                    $name { inner, done: false }
+                }
+
+                /// Rewinds enumerator to the beginning of the sequence.
+                pub fn reset(&mut self) -> ComResult<()> {
+                    check(unsafe { self.inner.Reset() })?;
+                    self.done = false;
+                    Ok(())
+                }
+
+                /// Skips `count` elements, returns `false` if fewer than `count` remained.
+                pub fn skip_elements(&mut self, count: ULONG) -> ComResult<bool> {
+                    Ok(check(unsafe { self.inner.Skip(count) })? == winerror::S_OK)
+                }
+
+                /// Unwraps the enumerator.
+                pub fn into_inner(self) -> AutoCOMInterface<$interface> {
+                    self.inner
+                }
+
+                /// Fetches the next element with `fetch`, which receives the element buffer.
+                fn fetch<T, F>(&mut self, fetch: F) -> Option<ComResult<T>>
+                where
+                    F: FnOnce(&$interface, *mut ULONG) -> (HRESULT, Option<T>),
+                {
+                    if self.done {
+                        return None;
+                    }
+
+                    let mut fetched: ULONG = 0;
+                    let (hresult, item) = fetch(&self.inner, &mut fetched);
+                    match check(hresult) {
+                        Ok(_) if fetched == 1 => item.map(Ok),
+                        Ok(_) => {
+                            self.done = true;
+                            None
+                        }
+                        Err(e) => {
+                            self.done = true;
+                            Some(Err(e))
+                        }
+                    }
+                }
+            }
+
+            impl From<AutoCOMInterface<$interface>> for $name {
+                fn from(x: AutoCOMInterface<$interface>) -> Self {
+                    $name::new(x)
+                }
+            }
+        )+
+    };
+}
+
+impl_enumerator!(
+    EnumString => IEnumString,
+    EnumUnknown => IEnumUnknown,
+    EnumConnectionPoints => IEnumConnectionPoints
+);
+
+fn check(hresult: HRESULT) -> ComResult<HRESULT> {
+    if winerror::SUCCEEDED(hresult) {
+        Ok(hresult)
+    } else {
+        Err(hresult.into())
+    }
+}
+
+impl EnumVariant {
+    /// Wraps enumerator, iteration continues from its current position.
+    pub fn new(inner: AutoCOMInterface<IEnumVARIANT>) -> Self {
+        EnumVariant {
+            inner,
+            done: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Sets number of elements requested per Next call (at least 1), builder style.
+    ///
+    /// Use 1 for enumerators which compute elements lazily and may be abandoned early.
+    pub fn with_batch_size(mut self, batch_size: ULONG) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Number of elements requested per Next call.
+    #[inline]
+    pub fn batch_size(&self) -> ULONG {
+        self.batch_size
+    }
+
+    /// Rewinds enumerator to the beginning of the sequence, drops buffered elements.
+    pub fn reset(&mut self) -> ComResult<()> {
+        check(unsafe { self.inner.Reset() })?;
+        self.buffer.clear();
+        self.done = false;
+        Ok(())
+    }
+
+    /// Skips `count` elements, buffered ones first, returns `false` if fewer than `count` remained.
+    pub fn skip_elements(&mut self, count: ULONG) -> ComResult<bool> {
+        let buffered = self.buffer.len().min(count as usize);
+        self.buffer.drain(..buffered);
+        let count = count - buffered as ULONG;
+        if count == 0 {
+            Ok(true)
+        } else if self.done {
+            Ok(false)
+        } else {
+            Ok(check(unsafe { self.inner.Skip(count) })? == winerror::S_OK)
+        }
+    }
+
+    /// Unwraps the enumerator, its position is past the buffered elements, which are dropped.
+    pub fn into_inner(self) -> AutoCOMInterface<IEnumVARIANT> {
+        self.inner
+    }
+
+    /// Fetches the next batch into the buffer.
+    fn fill(&mut self) -> ComResult<()> {
+        let mut items: Vec<VARIANT> = (0..self.batch_size)
+            .map(|_| AutoVariant::new().into())
+            .collect();
+        let mut fetched: ULONG = 0;
+        let hresult = check(unsafe {
+            self.inner
+                .Next(self.batch_size, items.as_mut_ptr(), &mut fetched)
+        })?;
+
+        // Elements past `fetched` are left VT_EMPTY, elements SmartVariant can't hold (e.g. VT_NULL) are yielded as
+        // errors without stopping the iteration.
+        let fetched = (fetched as usize).min(items.len());
+        self.buffer.extend(
+            items
+                .into_iter()
+                .take(fetched)
+                .map(|x| SmartVariant::try_from(x).map_err(RustyWinapiError::from)),
+        );
+        self.done = hresult != winerror::S_OK || fetched < self.batch_size as usize;
+        Ok(())
+    }
+
+    /// Enumerator of an automation collection, returned by its `_NewEnum` member (DISPID_NEWENUM).
+    pub fn of<D: SmartIDispatch + ?Sized>(collection: &mut D) -> ComResult<Self> {
+        let lcid = collection.lcid();
+        let result = collection.invoke(
+            DISPID_NEWENUM,
+            lcid,
+            DISPATCH_METHOD | DISPATCH_PROPERTYGET,
+            &[],
+        )?;
+        let unknown: AutoCOMInterface<IUnknown> = match result {
+            SmartVariant::IDispatch(x) => x.to_iunknown(),
+            x => x.try_into()?,
+        };
+        Ok(EnumVariant::new(unknown.cast()?))
+    }
+}
+
+impl Iterator for EnumVariant {
+    type Item = ComResult<SmartVariant>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            if let Err(e) = self.fill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.buffer.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.done {
+            true => (self.buffer.len(), Some(self.buffer.len())),
+            false => (self.buffer.len(), None),
+        }
+    }
+}
+
+impl Iterator for EnumString {
+    type Item = ComResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fetch(|inner, fetched| {
+            let mut item: LPOLESTR = std::ptr::null_mut();
+            let hresult = unsafe { inner.Next(1, &mut item, fetched) };
+            (hresult, Some(take_co_task_string(item)))
+        })
+    }
+}
+
+impl Iterator for EnumUnknown {
+    type Item = ComResult<AutoCOMInterface<IUnknown>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fetch(|inner, fetched| {
+            let mut item: *mut IUnknown = std::ptr::null_mut();
+            let hresult = unsafe { inner.Next(1, &mut item, fetched) };
+            (hresult, Some(unsafe { AutoCOMInterface::from_raw(item) }))
+        })
+    }
+}
+
+impl Iterator for EnumConnectionPoints {
+    type Item = ComResult<AutoCOMInterface<IConnectionPoint>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fetch(|inner, fetched| {
+            let mut item: *mut IConnectionPoint = std::ptr::null_mut();
+            let hresult = unsafe { inner.Next(1, &mut item, fetched) };
+            (hresult, Some(unsafe { AutoCOMInterface::from_raw(item) }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hresult::HResult;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+    use winapi::Interface;
+
+    #[test]
+    fn test_EnumVariant_of_non_collection() {
+        let mut object = DynamicObject::new().into_dispatch();
+        let error = EnumVariant::of(&mut object).err().unwrap();
+        assert_eq!(HResult::DISP_E_MEMBERNOTFOUND, error.hresult());
+        assert_eq!(0x00020404, IEnumVARIANT::uuidof().Data1);
+    }
+
+    #[test]
+    fn test_EnumVariant_batch_size() {
+        let null = AutoCOMInterface::<IEnumVARIANT>::default();
+        assert_eq!(DEFAULT_BATCH_SIZE, EnumVariant::new(null).batch_size());
+        let null = AutoCOMInterface::<IEnumVARIANT>::default();
+        assert_eq!(1, EnumVariant::new(null).with_batch_size(0).batch_size());
+    }
+}
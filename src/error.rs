@@ -0,0 +1,633 @@
+#![allow(non_snake_case)]
+
+//! Error types shared across the crate.
+//!
+
+use std::error::Error;
+use std::fmt;
+
+use winapi::shared::guiddef::IID;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::oaidl::DISPID;
+
+use crate::error_info::ErrorInfo;
+use crate::hresult::{describe, HResult};
+use crate::safe::bstr::SysAllocError;
+use crate::safe::guid::Guid;
+
+/// Error of a failed `TryFrom` conversion, carrying both the source value summary and the requested target type.
+///
+/// Displays as `cannot convert VT_BSTR "abc" to i32`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConversionError {
+    source: String,
+    target: &'static str,
+}
+
+impl ConversionError {
+    /// Creates a new error from a source value summary and a target type name.
+    pub fn new<S: Into<String>>(source: S, target: &'static str) -> Self {
+        ConversionError {
+            source: source.into(),
+            target,
+        }
+    }
+
+    /// Summary of the value which failed to convert (VT name and value, when printable).
+    #[inline]
+    pub fn source_summary(&self) -> &str {
+        &self.source
+    }
+
+    /// Name of the requested target type.
+    #[inline]
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot convert {} to {}", self.source, self.target)
+    }
+}
+
+impl Error for ConversionError {}
+
+/// Error of object creation by ProgID, telling an unknown ProgID from a failure of a registered class.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActivationError {
+    /// ProgID isn't registered (HRESULT of CLSIDFromProgID, usually `CO_E_CLASSSTRING`).
+    ProgIdNotFound { progid: String, hresult: HRESULT },
+    /// Class is registered, but object creation failed.
+    Activation(HRESULT),
+}
+
+impl ActivationError {
+    /// Underlying HRESULT.
+    pub fn hresult(&self) -> HResult {
+        match self {
+            ActivationError::ProgIdNotFound { hresult, .. } => HResult(*hresult),
+            ActivationError::Activation(x) => HResult(*x),
+        }
+    }
+}
+
+impl From<ActivationError> for HRESULT {
+    fn from(x: ActivationError) -> Self {
+        x.hresult().into()
+    }
+}
+
+impl fmt::Display for ActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActivationError::ProgIdNotFound { progid, hresult } => write!(
+                f,
+                "ProgID {:?} is not registered (HRESULT {:#010X})",
+                progid, hresult
+            ),
+            ActivationError::Activation(hresult) => match describe(*hresult) {
+                Some(x) => write!(
+                    f,
+                    "object creation failed: {} (HRESULT {:#010X})",
+                    x, hresult
+                ),
+                None => write!(f, "object creation failed (HRESULT {:#010X})", hresult),
+            },
+        }
+    }
+}
+
+impl Error for ActivationError {}
+
+/// Error of a member call of an automation object implemented in Rust, see [`DispatchServer`].
+///
+/// [`DispatchServer`]: ../server/dispatch/trait.DispatchServer.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum DispatchError {
+    /// Call fails with HRESULT returned from Invoke, e.g. `DISP_E_MEMBERNOTFOUND` or `DISP_E_BADPARAMCOUNT`.
+    Failed(HRESULT),
+    /// Argument at `index` (in caller's order, from 0) is wrong, e.g. `DISP_E_TYPEMISMATCH`.
+    Argument { index: usize, hresult: HRESULT },
+    /// Exception reported through EXCEPINFO, Invoke returns `DISP_E_EXCEPTION`.
+    Exception {
+        scode: HRESULT,
+        source: String,
+        description: String,
+    },
+}
+
+impl DispatchError {
+    /// Creates an exception with `E_FAIL` scode, displayed by clients as an error message.
+    pub fn exception<S: Into<String>>(description: S) -> Self {
+        DispatchError::Exception {
+            scode: winapi::shared::winerror::E_FAIL,
+            source: String::new(),
+            description: description.into(),
+        }
+    }
+
+    /// Creates a type mismatch error of argument at `index`.
+    pub fn type_mismatch(index: usize) -> Self {
+        DispatchError::Argument {
+            index,
+            hresult: winapi::shared::winerror::DISP_E_TYPEMISMATCH,
+        }
+    }
+
+    /// HRESULT returned from Invoke.
+    pub fn hresult(&self) -> HResult {
+        match self {
+            DispatchError::Failed(x) | DispatchError::Argument { hresult: x, .. } => HResult(*x),
+            DispatchError::Exception { .. } => HResult::DISP_E_EXCEPTION,
+        }
+    }
+}
+
+impl From<HRESULT> for DispatchError {
+    fn from(x: HRESULT) -> Self {
+        DispatchError::Failed(x)
+    }
+}
+
+impl From<ConversionError> for DispatchError {
+    fn from(_: ConversionError) -> Self {
+        DispatchError::Failed(winapi::shared::winerror::DISP_E_TYPEMISMATCH)
+    }
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchError::Failed(x) => match describe(*x) {
+                Some(message) => write!(f, "{} (HRESULT {:#010X})", message, x),
+                None => write!(f, "call failed (HRESULT {:#010X})", x),
+            },
+            DispatchError::Argument { index, hresult } => write!(
+                f,
+                "argument {} is invalid (HRESULT {:#010X})",
+                index, hresult
+            ),
+            DispatchError::Exception { description, .. } => write!(f, "{}", description),
+        }
+    }
+}
+
+impl Error for DispatchError {}
+
+/// Automation member a failure is attributed to, see [`RustyWinapiError::Member`].
+///
+/// Displays as `Workbook.Save (DISPID 283)`, parts which aren't known are omitted. Member reached by a dot path
+/// (see [`SmartIDispatch::get_path`]) is followed by the path, e.g. `Save (DISPID 283) in "ActiveWorkbook.Save"`.
+///
+/// [`SmartIDispatch::get_path`]: ../smart_idispatch/trait.SmartIDispatch.html#method.get_path
+///
+/// [`RustyWinapiError::Member`]: enum.RustyWinapiError.html#variant.Member
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemberContext {
+    /// Interface name from type information of the object, `None` if the object doesn't provide it.
+    pub interface: Option<String>,
+    pub member: String,
+    /// DISPID of the member, `None` if the name wasn't resolved.
+    pub dispid: Option<DISPID>,
+    /// Dot path the member was reached by, `None` if it was called directly.
+    pub path: Option<String>,
+    /// Index of the member among segments of `path`.
+    pub segment: usize,
+}
+
+impl fmt::Display for MemberContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(x) = &self.interface {
+            write!(f, "{}.", x)?;
+        }
+        write!(f, "{}", self.member)?;
+        if let Some(x) = self.dispid {
+            write!(f, " (DISPID {})", x)?;
+        }
+        if let Some(x) = &self.path {
+            write!(f, " in \"{}\"", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// Crate-wide error, every failure of the crate converts into it, so `?` composes with `std::error::Error` based
+/// error handling of applications.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RustyWinapiError {
+    /// BSTR allocation failed.
+    Allocation(SysAllocError),
+    /// Object creation failed.
+    Activation(ActivationError),
+    /// Object doesn't implement the requested interface (QueryInterface failed).
+    QueryInterface { iid: Guid, hresult: HRESULT },
+    /// Call of an automation member failed, `arg_err` is the index of the offending argument if meaningful.
+    Dispatch {
+        hresult: HRESULT,
+        info: ErrorInfo,
+        arg_err: u32,
+    },
+    /// Member of an automation object implemented in Rust failed.
+    Server(DispatchError),
+    /// Value can't be converted into the requested type.
+    Conversion(ConversionError),
+    /// Argument at `argument` position (in caller's order, `None` if the server didn't tell) has a wrong type
+    /// (`DISP_E_TYPEMISMATCH`).
+    TypeMismatch { argument: Option<usize> },
+    /// Argument at `argument` position (in caller's order, `None` if the server didn't tell) isn't a known parameter
+    /// (`DISP_E_PARAMNOTFOUND`).
+    ParamNotFound { argument: Option<usize> },
+    /// Wrong number of arguments (`DISP_E_BADPARAMCOUNT`).
+    BadParamCount,
+    /// Required parameter is missing (`DISP_E_PARAMNOTOPTIONAL`).
+    ParamNotOptional,
+    /// Member doesn't exist or doesn't support the kind of call, e.g. put of a read-only property
+    /// (`DISP_E_MEMBERNOTFOUND`).
+    MemberNotFound,
+    /// Name isn't known to the object (`DISP_E_UNKNOWNNAME`).
+    UnknownName,
+    /// Member doesn't accept named arguments (`DISP_E_NONAMEDARGS`).
+    NoNamedArgs,
+    /// Argument can't be coerced to the parameter type without overflow (`DISP_E_OVERFLOW`).
+    Overflow,
+    /// Argument has a VARTYPE the object doesn't support (`DISP_E_BADVARTYPE`).
+    BadVarType,
+    /// Call was cancelled (`RPC_E_CALL_CANCELED`), see [`cancel`].
+    ///
+    /// [`cancel`]: ../cancel/index.html
+    Cancelled,
+    /// Any other failed COM call.
+    Com(HRESULT),
+    /// Failure without HRESULT.
+    Other(String),
+    /// Failure of `error` in a call of a named member, see [`root_cause`].
+    ///
+    /// [`root_cause`]: #method.root_cause
+    Member {
+        context: MemberContext,
+        error: Box<RustyWinapiError>,
+    },
+}
+
+/// Result of COM calls of the crate, see [`RustyWinapiError`].
+///
+/// [`RustyWinapiError`]: enum.RustyWinapiError.html
+pub type ComResult<T> = Result<T, RustyWinapiError>;
+
+impl RustyWinapiError {
+    /// Error of a failed automation call, well-known `DISP_E_*` codes map into their dedicated variants.
+    pub fn from_dispatch(hresult: HRESULT, info: ErrorInfo, arg_err: u32) -> Self {
+        Self::well_known(hresult, Some(arg_err as usize)).unwrap_or(RustyWinapiError::Dispatch {
+            hresult,
+            info,
+            arg_err,
+        })
+    }
+
+    /// Dedicated variant of a well-known failure code.
+    fn well_known(hresult: HRESULT, argument: Option<usize>) -> Option<Self> {
+        Some(match hresult {
+            winerror::RPC_E_CALL_CANCELED => RustyWinapiError::Cancelled,
+            winerror::DISP_E_TYPEMISMATCH => RustyWinapiError::TypeMismatch { argument },
+            winerror::DISP_E_PARAMNOTFOUND => RustyWinapiError::ParamNotFound { argument },
+            winerror::DISP_E_BADPARAMCOUNT => RustyWinapiError::BadParamCount,
+            winerror::DISP_E_PARAMNOTOPTIONAL => RustyWinapiError::ParamNotOptional,
+            winerror::DISP_E_MEMBERNOTFOUND => RustyWinapiError::MemberNotFound,
+            winerror::DISP_E_UNKNOWNNAME => RustyWinapiError::UnknownName,
+            winerror::DISP_E_NONAMEDARGS => RustyWinapiError::NoNamedArgs,
+            winerror::DISP_E_OVERFLOW => RustyWinapiError::Overflow,
+            winerror::DISP_E_BADVARTYPE => RustyWinapiError::BadVarType,
+            _ => return None,
+        })
+    }
+
+    /// Error of a failed QueryInterface of `iid`.
+    pub fn query_interface(iid: &IID, hresult: HRESULT) -> Self {
+        match hresult {
+            winerror::RPC_E_CALL_CANCELED => RustyWinapiError::Cancelled,
+            _ => RustyWinapiError::QueryInterface {
+                iid: Guid::from(*iid),
+                hresult,
+            },
+        }
+    }
+
+    /// Attributes the failure to a member, an error attributed already is returned as is.
+    pub fn in_member(self, context: MemberContext) -> Self {
+        match self {
+            RustyWinapiError::Member { .. } => self,
+            _ => RustyWinapiError::Member {
+                context,
+                error: Box::new(self),
+            },
+        }
+    }
+
+    /// Attributes the failure to `segment` of dot `path`, named `member`.
+    pub fn at_path(self, path: &str, segment: usize, member: &str) -> Self {
+        let mut error = self.in_member(MemberContext {
+            member: member.to_string(),
+            ..Default::default()
+        });
+        if let RustyWinapiError::Member { context, .. } = &mut error {
+            context.path = Some(path.to_string());
+            context.segment = segment;
+        }
+        error
+    }
+
+    /// Member the failure is attributed to, if any.
+    pub fn member(&self) -> Option<&MemberContext> {
+        match self {
+            RustyWinapiError::Member { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The failure itself, without member attribution, for matching on the variants.
+    pub fn root_cause(&self) -> &RustyWinapiError {
+        match self {
+            RustyWinapiError::Member { error, .. } => error.root_cause(),
+            _ => self,
+        }
+    }
+
+    /// HRESULT describing the failure, `E_FAIL` if there is none.
+    pub fn hresult(&self) -> HResult {
+        match self {
+            RustyWinapiError::Allocation(SysAllocError::BStrAllocationError) => {
+                HResult::E_OUTOFMEMORY
+            }
+            RustyWinapiError::Allocation(_) => HResult::E_INVALIDARG,
+            RustyWinapiError::Activation(x) => x.hresult(),
+            RustyWinapiError::QueryInterface { hresult, .. }
+            | RustyWinapiError::Dispatch { hresult, .. }
+            | RustyWinapiError::Com(hresult) => HResult(*hresult),
+            RustyWinapiError::Server(x) => x.hresult(),
+            RustyWinapiError::Conversion(_) | RustyWinapiError::TypeMismatch { .. } => {
+                HResult::DISP_E_TYPEMISMATCH
+            }
+            RustyWinapiError::ParamNotFound { .. } => HResult::DISP_E_PARAMNOTFOUND,
+            RustyWinapiError::BadParamCount => HResult::DISP_E_BADPARAMCOUNT,
+            RustyWinapiError::ParamNotOptional => HResult::DISP_E_PARAMNOTOPTIONAL,
+            RustyWinapiError::MemberNotFound => HResult::DISP_E_MEMBERNOTFOUND,
+            RustyWinapiError::UnknownName => HResult::DISP_E_UNKNOWNNAME,
+            RustyWinapiError::NoNamedArgs => HResult::DISP_E_NONAMEDARGS,
+            RustyWinapiError::Overflow => HResult::DISP_E_OVERFLOW,
+            RustyWinapiError::BadVarType => HResult::DISP_E_BADVARTYPE,
+            RustyWinapiError::Cancelled => HResult::RPC_E_CALL_CANCELED,
+            RustyWinapiError::Other(_) => HResult::E_FAIL,
+            RustyWinapiError::Member { error, .. } => error.hresult(),
+        }
+    }
+}
+
+/// Well-known codes map into their dedicated variants (as of [`from_dispatch`]), e.g. `DISP_E_UNKNOWNNAME` of a
+/// failed name resolution into `UnknownName`.
+///
+/// [`from_dispatch`]: enum.RustyWinapiError.html#method.from_dispatch
+impl From<HRESULT> for RustyWinapiError {
+    fn from(x: HRESULT) -> Self {
+        Self::well_known(x, None).unwrap_or(RustyWinapiError::Com(x))
+    }
+}
+
+impl From<HResult> for RustyWinapiError {
+    fn from(x: HResult) -> Self {
+        x.0.into()
+    }
+}
+
+impl From<SysAllocError> for RustyWinapiError {
+    fn from(x: SysAllocError) -> Self {
+        RustyWinapiError::Allocation(x)
+    }
+}
+
+impl From<ActivationError> for RustyWinapiError {
+    fn from(x: ActivationError) -> Self {
+        RustyWinapiError::Activation(x)
+    }
+}
+
+impl From<DispatchError> for RustyWinapiError {
+    fn from(x: DispatchError) -> Self {
+        RustyWinapiError::Server(x)
+    }
+}
+
+impl From<ConversionError> for RustyWinapiError {
+    fn from(x: ConversionError) -> Self {
+        RustyWinapiError::Conversion(x)
+    }
+}
+
+/// Error tuple of automation calls: HRESULT, description and index of the offending argument, see
+/// [`from_dispatch`].
+///
+/// [`from_dispatch`]: enum.RustyWinapiError.html#method.from_dispatch
+impl From<(HRESULT, String, u32)> for RustyWinapiError {
+    fn from((hresult, description, arg_err): (HRESULT, String, u32)) -> Self {
+        let info = ErrorInfo {
+            scode: hresult,
+            description,
+            ..Default::default()
+        };
+
+        RustyWinapiError::from_dispatch(hresult, info, arg_err)
+    }
+}
+
+impl From<&'static str> for RustyWinapiError {
+    fn from(x: &'static str) -> Self {
+        RustyWinapiError::Other(x.to_string())
+    }
+}
+
+impl From<RustyWinapiError> for HRESULT {
+    fn from(x: RustyWinapiError) -> Self {
+        x.hresult().into()
+    }
+}
+
+impl fmt::Display for RustyWinapiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RustyWinapiError::Allocation(x) => write!(f, "{}", x),
+            RustyWinapiError::Activation(x) => write!(f, "{}", x),
+            RustyWinapiError::QueryInterface { iid, hresult } => write!(
+                f,
+                "interface {} is not supported (HRESULT {:#010X})",
+                iid, hresult
+            ),
+            RustyWinapiError::Dispatch { hresult, info, .. } if !info.is_empty() => {
+                write!(f, "{} (HRESULT {:#010X})", info, hresult)
+            }
+            RustyWinapiError::Dispatch { hresult, .. } => match describe(*hresult) {
+                Some(x) => write!(f, "{} (HRESULT {:#010X})", x, hresult),
+                None => write!(f, "automation call failed (HRESULT {:#010X})", hresult),
+            },
+            RustyWinapiError::Server(x) => write!(f, "{}", x),
+            RustyWinapiError::Conversion(x) => write!(f, "{}", x),
+            RustyWinapiError::TypeMismatch { argument: Some(x) } => {
+                write!(f, "type mismatch of argument {}", x)
+            }
+            RustyWinapiError::TypeMismatch { argument: None } => {
+                write!(f, "type mismatch of an argument")
+            }
+            RustyWinapiError::ParamNotFound { argument: Some(x) } => {
+                write!(f, "argument {} is not a known parameter", x)
+            }
+            RustyWinapiError::ParamNotFound { argument: None } => {
+                write!(f, "argument is not a known parameter")
+            }
+            RustyWinapiError::BadParamCount => write!(f, "wrong number of arguments"),
+            RustyWinapiError::ParamNotOptional => write!(f, "required argument is missing"),
+            RustyWinapiError::MemberNotFound => write!(f, "member not found"),
+            RustyWinapiError::UnknownName => write!(f, "unknown name"),
+            RustyWinapiError::NoNamedArgs => write!(f, "named arguments are not supported"),
+            RustyWinapiError::Overflow => write!(f, "argument is out of range"),
+            RustyWinapiError::BadVarType => write!(f, "argument type is not supported"),
+            RustyWinapiError::Cancelled => write!(f, "call was cancelled"),
+            RustyWinapiError::Com(x) => match describe(*x) {
+                Some(message) => write!(f, "{} (HRESULT {:#010X})", message, x),
+                None => write!(f, "COM call failed (HRESULT {:#010X})", x),
+            },
+            RustyWinapiError::Other(x) => write!(f, "{}", x),
+            RustyWinapiError::Member { context, error } => write!(f, "{}: {}", context, error),
+        }
+    }
+}
+
+impl Error for RustyWinapiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RustyWinapiError::Allocation(x) => Some(x),
+            RustyWinapiError::Activation(x) => Some(x),
+            RustyWinapiError::Server(x) => Some(x),
+            RustyWinapiError::Conversion(x) => Some(x),
+            RustyWinapiError::Member { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ConversionError_display() {
+        let e = ConversionError::new(r#"VT_BSTR "abc""#, "i32");
+        assert_eq!(r#"cannot convert VT_BSTR "abc" to i32"#, e.to_string());
+        assert_eq!("i32", e.target());
+    }
+
+    #[test]
+    fn test_ActivationError_display() {
+        let e = ActivationError::ProgIdNotFound {
+            progid: "No.Such".into(),
+            hresult: 0x800401F3u32 as HRESULT,
+        };
+        assert_eq!(
+            r#"ProgID "No.Such" is not registered (HRESULT 0x800401F3)"#,
+            e.to_string()
+        );
+        assert_eq!(5, HRESULT::from(ActivationError::Activation(5)));
+    }
+
+    #[test]
+    fn test_DispatchError_hresult() {
+        assert_eq!(
+            winapi::shared::winerror::DISP_E_EXCEPTION,
+            DispatchError::exception("oops").hresult()
+        );
+        assert_eq!(
+            winapi::shared::winerror::DISP_E_TYPEMISMATCH,
+            DispatchError::type_mismatch(1).hresult()
+        );
+        assert_eq!(
+            "argument 1 is invalid (HRESULT 0x80020005)",
+            DispatchError::type_mismatch(1).to_string()
+        );
+    }
+
+    #[test]
+    fn test_RustyWinapiError_from() {
+        assert_eq!(
+            RustyWinapiError::Cancelled,
+            RustyWinapiError::from(winerror::RPC_E_CALL_CANCELED)
+        );
+        assert_eq!(
+            winerror::E_OUTOFMEMORY,
+            RustyWinapiError::from(SysAllocError::BStrAllocationError).hresult()
+        );
+        assert_eq!(
+            RustyWinapiError::UnknownName,
+            RustyWinapiError::from(winerror::DISP_E_UNKNOWNNAME)
+        );
+
+        let e = RustyWinapiError::from((winerror::DISP_E_EXCEPTION, "oops".to_string(), 0));
+        assert_eq!(winerror::DISP_E_EXCEPTION, HRESULT::from(e.clone()));
+        assert_eq!("oops (HRESULT 0x80020009)", e.to_string());
+
+        assert_eq!(
+            RustyWinapiError::TypeMismatch { argument: Some(1) },
+            RustyWinapiError::from((winerror::DISP_E_TYPEMISMATCH, String::new(), 1))
+        );
+        assert_eq!(
+            "type mismatch of argument 1",
+            RustyWinapiError::TypeMismatch { argument: Some(1) }.to_string()
+        );
+        assert_eq!(
+            RustyWinapiError::TypeMismatch { argument: None },
+            RustyWinapiError::from(winerror::DISP_E_TYPEMISMATCH)
+        );
+
+        let e: Box<dyn Error> = RustyWinapiError::from(DispatchError::type_mismatch(1)).into();
+        assert!(e.source().is_some());
+    }
+
+    #[test]
+    fn test_RustyWinapiError_in_member() {
+        let context = MemberContext {
+            interface: Some("Workbook".into()),
+            member: "Save".into(),
+            dispid: Some(283),
+            ..Default::default()
+        };
+        let e = RustyWinapiError::TypeMismatch { argument: Some(0) }.in_member(context.clone());
+        assert_eq!(
+            "Workbook.Save (DISPID 283): type mismatch of argument 0",
+            e.to_string()
+        );
+        assert_eq!(Some(&context), e.member());
+        assert_eq!(
+            &RustyWinapiError::TypeMismatch { argument: Some(0) },
+            e.root_cause()
+        );
+        assert_eq!(winerror::DISP_E_TYPEMISMATCH, e.hresult());
+
+        let e = e.in_member(MemberContext::default());
+        assert_eq!(Some(&context), e.member());
+        assert_eq!(
+            "Open: unknown name",
+            RustyWinapiError::UnknownName
+                .in_member(MemberContext {
+                    member: "Open".into(),
+                    ..Default::default()
+                })
+                .to_string()
+        );
+
+        let e = RustyWinapiError::UnknownName.at_path("ActiveWorkbook.Nothing", 1, "Nothing");
+        assert_eq!(
+            r#"Nothing in "ActiveWorkbook.Nothing": unknown name"#,
+            e.to_string()
+        );
+        assert_eq!(Some(1), e.member().map(|x| x.segment));
+    }
+}
@@ -0,0 +1,317 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Error objects of the thread (IErrorInfo), the rich error channel of OLE Automation.
+//!
+//! A failing server method sets an error object describing the failure with [`set_error_info`] and returns the
+//! failure HRESULT, clients like VB and 1C show its source and description instead of a bare code.
+//!
+//! On the client side [`get_error_info`] takes the error object of the thread as [`ErrorInfo`]. Failed
+//! [`SmartIDispatch::invoke`] collects it from EXCEPINFO or from the error object, its description is returned with
+//! the error and the whole [`ErrorInfo`] (source, help file) is kept for [`last_error_info`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::error_info::set_error_info;
+//! use winapi::shared::ntdef::HRESULT;
+//! use winapi::shared::winerror;
+//!
+//! fn open_document(path: &str) -> HRESULT {
+//!     if path.is_empty() {
+//!         return set_error_info("Sample.Documents", "Path is empty", None, winerror::E_INVALIDARG);
+//!     }
+//!     winerror::S_OK
+//! }
+//! ```
+//!
+//! [`set_error_info`]: fn.set_error_info.html
+//! [`get_error_info`]: fn.get_error_info.html
+//! [`ErrorInfo`]: struct.ErrorInfo.html
+//! [`SmartIDispatch::invoke`]: ../smart_idispatch/trait.SmartIDispatch.html#method.invoke
+//! [`last_error_info`]: fn.last_error_info.html
+
+use std::cell::RefCell;
+use std::fmt;
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::shared::wtypes::BSTR;
+use winapi::um::oaidl::{ICreateErrorInfo, IErrorInfo, EXCEPINFO};
+use winapi::um::oleauto::{CreateErrorInfo, GetErrorInfo, SetErrorInfo};
+
+use crate::auto_bstr::AutoBSTR;
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::safe::clsid::to_wide;
+use crate::safe::guid::Guid;
+
+thread_local! {
+    static LAST_ERROR_INFO: RefCell<Option<ErrorInfo>> = const { RefCell::new(None) };
+}
+
+/// Description of a failure, from an error object (IErrorInfo) or from EXCEPINFO of IDispatch::Invoke.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorInfo {
+    /// Failure code, `S_OK` if unknown (error objects don't carry it).
+    pub scode: HRESULT,
+    /// IID of the interface which defined the error, nil if unknown.
+    pub interface: Guid,
+    /// ProgID or name of the object which raised the error.
+    pub source: String,
+    pub description: String,
+    pub helpfile: String,
+    pub help_context: DWORD,
+}
+
+impl ErrorInfo {
+    /// Moves strings out of `info` (its BSTRs are freed and set to NULL).
+    ///
+    /// Servers filling EXCEPINFO lazily (notably Office) leave `pfnDeferredFillIn`, it's called first and reset, so
+    /// the fields are populated. Failure of the callback is ignored, whatever it filled is taken.
+    pub fn take_excep_info(info: &mut EXCEPINFO) -> Self {
+        if let Some(fill_in) = info.pfnDeferredFillIn.take() {
+            unsafe { fill_in(info) };
+        }
+
+        let take =
+            |x: &mut BSTR| String::from(AutoBSTR::from(std::mem::replace(x, std::ptr::null_mut())));
+        ErrorInfo {
+            scode: if info.scode != 0 {
+                info.scode
+            } else {
+                winerror::DISP_E_EXCEPTION
+            },
+            interface: Guid::default(),
+            source: take(&mut info.bstrSource),
+            description: take(&mut info.bstrDescription),
+            helpfile: take(&mut info.bstrHelpFile),
+            help_context: info.dwHelpContext,
+        }
+    }
+
+    /// Reads an error object.
+    pub fn from_error_info(info: &IErrorInfo) -> Self {
+        let read = |f: &dyn Fn(*mut BSTR) -> HRESULT| {
+            let mut x: BSTR = std::ptr::null_mut();
+            f(&mut x);
+            String::from(AutoBSTR::from(x))
+        };
+
+        let mut interface = GUID::default();
+        let mut help_context: DWORD = 0;
+        unsafe {
+            info.GetGUID(&mut interface);
+            info.GetHelpContext(&mut help_context);
+        }
+
+        ErrorInfo {
+            scode: winerror::S_OK,
+            interface: interface.into(),
+            source: read(&|x| unsafe { info.GetSource(x) }),
+            description: read(&|x| unsafe { info.GetDescription(x) }),
+            helpfile: read(&|x| unsafe { info.GetHelpFile(x) }),
+            help_context,
+        }
+    }
+
+    /// Fills empty fields from `other`.
+    pub fn merge(mut self, other: ErrorInfo) -> Self {
+        if self.scode == winerror::S_OK {
+            self.scode = other.scode;
+        }
+        if self.interface.is_nil() {
+            self.interface = other.interface;
+        }
+        for (x, y) in [
+            (&mut self.source, other.source),
+            (&mut self.description, other.description),
+            (&mut self.helpfile, other.helpfile),
+        ] {
+            if x.is_empty() {
+                *x = y;
+            }
+        }
+        if self.help_context == 0 {
+            self.help_context = other.help_context;
+        }
+
+        self
+    }
+
+    /// Returns `true` if there is neither source nor description.
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty() && self.description.is_empty()
+    }
+}
+
+impl fmt::Display for ErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.source.is_empty(), self.description.is_empty()) {
+            (false, false) => write!(f, "{}: {}", self.source, self.description),
+            (true, false) => write!(f, "{}", self.description),
+            (false, true) => write!(f, "{}", self.source),
+            (true, true) => write!(f, "HRESULT {:#010X}", self.scode),
+        }
+    }
+}
+
+/// Takes error object of the thread, `None` if there is none.
+///
+/// Error object is cleared, as by [MSDN GetErrorInfo], so it's reported once.
+///
+/// [MSDN GetErrorInfo]: https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-geterrorinfo
+pub fn get_error_info() -> Option<ErrorInfo> {
+    let mut perrinfo: *mut IErrorInfo = std::ptr::null_mut();
+    let hresult = unsafe { GetErrorInfo(0, &mut perrinfo) };
+    if hresult != winerror::S_OK || perrinfo.is_null() {
+        return None;
+    }
+
+    let info = unsafe { AutoCOMInterface::from_raw(perrinfo) };
+    Some(ErrorInfo::from_error_info(info.as_inner()))
+}
+
+/// Error description collected by the last failed call of this thread (see [module level documentation]).
+///
+/// [module level documentation]: index.html
+pub fn last_error_info() -> Option<ErrorInfo> {
+    LAST_ERROR_INFO.with(|x| x.borrow().clone())
+}
+
+pub(crate) fn set_last_error_info(info: Option<ErrorInfo>) {
+    LAST_ERROR_INFO.with(|x| *x.borrow_mut() = info);
+}
+
+/// Sets error object of the thread with `source`, `description` and optional `helpfile`, returns `hresult`.
+///
+/// Returning `hresult` makes it a one-liner at the end of a failing method. Failure to create the error object is
+/// ignored, the caller still gets `hresult`.
+///
+/// See also [MSDN SetErrorInfo] description.
+///
+/// [MSDN SetErrorInfo]: https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-seterrorinfo
+pub fn set_error_info(
+    source: &str,
+    description: &str,
+    helpfile: Option<&str>,
+    hresult: HRESULT,
+) -> HRESULT {
+    let _ = try_set_error_info(source, description, helpfile);
+    hresult
+}
+
+/// Clears error object of the thread, so a stale one isn't reported for a later failure.
+pub fn clear_error_info() {
+    unsafe { SetErrorInfo(0, std::ptr::null_mut()) };
+}
+
+fn try_set_error_info(
+    source: &str,
+    description: &str,
+    helpfile: Option<&str>,
+) -> Result<(), HRESULT> {
+    let mut pcei: *mut ICreateErrorInfo = std::ptr::null_mut();
+    let hresult = unsafe { CreateErrorInfo(&mut pcei) };
+    if !winerror::SUCCEEDED(hresult) {
+        return Err(hresult);
+    }
+    let cei = unsafe { AutoCOMInterface::from_raw(pcei) };
+
+    let mut source = to_wide(source);
+    let mut description = to_wide(description);
+    unsafe {
+        let cei = cei.as_inner();
+        cei.SetSource(source.as_mut_ptr());
+        cei.SetDescription(description.as_mut_ptr());
+        if let Some(x) = helpfile {
+            cei.SetHelpFile(to_wide(x).as_mut_ptr());
+        }
+    }
+
+    let error_info = cei.cast::<IErrorInfo>()?;
+    let hresult = unsafe { SetErrorInfo(0, error_info.as_inner() as *const _ as *mut IErrorInfo) };
+    if winerror::SUCCEEDED(hresult) {
+        Ok(())
+    } else {
+        Err(hresult)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_apartment::ComApartment;
+    use crate::hresult::HResult;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_set_error_info() {
+        let _com = ComApartment::init_mta().unwrap();
+        assert_eq!(
+            winerror::E_INVALIDARG,
+            set_error_info(
+                "Sample",
+                "Path is empty",
+                Some("sample.chm"),
+                winerror::E_INVALIDARG
+            )
+        );
+
+        let info = get_error_info().unwrap();
+        assert_eq!("Sample: Path is empty", info.to_string());
+        assert_eq!("sample.chm", info.helpfile);
+        assert_eq!(None, get_error_info());
+
+        let mut perrinfo: *mut IErrorInfo = std::ptr::null_mut();
+
+        set_error_info("Sample", "Stale", None, winerror::E_FAIL);
+        clear_error_info();
+        assert_eq!(winerror::S_FALSE, unsafe { GetErrorInfo(0, &mut perrinfo) });
+    }
+
+    #[test]
+    fn test_last_error_info() {
+        use crate::error::DispatchError;
+        use crate::server::dispatch::DispatchServer;
+        use crate::server::dynamic_object::DynamicObject;
+        use crate::smart_idispatch::SmartIDispatch;
+
+        let _com = ComApartment::init_mta().unwrap();
+        let mut dispatch = DynamicObject::new()
+            .with_method("Fail", |_| {
+                Err(DispatchError::Exception {
+                    scode: winerror::E_FAIL,
+                    source: "Sample".to_string(),
+                    description: "Failed".to_string(),
+                })
+            })
+            .into_dispatch();
+
+        assert_eq!(
+            Some(HResult::DISP_E_EXCEPTION),
+            dispatch.call("Fail", &[]).err().map(|x| x.hresult())
+        );
+        let info = last_error_info().unwrap();
+        assert_eq!(winerror::E_FAIL, info.scode);
+        assert_eq!("Sample: Failed", info.to_string());
+    }
+
+    #[test]
+    fn test_ErrorInfo_take_excep_info_deferred() {
+        unsafe extern "system" fn fill_in(info: *mut EXCEPINFO) -> HRESULT {
+            (*info).bstrSource = AutoBSTR::try_from("Excel").unwrap().into();
+            (*info).bstrDescription = AutoBSTR::try_from("Deferred").unwrap().into();
+            (*info).scode = winerror::E_FAIL;
+            winerror::S_OK
+        }
+
+        let mut excep_info: EXCEPINFO = unsafe { std::mem::zeroed() };
+        excep_info.pfnDeferredFillIn = Some(fill_in);
+        let info = ErrorInfo::take_excep_info(&mut excep_info);
+        assert_eq!(winerror::E_FAIL, info.scode);
+        assert_eq!("Excel: Deferred", info.to_string());
+        assert!(excep_info.pfnDeferredFillIn.is_none());
+        assert!(excep_info.bstrDescription.is_null());
+    }
+}
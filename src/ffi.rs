@@ -0,0 +1,88 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Declarations of WinAPI functions missing in `winapi` 0.3 crate.
+
+use winapi::ctypes::c_void;
+use winapi::shared::basetsd::ULONG_PTR;
+use winapi::shared::guiddef::{REFCLSID, REFIID};
+use winapi::shared::minwindef::{DWORD, LPVOID, UINT};
+use winapi::shared::ntdef::{HRESULT, LONG, LPCWSTR, ULONG};
+use winapi::shared::wtypes::VARTYPE;
+use winapi::um::oaidl::{CALLCONV, LPSAFEARRAY, SAFEARRAYBOUND, VARIANT, VARIANTARG};
+use winapi::um::objidl::{IBindCtx, IMoniker, IRunningObjectTable, BIND_OPTS};
+use winapi::um::unknwnbase::IUnknown;
+
+#[link(name = "ole32")]
+extern "system" {
+    pub fn CoRegisterMessageFilter(
+        lpMessageFilter: LPVOID,
+        lplpMessageFilter: *mut LPVOID,
+    ) -> HRESULT;
+    pub fn BindMoniker(
+        pmk: *mut IMoniker,
+        grfOpt: DWORD,
+        iidResult: REFIID,
+        ppvResult: *mut LPVOID,
+    ) -> HRESULT;
+    pub fn CoGetObject(
+        pszName: LPCWSTR,
+        pBindOptions: *mut BIND_OPTS,
+        riid: REFIID,
+        ppv: *mut LPVOID,
+    ) -> HRESULT;
+    pub fn CreateBindCtx(reserved: DWORD, ppbc: *mut *mut IBindCtx) -> HRESULT;
+    pub fn CreateFileMoniker(lpszPathName: LPCWSTR, ppmk: *mut *mut IMoniker) -> HRESULT;
+    pub fn CreateItemMoniker(
+        lpszDelim: LPCWSTR,
+        lpszItem: LPCWSTR,
+        ppmk: *mut *mut IMoniker,
+    ) -> HRESULT;
+    pub fn GetRunningObjectTable(reserved: DWORD, pprot: *mut *mut IRunningObjectTable) -> HRESULT;
+    pub fn MkParseDisplayName(
+        pbc: *mut IBindCtx,
+        szUserName: LPCWSTR,
+        pchEaten: *mut ULONG,
+        ppmk: *mut *mut IMoniker,
+    ) -> HRESULT;
+}
+
+#[link(name = "oleaut32")]
+extern "system" {
+    pub fn GetActiveObject(
+        rclsid: REFCLSID,
+        pvReserved: LPVOID,
+        ppunk: *mut *mut IUnknown,
+    ) -> HRESULT;
+    pub fn RegisterActiveObject(
+        punk: *mut IUnknown,
+        rclsid: REFCLSID,
+        dwFlags: DWORD,
+        pdwRegister: *mut DWORD,
+    ) -> HRESULT;
+    pub fn DispCallFunc(
+        pvInstance: *mut c_void,
+        oVft: ULONG_PTR,
+        cc: CALLCONV,
+        vtReturn: VARTYPE,
+        cActuals: UINT,
+        prgvt: *mut VARTYPE,
+        prgpvarg: *mut *mut VARIANTARG,
+        pvargResult: *mut VARIANT,
+    ) -> HRESULT;
+    // Declared without return value in `winapi`.
+    pub fn RevokeActiveObject(dwRegister: DWORD, pvReserved: LPVOID) -> HRESULT;
+    pub fn SafeArrayCreate(vt: VARTYPE, cDims: UINT, rgsabound: *mut SAFEARRAYBOUND)
+        -> LPSAFEARRAY;
+    pub fn SafeArrayCopy(psa: LPSAFEARRAY, ppsaOut: *mut LPSAFEARRAY) -> HRESULT;
+    pub fn SafeArrayGetDim(psa: LPSAFEARRAY) -> UINT;
+    pub fn SafeArrayGetElement(
+        psa: LPSAFEARRAY,
+        rgIndices: *const LONG,
+        pv: *mut VARIANT,
+    ) -> HRESULT;
+    pub fn SafeArrayPutElement(
+        psa: LPSAFEARRAY,
+        rgIndices: *const LONG,
+        pv: *const VARIANT,
+    ) -> HRESULT;
+}
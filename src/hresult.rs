@@ -0,0 +1,294 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! HRESULT newtype with structured accessors.
+//!
+//! [`HResult`] wraps a raw `HRESULT`, tells success from failure, splits it into facility and code, recovers Win32
+//! error codes, and displays as `0x8007000E (E_OUTOFMEMORY)` when the code is well-known. It compares equal to raw
+//! `HRESULT` values, so `winerror` constants keep working in comparisons. [`describe`] returns the system message
+//! of a code, e.g. "Class not registered" of `REGDB_E_CLASSNOTREG`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::hresult::HResult;
+//! use winapi::shared::winerror;
+//!
+//! let hresult = HResult::from_win32(winerror::ERROR_FILE_NOT_FOUND);
+//! assert!(hresult.is_failure());
+//! assert_eq!(Some(winerror::ERROR_FILE_NOT_FOUND), hresult.win32_error());
+//! assert_eq!("0x80070002 (ERROR_FILE_NOT_FOUND)", hresult.to_string());
+//! assert_eq!(HResult::E_OUTOFMEMORY, winerror::E_OUTOFMEMORY);
+//! ```
+//!
+//! [`HResult`]: struct.HResult.html
+//! [`describe`]: fn.describe.html
+
+use std::fmt;
+
+use winapi::shared::minwindef::{DWORD, HMODULE};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::winbase::{
+    FormatMessageW, FORMAT_MESSAGE_FROM_HMODULE, FORMAT_MESSAGE_FROM_SYSTEM,
+    FORMAT_MESSAGE_IGNORE_INSERTS,
+};
+
+use crate::safe::clsid::to_wide;
+
+/// HRESULT newtype, see [module level documentation].
+///
+/// [module level documentation]: index.html
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct HResult(pub HRESULT);
+
+macro_rules! well_known_hresults {
+    ($($name:ident),+ $(,)?) => {
+        impl HResult {
+            $(
+                pub const $name: HResult = HResult(winerror::$name as HRESULT);
+            )+
+
+            const NAMES: &'static [(HRESULT, &'static str)] = &[
+                $((winerror::$name as HRESULT, stringify!($name)),)+
+            ];
+        }
+    };
+}
+
+well_known_hresults!(
+    S_OK,
+    S_FALSE,
+    E_UNEXPECTED,
+    E_NOTIMPL,
+    E_OUTOFMEMORY,
+    E_INVALIDARG,
+    E_NOINTERFACE,
+    E_POINTER,
+    E_HANDLE,
+    E_ABORT,
+    E_FAIL,
+    E_ACCESSDENIED,
+    E_PENDING,
+    CLASS_E_NOAGGREGATION,
+    CLASS_E_CLASSNOTAVAILABLE,
+    REGDB_E_CLASSNOTREG,
+    CO_E_NOTINITIALIZED,
+    CO_E_CLASSSTRING,
+    CO_E_SERVER_EXEC_FAILURE,
+    MK_E_UNAVAILABLE,
+    MK_E_SYNTAX,
+    RPC_E_CHANGED_MODE,
+    RPC_E_WRONG_THREAD,
+    RPC_E_DISCONNECTED,
+    RPC_E_SERVERCALL_RETRYLATER,
+    RPC_E_CALL_REJECTED,
+    RPC_E_CALL_CANCELED,
+    RPC_E_SERVERFAULT,
+    DISP_E_UNKNOWNINTERFACE,
+    DISP_E_MEMBERNOTFOUND,
+    DISP_E_PARAMNOTFOUND,
+    DISP_E_TYPEMISMATCH,
+    DISP_E_UNKNOWNNAME,
+    DISP_E_NONAMEDARGS,
+    DISP_E_BADVARTYPE,
+    DISP_E_EXCEPTION,
+    DISP_E_OVERFLOW,
+    DISP_E_BADINDEX,
+    DISP_E_UNKNOWNLCID,
+    DISP_E_ARRAYISLOCKED,
+    DISP_E_BADPARAMCOUNT,
+    DISP_E_PARAMNOTOPTIONAL,
+    DISP_E_BADCALLEE,
+    DISP_E_NOTACOLLECTION,
+    DISP_E_DIVBYZERO,
+);
+
+impl HResult {
+    pub const fn new(x: HRESULT) -> Self {
+        HResult(x)
+    }
+
+    /// HRESULT of a Win32 error code, as HRESULT_FROM_WIN32.
+    pub fn from_win32(x: u32) -> Self {
+        HResult(winerror::HRESULT_FROM_WIN32(x))
+    }
+
+    pub const fn is_success(self) -> bool {
+        self.0 >= 0
+    }
+
+    pub const fn is_failure(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Facility, e.g. `FACILITY_WIN32` or `FACILITY_DISPATCH`.
+    pub const fn facility(self) -> u16 {
+        ((self.0 as u32 >> 16) & 0x1FFF) as u16
+    }
+
+    /// Code within the facility.
+    pub const fn code(self) -> u16 {
+        (self.0 as u32 & 0xFFFF) as u16
+    }
+
+    /// Win32 error code of a failure of `FACILITY_WIN32`.
+    pub fn win32_error(self) -> Option<u32> {
+        if self.is_failure() && self.facility() as u32 == winerror::FACILITY_WIN32 as u32 {
+            Some(self.code() as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Name of a well-known code, e.g. `"E_OUTOFMEMORY"`, or of a well-known Win32 error.
+    pub fn name(self) -> Option<&'static str> {
+        Self::NAMES
+            .iter()
+            .find(|(x, _)| *x == self.0)
+            .map(|(_, x)| *x)
+            .or_else(|| match self.win32_error()? {
+                winerror::ERROR_FILE_NOT_FOUND => Some("ERROR_FILE_NOT_FOUND"),
+                winerror::ERROR_PATH_NOT_FOUND => Some("ERROR_PATH_NOT_FOUND"),
+                winerror::ERROR_ACCESS_DENIED => Some("ERROR_ACCESS_DENIED"),
+                winerror::ERROR_INVALID_HANDLE => Some("ERROR_INVALID_HANDLE"),
+                winerror::ERROR_CANCELLED => Some("ERROR_CANCELLED"),
+                winerror::ERROR_TIMEOUT => Some("ERROR_TIMEOUT"),
+                winerror::RPC_S_SERVER_UNAVAILABLE => Some("RPC_S_SERVER_UNAVAILABLE"),
+                _ => None,
+            })
+    }
+
+    /// `Ok(())` on success, `Err(self)` on failure.
+    pub fn ok(self) -> Result<(), HResult> {
+        if self.is_success() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// Modules with message tables of HRESULTs missing in the system one, queried if they are loaded.
+const MESSAGE_MODULES: &[&str] = &["wininet.dll", "winhttp.dll", "ntdll.dll"];
+
+/// System message describing `hresult`, e.g. "Class not registered" of `REGDB_E_CLASSNOTREG`, `None` if unknown.
+///
+/// Looks up the system message table with FormatMessageW, then message tables of loaded modules which may have
+/// originated the code (WinINet, WinHTTP, NTDLL). Message is in the language of the system and without the
+/// trailing line break.
+pub fn describe<H: Into<HResult>>(hresult: H) -> Option<String> {
+    let hresult = hresult.into();
+    format_message(
+        FORMAT_MESSAGE_FROM_SYSTEM,
+        std::ptr::null_mut(),
+        hresult.0 as DWORD,
+    )
+    .or_else(|| {
+        MESSAGE_MODULES.iter().find_map(|x| {
+            let module = unsafe { GetModuleHandleW(to_wide(x).as_ptr()) };
+            if module.is_null() {
+                return None;
+            }
+
+            format_message(FORMAT_MESSAGE_FROM_HMODULE, module, hresult.0 as DWORD).or_else(|| {
+                format_message(FORMAT_MESSAGE_FROM_HMODULE, module, hresult.code() as DWORD)
+            })
+        })
+    })
+}
+
+fn format_message(flags: DWORD, module: HMODULE, id: DWORD) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            flags | FORMAT_MESSAGE_IGNORE_INSERTS,
+            module as *const _,
+            id,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as DWORD,
+            std::ptr::null_mut(),
+        )
+    } as usize;
+
+    let message = String::from_utf16_lossy(&buffer[..len.min(buffer.len())]);
+    let message = message.trim_end();
+    if message.is_empty() {
+        None
+    } else {
+        Some(message.to_string())
+    }
+}
+
+impl From<HRESULT> for HResult {
+    fn from(x: HRESULT) -> Self {
+        HResult(x)
+    }
+}
+
+impl From<HResult> for HRESULT {
+    fn from(x: HResult) -> Self {
+        x.0
+    }
+}
+
+impl PartialEq<HRESULT> for HResult {
+    fn eq(&self, other: &HRESULT) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<HResult> for HRESULT {
+    fn eq(&self, other: &HResult) -> bool {
+        *self == other.0
+    }
+}
+
+impl fmt::Display for HResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{:#010X} ({})", self.0, name),
+            None => write!(f, "{:#010X}", self.0),
+        }
+    }
+}
+
+impl fmt::Debug for HResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HResult({})", self)
+    }
+}
+
+impl std::error::Error for HResult {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_HResult_accessors() {
+        let x = HResult::DISP_E_TYPEMISMATCH;
+        assert!(x.is_failure());
+        assert_eq!(winerror::FACILITY_DISPATCH as u16, x.facility());
+        assert_eq!(5, x.code());
+        assert_eq!(None, x.win32_error());
+        assert_eq!("0x80020005 (DISP_E_TYPEMISMATCH)", x.to_string());
+
+        assert!(HResult::S_FALSE.is_success());
+        assert_eq!(Ok(()), HResult::S_FALSE.ok());
+        assert_eq!(Err(HResult::E_FAIL), HResult::E_FAIL.ok());
+        assert_eq!(
+            "0x80041234",
+            HResult(0x8004_1234_u32 as HRESULT).to_string()
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        assert!(describe(HResult::REGDB_E_CLASSNOTREG).is_some());
+        assert!(describe(winerror::E_OUTOFMEMORY).is_some());
+        assert_eq!(None, describe(HResult(0xA00F_4321_u32 as HRESULT)));
+    }
+}
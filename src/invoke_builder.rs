@@ -0,0 +1,165 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Fluent builder of automation calls with named arguments, LCID and flags.
+//!
+//! [`InvokeBuilder`] is started by [`SmartIDispatch::method`] and collects positional arguments with [`arg`] and
+//! named ones with [`named`], so calls with many optional parameters (common in Office object models) read like
+//! their VBA counterparts without a macro.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::smart_idispatch::SmartIDispatch;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let mut workbooks = Activate::<IDispatch>::new().progid("Excel.Application").create().unwrap();
+//! let workbook = workbooks
+//!     .method("Open")
+//!     .arg("C:\\report.xlsx")
+//!     .named("ReadOnly", true)
+//!     .invoke()
+//!     .unwrap();
+//! ```
+//!
+//! [`InvokeBuilder`]: struct.InvokeBuilder.html
+//! [`SmartIDispatch::method`]: ../smart_idispatch/trait.SmartIDispatch.html#method.method
+//! [`arg`]: struct.InvokeBuilder.html#method.arg
+//! [`named`]: struct.InvokeBuilder.html#method.named
+
+use winapi::shared::minwindef::WORD;
+use winapi::shared::ntdef::LCID;
+use winapi::um::oleauto::DISPATCH_METHOD;
+
+use crate::error::ComResult;
+use crate::smart_idispatch::{invoke_by_name, SmartIDispatch};
+use crate::smart_variant::SmartVariant;
+
+/// Automation call being built, see [module level documentation].
+///
+/// [module level documentation]: index.html
+pub struct InvokeBuilder<'a, D: SmartIDispatch> {
+    dispatch: &'a mut D,
+    member: String,
+    args: Vec<SmartVariant>,
+    named: Vec<(String, SmartVariant)>,
+    lcid: Option<LCID>,
+    flags: WORD,
+}
+
+impl<'a, D: SmartIDispatch> InvokeBuilder<'a, D> {
+    /// Call of `member` of `dispatch` as a method, without arguments.
+    pub fn new(dispatch: &'a mut D, member: &str) -> Self {
+        InvokeBuilder {
+            dispatch,
+            member: member.to_string(),
+            args: Vec::new(),
+            named: Vec::new(),
+            lcid: None,
+            flags: DISPATCH_METHOD,
+        }
+    }
+
+    /// Appends positional argument.
+    pub fn arg<T: Into<SmartVariant>>(mut self, value: T) -> Self {
+        self.args.push(value.into());
+        self
+    }
+
+    /// Appends positional arguments.
+    pub fn args<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<SmartVariant>,
+    {
+        self.args.extend(values.into_iter().map(Into::into));
+        self
+    }
+
+    /// Appends argument passed by parameter `name`.
+    pub fn named<T: Into<SmartVariant>>(mut self, name: &str, value: T) -> Self {
+        self.named.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// LCID of the call, LCID of the object by default, see [`SmartIDispatch::lcid`].
+    ///
+    /// [`SmartIDispatch::lcid`]: ../smart_idispatch/trait.SmartIDispatch.html#method.lcid
+    pub fn lcid(mut self, lcid: LCID) -> Self {
+        self.lcid = Some(lcid);
+        self
+    }
+
+    /// Invoke flags, `DISPATCH_METHOD` by default.
+    pub fn flags(mut self, flags: WORD) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Makes the call.
+    pub fn invoke(self) -> ComResult<SmartVariant> {
+        let lcid = self.lcid.unwrap_or_else(|| self.dispatch.lcid());
+        let named: Vec<(&str, &SmartVariant)> = self
+            .named
+            .iter()
+            .map(|(name, x)| (name.as_str(), x))
+            .collect();
+
+        invoke_by_name(
+            self.dispatch,
+            &self.member,
+            lcid,
+            self.flags,
+            &self.args,
+            &named,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{DispatchError, RustyWinapiError};
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+    use std::convert::TryFrom;
+    use winapi::um::oleauto::DISPATCH_PROPERTYGET;
+
+    #[test]
+    fn test_InvokeBuilder_invoke() {
+        let mut object = DynamicObject::new()
+            .with("Name", SmartVariant::Text("sample".into()))
+            .with_method("Sum", |args| {
+                let mut sum = 0;
+                for x in args {
+                    sum += i32::try_from(x).map_err(|_| DispatchError::type_mismatch(0))?;
+                }
+                Ok(SmartVariant::Int4(sum))
+            })
+            .into_dispatch();
+
+        assert_eq!(
+            Ok(SmartVariant::Int4(6)),
+            object
+                .method("Sum")
+                .arg(1)
+                .args(vec![2, 3])
+                .lcid(0)
+                .invoke()
+        );
+        assert_eq!(
+            Ok(SmartVariant::Text("sample".into())),
+            object.method("Name").flags(DISPATCH_PROPERTYGET).invoke()
+        );
+        assert_eq!(
+            &RustyWinapiError::ParamNotFound { argument: Some(1) },
+            object
+                .method("Sum")
+                .arg(1)
+                .named("Extra", 2)
+                .invoke()
+                .unwrap_err()
+                .root_cause()
+        );
+    }
+}
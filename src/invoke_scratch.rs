@@ -0,0 +1,293 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Per-thread pool of argument buffers reused by IDispatch::Invoke calls.
+//!
+//! Invokes with up to [`INLINE_ARGS`] arguments keep rgvarg and rgdispidNamedArgs on the stack, larger ones borrow
+//! vectors from [`InvokeScratch`] of the calling thread and give them back cleared after the call. Once a thread
+//! made a call of some size, later calls of that size don't allocate for arguments. Nested calls (e.g. an in-process
+//! server calling back) borrow spare buffers or allocate new ones, the pool is never locked across a call. DISPPARAMS
+//! and EXCEPINFO live on the stack of the call. BSTRs and interface references of arguments are released when the
+//! call returns.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::invoke_scratch::InvokeScratch;
+//!
+//! // Polling loop passing 12 arguments: allocate buffers once, before the loop.
+//! InvokeScratch::reserve(12);
+//! assert!(InvokeScratch::capacity() >= 12);
+//! // ... calls ...
+//! InvokeScratch::release();
+//! ```
+//!
+//! [`INLINE_ARGS`]: constant.INLINE_ARGS.html
+//! [`InvokeScratch`]: struct.InvokeScratch.html
+
+use std::cell::RefCell;
+
+use winapi::shared::wtypes::{VARENUM, VT_BSTR, VT_DISPATCH, VT_UNKNOWN};
+use winapi::um::oaidl::{DISPID, VARIANT};
+use winapi::um::oleauto::VariantClear;
+
+use crate::safe::bstr::SysAllocError;
+use crate::smart_variant::{AutoVariant, SmartVariant, SmartVariantRef};
+
+/// Number of invoke arguments kept on the stack, calls with more of them use buffers of [`InvokeScratch`].
+///
+/// [`InvokeScratch`]: struct.InvokeScratch.html
+pub const INLINE_ARGS: usize = 8;
+
+/// Spare buffers of each kind kept by a thread, invoke_byref borrows two VARIANT buffers at once.
+const MAX_SPARE: usize = 4;
+
+thread_local! {
+    static SCRATCH: RefCell<InvokeScratch> = const {
+        RefCell::new(InvokeScratch {
+            variants: Vec::new(),
+            dispids: Vec::new(),
+        })
+    };
+}
+
+/// Spare argument buffers of the current thread, see [module level documentation].
+///
+/// [module level documentation]: index.html
+pub struct InvokeScratch {
+    variants: Vec<Vec<VARIANT>>,
+    dispids: Vec<Vec<DISPID>>,
+}
+
+impl InvokeScratch {
+    /// Allocates buffers of the current thread for calls with up to `args` arguments.
+    pub fn reserve(args: usize) {
+        let variants: Vec<VARIANT> = take(args);
+        let values: Vec<VARIANT> = take(args);
+        let dispids: Vec<DISPID> = take(args);
+        give_back(variants);
+        give_back(values);
+        give_back(dispids);
+    }
+
+    /// Returns number of arguments the largest spare buffer of the current thread holds without allocation.
+    pub fn capacity() -> usize {
+        SCRATCH
+            .try_with(|x| {
+                x.borrow()
+                    .variants
+                    .iter()
+                    .map(Vec::capacity)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Frees spare buffers of the current thread.
+    pub fn release() {
+        let _ = SCRATCH.try_with(|x| {
+            let mut scratch = x.borrow_mut();
+            scratch.variants = Vec::new();
+            scratch.dispids = Vec::new();
+        });
+    }
+}
+
+/// Item of an argument buffer, pooled by [`InvokeScratch`].
+///
+/// [`InvokeScratch`]: struct.InvokeScratch.html
+pub(crate) trait ScratchItem: Copy + Default {
+    fn spare(scratch: &mut InvokeScratch) -> &mut Vec<Vec<Self>>;
+
+    /// Frees what the call allocated for the item.
+    #[inline]
+    fn release(&mut self) {}
+}
+
+impl ScratchItem for VARIANT {
+    #[inline]
+    fn spare(scratch: &mut InvokeScratch) -> &mut Vec<Vec<Self>> {
+        &mut scratch.variants
+    }
+
+    /// Frees BSTR of a string argument and releases reference of an interface one. Arrays of arguments are
+    /// borrowed from the caller, they aren't destroyed.
+    #[inline]
+    fn release(&mut self) {
+        let vt = unsafe { self.n1.n2().vt } as VARENUM;
+        if let VT_BSTR | VT_DISPATCH | VT_UNKNOWN = vt {
+            unsafe { VariantClear(self) };
+        }
+    }
+}
+
+impl ScratchItem for DISPID {
+    #[inline]
+    fn spare(scratch: &mut InvokeScratch) -> &mut Vec<Vec<Self>> {
+        &mut scratch.dispids
+    }
+}
+
+/// Borrows an empty buffer for `capacity` items from the current thread, allocates if there's no spare one.
+fn take<T: ScratchItem>(capacity: usize) -> Vec<T> {
+    let mut result = SCRATCH
+        .try_with(|x| T::spare(&mut x.borrow_mut()).pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    result.reserve(capacity);
+
+    result
+}
+
+/// Returns a buffer to the current thread, the largest spare buffers are kept.
+fn give_back<T: ScratchItem>(mut x: Vec<T>) {
+    x.clear();
+    let _ = SCRATCH.try_with(|scratch| {
+        let mut scratch = scratch.borrow_mut();
+        let spare = T::spare(&mut scratch);
+        spare.push(x);
+        if spare.len() > MAX_SPARE {
+            spare.sort_by_key(|x| std::cmp::Reverse(x.capacity()));
+            spare.truncate(MAX_SPARE);
+        }
+    });
+}
+
+/// Buffer of rgvarg or rgdispidNamedArgs of an invoke, on the stack for up to [`INLINE_ARGS`] items, borrowed from
+/// [`InvokeScratch`] otherwise.
+///
+/// [`INLINE_ARGS`]: constant.INLINE_ARGS.html
+/// [`InvokeScratch`]: struct.InvokeScratch.html
+pub(crate) enum ArgBuffer<T: ScratchItem> {
+    Inline([T; INLINE_ARGS], usize),
+    Heap(Vec<T>),
+}
+
+impl<T: ScratchItem> ArgBuffer<T> {
+    /// Creates an empty buffer for `capacity` items, it can't grow beyond.
+    #[inline]
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        if capacity <= INLINE_ARGS {
+            ArgBuffer::Inline([T::default(); INLINE_ARGS], 0)
+        } else {
+            ArgBuffer::Heap(take(capacity))
+        }
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, x: T) {
+        match self {
+            ArgBuffer::Inline(items, len) => {
+                items[*len] = x;
+                *len += 1;
+            }
+            ArgBuffer::Heap(items) => items.push(x),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            ArgBuffer::Inline(_, len) => *len,
+            ArgBuffer::Heap(items) => items.len(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [T] {
+        match self {
+            ArgBuffer::Inline(items, len) => &mut items[..*len],
+            ArgBuffer::Heap(items) => items,
+        }
+    }
+
+    /// Pointer to the first item, null if the buffer is empty.
+    #[inline]
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut T {
+        match self.len() {
+            0 => std::ptr::null_mut(),
+            _ => self.as_mut_slice().as_mut_ptr(),
+        }
+    }
+}
+
+impl ArgBuffer<VARIANT> {
+    /// Converts `params` in one pass, strings are encoded into BSTRs the buffer owns.
+    pub(crate) fn try_from_refs<'a>(
+        capacity: usize,
+        params: impl Iterator<Item = SmartVariantRef<'a>>,
+    ) -> Result<Self, SysAllocError> {
+        let mut result = ArgBuffer::with_capacity(capacity);
+        for x in params {
+            result.push(AutoVariant::try_raw_from_ref(x)?);
+        }
+
+        Ok(result)
+    }
+}
+
+impl<T: ScratchItem> Drop for ArgBuffer<T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.as_mut_slice().iter_mut().for_each(T::release);
+        if let ArgBuffer::Heap(items) = self {
+            give_back(std::mem::take(items));
+        }
+    }
+}
+
+/// Argument of a call which can be marshaled without cloning.
+pub(crate) trait AsVariantRef {
+    fn as_variant_ref(&self) -> SmartVariantRef<'_>;
+}
+
+impl AsVariantRef for SmartVariant {
+    #[inline]
+    fn as_variant_ref(&self) -> SmartVariantRef<'_> {
+        SmartVariantRef::Value(self)
+    }
+}
+
+impl AsVariantRef for SmartVariantRef<'_> {
+    #[inline]
+    fn as_variant_ref(&self) -> SmartVariantRef<'_> {
+        *self
+    }
+}
+
+impl<T: AsVariantRef + ?Sized> AsVariantRef for &T {
+    #[inline]
+    fn as_variant_ref(&self) -> SmartVariantRef<'_> {
+        (**self).as_variant_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+    use crate::smart_idispatch::SmartIDispatch;
+
+    #[test]
+    fn test_InvokeScratch_reuse() {
+        InvokeScratch::release();
+        assert_eq!(0, InvokeScratch::capacity());
+
+        let mut object = DynamicObject::new()
+            .with_method("Count", |args| Ok(SmartVariant::Int4(args.len() as i32)))
+            .into_dispatch();
+        let params: Vec<_> = (0..12).map(SmartVariant::Int4).collect();
+        assert_eq!(Ok(SmartVariant::Int4(12)), object.call("Count", &params));
+        let capacity = InvokeScratch::capacity();
+        assert!(capacity >= 12);
+        assert_eq!(Ok(SmartVariant::Int4(12)), object.call("Count", &params));
+        assert_eq!(capacity, InvokeScratch::capacity());
+
+        InvokeScratch::release();
+        assert_eq!(0, InvokeScratch::capacity());
+        InvokeScratch::reserve(20);
+        assert!(InvokeScratch::capacity() >= 20);
+    }
+}
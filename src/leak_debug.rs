@@ -0,0 +1,154 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Tracking of interface references owned by [`AutoCOMInterface`] wrappers, enabled by the `leak-debug` feature.
+//!
+//! Every reference taken by a wrapper is recorded in a process-global registry with the interface type and a
+//! backtrace of the place it was wrapped. Release on drop, `into_raw` and `take_raw` remove the record, so what
+//! remains are references still owned by live wrappers. Records of the same pointer are matched by count: a drop
+//! removes the latest record of the pointer, whichever wrapper made it.
+//!
+//! References handed over with `into_raw`/`take_raw` and extra ones taken with `SmartIUnknown::add_ref` aren't
+//! tracked. Capturing backtraces is slow, don't enable the feature in release builds.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::leak_debug::dump_live_interfaces;
+//!
+//! // ... automation code ...
+//! eprintln!("{}", dump_live_interfaces());
+//! ```
+//!
+//! [`AutoCOMInterface`]: ../auto_com_interface/struct.AutoCOMInterface.html
+
+use std::backtrace::Backtrace;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    live: BTreeMap::new(),
+    counts: RefCounts {
+        acquired: 0,
+        released: 0,
+    },
+});
+
+struct Registry {
+    live: BTreeMap<usize, Vec<LiveInterface>>,
+    counts: RefCounts,
+}
+
+/// Reference owned by a live wrapper.
+#[derive(Clone, Debug)]
+pub struct LiveInterface {
+    /// Interface pointer.
+    pub pointer: usize,
+    /// Type name of the interface, e.g. `winapi::um::oaidl::IDispatch`.
+    pub interface: &'static str,
+    /// Where the reference was wrapped.
+    pub backtrace: Arc<Backtrace>,
+}
+
+/// Totals of references taken and given up by wrappers since the process start.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RefCounts {
+    pub acquired: usize,
+    pub released: usize,
+}
+
+impl RefCounts {
+    /// References still owned by wrappers.
+    #[inline]
+    pub fn balance(&self) -> usize {
+        self.acquired - self.released
+    }
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    match REGISTRY.lock() {
+        Ok(x) => x,
+        Err(x) => x.into_inner(),
+    }
+}
+
+/// Records a reference taken by a wrapper.
+pub(crate) fn acquire(pointer: usize, interface: &'static str) {
+    let backtrace = Arc::new(Backtrace::force_capture());
+    let mut registry = registry();
+    registry.counts.acquired += 1;
+    registry
+        .live
+        .entry(pointer)
+        .or_default()
+        .push(LiveInterface {
+            pointer,
+            interface,
+            backtrace,
+        });
+}
+
+/// Removes record of a reference given up by a wrapper.
+pub(crate) fn release(pointer: usize) {
+    let mut registry = registry();
+    registry.counts.released += 1;
+    if let Some(records) = registry.live.get_mut(&pointer) {
+        records.pop();
+        if records.is_empty() {
+            registry.live.remove(&pointer);
+        }
+    }
+}
+
+/// Returns totals of references taken and given up by wrappers.
+pub fn ref_counts() -> RefCounts {
+    registry().counts
+}
+
+/// Returns references owned by live wrappers, ordered by pointer.
+pub fn live_interfaces() -> Vec<LiveInterface> {
+    registry().live.values().flatten().cloned().collect()
+}
+
+/// Renders references owned by live wrappers with backtraces of the places they were wrapped.
+pub fn dump_live_interfaces() -> String {
+    let live = live_interfaces();
+    let counts = ref_counts();
+    let mut result = format!(
+        "{} live interface references ({} acquired, {} released)\n",
+        live.len(),
+        counts.acquired,
+        counts.released
+    );
+    for x in live {
+        let _ = write!(
+            result,
+            "\n{} at 0x{:X}, wrapped at:\n{}\n",
+            x.interface, x.pointer, x.backtrace
+        );
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+
+    #[test]
+    fn test_live_interfaces() {
+        let dispatch = DynamicObject::new().into_dispatch();
+        let pointer = dispatch.as_iunknown_ptr() as usize;
+        let unknown = dispatch.to_iunknown();
+
+        let live = live_interfaces();
+        assert_eq!(2, live.iter().filter(|x| x.pointer == pointer).count());
+        assert!(dump_live_interfaces().contains("IUnknown"));
+
+        drop(unknown);
+        drop(dispatch);
+        assert!(live_interfaces().iter().all(|x| x.pointer != pointer));
+    }
+}
@@ -1,12 +1,135 @@
 //! Various rustified  WinAPI's for pleasant and safe use with Rust.
 
+// Lets `::rusty_winapi` paths emitted by the procedural macros resolve inside the crate.
+extern crate self as rusty_winapi;
+
+pub use rusty_winapi_macros::{com_client, dispatch_server, event_sink};
+
+// On non-Windows targets only stubs of the portable API (`prelude`, `SmartVariant`, `Activate`, name-based
+// `SmartIDispatch` calls...) are compiled, every COM operation fails with `RustyWinapiError::NotSupported`. So
+// cross-platform crates can depend on the crate unconditionally and check `is_supported()` at runtime.
+#[cfg(windows)]
+pub mod activate;
+#[cfg(not(windows))]
+#[path = "stub/activate.rs"]
+pub mod activate;
+#[cfg(windows)]
+pub mod activation_context;
+#[cfg(windows)]
+pub mod agile_ref;
+#[cfg(all(windows, feature = "async"))]
+pub mod async_dispatch;
+#[cfg(windows)]
 pub mod auto_bstr;
+#[cfg(windows)]
+pub mod auto_com_interface;
+#[cfg(not(windows))]
+#[path = "stub/auto_com_interface.rs"]
 pub mod auto_com_interface;
+#[cfg(windows)]
+pub mod call_observer;
+#[cfg(windows)]
+pub mod cancel;
+#[cfg(windows)]
+pub mod class_object;
+#[cfg(windows)]
+pub mod cls_ctx;
+#[cfg(windows)]
+pub mod com_apartment;
+#[cfg(not(windows))]
+#[path = "stub/com_apartment.rs"]
+pub mod com_apartment;
+#[cfg(windows)]
+pub mod com_client;
+#[cfg(windows)]
+pub mod com_diagnostics;
+#[cfg(windows)]
+pub mod com_interface;
+#[cfg(windows)]
+pub mod config;
+#[cfg(windows)]
+pub mod debug_dump;
+#[cfg(windows)]
+pub mod early_bound;
+#[cfg(windows)]
+pub mod enumerator;
+#[cfg(windows)]
+pub mod error;
+#[cfg(not(windows))]
+#[path = "stub/error.rs"]
+pub mod error;
+#[cfg(windows)]
+pub mod error_info;
+#[cfg(windows)]
+mod ffi;
+#[cfg(windows)]
+pub mod hresult;
+#[cfg(windows)]
+pub mod invoke_builder;
+#[cfg(windows)]
+pub mod invoke_scratch;
+#[cfg(all(windows, feature = "leak-debug"))]
+pub mod leak_debug;
+#[cfg(windows)]
+pub mod message_filter;
+#[cfg(all(windows, feature = "mock"))]
+pub mod mock;
+#[cfg(windows)]
+pub mod mta_pool;
+#[cfg(windows)]
+pub mod office;
+#[cfg(windows)]
+pub mod prelude;
+#[cfg(not(windows))]
+#[path = "stub/prelude.rs"]
+pub mod prelude;
+#[cfg(all(windows, feature = "replay"))]
+pub mod replay;
+#[cfg(windows)]
+pub mod running_object;
+#[cfg(windows)]
 pub mod safe;
+#[cfg(all(windows, feature = "selftest"))]
+pub mod selftest;
+#[cfg(windows)]
+pub mod sendable_interface;
+#[cfg(windows)]
+pub mod server;
+#[cfg(windows)]
 pub mod smart_iclassfactory;
+#[cfg(windows)]
+pub mod smart_iconnectionpoint;
+#[cfg(windows)]
 pub mod smart_idispatch;
+#[cfg(not(windows))]
+#[path = "stub/smart_idispatch.rs"]
+pub mod smart_idispatch;
+#[cfg(windows)]
+pub mod smart_iobjectsafety;
+#[cfg(windows)]
+pub mod smart_itypeinfo;
+#[cfg(windows)]
 pub mod smart_iunknown;
+#[cfg(windows)]
+pub mod smart_variant;
+#[cfg(not(windows))]
+#[path = "stub/smart_variant.rs"]
 pub mod smart_variant;
+#[cfg(windows)]
+pub mod sta_thread;
+#[cfg(windows)]
+pub mod trace;
+#[cfg(windows)]
+pub mod typelib;
+#[cfg(all(windows, feature = "csv"))]
+pub mod variant_csv;
+
+/// Whether COM is available, `false` on non-Windows targets where COM initialization fails with
+/// `RustyWinapiError::NotSupported`.
+#[inline]
+pub const fn is_supported() -> bool {
+    cfg!(windows)
+}
 
 // #[cfg(test)]
 // mod tests {
@@ -0,0 +1,367 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Installable IMessageFilter retrying calls rejected by busy servers.
+//!
+//! Long-running servers (Office, 1C) reject incoming calls with `RPC_E_CALL_REJECTED` or
+//! `RPC_E_SERVERCALL_RETRYLATER` while they are busy, e.g. when a modal dialog is shown. A message filter registered
+//! on an STA thread lets COM repeat such calls transparently. [`MessageFilter`] is a safe implementation configured
+//! with a [`RetryPolicy`] and optional callbacks, [`MessageFilterGuard`] revokes it on drop.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use rusty_winapi::com_apartment::ComApartment;
+//! use rusty_winapi::config::RetryPolicy;
+//! use rusty_winapi::message_filter::MessageFilter;
+//!
+//! let _com = ComApartment::init_sta().unwrap();
+//! let _filter = MessageFilter::new(RetryPolicy::new(50, Duration::from_millis(200)))
+//!     .on_retry(|elapsed, _| eprintln!("server is busy for {:?}", elapsed))
+//!     .register()
+//!     .unwrap();
+//! ```
+//!
+//! [`MessageFilter`]: struct.MessageFilter.html
+//! [`MessageFilterGuard`]: struct.MessageFilterGuard.html
+//! [`RetryPolicy`]: ../config/struct.RetryPolicy.html
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, IID, REFIID};
+use winapi::shared::minwindef::{DWORD, HTASK, LPVOID, WORD};
+use winapi::shared::ntdef::{HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl, LPUNKNOWN};
+use winapi::{Interface, RIDL};
+
+use crate::config::RetryPolicy;
+use crate::error::ComResult;
+use crate::ffi::CoRegisterMessageFilter;
+
+/// Incoming call is accepted.
+pub const SERVERCALL_ISHANDLED: DWORD = 0;
+/// Incoming call is rejected.
+pub const SERVERCALL_REJECTED: DWORD = 1;
+/// Incoming call is rejected, but may be retried later.
+pub const SERVERCALL_RETRYLATER: DWORD = 2;
+
+/// Cancel the outgoing call.
+pub const PENDINGMSG_CANCELCALL: DWORD = 0;
+/// Wait for the call to return without dispatching the message.
+pub const PENDINGMSG_WAITNOPROCESS: DWORD = 1;
+/// Wait for the call to return, dispatching keyboard and mouse messages.
+pub const PENDINGMSG_WAITDEFPROCESS: DWORD = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct INTERFACEINFO {
+    pub pUnk: LPUNKNOWN,
+    pub iid: IID,
+    pub wMethod: WORD,
+}
+pub type LPINTERFACEINFO = *mut INTERFACEINFO;
+
+RIDL! {#[uuid(0x00000016, 0x0000, 0x0000, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46)]
+interface IMessageFilter(IMessageFilterVtbl): IUnknown(IUnknownVtbl) {
+    fn HandleInComingCall(
+        dwCallType: DWORD,
+        htaskCaller: HTASK,
+        dwTickCount: DWORD,
+        lpInterfaceInfo: LPINTERFACEINFO,
+    ) -> DWORD,
+    fn RetryRejectedCall(
+        htaskCallee: HTASK,
+        dwTickCount: DWORD,
+        dwRejectType: DWORD,
+    ) -> DWORD,
+    fn MessagePending(
+        htaskCallee: HTASK,
+        dwTickCount: DWORD,
+        dwPendingType: DWORD,
+    ) -> DWORD,
+}}
+
+crate::impl_interface_vtbl!(IMessageFilter => IMessageFilterVtbl);
+
+/// Return value of RetryRejectedCall cancelling the call.
+const CANCEL_CALL: DWORD = 0xFFFF_FFFF;
+
+type RetryCallback = Box<dyn Fn(Duration, DWORD)>;
+type IncomingCallCallback = Box<dyn Fn(DWORD) -> DWORD>;
+type MessagePendingCallback = Box<dyn Fn(Duration) -> DWORD>;
+
+/// Builder of an IMessageFilter implementation.
+///
+/// By default rejected calls are retried according to the policy, all incoming calls are accepted and pending
+/// messages are dispatched while waiting (`PENDINGMSG_WAITDEFPROCESS`). These defaults are answered if a callback
+/// panics, a panicking `on_retry` cancels the call.
+pub struct MessageFilter {
+    policy: RetryPolicy,
+    on_retry: Option<RetryCallback>,
+    on_incoming_call: Option<IncomingCallCallback>,
+    on_message_pending: Option<MessagePendingCallback>,
+}
+
+impl MessageFilter {
+    /// Filter retrying rejected calls every `policy.delay` for up to `policy.attempts` times.
+    pub fn new(policy: RetryPolicy) -> Self {
+        MessageFilter {
+            policy,
+            on_retry: None,
+            on_incoming_call: None,
+            on_message_pending: None,
+        }
+    }
+
+    /// Callback notified with time elapsed since the call started and reject type before every retry.
+    pub fn on_retry<F: Fn(Duration, DWORD) + 'static>(mut self, f: F) -> Self {
+        self.on_retry = Some(Box::new(f));
+        self
+    }
+
+    /// Callback deciding whether an incoming call of the given type is handled, returns `SERVERCALL_*` value.
+    pub fn on_incoming_call<F: Fn(DWORD) -> DWORD + 'static>(mut self, f: F) -> Self {
+        self.on_incoming_call = Some(Box::new(f));
+        self
+    }
+
+    /// Callback deciding what to do with a message arrived while waiting for a call, returns `PENDINGMSG_*` value.
+    pub fn on_message_pending<F: Fn(Duration) -> DWORD + 'static>(mut self, f: F) -> Self {
+        self.on_message_pending = Some(Box::new(f));
+        self
+    }
+
+    /// Returns delay before the next retry of a call rejected `elapsed` after it started, `None` to give up.
+    pub fn retry_delay(&self, elapsed: Duration) -> Option<Duration> {
+        let timeout = self.policy.delay * self.policy.attempts;
+        if self.policy.attempts > 0 && elapsed < timeout {
+            Some(self.policy.delay)
+        } else {
+            None
+        }
+    }
+
+    /// Registers filter on the current thread, which must be an STA.
+    ///
+    /// # Errors
+    ///
+    /// Returns HRESULT of CoRegisterMessageFilter, e.g. `CO_E_NOT_SUPPORTED` on an MTA thread.
+    pub fn register(self) -> ComResult<MessageFilterGuard> {
+        let object = Box::into_raw(Box::new(MessageFilterObject {
+            vtbl: &MESSAGE_FILTER_VTBL,
+            refs: Cell::new(1),
+            filter: self,
+        }));
+
+        let mut previous: LPVOID = std::ptr::null_mut();
+        let hresult = unsafe { CoRegisterMessageFilter(object as LPVOID, &mut previous) };
+        // COM holds its own reference after registration.
+        unsafe { message_filter_release(object as *mut IUnknown) };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(MessageFilterGuard {
+                previous,
+                _not_send: PhantomData,
+            })
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+/// Registered message filter, previous filter of the thread is restored on drop.
+pub struct MessageFilterGuard {
+    previous: LPVOID,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl Drop for MessageFilterGuard {
+    fn drop(&mut self) {
+        unsafe {
+            let mut revoked: LPVOID = std::ptr::null_mut();
+            CoRegisterMessageFilter(self.previous, &mut revoked);
+            for x in &[revoked, self.previous] {
+                if !x.is_null() {
+                    (*(*x as *mut IUnknown)).Release();
+                }
+            }
+        }
+    }
+}
+
+#[repr(C)]
+struct MessageFilterObject {
+    vtbl: *const IMessageFilterVtbl,
+    refs: Cell<ULONG>,
+    filter: MessageFilter,
+}
+
+static MESSAGE_FILTER_VTBL: IMessageFilterVtbl = IMessageFilterVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: message_filter_query_interface,
+        AddRef: message_filter_add_ref,
+        Release: message_filter_release,
+    },
+    HandleInComingCall: message_filter_handle_incoming_call,
+    RetryRejectedCall: message_filter_retry_rejected_call,
+    MessagePending: message_filter_message_pending,
+};
+
+unsafe extern "system" fn message_filter_query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if ppv.is_null() {
+        return winerror::E_POINTER;
+    }
+
+    if IsEqualGUID(&*riid, &IUnknown::uuidof()) || IsEqualGUID(&*riid, &IMessageFilter::uuidof()) {
+        message_filter_add_ref(this);
+        *ppv = this as *mut c_void;
+        winerror::S_OK
+    } else {
+        *ppv = std::ptr::null_mut();
+        winerror::E_NOINTERFACE
+    }
+}
+
+// Message filter is called on the thread it's registered on only, so reference count needn't be atomic.
+unsafe extern "system" fn message_filter_add_ref(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const MessageFilterObject);
+    object.refs.set(object.refs.get() + 1);
+    object.refs.get()
+}
+
+unsafe extern "system" fn message_filter_release(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const MessageFilterObject);
+    let refs = object.refs.get() - 1;
+    object.refs.set(refs);
+    if refs == 0 {
+        drop(Box::from_raw(this as *mut MessageFilterObject));
+    }
+
+    refs
+}
+
+unsafe extern "system" fn message_filter_handle_incoming_call(
+    this: *mut IMessageFilter,
+    dwCallType: DWORD,
+    htaskCaller: HTASK,
+    dwTickCount: DWORD,
+    lpInterfaceInfo: LPINTERFACEINFO,
+) -> DWORD {
+    let filter = &(*(this as *const MessageFilterObject)).filter;
+    match &filter.on_incoming_call {
+        Some(f) => catch_unwind(AssertUnwindSafe(|| f(dwCallType))).unwrap_or(SERVERCALL_ISHANDLED),
+        None => SERVERCALL_ISHANDLED,
+    }
+}
+
+unsafe extern "system" fn message_filter_retry_rejected_call(
+    this: *mut IMessageFilter,
+    htaskCallee: HTASK,
+    dwTickCount: DWORD,
+    dwRejectType: DWORD,
+) -> DWORD {
+    let filter = &(*(this as *const MessageFilterObject)).filter;
+    if dwRejectType == SERVERCALL_REJECTED {
+        return CANCEL_CALL;
+    }
+
+    let elapsed = Duration::from_millis(dwTickCount as u64);
+    match filter.retry_delay(elapsed) {
+        Some(delay) => {
+            if let Some(f) = &filter.on_retry {
+                if catch_unwind(AssertUnwindSafe(|| f(elapsed, dwRejectType))).is_err() {
+                    return CANCEL_CALL;
+                }
+            }
+            delay.as_millis().min(CANCEL_CALL as u128 - 1) as DWORD
+        }
+        None => CANCEL_CALL,
+    }
+}
+
+unsafe extern "system" fn message_filter_message_pending(
+    this: *mut IMessageFilter,
+    htaskCallee: HTASK,
+    dwTickCount: DWORD,
+    dwPendingType: DWORD,
+) -> DWORD {
+    let filter = &(*(this as *const MessageFilterObject)).filter;
+    match &filter.on_message_pending {
+        Some(f) => catch_unwind(AssertUnwindSafe(|| {
+            f(Duration::from_millis(dwTickCount as u64))
+        }))
+        .unwrap_or(PENDINGMSG_WAITDEFPROCESS),
+        None => PENDINGMSG_WAITDEFPROCESS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_MessageFilter_retry_delay() {
+        let filter = MessageFilter::new(RetryPolicy::new(3, Duration::from_millis(100)));
+        assert_eq!(
+            Some(Duration::from_millis(100)),
+            filter.retry_delay(Duration::from_millis(0))
+        );
+        assert_eq!(
+            Some(Duration::from_millis(100)),
+            filter.retry_delay(Duration::from_millis(250))
+        );
+        assert_eq!(None, filter.retry_delay(Duration::from_millis(300)));
+        assert_eq!(
+            None,
+            MessageFilter::new(RetryPolicy::none()).retry_delay(Duration::from_millis(0))
+        );
+    }
+
+    #[test]
+    fn test_MessageFilter_callback_panic() {
+        let mut object = MessageFilterObject {
+            vtbl: &MESSAGE_FILTER_VTBL,
+            refs: Cell::new(1),
+            filter: MessageFilter::new(RetryPolicy::new(3, Duration::from_millis(100)))
+                .on_retry(|_, _| panic!("on_retry"))
+                .on_incoming_call(|_| panic!("on_incoming_call"))
+                .on_message_pending(|_| panic!("on_message_pending")),
+        };
+        let this = &mut object as *mut MessageFilterObject as *mut IMessageFilter;
+
+        unsafe {
+            assert_eq!(
+                SERVERCALL_ISHANDLED,
+                message_filter_handle_incoming_call(
+                    this,
+                    0,
+                    std::ptr::null_mut(),
+                    0,
+                    std::ptr::null_mut()
+                )
+            );
+            assert_eq!(
+                CANCEL_CALL,
+                message_filter_retry_rejected_call(
+                    this,
+                    std::ptr::null_mut(),
+                    0,
+                    SERVERCALL_RETRYLATER
+                )
+            );
+            assert_eq!(
+                PENDINGMSG_WAITDEFPROCESS,
+                message_filter_message_pending(this, std::ptr::null_mut(), 0, 0)
+            );
+        }
+    }
+}
@@ -0,0 +1,310 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Scripted IDispatch test double, behind `mock` feature.
+//!
+//! [`MockDispatch`] is an in-process automation object implemented in Rust: members return canned values, fail
+//! with HRESULTs or run closures, every call is recorded and expected calls are verified at the end of a test. Code
+//! built on [`SmartIDispatch`] can be unit-tested with it instead of registered servers like Excel or 1C. Clones
+//! share the script and the record, keep one in the test and pass IDispatch of another to the code under test.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::mock::MockDispatch;
+//! use rusty_winapi::prelude::*;
+//! use rusty_winapi::server::dispatch::DispatchServer;
+//!
+//! let mock = MockDispatch::new()
+//!     .returns("Name", SmartVariant::Text("Book1".into()))
+//!     .expect("Save", vec![SmartVariant::Bool(true)]);
+//! let mut workbook = mock.clone().into_dispatch();
+//!
+//! // Code under test.
+//! workbook.call("Save", &[SmartVariant::Bool(true)])?;
+//!
+//! mock.verify();
+//! assert_eq!(0, mock.call_count("Name"));
+//! # Ok::<(), RustyWinapiError>(())
+//! ```
+//!
+//! [`MockDispatch`]: struct.MockDispatch.html
+//! [`SmartIDispatch`]: ../smart_idispatch/trait.SmartIDispatch.html
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use winapi::shared::minwindef::WORD;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::oaidl::DISPID;
+use winapi::um::oleauto::{DISPATCH_PROPERTYPUT, DISPATCH_PROPERTYPUTREF};
+
+use crate::error::DispatchError;
+use crate::server::dispatch::DispatchServer;
+use crate::smart_variant::SmartVariant;
+
+/// Scripted member of a [`MockDispatch`], called with `DISPATCH_*` flags and arguments in caller's order.
+///
+/// [`MockDispatch`]: struct.MockDispatch.html
+pub type MockHandler = dyn Fn(WORD, Vec<SmartVariant>) -> Result<SmartVariant, DispatchError>;
+
+/// Call recorded by a [`MockDispatch`].
+///
+/// [`MockDispatch`]: struct.MockDispatch.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockCall {
+    /// Member name as scripted.
+    pub member: String,
+    /// `DISPATCH_*` flags.
+    pub flags: WORD,
+    /// Arguments in caller's order, value of property put is the last one.
+    pub args: Vec<SmartVariant>,
+}
+
+#[derive(Clone)]
+enum Behavior {
+    Value(SmartVariant),
+    Fail(DispatchError),
+    Handler(Rc<MockHandler>),
+}
+
+#[derive(Default)]
+struct State {
+    /// Member of DISPID `i + 1`.
+    members: Vec<(String, Behavior)>,
+    calls: Vec<MockCall>,
+    expected: Vec<(String, Vec<SmartVariant>)>,
+}
+
+impl State {
+    fn find(&self, name: &str) -> Option<usize> {
+        self.members
+            .iter()
+            .position(|(x, _)| x.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Scripted automation object, see [module level documentation].
+///
+/// Names unknown to the script fail GetIDsOfNames with `DISP_E_UNKNOWNNAME`.
+///
+/// [module level documentation]: index.html
+#[derive(Clone, Default)]
+pub struct MockDispatch(Rc<RefCell<State>>);
+
+impl MockDispatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scripts member to return `value`, builder style. Property put replaces the value.
+    pub fn returns(self, name: &str, value: SmartVariant) -> Self {
+        self.script(name, Behavior::Value(value));
+        self
+    }
+
+    /// Scripts member to fail with `hresult`, builder style.
+    pub fn fails(self, name: &str, hresult: HRESULT) -> Self {
+        self.script(name, Behavior::Fail(DispatchError::Failed(hresult)));
+        self
+    }
+
+    /// Scripts member to fail with `error`, e.g. an exception with a description, builder style.
+    pub fn fails_with(self, name: &str, error: DispatchError) -> Self {
+        self.script(name, Behavior::Fail(error));
+        self
+    }
+
+    /// Scripts member to be handled by `f`, builder style.
+    pub fn handles<F>(self, name: &str, f: F) -> Self
+    where
+        F: Fn(WORD, Vec<SmartVariant>) -> Result<SmartVariant, DispatchError> + 'static,
+    {
+        self.script(name, Behavior::Handler(Rc::new(f)));
+        self
+    }
+
+    /// Expects a call of member with `args`, builder style. Member returns `Empty` unless it's scripted otherwise.
+    ///
+    /// Expected calls must be made in order of expectation, other calls may come in between, see [`verify`].
+    ///
+    /// [`verify`]: #method.verify
+    pub fn expect(self, name: &str, args: Vec<SmartVariant>) -> Self {
+        {
+            let mut state = self.0.borrow_mut();
+            if state.find(name).is_none() {
+                state
+                    .members
+                    .push((name.to_string(), Behavior::Value(SmartVariant::Empty)));
+            }
+            state.expected.push((name.to_string(), args));
+        }
+        self
+    }
+
+    /// Recorded calls in order they were made.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.0.borrow().calls.clone()
+    }
+
+    /// Number of recorded calls of member, names are case-insensitive.
+    pub fn call_count(&self, name: &str) -> usize {
+        self.0
+            .borrow()
+            .calls
+            .iter()
+            .filter(|x| x.member.eq_ignore_ascii_case(name))
+            .count()
+    }
+
+    /// Forgets recorded calls, expectations stay.
+    pub fn clear_calls(&self) {
+        self.0.borrow_mut().calls.clear();
+    }
+
+    /// Panics if expected calls weren't made in order of expectation.
+    pub fn verify(&self) {
+        let state = self.0.borrow();
+        let mut calls = state.calls.iter();
+        for (name, args) in &state.expected {
+            if !calls
+                .by_ref()
+                .any(|x| x.member.eq_ignore_ascii_case(name) && x.args == *args)
+            {
+                panic!(
+                    "expected call {}({:?}) wasn't made, recorded calls: {:?}",
+                    name, args, state.calls
+                );
+            }
+        }
+    }
+
+    fn script(&self, name: &str, behavior: Behavior) {
+        let mut state = self.0.borrow_mut();
+        match state.find(name) {
+            Some(i) => state.members[i].1 = behavior,
+            None => state.members.push((name.to_string(), behavior)),
+        }
+    }
+}
+
+impl fmt::Debug for MockDispatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0.borrow();
+        f.debug_struct("MockDispatch")
+            .field(
+                "members",
+                &state.members.iter().map(|(x, _)| x).collect::<Vec<_>>(),
+            )
+            .field("calls", &state.calls)
+            .finish()
+    }
+}
+
+impl DispatchServer for MockDispatch {
+    fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+        let state = self.0.borrow();
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, x)| match i {
+                0 => state.find(x).map(|x| x as DISPID + 1),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn invoke(
+        &self,
+        dispid: DISPID,
+        flags: WORD,
+        args: Vec<SmartVariant>,
+    ) -> Result<SmartVariant, DispatchError> {
+        let (name, behavior) = {
+            let mut state = self.0.borrow_mut();
+            let (name, behavior) = match dispid {
+                x if x >= 1 => state.members.get(x as usize - 1).cloned(),
+                _ => None,
+            }
+            .ok_or(DispatchError::Failed(winerror::DISP_E_MEMBERNOTFOUND))?;
+            state.calls.push(MockCall {
+                member: name.clone(),
+                flags,
+                args: args.clone(),
+            });
+            (name, behavior)
+        };
+
+        // Handler is called after the borrow is dropped, it may inspect the mock.
+        match behavior {
+            Behavior::Handler(f) => f(flags, args),
+            Behavior::Fail(x) => Err(x),
+            Behavior::Value(_) if flags & (DISPATCH_PROPERTYPUT | DISPATCH_PROPERTYPUTREF) != 0 => {
+                if let Some(x) = args.last() {
+                    self.script(&name, Behavior::Value(x.clone()));
+                }
+                Ok(SmartVariant::Empty)
+            }
+            Behavior::Value(x) => Ok(x),
+        }
+    }
+
+    fn member_name(&self, dispid: DISPID) -> Option<String> {
+        match dispid {
+            x if x >= 1 => self
+                .0
+                .borrow()
+                .members
+                .get(x as usize - 1)
+                .map(|(x, _)| x.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hresult::HResult;
+    use crate::smart_idispatch::SmartIDispatch;
+
+    #[test]
+    fn test_MockDispatch() {
+        let mock = MockDispatch::new()
+            .returns("Name", SmartVariant::Text("Book1".into()))
+            .fails("Close", winerror::E_ACCESSDENIED)
+            .handles("Twice", |_, args| match args.as_slice() {
+                [SmartVariant::Int4(x)] => Ok(SmartVariant::Int4(x * 2)),
+                _ => Err(DispatchError::type_mismatch(0)),
+            })
+            .expect("Save", vec![SmartVariant::Bool(true)]);
+        let mut dispatch = mock.clone().into_dispatch();
+
+        assert_eq!(Ok(SmartVariant::Text("Book1".into())), dispatch.get("name"));
+        assert!(dispatch
+            .put("Name", SmartVariant::Text("Book2".into()))
+            .is_ok());
+        assert_eq!(Ok(SmartVariant::Text("Book2".into())), dispatch.get("Name"));
+        assert_eq!(
+            Ok(SmartVariant::Int4(42)),
+            dispatch.call("Twice", &[SmartVariant::Int4(21)])
+        );
+        assert_eq!(
+            Some(HResult(winerror::E_ACCESSDENIED)),
+            dispatch.call("Close", &[]).err().map(|x| x.hresult())
+        );
+        assert!(dispatch.call("Print", &[]).is_err());
+        assert!(dispatch.call("Save", &[SmartVariant::Bool(true)]).is_ok());
+
+        mock.verify();
+        assert_eq!(3, mock.call_count("NAME"));
+        assert_eq!(6, mock.calls().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected call Save")]
+    fn test_MockDispatch_verify() {
+        MockDispatch::new().expect("Save", vec![]).verify();
+    }
+}
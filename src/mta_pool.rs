@@ -0,0 +1,204 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Pool of multithreaded apartment workers.
+//!
+//! Free-threaded servers accept calls from any MTA thread concurrently, so heavy automation workloads may be
+//! parallelized. [`MtaPool`] joins a set of worker threads to the MTA and runs submitted closures on them.
+//! Interface pointers are passed to closures via [`SendableInterface`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::com_apartment::ComApartment;
+//! use rusty_winapi::mta_pool::MtaPool;
+//! use rusty_winapi::sendable_interface::SendableInterface;
+//! use rusty_winapi::smart_idispatch::SmartIDispatch;
+//! use rusty_winapi::smart_variant::SmartVariant;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let _com = ComApartment::init_mta().unwrap();
+//! let server = Activate::<IDispatch>::new().progid("My.FreeThreadedServer").create().unwrap();
+//! let server = Arc::new(SendableInterface::new(&server).unwrap());
+//!
+//! let pool = MtaPool::new(4).unwrap();
+//! let results = pool.map((0..100).collect(), move |i: i32| {
+//!     let mut server = server.get().map_err(|x| x.to_string())?;
+//!     server
+//!         .call("Process", &[SmartVariant::Int4(i)])
+//!         .map(|x| x.summary())
+//!         .map_err(|x| x.to_string())
+//! });
+//! ```
+//!
+//! [`MtaPool`]: struct.MtaPool.html
+//! [`SendableInterface`]: ../sendable_interface/struct.SendableInterface.html
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use winapi::shared::winerror;
+
+use crate::com_apartment::ComApartment;
+use crate::error::ComResult;
+use crate::sta_thread::StaReceiver;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads joined to the multithreaded apartment, running submitted closures concurrently.
+///
+/// Dropping the pool waits for queued closures to complete.
+pub struct MtaPool {
+    jobs: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl MtaPool {
+    /// Starts `threads` worker threads (at least one).
+    ///
+    /// # Errors
+    ///
+    /// Returns error of failed COM initialization of a worker.
+    pub fn new(threads: usize) -> ComResult<Self> {
+        let (jobs, queue) = channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let (started, start) = channel::<ComResult<()>>();
+
+        let mut pool = MtaPool {
+            jobs: Some(jobs),
+            threads: Vec::new(),
+        };
+
+        for i in 0..threads.max(1) {
+            let queue = queue.clone();
+            let started = started.clone();
+            let thread = std::thread::Builder::new()
+                .name(format!("rusty_winapi MTA #{}", i))
+                .spawn(move || {
+                    let _com = match ComApartment::init_mta() {
+                        Ok(x) => x,
+                        Err(x) => {
+                            let _ = started.send(Err(x));
+                            return;
+                        }
+                    };
+                    let _ = started.send(Ok(()));
+                    drop(started);
+
+                    run_jobs(&queue);
+                })
+                .map_err(|_| winerror::E_OUTOFMEMORY)?;
+            pool.threads.push(thread);
+        }
+        drop(started);
+
+        for _ in 0..pool.threads.len() {
+            start.recv().map_err(|_| winerror::RPC_E_DISCONNECTED)??;
+        }
+
+        Ok(pool)
+    }
+
+    /// Number of worker threads.
+    #[inline]
+    pub fn threads(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Submits a closure to be run on any worker thread, returns receiver of its result.
+    pub fn spawn<F, R>(&self, f: F) -> StaReceiver<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (result, receiver) = channel();
+        let job: Job = Box::new(move || {
+            let _ = result.send(f());
+        });
+
+        if let Some(x) = &self.jobs {
+            let _ = x.send(job);
+        }
+
+        StaReceiver::from(receiver)
+    }
+
+    /// Runs `f` for every item of a batch across worker threads, returns results in order of items.
+    ///
+    /// Result of an item is `Err(RustyWinapiError::Com(RPC_E_DISCONNECTED))` if `f` panicked on it.
+    pub fn map<I, F, R>(&self, items: Vec<I>, f: F) -> Vec<ComResult<R>>
+    where
+        I: Send + 'static,
+        F: Fn(I) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let f = Arc::new(f);
+        let receivers: Vec<StaReceiver<R>> = items
+            .into_iter()
+            .map(|x| {
+                let f = f.clone();
+                self.spawn(move || f(x))
+            })
+            .collect();
+
+        receivers.into_iter().map(|x| x.wait()).collect()
+    }
+}
+
+impl Drop for MtaPool {
+    fn drop(&mut self) {
+        // Workers exit when the queue is closed and drained.
+        self.jobs.take();
+        for x in self.threads.drain(..) {
+            let _ = x.join();
+        }
+    }
+}
+
+fn run_jobs(queue: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match queue.lock() {
+            Ok(x) => x.recv(),
+            Err(_) => return,
+        };
+
+        match job {
+            // Panicking job drops its result sender, so its receiver gets RPC_E_DISCONNECTED.
+            Ok(x) => {
+                let _ = catch_unwind(AssertUnwindSafe(x));
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RustyWinapiError;
+
+    #[test]
+    fn test_MtaPool_map() {
+        let pool = MtaPool::new(3).unwrap();
+        assert_eq!(3, pool.threads());
+
+        let results = pool.map(vec![1, 2, 0, 4], |x| {
+            if x == 0 {
+                panic!("zero");
+            }
+            x * 10
+        });
+        assert_eq!(
+            vec![
+                Ok(10),
+                Ok(20),
+                Err(RustyWinapiError::Com(winerror::RPC_E_DISCONNECTED)),
+                Ok(40)
+            ],
+            results
+        );
+    }
+}
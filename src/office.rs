@@ -0,0 +1,120 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Helpers for unattended automation of Microsoft Office applications.
+//!
+//! Office applications show modal dialogs (save conflicts, link updates, feature installation) which hang unattended
+//! automation, since nobody sees them. [`PropertyGuard`] sets properties controlling such prompts via late binding
+//! and restores previous values on drop.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::office::without_prompts;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create().unwrap();
+//! without_prompts(&excel, || {
+//!     // ... open, change and save workbooks without confirmation dialogs ...
+//! });
+//! ```
+//!
+//! [`PropertyGuard`]: struct.PropertyGuard.html
+
+use winapi::um::oaidl::IDispatch;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::smart_idispatch::{DispatchInterface, SmartIDispatch};
+use crate::smart_variant::SmartVariant;
+
+/// Well-known application properties controlling interactive prompts, with values disabling them.
+///
+/// * `DisplayAlerts` — alerts and messages (Excel, Word, PowerPoint).
+/// * `AskToUpdateLinks` — prompt to update links on open (Excel).
+/// * `AlertBeforeOverwriting` — prompt before overwriting non-blank cells (Excel).
+/// * `FeatureInstall` — install-on-demand of features, `msoFeatureInstallNone` (Office).
+pub fn prompt_properties() -> Vec<(&'static str, SmartVariant)> {
+    vec![
+        ("DisplayAlerts", SmartVariant::Bool(false)),
+        ("AskToUpdateLinks", SmartVariant::Bool(false)),
+        ("AlertBeforeOverwriting", SmartVariant::Bool(false)),
+        ("FeatureInstall", SmartVariant::Int4(0)),
+    ]
+}
+
+/// Sets properties of an object and restores their previous values (in reverse order) on drop.
+///
+/// Properties which the object doesn't have, or which can't be read or written, are skipped, so the same set of
+/// properties may be applied to different applications.
+pub struct PropertyGuard {
+    object: AutoCOMInterface<IDispatch>,
+    saved: Vec<(&'static str, SmartVariant)>,
+}
+
+impl PropertyGuard {
+    /// Sets `values` of properties of `object`, remembering previous ones.
+    pub fn set<T: DispatchInterface>(
+        object: &AutoCOMInterface<T>,
+        values: &[(&'static str, SmartVariant)],
+    ) -> Self {
+        let mut object = object.to_dispatch();
+        let mut saved = Vec::new();
+
+        if !object.is_null() {
+            for (name, value) in values {
+                if let Ok(previous) = object.get(name) {
+                    if object.put(name, value.clone()).is_ok() {
+                        saved.push((*name, previous));
+                    }
+                }
+            }
+        }
+
+        PropertyGuard { object, saved }
+    }
+
+    /// Disables interactive prompts of an application, see [`prompt_properties`].
+    ///
+    /// [`prompt_properties`]: fn.prompt_properties.html
+    pub fn suppress_prompts<T: DispatchInterface>(application: &AutoCOMInterface<T>) -> Self {
+        PropertyGuard::set(application, &prompt_properties())
+    }
+
+    /// Names of properties which were changed and will be restored.
+    pub fn changed(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.saved.iter().map(|(name, _)| *name)
+    }
+}
+
+impl Drop for PropertyGuard {
+    fn drop(&mut self) {
+        while let Some((name, value)) = self.saved.pop() {
+            let _ = self.object.put(name, value);
+        }
+    }
+}
+
+/// Runs `f` with interactive prompts of an application disabled, see [`PropertyGuard::suppress_prompts`].
+///
+/// [`PropertyGuard::suppress_prompts`]: struct.PropertyGuard.html#method.suppress_prompts
+pub fn without_prompts<T, F, R>(application: &AutoCOMInterface<T>, f: F) -> R
+where
+    T: DispatchInterface,
+    F: FnOnce() -> R,
+{
+    let _guard = PropertyGuard::suppress_prompts(application);
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_PropertyGuard_null() {
+        let empty = AutoCOMInterface::<IDispatch>::default();
+        let guard = PropertyGuard::suppress_prompts(&empty);
+        assert_eq!(0, guard.changed().count());
+        assert_eq!(42, without_prompts(&empty, || 42));
+    }
+}
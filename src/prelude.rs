@@ -0,0 +1,36 @@
+//! Commonly used traits and types, glob-import them with `use rusty_winapi::prelude::*;`.
+//!
+//! Smart traits must be in scope to call their methods on COM interfaces and [`AutoCOMInterface`] wrappers.
+//!
+//! [`AutoCOMInterface`]: ../auto_com_interface/struct.AutoCOMInterface.html
+
+pub use std::convert::{TryFrom, TryInto};
+
+pub use winapi::um::oaidl::IDispatch;
+pub use winapi::um::unknwnbase::IUnknown;
+
+pub use crate::activate::Activate;
+pub use crate::agile_ref::AgileRef;
+pub use crate::auto_bstr::{AutoBSTR, BStr};
+pub use crate::auto_com_interface::{AutoCOMInterface, InterfaceVtbl, MarshaledInterface};
+pub use crate::cls_ctx::ClsCtx;
+pub use crate::com_apartment::ComApartment;
+pub use crate::config::Config;
+pub use crate::error::{
+    ActivationError, ComResult, ConversionError, DispatchError, MemberContext, RustyWinapiError,
+};
+pub use crate::hresult::HResult;
+pub use crate::invoke_builder::InvokeBuilder;
+pub use crate::safe::bstr::SysAllocError;
+pub use crate::sendable_interface::SendableInterface;
+pub use crate::server::dispatch::DispatchServer;
+pub use crate::smart_iclassfactory::SmartIClassFactory;
+pub use crate::smart_iconnectionpoint::{SmartIConnectionPoint, SmartIConnectionPointContainer};
+pub use crate::smart_idispatch::{
+    DispatchInterface, DynamicMember, MemberDescription, MemberKind, ResolvedNames, SmartIDispatch,
+    TypeDescription,
+};
+pub use crate::smart_iobjectsafety::SmartIObjectSafety;
+pub use crate::smart_itypeinfo::SmartITypeInfo;
+pub use crate::smart_iunknown::SmartIUnknown;
+pub use crate::smart_variant::{AutoVariant, SmartVariant, SmartVariantRef, VariantType};
@@ -0,0 +1,661 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Recording of automation calls made to a real object and their replay by a fake one, behind `replay` feature.
+//!
+//! [`Recorder`] is a proxy object forwarding calls to a real IDispatch (Excel, 1C...) and recording every call with
+//! its arguments and result into a [`ReplayLog`]. Objects returned by recorded calls are proxied as well, so whole
+//! call chains like `Workbooks.Add().Sheets(1).Range("A1")` are captured. The log is saved as CSV and loaded on a
+//! machine without the product, where [`Replayer`] plays it back: it answers calls of the recorded members with the
+//! recorded results, in order of recording.
+//!
+//! Interfaces passed as arguments, arrays and references aren't recorded, they are saved as their VARTYPE and
+//! replayed as `Empty`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::prelude::*;
+//! use rusty_winapi::replay::{Recorder, ReplayLog, Replayer};
+//! use rusty_winapi::server::dispatch::DispatchServer;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! # fn report<D: SmartIDispatch>(excel: &mut D) -> ComResult<()> { Ok(()) }
+//! // With Excel installed.
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create()?;
+//! let recorder = Recorder::new(excel);
+//! report(&mut recorder.clone().into_dispatch())?;
+//! recorder.log().write(std::fs::File::create("report.csv")?)?;
+//!
+//! // In CI.
+//! let log = ReplayLog::read(std::fs::File::open("report.csv")?)?;
+//! report(&mut Replayer::new(log).into_dispatch())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`Recorder`]: struct.Recorder.html
+//! [`ReplayLog`]: struct.ReplayLog.html
+//! [`Replayer`]: struct.Replayer.html
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+use winapi::shared::minwindef::WORD;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::shared::wtypes::VARTYPE;
+use winapi::um::oaidl::{IDispatch, DISPID};
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::config::Config;
+use crate::error::{DispatchError, RustyWinapiError};
+use crate::server::dispatch::DispatchServer;
+use crate::smart_idispatch::SmartIDispatch;
+use crate::smart_variant::SmartVariant;
+
+/// Recorded argument or result.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedValue {
+    /// Scalar or string value.
+    Value(SmartVariant),
+    /// Object returned by a call, its calls are recorded under this id. Root object is 0.
+    Object(u32),
+    /// Value which isn't recorded: interface argument, array or reference.
+    Unsupported(VARTYPE),
+}
+
+/// Call recorded by a [`Recorder`].
+///
+/// [`Recorder`]: struct.Recorder.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedCall {
+    /// Id of the called object, 0 for the root one.
+    pub object: u32,
+    pub member: String,
+    /// `DISPATCH_*` flags.
+    pub flags: WORD,
+    /// Arguments in caller's order, value of property put is the last one.
+    pub args: Vec<RecordedValue>,
+    pub result: Result<RecordedValue, DispatchError>,
+}
+
+/// Calls recorded by a [`Recorder`], in order they were made.
+///
+/// Saved as CSV, one call per record: object id, flags, member, result and arguments. Values are written as
+/// `VT:value`, e.g. `I4:42` or `BSTR:text`, failures as `FAIL:0x80020003` or `EXCEPTION:scode:source:description`.
+///
+/// [`Recorder`]: struct.Recorder.html
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayLog {
+    pub calls: Vec<RecordedCall>,
+}
+
+impl ReplayLog {
+    /// Writes the log as CSV.
+    pub fn write<W: io::Write>(&self, writer: W) -> csv::Result<()> {
+        let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+        for call in &self.calls {
+            let mut record = vec![
+                call.object.to_string(),
+                call.flags.to_string(),
+                call.member.clone(),
+                match &call.result {
+                    Ok(x) => format_value(x),
+                    Err(x) => format_error(x),
+                },
+            ];
+            record.extend(call.args.iter().map(format_value));
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads the log written by [`write`].
+    ///
+    /// [`write`]: #method.write
+    pub fn read<R: io::Read>(reader: R) -> csv::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut calls = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let invalid = || {
+                csv::Error::from(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid replay log record {:?}", record),
+                ))
+            };
+            if record.len() < 4 {
+                return Err(invalid());
+            }
+
+            let result = match parse_error(&record[3]) {
+                Some(x) => Err(x),
+                None => Ok(parse_value(&record[3]).ok_or_else(invalid)?),
+            };
+            calls.push(RecordedCall {
+                object: record[0].parse().map_err(|_| invalid())?,
+                flags: record[1].parse().map_err(|_| invalid())?,
+                member: record[2].to_string(),
+                args: record
+                    .iter()
+                    .skip(4)
+                    .map(parse_value)
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?,
+                result,
+            });
+        }
+
+        Ok(ReplayLog { calls })
+    }
+}
+
+fn format_value(value: &RecordedValue) -> String {
+    let value = match value {
+        RecordedValue::Value(x) => x,
+        RecordedValue::Object(x) => return format!("OBJECT:{}", x),
+        RecordedValue::Unsupported(x) => return format!("UNSUPPORTED:{}", x),
+    };
+
+    match value {
+        SmartVariant::Empty => String::from("EMPTY"),
+        SmartVariant::Int1(x) => format!("I1:{}", x),
+        SmartVariant::Int2(x) => format!("I2:{}", x),
+        SmartVariant::Int4(x) => format!("I4:{}", x),
+        SmartVariant::Int8(x) => format!("I8:{}", x),
+        SmartVariant::UInt1(x) => format!("UI1:{}", x),
+        SmartVariant::UInt2(x) => format!("UI2:{}", x),
+        SmartVariant::UInt4(x) => format!("UI4:{}", x),
+        SmartVariant::UInt8(x) => format!("UI8:{}", x),
+        SmartVariant::Int(x) => format!("INT:{}", x),
+        SmartVariant::UInt(x) => format!("UINT:{}", x),
+        SmartVariant::Real4(x) => format!("R4:{:?}", x),
+        SmartVariant::Real8(x) => format!("R8:{:?}", x),
+        SmartVariant::Date(x) => format!("DATE:{:?}", x),
+        SmartVariant::Bool(x) => format!("BOOL:{}", x),
+        SmartVariant::ErrorCode(x) => format!("ERROR:{}", x),
+        SmartVariant::Text(x) => format!("BSTR:{}", x),
+        SmartVariant::Text16(x) => format!(
+            "BSTR16:{}",
+            x.iter()
+                .map(|x| format!("{:04x}", x))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        x => format!("UNSUPPORTED:{}", x.vtype()),
+    }
+}
+
+fn parse_value(text: &str) -> Option<RecordedValue> {
+    if text == "EMPTY" {
+        return Some(RecordedValue::Value(SmartVariant::Empty));
+    }
+
+    let (tag, x) = text.split_at(text.find(':')?);
+    let x = &x[1..];
+    let value = match tag {
+        "OBJECT" => return x.parse().ok().map(RecordedValue::Object),
+        "UNSUPPORTED" => return x.parse().ok().map(RecordedValue::Unsupported),
+        "I1" => SmartVariant::Int1(x.parse().ok()?),
+        "I2" => SmartVariant::Int2(x.parse().ok()?),
+        "I4" => SmartVariant::Int4(x.parse().ok()?),
+        "I8" => SmartVariant::Int8(x.parse().ok()?),
+        "UI1" => SmartVariant::UInt1(x.parse().ok()?),
+        "UI2" => SmartVariant::UInt2(x.parse().ok()?),
+        "UI4" => SmartVariant::UInt4(x.parse().ok()?),
+        "UI8" => SmartVariant::UInt8(x.parse().ok()?),
+        "INT" => SmartVariant::Int(x.parse().ok()?),
+        "UINT" => SmartVariant::UInt(x.parse().ok()?),
+        "R4" => SmartVariant::Real4(x.parse().ok()?),
+        "R8" => SmartVariant::Real8(x.parse().ok()?),
+        "DATE" => SmartVariant::Date(x.parse().ok()?),
+        "BOOL" => SmartVariant::Bool(x.parse().ok()?),
+        "ERROR" => SmartVariant::ErrorCode(x.parse().ok()?),
+        "BSTR" => SmartVariant::Text(x.into()),
+        "BSTR16" => SmartVariant::Text16(
+            x.split_whitespace()
+                .map(|x| u16::from_str_radix(x, 16).ok())
+                .collect::<Option<Vec<_>>>()?
+                .into(),
+        ),
+        _ => return None,
+    };
+
+    Some(RecordedValue::Value(value))
+}
+
+fn format_error(error: &DispatchError) -> String {
+    match error {
+        DispatchError::Exception {
+            scode,
+            source,
+            description,
+        } => format!("EXCEPTION:0x{:08X}:{}:{}", scode, source, description),
+        DispatchError::Failed(x) | DispatchError::Argument { hresult: x, .. } => {
+            format!("FAIL:0x{:08X}", x)
+        }
+    }
+}
+
+fn parse_error(text: &str) -> Option<DispatchError> {
+    let hresult = |x: &str| {
+        u32::from_str_radix(x.strip_prefix("0x")?, 16)
+            .ok()
+            .map(|x| x as HRESULT)
+    };
+
+    if let Some(x) = text.strip_prefix("FAIL:") {
+        return hresult(x).map(DispatchError::Failed);
+    }
+    let mut parts = text.strip_prefix("EXCEPTION:")?.splitn(3, ':');
+    Some(DispatchError::Exception {
+        scode: hresult(parts.next()?)?,
+        source: parts.next()?.to_string(),
+        description: parts.next()?.to_string(),
+    })
+}
+
+#[derive(Default)]
+struct Recording {
+    log: ReplayLog,
+    /// Number of objects, including the root one.
+    objects: u32,
+}
+
+/// Proxy recording calls to a real object, see [module level documentation].
+///
+/// Clones share the log, keep one to get the log after the calls.
+///
+/// [module level documentation]: index.html
+#[derive(Clone)]
+pub struct Recorder {
+    recording: Rc<RefCell<Recording>>,
+    id: u32,
+    target: Rc<RefCell<AutoCOMInterface<IDispatch>>>,
+    /// Names resolved by the target, DISPID of the proxy is `i + 1`.
+    members: Rc<RefCell<Vec<(String, DISPID)>>>,
+}
+
+impl Recorder {
+    pub fn new(target: AutoCOMInterface<IDispatch>) -> Self {
+        let recording = Recording {
+            log: ReplayLog::default(),
+            objects: 1,
+        };
+        Recorder::object(Rc::new(RefCell::new(recording)), 0, target)
+    }
+
+    /// Returns copy of calls recorded so far.
+    pub fn log(&self) -> ReplayLog {
+        self.recording.borrow().log.clone()
+    }
+
+    fn object(
+        recording: Rc<RefCell<Recording>>,
+        id: u32,
+        target: AutoCOMInterface<IDispatch>,
+    ) -> Self {
+        Recorder {
+            recording,
+            id,
+            target: Rc::new(RefCell::new(target)),
+            members: Default::default(),
+        }
+    }
+
+    /// Records a result, proxying returned object.
+    fn record_result(&self, result: SmartVariant) -> (SmartVariant, RecordedValue) {
+        match result {
+            SmartVariant::IDispatch(target) if !target.is_null() => {
+                let id = {
+                    let mut recording = self.recording.borrow_mut();
+                    recording.objects += 1;
+                    recording.objects - 1
+                };
+                let proxy = Recorder::object(self.recording.clone(), id, target).into_dispatch();
+                (SmartVariant::IDispatch(proxy), RecordedValue::Object(id))
+            }
+            x => {
+                let recorded = record_value(&x);
+                (x, recorded)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("object", &self.id)
+            .field("calls", &self.recording.borrow().log.calls.len())
+            .finish()
+    }
+}
+
+/// Records an argument or a non-object result.
+fn record_value(value: &SmartVariant) -> RecordedValue {
+    match value {
+        SmartVariant::IDispatch(_)
+        | SmartVariant::IUnknown(_)
+        | SmartVariant::Array(_)
+        | SmartVariant::ByRef(_)
+        | SmartVariant::Variant(_) => RecordedValue::Unsupported(value.vtype() as VARTYPE),
+        x => RecordedValue::Value(x.clone()),
+    }
+}
+
+/// Converts failure of the target into the error returned to the caller.
+fn dispatch_error(error: &RustyWinapiError) -> DispatchError {
+    match error.root_cause() {
+        RustyWinapiError::Dispatch { info, .. } if !info.description.is_empty() => {
+            DispatchError::Exception {
+                scode: error.hresult().0,
+                source: info.source.clone(),
+                description: info.description.clone(),
+            }
+        }
+        x => DispatchError::Failed(x.hresult().0),
+    }
+}
+
+impl DispatchServer for Recorder {
+    fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+        let mut result = vec![None; names.len()];
+        let name = match names.first() {
+            Some(x) => x,
+            None => return result,
+        };
+
+        let mut members = self.members.borrow_mut();
+        result[0] = match members
+            .iter()
+            .position(|(x, _)| x.eq_ignore_ascii_case(name))
+        {
+            Some(i) => Some(i as DISPID + 1),
+            None => match self
+                .target
+                .borrow()
+                .get_dispid(name, Config::global().lcid())
+            {
+                Ok(x) => {
+                    members.push((name.clone(), x));
+                    Some(members.len() as DISPID)
+                }
+                Err(_) => None,
+            },
+        };
+
+        result
+    }
+
+    fn invoke(
+        &self,
+        dispid: DISPID,
+        flags: WORD,
+        args: Vec<SmartVariant>,
+    ) -> Result<SmartVariant, DispatchError> {
+        let (member, target_dispid) = match dispid {
+            x if x >= 1 => self.members.borrow().get(x as usize - 1).cloned(),
+            _ => None,
+        }
+        .ok_or(DispatchError::Failed(winerror::DISP_E_MEMBERNOTFOUND))?;
+
+        let recorded_args = args.iter().map(record_value).collect();
+        let result =
+            self.target
+                .borrow_mut()
+                .invoke(target_dispid, Config::global().lcid(), flags, &args);
+        let (result, recorded) = match result {
+            Ok(x) => {
+                let (x, recorded) = self.record_result(x);
+                (Ok(x), Ok(recorded))
+            }
+            Err(e) => {
+                let e = dispatch_error(&e);
+                (Err(e.clone()), Err(e))
+            }
+        };
+
+        self.recording.borrow_mut().log.calls.push(RecordedCall {
+            object: self.id,
+            member,
+            flags,
+            args: recorded_args,
+            result: recorded,
+        });
+
+        result
+    }
+
+    fn member_name(&self, dispid: DISPID) -> Option<String> {
+        match dispid {
+            x if x >= 1 => self
+                .members
+                .borrow()
+                .get(x as usize - 1)
+                .map(|(x, _)| x.clone()),
+            _ => None,
+        }
+    }
+}
+
+struct Replay {
+    log: ReplayLog,
+    /// Whether a call of the log was played back.
+    played: Vec<bool>,
+}
+
+/// Fake object playing back a [`ReplayLog`], see [module level documentation].
+///
+/// A call is answered by the first call of the same object, member and flags which wasn't played back yet. It fails
+/// with an exception if there is no such call or if its recorded arguments differ. Clones share the playback.
+///
+/// [`ReplayLog`]: struct.ReplayLog.html
+/// [module level documentation]: index.html
+#[derive(Clone)]
+pub struct Replayer {
+    replay: Rc<RefCell<Replay>>,
+    id: u32,
+    /// Names asked for, DISPID is `i + 1`.
+    members: Rc<RefCell<Vec<String>>>,
+}
+
+impl Replayer {
+    pub fn new(log: ReplayLog) -> Self {
+        let played = vec![false; log.calls.len()];
+        Replayer::object(Rc::new(RefCell::new(Replay { log, played })), 0)
+    }
+
+    /// Number of recorded calls which weren't played back yet.
+    pub fn remaining(&self) -> usize {
+        self.replay.borrow().played.iter().filter(|x| !**x).count()
+    }
+
+    fn object(replay: Rc<RefCell<Replay>>, id: u32) -> Self {
+        Replayer {
+            replay,
+            id,
+            members: Default::default(),
+        }
+    }
+}
+
+impl fmt::Debug for Replayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replayer")
+            .field("object", &self.id)
+            .field("remaining", &self.remaining())
+            .finish()
+    }
+}
+
+impl DispatchServer for Replayer {
+    fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+        let mut result = vec![None; names.len()];
+        let name = match names.first() {
+            Some(x) => x,
+            None => return result,
+        };
+
+        let mut members = self.members.borrow_mut();
+        result[0] = match members.iter().position(|x| x.eq_ignore_ascii_case(name)) {
+            Some(i) => Some(i as DISPID + 1),
+            None if self
+                .replay
+                .borrow()
+                .log
+                .calls
+                .iter()
+                .any(|x| x.object == self.id && x.member.eq_ignore_ascii_case(name)) =>
+            {
+                members.push(name.clone());
+                Some(members.len() as DISPID)
+            }
+            None => None,
+        };
+
+        result
+    }
+
+    fn invoke(
+        &self,
+        dispid: DISPID,
+        flags: WORD,
+        args: Vec<SmartVariant>,
+    ) -> Result<SmartVariant, DispatchError> {
+        let member = match dispid {
+            x if x >= 1 => self.members.borrow().get(x as usize - 1).cloned(),
+            _ => None,
+        }
+        .ok_or(DispatchError::Failed(winerror::DISP_E_MEMBERNOTFOUND))?;
+
+        let result = {
+            let mut replay = self.replay.borrow_mut();
+            let Replay { log, played } = &mut *replay;
+            let (index, call) = log
+                .calls
+                .iter()
+                .enumerate()
+                .find(|(i, x)| {
+                    !played[*i]
+                        && x.object == self.id
+                        && x.flags == flags
+                        && x.member.eq_ignore_ascii_case(&member)
+                })
+                .ok_or_else(|| {
+                    DispatchError::exception(format!(
+                        "replay: no more recorded calls of {}",
+                        member
+                    ))
+                })?;
+
+            let matches = call.args.len() == args.len()
+                && call.args.iter().zip(&args).all(|(x, y)| match x {
+                    RecordedValue::Value(x) => x == y,
+                    _ => true,
+                });
+            if !matches {
+                return Err(DispatchError::exception(format!(
+                    "replay: {} is called with {:?}, recorded with {:?}",
+                    member, args, call.args
+                )));
+            }
+
+            played[index] = true;
+            call.result.clone()
+        };
+
+        match result? {
+            RecordedValue::Value(x) => Ok(x),
+            RecordedValue::Object(x) => Ok(SmartVariant::IDispatch(
+                Replayer::object(self.replay.clone(), x).into_dispatch(),
+            )),
+            RecordedValue::Unsupported(_) => Ok(SmartVariant::Empty),
+        }
+    }
+
+    fn member_name(&self, dispid: DISPID) -> Option<String> {
+        match dispid {
+            x if x >= 1 => self.members.borrow().get(x as usize - 1).cloned(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::dynamic_object::DynamicObject;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_ReplayLog_roundtrip() {
+        let log = ReplayLog {
+            calls: vec![
+                RecordedCall {
+                    object: 0,
+                    member: String::from("Range"),
+                    flags: 2,
+                    args: vec![
+                        RecordedValue::Value(SmartVariant::Text("A1, \"B2\"".into())),
+                        RecordedValue::Value(SmartVariant::Real8(0.1)),
+                        RecordedValue::Unsupported(9),
+                    ],
+                    result: Ok(RecordedValue::Object(1)),
+                },
+                RecordedCall {
+                    object: 1,
+                    member: String::from("Value"),
+                    flags: 4,
+                    args: vec![RecordedValue::Value(SmartVariant::Text16(
+                        vec![0xd800, 0x41].into(),
+                    ))],
+                    result: Err(DispatchError::Exception {
+                        scode: winerror::E_FAIL,
+                        source: String::from("Excel"),
+                        description: String::from("Error: locked"),
+                    }),
+                },
+            ],
+        };
+
+        let mut csv = Vec::new();
+        log.write(&mut csv).unwrap();
+        assert_eq!(log, ReplayLog::read(csv.as_slice()).unwrap());
+        assert!(ReplayLog::read("0,1".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_Recorder_Replayer() {
+        let child = DynamicObject::new().with("Name", SmartVariant::Text("Sheet1".into()));
+        let object = DynamicObject::new()
+            .with("Count", SmartVariant::Int4(1))
+            .with_method("Sheet", move |_| {
+                Ok(SmartVariant::IDispatch(child.clone().into_dispatch()))
+            });
+
+        let recorder = Recorder::new(object.into_dispatch());
+        let mut proxy = recorder.clone().into_dispatch();
+        proxy.put("Count", SmartVariant::Int4(2)).unwrap();
+        let mut sheet =
+            AutoCOMInterface::<IDispatch>::try_from(proxy.call("Sheet", &[]).unwrap()).unwrap();
+        assert_eq!(Ok(SmartVariant::Text("Sheet1".into())), sheet.get("Name"));
+        assert_eq!(3, recorder.log().calls.len());
+
+        let replayer = Replayer::new(recorder.log());
+        let mut fake = replayer.clone().into_dispatch();
+        assert!(fake.put("Count", SmartVariant::Int4(3)).is_err());
+        fake.put("Count", SmartVariant::Int4(2)).unwrap();
+        let mut sheet =
+            AutoCOMInterface::<IDispatch>::try_from(fake.call("Sheet", &[]).unwrap()).unwrap();
+        assert_eq!(Ok(SmartVariant::Text("Sheet1".into())), sheet.get("Name"));
+        assert_eq!(0, replayer.remaining());
+        assert!(sheet.get("Name").is_err());
+    }
+}
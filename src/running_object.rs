@@ -0,0 +1,222 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Registration of automation objects in the Running Object Table.
+//!
+//! Clients find running instances of a server with GetActiveObject (`GetObject(, "Prog.Id")` in VBScript) or by
+//! moniker. [`RunningObject`] registers an object either way and revokes registration on drop, and
+//! [`get_active_object`] is the client side counterpart.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::auto_com_interface::AutoCOMInterface;
+//! use rusty_winapi::running_object::{get_active_object, RunningObject, ACTIVEOBJECT_STRONG};
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! # fn application() -> AutoCOMInterface<IDispatch> { unimplemented!() }
+//! # let clsid = unsafe { std::mem::zeroed() };
+//! let app: AutoCOMInterface<IDispatch> = application();
+//! let registration = RunningObject::register(&app, &clsid, ACTIVEOBJECT_STRONG).unwrap();
+//!
+//! let same_app = get_active_object::<IDispatch>(&clsid).unwrap();
+//! ```
+//!
+//! [`RunningObject`]: struct.RunningObject.html
+//! [`get_active_object`]: fn.get_active_object.html
+
+use std::marker::PhantomData;
+
+use winapi::shared::guiddef::CLSID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::winerror;
+use winapi::um::objidl::{IMoniker, IRunningObjectTable};
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+
+pub use winapi::shared::wtypes::{ROTFLAGS_ALLOWANYCLIENT, ROTFLAGS_REGISTRATIONKEEPSALIVE};
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+use crate::ffi::{
+    GetActiveObject, GetRunningObjectTable, RegisterActiveObject, RevokeActiveObject,
+};
+use crate::smart_iunknown::SmartIUnknown;
+
+/// Table holds a strong reference, object is kept alive until revoked.
+pub const ACTIVEOBJECT_STRONG: DWORD = 0;
+/// Table holds a weak reference, object must revoke registration when its last external reference is released.
+pub const ACTIVEOBJECT_WEAK: DWORD = 1;
+
+enum Registration {
+    ActiveObject(DWORD),
+    Moniker(AutoCOMInterface<IRunningObjectTable>, DWORD),
+}
+
+/// Registration of an object in the Running Object Table, revoked on drop.
+///
+/// Registration isn't `Send`, it should be revoked in the apartment which registered it.
+pub struct RunningObject {
+    registration: Option<Registration>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl RunningObject {
+    /// Registers `object` as the active object of class `clsid` via RegisterActiveObject.
+    ///
+    /// `flags` are `ACTIVEOBJECT_STRONG` or `ACTIVEOBJECT_WEAK`.
+    pub fn register<T: Interface>(
+        object: &AutoCOMInterface<T>,
+        clsid: &CLSID,
+        flags: DWORD,
+    ) -> ComResult<Self> {
+        if object.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        let mut cookie: DWORD = 0;
+        let hresult =
+            unsafe { RegisterActiveObject(object.as_iunknown_ptr(), clsid, flags, &mut cookie) };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(RunningObject {
+                registration: Some(Registration::ActiveObject(cookie)),
+                _not_send: PhantomData,
+            })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Registers `object` under `moniker` via IRunningObjectTable::Register.
+    ///
+    /// `flags` are combination of `ROTFLAGS_REGISTRATIONKEEPSALIVE` and `ROTFLAGS_ALLOWANYCLIENT`.
+    pub fn register_moniker<T: Interface>(
+        object: &AutoCOMInterface<T>,
+        moniker: &AutoCOMInterface<IMoniker>,
+        flags: DWORD,
+    ) -> ComResult<Self> {
+        if object.is_null() || moniker.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        let table = running_object_table()?;
+        let mut cookie: DWORD = 0;
+        let hresult = unsafe {
+            table.as_inner().Register(
+                flags,
+                object.as_iunknown_ptr(),
+                moniker.as_iunknown_ptr() as *mut IMoniker,
+                &mut cookie,
+            )
+        };
+
+        // MK_S_MONIKERALREADYREGISTERED is a success, the object is registered once more.
+        if winerror::SUCCEEDED(hresult) {
+            Ok(RunningObject {
+                registration: Some(Registration::Moniker(table, cookie)),
+                _not_send: PhantomData,
+            })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Registration cookie.
+    pub fn cookie(&self) -> DWORD {
+        match &self.registration {
+            Some(Registration::ActiveObject(x)) | Some(Registration::Moniker(_, x)) => *x,
+            None => 0,
+        }
+    }
+
+    /// Revokes registration, unlike drop reports failure.
+    pub fn revoke(mut self) -> ComResult<()> {
+        self.revoke_registration()
+    }
+
+    fn revoke_registration(&mut self) -> ComResult<()> {
+        let hresult = match self.registration.take() {
+            Some(Registration::ActiveObject(x)) => unsafe {
+                RevokeActiveObject(x, std::ptr::null_mut())
+            },
+            Some(Registration::Moniker(table, x)) => unsafe { table.as_inner().Revoke(x) },
+            None => winerror::S_OK,
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(())
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+impl Drop for RunningObject {
+    fn drop(&mut self) {
+        let _ = self.revoke_registration();
+    }
+}
+
+/// Returns the Running Object Table of the local machine.
+pub fn running_object_table() -> ComResult<AutoCOMInterface<IRunningObjectTable>> {
+    let mut table: *mut IRunningObjectTable = std::ptr::null_mut();
+    let hresult = unsafe { GetRunningObjectTable(0, &mut table) };
+
+    if winerror::SUCCEEDED(hresult) {
+        let table = unsafe { AutoCOMInterface::from_raw(table) };
+        if table.is_null() {
+            Err(winerror::E_POINTER.into())
+        } else {
+            Ok(table)
+        }
+    } else {
+        Err(hresult.into())
+    }
+}
+
+/// Returns interface `T` of the running object of class `clsid` registered by RegisterActiveObject.
+///
+/// # Errors
+///
+/// * If there is no such object, returns `MK_E_UNAVAILABLE`.
+/// * If object doesn't support `T`, returns `E_NOINTERFACE`.
+pub fn get_active_object<T: Interface>(clsid: &CLSID) -> ComResult<AutoCOMInterface<T>> {
+    let mut unknown: *mut IUnknown = std::ptr::null_mut();
+    let hresult = unsafe { GetActiveObject(clsid, std::ptr::null_mut(), &mut unknown) };
+
+    if !winerror::SUCCEEDED(hresult) {
+        return Err(hresult.into());
+    }
+
+    let unknown = unsafe { AutoCOMInterface::from_raw(unknown) };
+    if unknown.is_null() {
+        return Err(winerror::E_POINTER.into());
+    }
+
+    unknown.cast::<T>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_apartment::ComApartment;
+    use crate::error::RustyWinapiError;
+    use crate::safe::guid::Guid;
+
+    #[test]
+    fn test_RunningObject_register() {
+        let _com = ComApartment::init_mta().unwrap();
+        let clsid: CLSID = Guid::generate().unwrap().into();
+        assert_eq!(
+            Err(RustyWinapiError::Com(winerror::MK_E_UNAVAILABLE)),
+            get_active_object::<IUnknown>(&clsid).map(|_| ())
+        );
+
+        let table = running_object_table().unwrap();
+        let registration = RunningObject::register(&table, &clsid, ACTIVEOBJECT_STRONG).unwrap();
+        assert!(get_active_object::<IRunningObjectTable>(&clsid).is_ok());
+
+        registration.revoke().unwrap();
+        assert!(get_active_object::<IUnknown>(&clsid).is_err());
+    }
+}
@@ -14,6 +14,7 @@
 //!
 
 use std::convert::TryFrom;
+use std::fmt;
 
 use winapi::shared::minwindef::{BOOL, TRUE, UINT};
 use winapi::shared::ntdef::{NULL, PVOID};
@@ -27,6 +28,19 @@ pub enum SysAllocError {
     SourceStringTooLongError,
 }
 
+impl fmt::Display for SysAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SysAllocError::BStrAllocationError => "BSTR allocation failed",
+            SysAllocError::InvalidPointerError => "invalid BSTR pointer",
+            SysAllocError::NullTerminatedStringRequiredError => "null-terminated string required",
+            SysAllocError::SourceStringTooLongError => "source string is too long for BSTR",
+        })
+    }
+}
+
+impl std::error::Error for SysAllocError {}
+
 /// Allocates a new [BSTR] string and copies the passed UTF-16 null-terminated source string into it.
 ///
 /// If source is a zero-length string, returns a new zero-length [BSTR] string.
@@ -229,6 +243,91 @@ pub fn SysReAllocStringLen(bstr: BSTR, src: &[u16]) -> Result<BSTR, SysAllocErro
     }
 }
 
+/// Allocates a new [BSTR] string and copies the passed Rust string slice into it, encoded as UTF-16
+/// (max up to std::u32::MAX UTF-16 characters), appending a null-terminating character.
+///
+/// The string can contain embedded null characters, they are copied as is.
+///
+/// See also [`SysAllocStringLen`].
+///
+/// # Errors
+///
+/// * If there is insufficient memory to complete the operation, returns [`BStrAllocationError`].
+/// * If source string length is more than std::u32::MAX, returns [`SourceStringTooLongError`].
+///
+/// # Examples
+///
+/// ```
+/// use rusty_winapi::safe::bstr::{SysAllocStringFromStr, SysFreeString, SysStringLen};
+///
+/// let bstr = SysAllocStringFromStr("Test string.").expect("BSTR");
+/// let bstr_slice = unsafe { std::slice::from_raw_parts(bstr, SysStringLen(bstr) as usize) };
+///
+/// assert_eq!("Test string.", String::from_utf16_lossy(bstr_slice));
+/// SysFreeString(bstr);
+/// ```
+///
+/// [BSTR]: https://docs.microsoft.com/en-us/previous-versions/windows/desktop/automat/bstr/
+/// [`BStrAllocationError`]: enum.SysAllocError.html#variant.BStrAllocationError
+/// [`SourceStringTooLongError`]: enum.SysAllocError.html#variant.SourceStringTooLongError
+/// [`SysAllocStringLen`]: fn.SysAllocStringLen.html
+pub fn SysAllocStringFromStr(src: &str) -> Result<BSTR, SysAllocError> {
+    // Encoded straight into the allocated BSTR, without an intermediate buffer.
+    let len: u32 = match TryFrom::try_from(src.encode_utf16().count()) {
+        Ok(x) => x,
+        Err(_) => return Err(SysAllocError::SourceStringTooLongError),
+    };
+
+    unsafe {
+        let bstr = winapi::um::oleauto::SysAllocStringLen(std::ptr::null(), len);
+        if bstr.is_null() {
+            return Err(SysAllocError::BStrAllocationError);
+        }
+        for (i, x) in src.encode_utf16().enumerate() {
+            *bstr.add(i) = x;
+        }
+
+        Ok(bstr)
+    }
+}
+
+/// Reallocates a previously allocated [BSTR] string to be the size of a Rust string slice encoded as UTF-16,
+/// and copies the encoded string into the reallocated memory (max up to std::u32::MAX UTF-16 characters).
+/// Then frees the old BSTR.
+///
+/// The string can contain embedded null characters, they are copied as is.
+///
+/// See also [`SysReAllocStringLen`].
+///
+/// # Errors
+///
+/// * If bstr is NULL, returns [`InvalidPointerError`].
+/// * If source string length is more than std::u32::MAX, returns [`SourceStringTooLongError`].
+/// * If insufficient memory exists, returns [`BStrAllocationError`].
+///
+/// # Examples
+///
+/// ```
+/// use rusty_winapi::safe::bstr::{SysAllocStringFromStr, SysFreeString, SysReAllocStringFromStr, SysStringLen};
+///
+/// let bstr = SysAllocStringFromStr("Test string.").expect("BSTR");
+/// let bstr = SysReAllocStringFromStr(bstr, "New test string.").expect("BSTR");
+/// let bstr_slice = unsafe { std::slice::from_raw_parts(bstr, SysStringLen(bstr) as usize) };
+///
+/// assert_eq!("New test string.", String::from_utf16_lossy(bstr_slice));
+/// SysFreeString(bstr);
+/// ```
+///
+/// [BSTR]: https://docs.microsoft.com/en-us/previous-versions/windows/desktop/automat/bstr/
+/// [`BStrAllocationError`]: enum.SysAllocError.html#variant.BStrAllocationError
+/// [`InvalidPointerError`]: enum.SysAllocError.html#variant.InvalidPointerError
+/// [`SourceStringTooLongError`]: enum.SysAllocError.html#variant.SourceStringTooLongError
+/// [`SysReAllocStringLen`]: fn.SysReAllocStringLen.html
+pub fn SysReAllocStringFromStr(bstr: BSTR, src: &str) -> Result<BSTR, SysAllocError> {
+    let utf16_buf: Vec<u16> = src.encode_utf16().collect();
+    SysReAllocStringLen(bstr, &utf16_buf)
+}
+
 /// Returns the length of a [BSTR].
 ///
 /// The number of characters in bstr, not including the terminating NULL character. If bstr is NULL the return value is zero.
@@ -450,6 +549,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_SysAllocStringFromStr() {
+        // If successful, returns the string, embedded nulls are preserved.
+        let bstr = SysAllocStringFromStr(TEST_LINE).unwrap();
+        assert_eq!(TEST_LINE, bstr2string(bstr));
+        SysFreeString(bstr);
+
+        // If source is a zero-length string, returns a zero-length BSTR.
+        let bstr = SysAllocStringFromStr("").unwrap();
+        assert_eq!("", bstr2string(bstr));
+        SysFreeString(bstr);
+    }
+
+    #[test]
+    fn test_SysReAllocStringFromStr() {
+        // If successful, returns the string.
+        let bstr = SysAllocStringFromStr(TEST_LINE).unwrap();
+        let bstr = SysReAllocStringFromStr(bstr, "New line.").unwrap();
+        assert_eq!("New line.", bstr2string(bstr));
+        SysFreeString(bstr);
+
+        // If bstr is NULL, returns InvalidPointerError.
+        assert_eq!(
+            Err(SysAllocError::InvalidPointerError),
+            SysReAllocStringFromStr(NULL as BSTR, "New line.")
+        );
+    }
+
     #[test]
     fn test_SysStringLen() {
         let test_line_utf16: Vec<u16> = TEST_LINE.encode_utf16().collect();
@@ -0,0 +1,149 @@
+#![allow(non_camel_case_types, non_snake_case)]
+
+//! Safe counterparts of WinAPI functions converting CLSIDs from/to ProgIDs and strings.
+//!
+//! Strings returned by COM are copied into [`String`] and freed with CoTaskMemFree internally.
+//!
+//! [`String`]: https://doc.rust-lang.org/std/string/struct.String.html
+//!
+
+use winapi::shared::guiddef::CLSID;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::shared::wtypesbase::LPOLESTR;
+use winapi::um::combaseapi::CoTaskMemFree;
+
+/// Looks up CLSID of a ProgID in the registry, e.g. of `"Excel.Application"`.
+///
+/// See also [MSDN CLSIDFromProgID] description.
+///
+/// # Errors
+///
+/// * If ProgID isn't registered, returns `CO_E_CLASSSTRING`.
+/// * Otherwise returns HRESULT of CLSIDFromProgID.
+///
+/// [MSDN CLSIDFromProgID]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-clsidfromprogid
+pub fn CLSIDFromProgID(progid: &str) -> Result<CLSID, HRESULT> {
+    let progid = to_wide(progid);
+    let mut clsid: CLSID = unsafe { std::mem::zeroed() };
+    let hresult = unsafe { winapi::um::combaseapi::CLSIDFromProgID(progid.as_ptr(), &mut clsid) };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(clsid)
+    } else {
+        Err(hresult)
+    }
+}
+
+/// Parses CLSID from its string form `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}`, or looks up a ProgID.
+///
+/// See also [MSDN CLSIDFromString] description.
+///
+/// # Errors
+///
+/// * If string is neither a valid CLSID nor a registered ProgID, returns `CO_E_CLASSSTRING`.
+/// * Otherwise returns HRESULT of CLSIDFromString.
+///
+/// # Examples
+///
+/// ```
+///     use rusty_winapi::safe::clsid::{CLSIDFromString, StringFromCLSID};
+///
+///     let clsid = CLSIDFromString("{00020400-0000-0000-C000-000000000046}").unwrap();
+///     assert_eq!("{00020400-0000-0000-C000-000000000046}", StringFromCLSID(&clsid).unwrap());
+/// ```
+///
+/// [MSDN CLSIDFromString]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-clsidfromstring
+pub fn CLSIDFromString(s: &str) -> Result<CLSID, HRESULT> {
+    let s = to_wide(s);
+    let mut clsid: CLSID = unsafe { std::mem::zeroed() };
+    let hresult = unsafe { winapi::um::combaseapi::CLSIDFromString(s.as_ptr(), &mut clsid) };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(clsid)
+    } else {
+        Err(hresult)
+    }
+}
+
+/// Looks up ProgID of a CLSID in the registry.
+///
+/// See also [MSDN ProgIDFromCLSID] description.
+///
+/// # Errors
+///
+/// * If CLSID isn't registered or has no ProgID, returns `REGDB_E_CLASSNOTREG`.
+/// * Otherwise returns HRESULT of ProgIDFromCLSID.
+///
+/// [MSDN ProgIDFromCLSID]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-progidfromclsid
+pub fn ProgIDFromCLSID(clsid: &CLSID) -> Result<String, HRESULT> {
+    let mut progid: LPOLESTR = std::ptr::null_mut();
+    let hresult = unsafe { winapi::um::combaseapi::ProgIDFromCLSID(clsid, &mut progid) };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(take_co_task_string(progid))
+    } else {
+        Err(hresult)
+    }
+}
+
+/// Formats CLSID as `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}`.
+///
+/// See also [MSDN StringFromCLSID] description.
+///
+/// # Errors
+///
+/// If insufficient memory exists, returns `E_OUTOFMEMORY`.
+///
+/// [MSDN StringFromCLSID]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-stringfromclsid
+pub fn StringFromCLSID(clsid: &CLSID) -> Result<String, HRESULT> {
+    let mut s: LPOLESTR = std::ptr::null_mut();
+    let hresult = unsafe { winapi::um::combaseapi::StringFromCLSID(clsid, &mut s) };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(take_co_task_string(s))
+    } else {
+        Err(hresult)
+    }
+}
+
+pub(crate) fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Copies a null-terminated string allocated by COM and frees it.
+pub(crate) fn take_co_task_string(s: LPOLESTR) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+
+    let result = unsafe {
+        let len = (0..).take_while(|&i| *s.offset(i) != 0).count();
+        String::from_utf16_lossy(std::slice::from_raw_parts(s, len))
+    };
+    unsafe { CoTaskMemFree(s as _) };
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::shared::guiddef::IsEqualGUID;
+    use winapi::um::oaidl::IDispatch;
+    use winapi::Interface;
+
+    #[test]
+    fn test_CLSIDFromString() {
+        let clsid = CLSIDFromString("{00020400-0000-0000-C000-000000000046}").unwrap();
+        assert!(IsEqualGUID(&IDispatch::uuidof(), &clsid));
+        assert_eq!(
+            "{00020400-0000-0000-C000-000000000046}",
+            StringFromCLSID(&clsid).unwrap()
+        );
+        assert_eq!(
+            Err(winerror::CO_E_CLASSSTRING),
+            CLSIDFromString("not a clsid").map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,242 @@
+#![allow(non_camel_case_types, non_snake_case)]
+
+//! GUID parsing, formatting and generation.
+//!
+//! [`Guid`] wraps winapi [`GUID`] adding conversion from/to `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}` strings,
+//! comparison and hashing, so GUIDs needn't be assembled field by field.
+//!
+//! # Examples
+//!
+//! ```
+//!     use rusty_winapi::safe::guid::Guid;
+//!
+//!     const IID_IDISPATCH: Guid = Guid::from_u128(0x00020400_0000_0000_C000_000000000046);
+//!
+//!     let iid: Guid = "{00020400-0000-0000-C000-000000000046}".parse().unwrap();
+//!     assert_eq!(IID_IDISPATCH, iid);
+//!     assert_eq!("{00020400-0000-0000-C000-000000000046}", iid.to_string());
+//! ```
+//!
+//! [`Guid`]: struct.Guid.html
+//! [`GUID`]: https://docs.rs/winapi/*/winapi/shared/guiddef/struct.GUID.html
+//!
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use winapi::shared::guiddef::{IsEqualGUID, GUID};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+
+/// Builds a GUID from its four fields, usable in constants.
+#[inline]
+pub const fn guid(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> GUID {
+    GUID {
+        Data1: data1,
+        Data2: data2,
+        Data3: data3,
+        Data4: data4,
+    }
+}
+
+/// Generates a new unique GUID.
+///
+/// See also [MSDN CoCreateGuid] description.
+///
+/// [MSDN CoCreateGuid]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cocreateguid
+pub fn CoCreateGuid() -> Result<GUID, HRESULT> {
+    let mut guid: GUID = unsafe { std::mem::zeroed() };
+    let hresult = unsafe { winapi::um::combaseapi::CoCreateGuid(&mut guid) };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(guid)
+    } else {
+        Err(hresult)
+    }
+}
+
+/// Comparable, hashable and printable [`GUID`].
+///
+/// [`GUID`]: https://docs.rs/winapi/*/winapi/shared/guiddef/struct.GUID.html
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct Guid(GUID);
+
+impl Guid {
+    /// Nil GUID `{00000000-0000-0000-0000-000000000000}`.
+    pub const NIL: Guid = Guid::from_u128(0);
+
+    /// Builds a GUID from its four fields.
+    #[inline]
+    pub const fn new(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        Guid(guid(data1, data2, data3, data4))
+    }
+
+    /// Builds a GUID from a number written the same way as the string form, e.g.
+    /// `0x00020400_0000_0000_C000_000000000046`.
+    pub const fn from_u128(x: u128) -> Self {
+        Guid::new(
+            (x >> 96) as u32,
+            (x >> 80) as u16,
+            (x >> 64) as u16,
+            (x as u64).to_be_bytes(),
+        )
+    }
+
+    /// Number written the same way as the string form.
+    pub const fn to_u128(&self) -> u128 {
+        ((self.0.Data1 as u128) << 96)
+            | ((self.0.Data2 as u128) << 80)
+            | ((self.0.Data3 as u128) << 64)
+            | u64::from_be_bytes(self.0.Data4) as u128
+    }
+
+    /// Generates a new unique GUID via CoCreateGuid.
+    pub fn generate() -> Result<Self, HRESULT> {
+        CoCreateGuid().map(Guid)
+    }
+
+    #[inline]
+    pub fn is_nil(&self) -> bool {
+        self.to_u128() == 0
+    }
+
+    /// Reference to the winapi struct, e.g. to pass as REFIID.
+    #[inline]
+    pub fn as_guid(&self) -> &GUID {
+        &self.0
+    }
+}
+
+impl Default for Guid {
+    fn default() -> Self {
+        Guid::NIL
+    }
+}
+
+impl From<GUID> for Guid {
+    fn from(x: GUID) -> Self {
+        Guid(x)
+    }
+}
+
+impl From<Guid> for GUID {
+    fn from(x: Guid) -> Self {
+        x.0
+    }
+}
+
+impl AsRef<GUID> for Guid {
+    fn as_ref(&self) -> &GUID {
+        &self.0
+    }
+}
+
+impl PartialEq for Guid {
+    fn eq(&self, other: &Guid) -> bool {
+        IsEqualGUID(&self.0, &other.0)
+    }
+}
+
+impl Eq for Guid {}
+
+impl PartialEq<GUID> for Guid {
+    fn eq(&self, other: &GUID) -> bool {
+        IsEqualGUID(&self.0, other)
+    }
+}
+
+impl Hash for Guid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_u128().hash(state);
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let x = &self.0;
+        write!(
+            f,
+            "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+            x.Data1,
+            x.Data2,
+            x.Data3,
+            x.Data4[0],
+            x.Data4[1],
+            x.Data4[2],
+            x.Data4[3],
+            x.Data4[4],
+            x.Data4[5],
+            x.Data4[6],
+            x.Data4[7]
+        )
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+impl FromStr for Guid {
+    type Err = HRESULT;
+
+    /// Parses `XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX`, optionally enclosed in braces, case-insensitive.
+    ///
+    /// Returns `CO_E_CLASSSTRING` if string is malformed, like CLSIDFromString.
+    fn from_str(s: &str) -> Result<Self, HRESULT> {
+        let s = s.trim();
+        let s = if s.starts_with('{') && s.ends_with('}') {
+            &s[1..s.len() - 1]
+        } else {
+            s
+        };
+
+        let groups: Vec<&str> = s.split('-').collect();
+        let lengths = [8, 4, 4, 4, 12];
+        if groups.len() != lengths.len()
+            || groups
+                .iter()
+                .zip(lengths.iter())
+                .any(|(x, n)| x.len() != *n || !x.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            return Err(winerror::CO_E_CLASSSTRING);
+        }
+
+        u128::from_str_radix(&groups.concat(), 16)
+            .map(Guid::from_u128)
+            .map_err(|_| winerror::CO_E_CLASSSTRING)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::um::oaidl::IDispatch;
+    use winapi::Interface;
+
+    #[test]
+    fn test_Guid_parse() {
+        let iid: Guid = "{00020400-0000-0000-c000-000000000046}".parse().unwrap();
+        assert_eq!(Guid::from(IDispatch::uuidof()), iid);
+        assert!(iid == IDispatch::uuidof());
+        assert_eq!(
+            Ok(iid),
+            "00020400-0000-0000-C000-000000000046".parse::<Guid>()
+        );
+        assert_eq!("{00020400-0000-0000-C000-000000000046}", iid.to_string());
+        assert_eq!(0x00020400_0000_0000_C000_000000000046, iid.to_u128());
+
+        assert_eq!(
+            Err(winerror::CO_E_CLASSSTRING),
+            "{00020400-0000-0000-C000-00000000004}".parse::<Guid>()
+        );
+        assert_eq!(
+            Err(winerror::CO_E_CLASSSTRING),
+            "{00020400-0000-0000-C000+000000000046}".parse::<Guid>()
+        );
+        assert!(Guid::default().is_nil());
+    }
+}
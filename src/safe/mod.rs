@@ -2,3 +2,6 @@
 //!
 
 pub mod bstr;
+pub mod clsid;
+pub mod guid;
+pub mod moniker;
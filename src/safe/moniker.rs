@@ -0,0 +1,217 @@
+#![allow(non_camel_case_types, non_snake_case)]
+
+//! Safe counterparts of WinAPI functions for moniker parsing and binding.
+//!
+//! A moniker names an object by a display name, e.g. a file path, `winmgmts:\\.\root\cimv2`,
+//! `Elevation:Administrator!new:{clsid}` or a composite like `C:\Book.xlsx!Sheet1`. [`bind_to_object`] parses a
+//! display name and binds it to the named object in one step, like `GetObject("...")` in VBScript.
+//!
+//! # Examples
+//!
+//! ```no_run
+//!     use rusty_winapi::safe::moniker::bind_to_object;
+//!     use rusty_winapi::smart_idispatch::SmartIDispatch;
+//!     use rusty_winapi::smart_variant::SmartVariant;
+//!     use winapi::um::oaidl::IDispatch;
+//!
+//!     let mut wmi = bind_to_object::<IDispatch>(r"winmgmts:\\.\root\cimv2").expect("WMI");
+//!     let processes = wmi.call("ExecQuery", &[SmartVariant::Text("SELECT * FROM Win32_Process".into())]);
+//! ```
+//!
+//! [`bind_to_object`]: fn.bind_to_object.html
+//!
+
+use winapi::shared::minwindef::LPVOID;
+use winapi::shared::ntdef::{HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::shared::wtypesbase::LPOLESTR;
+use winapi::um::objidl::{IBindCtx, IMoniker, BIND_OPTS};
+use winapi::Interface;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::ffi;
+use crate::safe::clsid::{take_co_task_string, to_wide};
+
+/// Binds a display name to the named object and returns its interface `T`, via CoGetObject.
+///
+/// See also [MSDN CoGetObject] description.
+///
+/// # Errors
+///
+/// * If display name can't be parsed, returns `MK_E_SYNTAX`.
+/// * Otherwise returns HRESULT of binding, e.g. `E_NOINTERFACE` if object doesn't support `T`.
+///
+/// [MSDN CoGetObject]: https://docs.microsoft.com/en-us/windows/win32/api/objbase/nf-objbase-cogetobject
+pub fn bind_to_object<T: Interface>(display_name: &str) -> Result<AutoCOMInterface<T>, HRESULT> {
+    bind_to_object_with::<T>(display_name, None)
+}
+
+/// Binds a display name like [`bind_to_object`] with bind options.
+///
+/// Extended options structures (`BIND_OPTS2`, `BIND_OPTS3`) start with `BIND_OPTS` and are passed by a pointer to it,
+/// `cbStruct` must be set to the size of the full structure.
+///
+/// [`bind_to_object`]: fn.bind_to_object.html
+pub fn bind_to_object_with<T: Interface>(
+    display_name: &str,
+    bind_options: Option<&mut BIND_OPTS>,
+) -> Result<AutoCOMInterface<T>, HRESULT> {
+    let display_name = to_wide(display_name);
+    let bind_options = bind_options.map_or(std::ptr::null_mut(), |x| x as *mut BIND_OPTS);
+    let mut pvoid: LPVOID = std::ptr::null_mut();
+    let hresult = unsafe {
+        ffi::CoGetObject(
+            display_name.as_ptr(),
+            bind_options,
+            &T::uuidof(),
+            &mut pvoid,
+        )
+    };
+
+    wrap(hresult, pvoid as *mut T)
+}
+
+/// Parses a display name into a moniker, via MkParseDisplayName.
+///
+/// # Errors
+///
+/// If display name can't be parsed, returns `MK_E_SYNTAX`.
+pub fn parse_display_name(display_name: &str) -> Result<AutoCOMInterface<IMoniker>, HRESULT> {
+    let context = create_bind_context()?;
+    let display_name = to_wide(display_name);
+    let mut eaten: ULONG = 0;
+    let mut moniker: *mut IMoniker = std::ptr::null_mut();
+    let hresult = unsafe {
+        ffi::MkParseDisplayName(
+            context.as_inner() as *const _ as *mut IBindCtx,
+            display_name.as_ptr(),
+            &mut eaten,
+            &mut moniker,
+        )
+    };
+
+    wrap(hresult, moniker)
+}
+
+/// Creates a file moniker of a path, via CreateFileMoniker.
+pub fn file_moniker(path: &str) -> Result<AutoCOMInterface<IMoniker>, HRESULT> {
+    let path = to_wide(path);
+    let mut moniker: *mut IMoniker = std::ptr::null_mut();
+    let hresult = unsafe { ffi::CreateFileMoniker(path.as_ptr(), &mut moniker) };
+
+    wrap(hresult, moniker)
+}
+
+/// Creates an item moniker, e.g. a sheet of a workbook named by a file moniker, via CreateItemMoniker.
+///
+/// `delimiter` separates the item from the preceding part of a display name, usually `"!"`.
+pub fn item_moniker(delimiter: &str, item: &str) -> Result<AutoCOMInterface<IMoniker>, HRESULT> {
+    let delimiter = to_wide(delimiter);
+    let item = to_wide(item);
+    let mut moniker: *mut IMoniker = std::ptr::null_mut();
+    let hresult =
+        unsafe { ffi::CreateItemMoniker(delimiter.as_ptr(), item.as_ptr(), &mut moniker) };
+
+    wrap(hresult, moniker)
+}
+
+/// Composes two monikers, e.g. a file moniker with an item moniker, via IMoniker::ComposeWith.
+pub fn compose(
+    left: &AutoCOMInterface<IMoniker>,
+    right: &AutoCOMInterface<IMoniker>,
+) -> Result<AutoCOMInterface<IMoniker>, HRESULT> {
+    if right.is_null() {
+        return Err(winerror::E_POINTER);
+    }
+
+    let mut moniker: *mut IMoniker = std::ptr::null_mut();
+    let hresult = unsafe {
+        left.try_as_inner().ok_or(winerror::E_POINTER)?.ComposeWith(
+            right.as_inner() as *const _ as *mut IMoniker,
+            0,
+            &mut moniker,
+        )
+    };
+
+    wrap(hresult, moniker)
+}
+
+/// Binds a moniker to the named object and returns its interface `T`, via BindMoniker.
+pub fn bind_moniker<T: Interface>(
+    moniker: &AutoCOMInterface<IMoniker>,
+) -> Result<AutoCOMInterface<T>, HRESULT> {
+    if moniker.is_null() {
+        return Err(winerror::E_POINTER);
+    }
+
+    let mut pvoid: LPVOID = std::ptr::null_mut();
+    let hresult = unsafe {
+        ffi::BindMoniker(
+            moniker.as_inner() as *const _ as *mut IMoniker,
+            0,
+            &T::uuidof(),
+            &mut pvoid,
+        )
+    };
+
+    wrap(hresult, pvoid as *mut T)
+}
+
+/// Returns display name of a moniker.
+pub fn display_name(moniker: &AutoCOMInterface<IMoniker>) -> Result<String, HRESULT> {
+    let moniker = moniker.try_as_inner().ok_or(winerror::E_POINTER)?;
+    let context = create_bind_context()?;
+    let mut name: LPOLESTR = std::ptr::null_mut();
+    let hresult = unsafe {
+        moniker.GetDisplayName(
+            context.as_inner() as *const _ as *mut IBindCtx,
+            std::ptr::null_mut(),
+            &mut name,
+        )
+    };
+
+    if winerror::SUCCEEDED(hresult) {
+        Ok(take_co_task_string(name))
+    } else {
+        Err(hresult)
+    }
+}
+
+/// Creates a bind context, via CreateBindCtx.
+pub fn create_bind_context() -> Result<AutoCOMInterface<IBindCtx>, HRESULT> {
+    let mut context: *mut IBindCtx = std::ptr::null_mut();
+    let hresult = unsafe { ffi::CreateBindCtx(0, &mut context) };
+
+    wrap(hresult, context)
+}
+
+fn wrap<T: Interface>(hresult: HRESULT, x: *mut T) -> Result<AutoCOMInterface<T>, HRESULT> {
+    if winerror::SUCCEEDED(hresult) {
+        let x = unsafe { AutoCOMInterface::from_raw(x) };
+        if x.is_null() {
+            Err(winerror::E_POINTER)
+        } else {
+            Ok(x)
+        }
+    } else {
+        Err(hresult)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_apartment::ComApartment;
+
+    #[test]
+    fn test_moniker_display_name() {
+        let _com = ComApartment::init_mta().unwrap();
+
+        let file = file_moniker(r"C:\Book.xlsx").unwrap();
+        let item = item_moniker("!", "Sheet1").unwrap();
+        let composite = compose(&file, &item).unwrap();
+        assert_eq!(r"C:\Book.xlsx!Sheet1", display_name(&composite).unwrap());
+
+        assert!(parse_display_name("no such moniker:").is_err());
+    }
+}
@@ -0,0 +1,432 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Round-trip conformance suite of the client stack, behind `selftest` feature.
+//!
+//! Contains a Rust-implemented in-process automation object and a client suite driving it through
+//! [`SmartIDispatch`], so users can validate argument marshaling, property access and error reporting on their
+//! machines without any registered server.
+//!
+//! Sample object members:
+//!
+//! * `Echo(x)` returns a copy of its argument.
+//! * `Sum(a, b, ...)` returns sum of `Int4` arguments, checks order of positional arguments.
+//! * `Value` read/write property.
+//! * `Fail()` fails with `DISP_E_EXCEPTION` and a description in EXCEPINFO.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::selftest;
+//!
+//! for result in selftest::run() {
+//!     println!("{}", result);
+//! }
+//! ```
+//!
+//! [`SmartIDispatch`]: ../smart_idispatch/trait.SmartIDispatch.html
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, REFIID};
+use winapi::shared::minwindef::{UINT, WORD};
+use winapi::shared::ntdef::{HRESULT, LCID, ULONG};
+use winapi::shared::winerror;
+use winapi::shared::wtypes::VT_I4;
+use winapi::shared::wtypesbase::LPOLESTR;
+use winapi::um::oaidl::{
+    IDispatch, IDispatchVtbl, ITypeInfo, DISPID, DISPPARAMS, EXCEPINFO, VARIANT,
+};
+use winapi::um::oleauto::{
+    VariantClear, VariantCopy, DISPATCH_METHOD, DISPATCH_PROPERTYGET, DISPATCH_PROPERTYPUT,
+};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::Interface;
+
+use crate::auto_bstr::AutoBSTR;
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::RustyWinapiError;
+use crate::smart_idispatch::SmartIDispatch;
+use crate::smart_variant::{AutoVariant, SmartVariant};
+
+const DISPID_ECHO: DISPID = 1;
+const DISPID_SUM: DISPID = 2;
+const DISPID_VALUE: DISPID = 3;
+const DISPID_FAIL: DISPID = 4;
+
+const MEMBERS: &[(&str, DISPID)] = &[
+    ("Echo", DISPID_ECHO),
+    ("Sum", DISPID_SUM),
+    ("Value", DISPID_VALUE),
+    ("Fail", DISPID_FAIL),
+];
+
+/// Description of the error raised by `Fail()`.
+pub const FAIL_DESCRIPTION: &str = "selftest failure";
+
+#[repr(C)]
+struct SampleObject {
+    vtbl: *const IDispatchVtbl,
+    refs: AtomicU32,
+    value: RefCell<AutoVariant>,
+}
+
+static SAMPLE_OBJECT_VTBL: IDispatchVtbl = IDispatchVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: sample_query_interface,
+        AddRef: sample_add_ref,
+        Release: sample_release,
+    },
+    GetTypeInfoCount: sample_get_type_info_count,
+    GetTypeInfo: sample_get_type_info,
+    GetIDsOfNames: sample_get_ids_of_names,
+    Invoke: sample_invoke,
+};
+
+/// Creates a new instance of the sample automation object.
+pub fn create_sample_object() -> AutoCOMInterface<IDispatch> {
+    let object = Box::new(SampleObject {
+        vtbl: &SAMPLE_OBJECT_VTBL,
+        refs: AtomicU32::new(1),
+        value: RefCell::new(AutoVariant::new()),
+    });
+
+    unsafe { AutoCOMInterface::from_raw(Box::into_raw(object) as *mut IDispatch) }
+}
+
+unsafe extern "system" fn sample_query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if ppv.is_null() {
+        return winerror::E_POINTER;
+    }
+
+    if IsEqualGUID(&*riid, &IUnknown::uuidof()) || IsEqualGUID(&*riid, &IDispatch::uuidof()) {
+        sample_add_ref(this);
+        *ppv = this as *mut c_void;
+        winerror::S_OK
+    } else {
+        *ppv = std::ptr::null_mut();
+        winerror::E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn sample_add_ref(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const SampleObject);
+    object.refs.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn sample_release(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const SampleObject);
+    let refs = object.refs.fetch_sub(1, Ordering::AcqRel) - 1;
+    if refs == 0 {
+        drop(Box::from_raw(this as *mut SampleObject));
+    }
+
+    refs
+}
+
+unsafe extern "system" fn sample_get_type_info_count(
+    this: *mut IDispatch,
+    pctinfo: *mut UINT,
+) -> HRESULT {
+    *pctinfo = 0;
+    winerror::S_OK
+}
+
+unsafe extern "system" fn sample_get_type_info(
+    this: *mut IDispatch,
+    iTInfo: UINT,
+    lcid: LCID,
+    ppTInfo: *mut *mut ITypeInfo,
+) -> HRESULT {
+    *ppTInfo = std::ptr::null_mut();
+    winerror::DISP_E_BADINDEX
+}
+
+unsafe extern "system" fn sample_get_ids_of_names(
+    this: *mut IDispatch,
+    riid: REFIID,
+    rgszNames: *mut LPOLESTR,
+    cNames: UINT,
+    lcid: LCID,
+    rgDispId: *mut DISPID,
+) -> HRESULT {
+    let names = std::slice::from_raw_parts(rgszNames, cNames as usize);
+    let dispids = std::slice::from_raw_parts_mut(rgDispId, cNames as usize);
+    let mut result = winerror::S_OK;
+
+    for (name, dispid) in names.iter().zip(dispids.iter_mut()) {
+        let len = (0..).take_while(|&i| *name.offset(i) != 0).count();
+        let name = String::from_utf16_lossy(std::slice::from_raw_parts(*name, len));
+        *dispid = match MEMBERS.iter().find(|(x, _)| x.eq_ignore_ascii_case(&name)) {
+            Some((_, x)) => *x,
+            None => {
+                result = winerror::DISP_E_UNKNOWNNAME;
+                -1
+            }
+        };
+    }
+
+    result
+}
+
+unsafe extern "system" fn sample_invoke(
+    this: *mut IDispatch,
+    dispIdMember: DISPID,
+    riid: REFIID,
+    lcid: LCID,
+    wFlags: WORD,
+    pDispParams: *mut DISPPARAMS,
+    pVarResult: *mut VARIANT,
+    pExcepInfo: *mut EXCEPINFO,
+    puArgErr: *mut UINT,
+) -> HRESULT {
+    let object = &*(this as *const SampleObject);
+    let params = &*pDispParams;
+    let args: &[VARIANT] = if params.rgvarg.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(params.rgvarg, params.cArgs as usize)
+    };
+
+    match (dispIdMember, wFlags) {
+        (DISPID_ECHO, DISPATCH_METHOD) => match args {
+            [x] if !pVarResult.is_null() => VariantCopy(pVarResult, x),
+            [_] => winerror::S_OK,
+            _ => winerror::DISP_E_BADPARAMCOUNT,
+        },
+        (DISPID_SUM, DISPATCH_METHOD) => {
+            let mut sum: i32 = 0;
+            // Check in caller's order, puArgErr is an index in reversed rgvarg.
+            for (i, x) in args.iter().enumerate().rev() {
+                if x.n1.n2().vt != VT_I4 as u16 {
+                    if !puArgErr.is_null() {
+                        *puArgErr = i as UINT;
+                    }
+                    return winerror::DISP_E_TYPEMISMATCH;
+                }
+                sum = sum.wrapping_add(*x.n1.n2().n3.lVal());
+            }
+            if !pVarResult.is_null() {
+                if let Ok(result) = AutoVariant::try_from(SmartVariant::Int4(sum)) {
+                    *pVarResult = result.into();
+                }
+            }
+            winerror::S_OK
+        }
+        (DISPID_VALUE, DISPATCH_PROPERTYGET) if args.is_empty() => {
+            if pVarResult.is_null() {
+                winerror::S_OK
+            } else {
+                VariantCopy(pVarResult, object.value.borrow().as_ptr())
+            }
+        }
+        (DISPID_VALUE, DISPATCH_PROPERTYPUT) => match args {
+            [x] => VariantCopy(object.value.borrow_mut().as_mut_ptr(), x),
+            _ => winerror::DISP_E_BADPARAMCOUNT,
+        },
+        (DISPID_FAIL, DISPATCH_METHOD) => {
+            if !pExcepInfo.is_null() {
+                let description = AutoBSTR::try_from(FAIL_DESCRIPTION);
+                (*pExcepInfo).scode = winerror::E_FAIL;
+                if let Ok(x) = description {
+                    (*pExcepInfo).bstrDescription = x.into();
+                }
+            }
+            winerror::DISP_E_EXCEPTION
+        }
+        _ => winerror::DISP_E_MEMBERNOTFOUND,
+    }
+}
+
+/// Outcome of a single conformance check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfTestResult {
+    pub name: String,
+    pub outcome: Result<(), String>,
+}
+
+impl SelfTestResult {
+    fn check(name: String, outcome: Result<(), String>) -> Self {
+        SelfTestResult { name, outcome }
+    }
+
+    /// Returns `true` if the check passed.
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+impl fmt::Display for SelfTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Ok(()) => write!(f, "ok     {}", self.name),
+            Err(x) => write!(f, "FAILED {}: {}", self.name, x),
+        }
+    }
+}
+
+/// Values of every scalar SmartVariant type used by echo checks.
+pub fn sample_values() -> Vec<SmartVariant> {
+    vec![
+        SmartVariant::Empty,
+        SmartVariant::Int1(-8),
+        SmartVariant::UInt1(8),
+        SmartVariant::Int2(-16),
+        SmartVariant::UInt2(16),
+        SmartVariant::Int4(-32),
+        SmartVariant::UInt4(32),
+        SmartVariant::Int8(-64),
+        SmartVariant::UInt8(64),
+        SmartVariant::Int(-1),
+        SmartVariant::UInt(1),
+        SmartVariant::Real4(1.5),
+        SmartVariant::Real8(-2.25),
+        SmartVariant::Date(43831.5),
+        SmartVariant::Bool(true),
+        SmartVariant::Bool(false),
+        SmartVariant::ErrorCode(winerror::E_FAIL),
+        SmartVariant::Text("".into()),
+        SmartVariant::Text("Hello, мир! 🦀".into()),
+        SmartVariant::Text16(vec![0x0041, 0xD800, 0x0042].into()),
+    ]
+}
+
+/// Runs the client suite against a new sample object.
+pub fn run() -> Vec<SelfTestResult> {
+    run_against(&mut create_sample_object())
+}
+
+/// Runs the client suite against an object implementing sample object members, e.g. an out-of-process build of it.
+pub fn run_against<D: SmartIDispatch>(object: &mut D) -> Vec<SelfTestResult> {
+    let mut results = Vec::new();
+
+    for value in sample_values() {
+        let outcome = match object.call("Echo", std::slice::from_ref(&value)) {
+            Ok(x) if x == value => Ok(()),
+            Ok(x) => Err(format!("returned {}", x.summary())),
+            Err(e) => Err(e.to_string()),
+        };
+        results.push(SelfTestResult::check(
+            format!("Echo({})", value.summary()),
+            outcome,
+        ));
+    }
+
+    let outcome = match object.call(
+        "Sum",
+        &[
+            SmartVariant::Int4(1),
+            SmartVariant::Int4(20),
+            SmartVariant::Int4(300),
+        ],
+    ) {
+        Ok(SmartVariant::Int4(321)) => Ok(()),
+        Ok(x) => Err(format!("returned {}", x.summary())),
+        Err(e) => Err(e.to_string()),
+    };
+    results.push(SelfTestResult::check("Sum(1, 20, 300)".into(), outcome));
+
+    let outcome = match object.call(
+        "Sum",
+        &[SmartVariant::Int4(1), SmartVariant::Text("x".into())],
+    ) {
+        Err(e)
+            if matches!(
+                e.root_cause(),
+                RustyWinapiError::TypeMismatch { argument: Some(1) }
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+        Ok(x) => Err(format!("returned {}", x.summary())),
+    };
+    results.push(SelfTestResult::check(
+        "Sum(1, \"x\") type mismatch".into(),
+        outcome,
+    ));
+
+    let outcome = match object.call(
+        "Sum",
+        &[SmartVariant::Text("x".into()), SmartVariant::Int4(1)],
+    ) {
+        Err(e)
+            if matches!(
+                e.root_cause(),
+                RustyWinapiError::TypeMismatch { argument: Some(0) }
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+        Ok(x) => Err(format!("returned {}", x.summary())),
+    };
+    results.push(SelfTestResult::check(
+        "Sum(\"x\", 1) type mismatch".into(),
+        outcome,
+    ));
+
+    let value = SmartVariant::Text("property".into());
+    let outcome = object
+        .put("Value", value.clone())
+        .and_then(|_| object.get("Value"))
+        .map_err(|e| e.to_string())
+        .and_then(|x| {
+            if x == value {
+                Ok(())
+            } else {
+                Err(format!("returned {}", x.summary()))
+            }
+        });
+    results.push(SelfTestResult::check("Value put/get".into(), outcome));
+
+    let outcome = match object.call("Fail", &[]) {
+        Err(e)
+            if matches!(
+                e.root_cause(),
+                RustyWinapiError::Dispatch {
+                    hresult: winerror::DISP_E_EXCEPTION,
+                    info,
+                    ..
+                } if info.description == FAIL_DESCRIPTION
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+        Ok(x) => Err(format!("returned {}", x.summary())),
+    };
+    results.push(SelfTestResult::check("Fail() exception".into(), outcome));
+
+    let outcome = match object.call("Missing", &[]) {
+        Err(e) if e.root_cause() == &RustyWinapiError::UnknownName => Ok(()),
+        Err(e) => Err(e.to_string()),
+        Ok(x) => Err(format!("returned {}", x.summary())),
+    };
+    results.push(SelfTestResult::check(
+        "Missing() unknown name".into(),
+        outcome,
+    ));
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest_run() {
+        for result in run() {
+            assert!(result.passed(), "{}", result);
+        }
+    }
+}
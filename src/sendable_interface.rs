@@ -0,0 +1,112 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Thread-safe handle of a COM object with per-thread proxies.
+//!
+//! [`SendableInterface`] may be stored in `Arc`-shared state and used from any thread: [`get`] returns an interface
+//! pointer valid in the apartment of the calling thread, resolving it from an [`AgileRef`] at first access from
+//! the thread and caching it thread-locally afterwards.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::sendable_interface::SendableInterface;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create().unwrap();
+//! let shared = Arc::new(SendableInterface::new(&excel).unwrap());
+//!
+//! for _ in 0..4 {
+//!     let shared = shared.clone();
+//!     std::thread::spawn(move || {
+//!         let excel = shared.get().unwrap();
+//!     });
+//! }
+//! ```
+//!
+//! [`SendableInterface`]: struct.SendableInterface.html
+//! [`get`]: struct.SendableInterface.html#method.get
+//! [`AgileRef`]: ../agile_ref/struct.AgileRef.html
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+
+use crate::agile_ref::AgileRef;
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+
+/// Proxy cached by a thread, stale once its handle is dropped.
+type CachedProxy = (Weak<()>, AutoCOMInterface<IUnknown>);
+
+thread_local! {
+    /// Proxies resolved by the current thread, by handle key.
+    static PROXIES: RefCell<HashMap<usize, CachedProxy>> = RefCell::new(HashMap::new());
+}
+
+/// `Send + Sync` handle of interface `T`, resolving an apartment-correct proxy per accessing thread.
+///
+/// Clones share thread-local proxies. Proxies cached by other threads are released when those threads access any
+/// handle after this one is dropped, or when they exit.
+pub struct SendableInterface<T: Interface> {
+    agile: Arc<AgileRef<T>>,
+    alive: Arc<()>,
+}
+
+impl<T: Interface> SendableInterface<T> {
+    /// Creates a handle of the interface, see [`AgileRef::new`].
+    ///
+    /// [`AgileRef::new`]: ../agile_ref/struct.AgileRef.html#method.new
+    pub fn new(interface: &AutoCOMInterface<T>) -> ComResult<Self> {
+        Ok(SendableInterface {
+            agile: Arc::new(AgileRef::new(interface)?),
+            alive: Arc::new(()),
+        })
+    }
+
+    /// Returns interface pointer valid in the apartment of the calling thread.
+    pub fn get(&self) -> ComResult<AutoCOMInterface<T>> {
+        let key = Arc::as_ptr(&self.alive) as usize;
+
+        PROXIES.with(|x| {
+            let mut proxies = x.borrow_mut();
+            proxies.retain(|_, (alive, _)| alive.strong_count() > 0);
+
+            if let Some((_, proxy)) = proxies.get(&key) {
+                let proxy = proxy.to_iunknown();
+                return Ok(unsafe { AutoCOMInterface::from_raw(proxy.into_raw() as *mut T) });
+            }
+
+            let proxy = self.agile.resolve()?;
+            proxies.insert(key, (Arc::downgrade(&self.alive), proxy.to_iunknown()));
+            Ok(proxy)
+        })
+    }
+}
+
+impl<T: Interface> Clone for SendableInterface<T> {
+    fn clone(&self) -> Self {
+        SendableInterface {
+            agile: self.agile.clone(),
+            alive: self.alive.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::um::oaidl::IDispatch;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_SendableInterface_is_Send_Sync() {
+        assert_send_sync::<SendableInterface<IDispatch>>();
+        assert!(SendableInterface::new(&AutoCOMInterface::<IDispatch>::default()).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Server side IClassFactory implementation.
+//!
+//! [`ClassFactory`] creates objects with a closure, or with `Default` of a [`DispatchServer`] type, and implements
+//! `LockServer` with the module lock count. It plugs into the in-process server scaffolding with
+//! [`ClassFactory::register`] and into local servers with [`ClassObjectRegistration`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::cell::Cell;
+//!
+//! use rusty_winapi::error::DispatchError;
+//! use rusty_winapi::safe::guid::Guid;
+//! use rusty_winapi::server::class_factory::ClassFactory;
+//!
+//! #[derive(Default)]
+//! struct Counter(Cell<i32>);
+//!
+//! #[rusty_winapi::dispatch_server]
+//! impl Counter {
+//!     #[method("Increment")]
+//!     fn increment(&self) -> i32 {
+//!         self.0.set(self.0.get() + 1);
+//!         self.0.get()
+//!     }
+//! }
+//!
+//! const CLSID_COUNTER: Guid = Guid::from_u128(0x3f2a8c51_7d0e_4b6f_a1c9_5e8d2b7f0a34);
+//!
+//! fn init() {
+//!     ClassFactory::register::<Counter>(CLSID_COUNTER.as_guid());
+//! }
+//!
+//! rusty_winapi::export_dll_server!(init);
+//! ```
+//!
+//! [`ClassFactory`]: struct.ClassFactory.html
+//! [`DispatchServer`]: ../dispatch/trait.DispatchServer.html
+//! [`ClassFactory::register`]: struct.ClassFactory.html#method.register
+//! [`ClassObjectRegistration`]: ../../class_object/struct.ClassObjectRegistration.html
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, CLSID, REFIID};
+use winapi::shared::minwindef::BOOL;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::oaidl::IDispatch;
+use winapi::um::unknwnbase::{IClassFactory, IClassFactoryVtbl, IUnknown};
+use winapi::Interface;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+use crate::server::com_box::{ComBox, ComInterfaceEntry, ComObject};
+use crate::server::dispatch::{DispatchServer, Dispatcher};
+
+/// Constructor of objects of a [`ClassFactory`], called with the controlling IUnknown of the aggregate or NULL.
+///
+/// Aggregated object must be created with [`ComBox::create_aggregated`] (or alike) and its inner IUnknown returned.
+///
+/// [`ClassFactory`]: struct.ClassFactory.html
+/// [`ComBox::create_aggregated`]: ../com_box/struct.ComBox.html#method.create_aggregated
+pub type ObjectConstructor = dyn Fn(*mut IUnknown) -> ComResult<AutoCOMInterface<IUnknown>>;
+
+/// Class factory creating objects with a constructor, see [module level documentation].
+///
+/// [module level documentation]: index.html
+pub struct ClassFactory {
+    constructor: Box<ObjectConstructor>,
+    aggregatable: bool,
+}
+
+impl ClassFactory {
+    const VTBL: IClassFactoryVtbl = IClassFactoryVtbl {
+        parent: ComBox::<Self>::IUNKNOWN_VTBL,
+        CreateInstance: Self::create_instance,
+        LockServer: Self::lock_server,
+    };
+
+    const INTERFACES: &'static [ComInterfaceEntry] = &[ComInterfaceEntry::new(
+        &[IClassFactory::uuidof],
+        &Self::VTBL,
+    )];
+
+    /// Creates factory calling `constructor` for every new object, aggregation is refused with
+    /// `CLASS_E_NOAGGREGATION`.
+    pub fn new<F>(constructor: F) -> Self
+    where
+        F: Fn() -> ComResult<AutoCOMInterface<IUnknown>> + 'static,
+    {
+        ClassFactory {
+            constructor: Box::new(move |_| constructor()),
+            aggregatable: false,
+        }
+    }
+
+    /// Creates factory of objects supporting aggregation, `constructor` is called with the controlling IUnknown of
+    /// the aggregate or NULL, see [`ObjectConstructor`].
+    ///
+    /// [`ObjectConstructor`]: type.ObjectConstructor.html
+    pub fn aggregatable<F>(constructor: F) -> Self
+    where
+        F: Fn(*mut IUnknown) -> ComResult<AutoCOMInterface<IUnknown>> + 'static,
+    {
+        ClassFactory {
+            constructor: Box::new(constructor),
+            aggregatable: true,
+        }
+    }
+
+    /// Creates factory of automation objects initialized with `T::default()`, supports aggregation.
+    pub fn of_dispatch<T: DispatchServer + Default>() -> Self {
+        Self::aggregatable(|outer| {
+            if outer.is_null() {
+                Ok(T::default().into_dispatch().to_iunknown())
+            } else {
+                // Safety: COM requires the controlling IUnknown to outlive the inner object.
+                Ok(unsafe { ComBox::create_aggregated(Dispatcher(T::default()), outer) })
+            }
+        })
+    }
+
+    /// Returns `true` if factory supports aggregation.
+    pub fn is_aggregatable(&self) -> bool {
+        self.aggregatable
+    }
+
+    /// Moves factory into a new COM object and returns its IClassFactory.
+    pub fn into_class_factory(self) -> AutoCOMInterface<IClassFactory> {
+        ComBox::create_interface::<IClassFactory>(self)
+            .expect("ClassFactory implements IClassFactory")
+    }
+
+    /// Registers factory of automation objects `T` as the class object of `clsid` in the in-process server
+    /// scaffolding, see [`register_class`].
+    ///
+    /// [`register_class`]: ../fn.register_class.html
+    pub fn register<T: DispatchServer + Default>(clsid: &CLSID) {
+        crate::server::register_class(clsid, || {
+            Ok(Self::of_dispatch::<T>().into_class_factory().to_iunknown())
+        });
+    }
+
+    unsafe extern "system" fn create_instance(
+        this: *mut IClassFactory,
+        pUnkOuter: *mut IUnknown,
+        riid: REFIID,
+        ppvObject: *mut *mut c_void,
+    ) -> HRESULT {
+        if ppvObject.is_null() {
+            return winerror::E_POINTER;
+        }
+        *ppvObject = std::ptr::null_mut();
+
+        if riid.is_null() {
+            return winerror::E_INVALIDARG;
+        }
+
+        // Aggregated object may only hand out its inner IUnknown to the outer one.
+        let factory = ComBox::<Self>::value_of(this);
+        if !pUnkOuter.is_null()
+            && (!factory.aggregatable || !IsEqualGUID(&*riid, &IUnknown::uuidof()))
+        {
+            return winerror::CLASS_E_NOAGGREGATION;
+        }
+
+        // Panic must not unwind into COM.
+        match catch_unwind(AssertUnwindSafe(|| (factory.constructor)(pUnkOuter))) {
+            Ok(Ok(x)) => match x.try_as_iunknown() {
+                Some(unknown) => unknown.QueryInterface(riid, ppvObject),
+                None => winerror::E_POINTER,
+            },
+            Ok(Err(x)) => x.hresult().0,
+            Err(_) => winerror::E_UNEXPECTED,
+        }
+    }
+
+    unsafe extern "system" fn lock_server(this: *mut IClassFactory, fLock: BOOL) -> HRESULT {
+        if fLock != 0 {
+            crate::server::lock_module();
+        } else {
+            crate::server::unlock_module();
+        }
+
+        winerror::S_OK
+    }
+}
+
+impl ComObject for ClassFactory {
+    fn interfaces() -> &'static [ComInterfaceEntry] {
+        Self::INTERFACES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hresult::HResult;
+    use crate::server::dynamic_object::DynamicObject;
+    use crate::smart_iclassfactory::SmartIClassFactory;
+    use crate::smart_idispatch::SmartIDispatch;
+    use crate::smart_variant::SmartVariant;
+
+    #[test]
+    fn test_ClassFactory_create_instance() {
+        let factory = ClassFactory::new(|| {
+            Ok(DynamicObject::new()
+                .with("Name", SmartVariant::Text("sample".into()))
+                .into_dispatch()
+                .to_iunknown())
+        })
+        .into_class_factory();
+
+        let mut object = factory
+            .create_instance::<IDispatch>(std::ptr::null_mut())
+            .unwrap();
+        assert_eq!(Ok(SmartVariant::Text("sample".into())), object.get("Name"));
+
+        let outer = factory.to_iunknown();
+        assert_eq!(
+            Some(HResult::CLASS_E_NOAGGREGATION),
+            factory
+                .create_instance::<IDispatch>(outer.as_iunknown_ptr())
+                .err()
+                .map(|x| x.hresult())
+        );
+    }
+
+    #[test]
+    fn test_ClassFactory_constructor_panic() {
+        let factory = ClassFactory::new(|| panic!("constructor failed")).into_class_factory();
+        assert_eq!(
+            Some(HResult::E_UNEXPECTED),
+            factory
+                .create_instance::<IDispatch>(std::ptr::null_mut())
+                .err()
+                .map(|x| x.hresult())
+        );
+    }
+
+    #[test]
+    fn test_ClassFactory_aggregation() {
+        let factory = ClassFactory::of_dispatch::<DynamicObject>();
+        assert!(factory.is_aggregatable());
+        let factory = factory.into_class_factory();
+
+        let outer = ClassFactory::new(|| Err(winerror::E_NOTIMPL.into()))
+            .into_class_factory()
+            .to_iunknown();
+        assert_eq!(
+            Some(HResult::CLASS_E_NOAGGREGATION),
+            factory
+                .create_instance::<IDispatch>(outer.as_iunknown_ptr())
+                .err()
+                .map(|x| x.hresult())
+        );
+
+        let outer_object =
+            unsafe { ComBox::<ClassFactory>::from_interface(outer.as_iunknown_ptr()) };
+        assert_eq!(1, outer_object.ref_count());
+        let inner = factory
+            .create_instance::<IUnknown>(outer.as_iunknown_ptr())
+            .unwrap();
+        let inner_object =
+            unsafe { ComBox::<Dispatcher<DynamicObject>>::from_interface(inner.as_iunknown_ptr()) };
+        assert_eq!(1, inner_object.ref_count());
+        assert_eq!(1, outer_object.ref_count());
+
+        let dispatch = inner.cast::<IDispatch>().unwrap();
+        assert_eq!(1, inner_object.ref_count());
+        assert_eq!(2, outer_object.ref_count());
+        assert_eq!(
+            outer.as_iunknown_ptr(),
+            dispatch.cast::<IUnknown>().unwrap().as_iunknown_ptr()
+        );
+
+        drop(dispatch);
+        assert_eq!(1, inner_object.ref_count());
+        assert_eq!(1, outer_object.ref_count());
+    }
+}
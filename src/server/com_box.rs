@@ -0,0 +1,484 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Reference-counted container of COM objects implemented in Rust.
+//!
+//! [`ComBox`] owns a Rust value and an interface pointer (a vtable slot) per interface listed by its
+//! [`ComObject`] implementation. It implements IUnknown generically: AddRef/Release of any slot count references of
+//! the whole object, QueryInterface looks up listed IIDs and the value is dropped with the last reference. Vtables
+//! start with [`ComBox::IUNKNOWN_VTBL`], methods of the rest of the interface get the value with
+//! [`ComBox::value_of`].
+//!
+//! Objects may be aggregated with [`ComBox::create_aggregated`], then IUnknown methods of their interfaces delegate
+//! to the controlling (outer) IUnknown and a separate inner IUnknown controls the lifetime.
+//!
+//! COM calls an object from the apartment it was created in, unless it answers IAgileObject: then any thread may
+//! call it at any time. So IAgileObject is answered only by an entry created with [`ComInterfaceEntry::agile`],
+//! which requires the object to be `Send + Sync`.
+//!
+//! Every object holds a [`ModuleLock`], so in-process servers aren't unloaded while objects are alive.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::server::com_box::{ComBox, ComInterfaceEntry, ComObject};
+//! use winapi::um::objidlbase::IAgileObject;
+//! use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+//! use winapi::Interface;
+//!
+//! struct Counter(u32);
+//!
+//! static COUNTER_VTBL: IUnknownVtbl = ComBox::<Counter>::IUNKNOWN_VTBL;
+//!
+//! impl ComObject for Counter {
+//!     fn interfaces() -> &'static [ComInterfaceEntry] {
+//!         static INTERFACES: [ComInterfaceEntry; 1] =
+//!             [ComInterfaceEntry::agile::<Counter>(&COUNTER_VTBL)];
+//!         &INTERFACES
+//!     }
+//! }
+//!
+//! let object = ComBox::create(Counter(0));
+//! let agile = ComBox::create_interface::<IAgileObject>(Counter(1)).unwrap();
+//! ```
+//!
+//! [`ComBox`]: struct.ComBox.html
+//! [`ComObject`]: trait.ComObject.html
+//! [`ComBox::IUNKNOWN_VTBL`]: struct.ComBox.html#associatedconstant.IUNKNOWN_VTBL
+//! [`ComBox::value_of`]: struct.ComBox.html#method.value_of
+//! [`ComBox::create_aggregated`]: struct.ComBox.html#method.create_aggregated
+//! [`ComInterfaceEntry::agile`]: struct.ComInterfaceEntry.html#method.agile
+//! [`ModuleLock`]: ../struct.ModuleLock.html
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, IID, REFIID};
+use winapi::shared::ntdef::{HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::um::objidlbase::IAgileObject;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::Interface;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+use crate::server::ModuleLock;
+
+/// Interface implemented by a [`ComObject`]: IIDs (as `uuidof` functions of interfaces) answered by QueryInterface
+/// with a pointer to the vtable.
+///
+/// Vtable must start with [`ComBox::IUNKNOWN_VTBL`] of the same object type. Several IIDs may share a vtable, e.g.
+/// IDispatch and a dual interface derived from it.
+///
+/// [`ComObject`]: trait.ComObject.html
+/// [`ComBox::IUNKNOWN_VTBL`]: struct.ComBox.html#associatedconstant.IUNKNOWN_VTBL
+pub struct ComInterfaceEntry {
+    iids: &'static [fn() -> IID],
+    vtbl: *const c_void,
+    agile: bool,
+}
+
+// Vtables are immutable statics.
+unsafe impl Sync for ComInterfaceEntry {}
+
+impl ComInterfaceEntry {
+    pub const fn new<V>(iids: &'static [fn() -> IID], vtbl: &'static V) -> Self {
+        ComInterfaceEntry {
+            iids,
+            vtbl: vtbl as *const V as *const c_void,
+            agile: false,
+        }
+    }
+
+    /// IAgileObject of `T`, with [`ComBox::IUNKNOWN_VTBL`] of `T`. Entries created with [`new`] don't answer it.
+    ///
+    /// [`ComBox::IUNKNOWN_VTBL`]: struct.ComBox.html#associatedconstant.IUNKNOWN_VTBL
+    /// [`new`]: #method.new
+    pub const fn agile<T: ComObject + Send + Sync>(vtbl: &'static IUnknownVtbl) -> Self {
+        ComInterfaceEntry {
+            iids: &[IAgileObject::uuidof],
+            vtbl: vtbl as *const IUnknownVtbl as *const c_void,
+            agile: true,
+        }
+    }
+
+    /// IIDs answered with this interface.
+    pub fn iids(&self) -> impl Iterator<Item = IID> {
+        self.iids.iter().map(|x| x())
+    }
+}
+
+/// Rust type implementing COM interfaces, see [`ComBox`].
+///
+/// [`ComBox`]: struct.ComBox.html
+pub trait ComObject: Sized + 'static {
+    /// Implemented interfaces, IUnknown is answered by the first one which is the identity of the object.
+    fn interfaces() -> &'static [ComInterfaceEntry];
+
+    /// Index in [`interfaces`] of the interface answering `iid` not listed there, e.g. a dispinterface known only
+    /// at runtime. Default implementation answers none.
+    ///
+    /// [`interfaces`]: #tymethod.interfaces
+    fn interface_of(&self, iid: &IID) -> Option<usize> {
+        None
+    }
+}
+
+/// Interface pointer of a [`ComBox`] points to its slot, the first field is what COM sees as an interface.
+///
+/// [`ComBox`]: struct.ComBox.html
+#[repr(C)]
+struct Slot {
+    vtbl: *const c_void,
+    owner: *const c_void,
+}
+
+/// Heap allocated COM object with a Rust value, see [module level documentation].
+///
+/// [module level documentation]: index.html
+pub struct ComBox<T: ComObject> {
+    slots: Box<[Slot]>,
+    /// Non-delegating IUnknown of an aggregated object.
+    inner: Slot,
+    /// Controlling IUnknown of an aggregated object (not referenced), otherwise NULL.
+    outer: *mut IUnknown,
+    refs: AtomicU32,
+    _lock: ModuleLock,
+    value: T,
+}
+
+impl<T: ComObject> ComBox<T> {
+    /// IUnknown part of every vtable of `T`, delegates to the controlling IUnknown of an aggregated object.
+    pub const IUNKNOWN_VTBL: IUnknownVtbl = IUnknownVtbl {
+        QueryInterface: Self::query_interface,
+        AddRef: Self::add_ref,
+        Release: Self::release,
+    };
+
+    const INNER_VTBL: &'static IUnknownVtbl = &IUnknownVtbl {
+        QueryInterface: Self::inner_query_interface,
+        AddRef: Self::inner_add_ref,
+        Release: Self::inner_release,
+    };
+
+    /// Moves `value` into a new COM object and returns its identity IUnknown.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` implements no interfaces.
+    pub fn create(value: T) -> AutoCOMInterface<IUnknown> {
+        let object = Self::allocate(value, std::ptr::null_mut());
+        unsafe { AutoCOMInterface::from_raw(&(*object).slots[0] as *const Slot as *mut IUnknown) }
+    }
+
+    /// Moves `value` into a new COM object aggregated by `outer` and returns its inner (non-delegating) IUnknown.
+    ///
+    /// IUnknown methods of the object's interfaces delegate to `outer`, which controls the lifetime and the identity
+    /// of the aggregate. Inner object doesn't hold a reference to `outer`, so `outer` must keep the returned IUnknown
+    /// while alive and release it on destruction. It's how a class factory handles non-NULL `pUnkOuter` (for which
+    /// only IUnknown may be requested).
+    ///
+    /// # Safety
+    ///
+    /// `outer` must be a valid IUnknown outliving the returned inner IUnknown and all interfaces obtained from it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `outer` is NULL or `T` implements no interfaces.
+    pub unsafe fn create_aggregated(value: T, outer: *mut IUnknown) -> AutoCOMInterface<IUnknown> {
+        assert!(!outer.is_null(), "Controlling IUnknown must not be NULL!");
+
+        let object = Self::allocate(value, outer);
+        AutoCOMInterface::from_raw(&(*object).inner as *const Slot as *mut IUnknown)
+    }
+
+    fn allocate(value: T, outer: *mut IUnknown) -> *mut Self {
+        let interfaces = T::interfaces();
+        assert!(
+            !interfaces.is_empty(),
+            "COM object must implement at least one interface!"
+        );
+
+        let object = Box::into_raw(Box::new(ComBox {
+            slots: Box::new([]),
+            inner: Slot {
+                vtbl: Self::INNER_VTBL as *const IUnknownVtbl as *const c_void,
+                owner: std::ptr::null(),
+            },
+            outer,
+            refs: AtomicU32::new(1),
+            _lock: ModuleLock::new(),
+            value,
+        }));
+
+        unsafe {
+            (*object).inner.owner = object as *const c_void;
+            (*object).slots = interfaces
+                .iter()
+                .map(|x| Slot {
+                    vtbl: x.vtbl,
+                    owner: object as *const c_void,
+                })
+                .collect();
+        }
+
+        object
+    }
+
+    /// Moves `value` into a new COM object and returns its interface `I`.
+    ///
+    /// # Errors
+    ///
+    /// If `T` doesn't implement `I`, returns `E_NOINTERFACE`, the object is dropped.
+    pub fn create_interface<I: Interface>(value: T) -> ComResult<AutoCOMInterface<I>> {
+        Self::create(value).cast::<I>()
+    }
+
+    /// Returns object by any of its interface pointers.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be a live interface pointer of a `ComBox<T>` of the same `T`.
+    pub unsafe fn from_interface<'a, I>(this: *mut I) -> &'a Self {
+        &*((*(this as *const Slot)).owner as *const Self)
+    }
+
+    /// Returns value of object by any of its interface pointers, for use in vtable methods.
+    ///
+    /// # Safety
+    ///
+    /// See [`from_interface`].
+    ///
+    /// [`from_interface`]: #method.from_interface
+    pub unsafe fn value_of<'a, I>(this: *mut I) -> &'a T {
+        &Self::from_interface(this).value
+    }
+
+    /// Value of object.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Current reference count (of the inner object, if aggregated), for diagnostics only.
+    pub fn ref_count(&self) -> ULONG {
+        self.refs.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if object is aggregated.
+    pub fn is_aggregated(&self) -> bool {
+        !self.outer.is_null()
+    }
+
+    /// Returns a new reference to interface `iid` of the object, or `None` if it isn't implemented.
+    ///
+    /// It's the non-delegating QueryInterface: IUnknown of an aggregated object is its inner IUnknown.
+    pub fn get_interface(&self, iid: &IID) -> Option<*mut IUnknown> {
+        let index = if IsEqualGUID(iid, &IUnknown::uuidof()) {
+            if self.is_aggregated() {
+                self.refs.fetch_add(1, Ordering::Relaxed);
+                return Some(&self.inner as *const Slot as *mut IUnknown);
+            }
+            Some(0)
+        } else if IsEqualGUID(iid, &IAgileObject::uuidof()) {
+            T::interfaces().iter().position(|x| x.agile)
+        } else {
+            T::interfaces()
+                .iter()
+                .position(|x| x.iids().any(|y| IsEqualGUID(iid, &y)))
+                .or_else(|| self.value.interface_of(iid))
+                .filter(|&x| x < T::interfaces().len())
+        }?;
+
+        // Interfaces of an aggregated object delegate AddRef/Release to the controlling IUnknown, so does the
+        // reference returned here.
+        if self.is_aggregated() {
+            unsafe { (*self.outer).AddRef() };
+        } else {
+            self.refs.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(&self.slots[index] as *const Slot as *mut IUnknown)
+    }
+
+    unsafe extern "system" fn query_interface(
+        this: *mut IUnknown,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT {
+        let outer = Self::from_interface(this).outer;
+        if outer.is_null() {
+            Self::inner_query_interface(this, riid, ppv)
+        } else {
+            (*outer).QueryInterface(riid, ppv)
+        }
+    }
+
+    unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+        let outer = Self::from_interface(this).outer;
+        if outer.is_null() {
+            Self::inner_add_ref(this)
+        } else {
+            (*outer).AddRef()
+        }
+    }
+
+    unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+        let outer = Self::from_interface(this).outer;
+        if outer.is_null() {
+            Self::inner_release(this)
+        } else {
+            (*outer).Release()
+        }
+    }
+
+    unsafe extern "system" fn inner_query_interface(
+        this: *mut IUnknown,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT {
+        if ppv.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        match Self::from_interface(this).get_interface(&*riid) {
+            Some(x) => {
+                *ppv = x as *mut c_void;
+                winerror::S_OK
+            }
+            None => {
+                *ppv = std::ptr::null_mut();
+                winerror::E_NOINTERFACE
+            }
+        }
+    }
+
+    unsafe extern "system" fn inner_add_ref(this: *mut IUnknown) -> ULONG {
+        Self::from_interface(this)
+            .refs
+            .fetch_add(1, Ordering::Relaxed)
+            + 1
+    }
+
+    unsafe extern "system" fn inner_release(this: *mut IUnknown) -> ULONG {
+        let object = Self::from_interface(this);
+        let refs = object.refs.fetch_sub(1, Ordering::AcqRel) - 1;
+        if refs == 0 {
+            drop(Box::from_raw(object as *const Self as *mut Self));
+        }
+
+        refs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hresult::HResult;
+    use crate::smart_iunknown::SmartIUnknown;
+    use std::cell::Cell;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use winapi::um::oaidl::IDispatch;
+    use winapi::um::objidlbase::IAgileObject;
+
+    struct Sample(Arc<AtomicBool>);
+
+    impl Drop for Sample {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    static SAMPLE_VTBL: IUnknownVtbl = ComBox::<Sample>::IUNKNOWN_VTBL;
+    static SAMPLE_INTERFACES: [ComInterfaceEntry; 2] = [
+        ComInterfaceEntry::new(&[], &SAMPLE_VTBL),
+        ComInterfaceEntry::agile::<Sample>(&SAMPLE_VTBL),
+    ];
+
+    impl ComObject for Sample {
+        fn interfaces() -> &'static [ComInterfaceEntry] {
+            &SAMPLE_INTERFACES
+        }
+    }
+
+    struct Apartment(Cell<u32>);
+
+    static APARTMENT_VTBL: IUnknownVtbl = ComBox::<Apartment>::IUNKNOWN_VTBL;
+    static APARTMENT_INTERFACES: [ComInterfaceEntry; 1] = [ComInterfaceEntry::new(
+        &[IAgileObject::uuidof],
+        &APARTMENT_VTBL,
+    )];
+
+    impl ComObject for Apartment {
+        fn interfaces() -> &'static [ComInterfaceEntry] {
+            &APARTMENT_INTERFACES
+        }
+    }
+
+    #[test]
+    fn test_ComBox_create() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let unknown = ComBox::create(Sample(dropped.clone()));
+        let agile = unknown.cast::<IAgileObject>().unwrap();
+        assert_ne!(unknown.as_iunknown_ptr(), agile.as_iunknown_ptr());
+        assert_eq!(
+            unknown.as_iunknown_ptr(),
+            agile.cast::<IUnknown>().unwrap().as_iunknown_ptr()
+        );
+        assert_eq!(
+            Some(HResult::E_NOINTERFACE),
+            unknown.cast::<IDispatch>().err().map(|x| x.hresult())
+        );
+        assert_eq!(
+            2,
+            unsafe { ComBox::<Sample>::from_interface(agile.as_iunknown_ptr()) }.ref_count()
+        );
+
+        drop(unknown);
+        assert!(!dropped.load(Ordering::Acquire));
+        drop(agile);
+        assert!(dropped.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_ComBox_agile() {
+        let unknown = ComBox::create(Apartment(Cell::new(0)));
+        assert_eq!(
+            Some(HResult::E_NOINTERFACE),
+            unknown.cast::<IAgileObject>().err().map(|x| x.hresult())
+        );
+    }
+
+    #[test]
+    fn test_ComBox_create_aggregated() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let outer = ComBox::create(Sample(Arc::new(AtomicBool::new(false))));
+        let inner =
+            unsafe { ComBox::create_aggregated(Sample(dropped.clone()), outer.as_iunknown_ptr()) };
+        let object = unsafe { ComBox::<Sample>::from_interface(inner.as_iunknown_ptr()) };
+        let outer_object = unsafe { ComBox::<Sample>::from_interface(outer.as_iunknown_ptr()) };
+        assert!(object.is_aggregated());
+        assert_eq!(1, object.ref_count());
+        assert_eq!(1, outer_object.ref_count());
+
+        // Inner IUnknown is non-delegating, interfaces of the inner object delegate to the outer identity.
+        let agile = inner.cast::<IAgileObject>().unwrap();
+        assert_eq!(1, object.ref_count());
+        assert_eq!(2, outer_object.ref_count());
+        assert_eq!(
+            outer.as_iunknown_ptr(),
+            agile.cast::<IUnknown>().unwrap().as_iunknown_ptr()
+        );
+        assert_eq!(2, outer_object.ref_count());
+        assert_eq!(
+            inner.as_iunknown_ptr(),
+            inner.cast::<IUnknown>().unwrap().as_iunknown_ptr()
+        );
+        assert_eq!(1, object.ref_count());
+
+        drop(agile);
+        assert_eq!(1, object.ref_count());
+        assert_eq!(1, outer_object.ref_count());
+        drop(inner);
+        assert!(dropped.load(Ordering::Acquire));
+        assert_eq!(1, outer_object.ref_count());
+    }
+}
@@ -0,0 +1,908 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Automation objects implemented in Rust.
+//!
+//! A type implementing [`DispatchServer`] resolves member names to DISPIDs and handles calls with [`SmartVariant`]
+//! arguments, [`DispatchServer::into_dispatch`] exposes it as an IDispatch object (in a [`ComBox`]) which scripting
+//! clients like VBScript, VBA or 1C can call. DISPPARAMS are converted into arguments in caller's order and errors
+//! into HRESULTs, `puArgErr` and EXCEPINFO. Objects implement IDispatchEx as well, so servers may support members
+//! added by clients and enumeration of members.
+//!
+//! [`#[dispatch_server]`](../../attr.dispatch_server.html) implements [`DispatchServer`] from the methods of an
+//! impl block marked `#[method]`, `#[get]` or `#[put]`, optionally with a member name which defaults to the Rust
+//! name in PascalCase, e.g. `#[get("Value")]`. Members get DISPIDs from 1 in order of declaration, property get
+//! and put of the same name share DISPID. Arguments are converted from [`SmartVariant`] with `TryFrom`
+//! (`DISP_E_TYPEMISMATCH` with the argument index on failure), return value with [`IntoDispatchResult`], methods
+//! may return `Result<_, DispatchError>`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::cell::Cell;
+//! use std::convert::TryFrom;
+//!
+//! use rusty_winapi::error::DispatchError;
+//! use rusty_winapi::server::dispatch::DispatchServer;
+//! use rusty_winapi::smart_variant::SmartVariant;
+//! use winapi::um::oaidl::DISPID;
+//! use winapi::shared::minwindef::WORD;
+//! use winapi::shared::winerror::DISP_E_MEMBERNOTFOUND;
+//!
+//! struct Counter(Cell<i32>);
+//!
+//! impl DispatchServer for Counter {
+//!     fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+//!         names.iter().map(|x| if x.eq_ignore_ascii_case("Add") { Some(1) } else { None }).collect()
+//!     }
+//!
+//!     fn invoke(&self, dispid: DISPID, flags: WORD, args: Vec<SmartVariant>) -> Result<SmartVariant, DispatchError> {
+//!         match (dispid, args.as_slice()) {
+//!             (1, [x]) => {
+//!                 self.0.set(self.0.get() + i32::try_from(x.clone()).map_err(|_| DispatchError::type_mismatch(0))?);
+//!                 Ok(SmartVariant::Int4(self.0.get()))
+//!             }
+//!             _ => Err(DISP_E_MEMBERNOTFOUND.into()),
+//!         }
+//!     }
+//! }
+//!
+//! let counter = Counter(Cell::new(0)).into_dispatch();
+//! ```
+//!
+//! The same server with annotated methods:
+//!
+//! ```no_run
+//! use std::cell::Cell;
+//!
+//! use rusty_winapi::error::DispatchError;
+//! use rusty_winapi::server::dispatch::DispatchServer;
+//!
+//! struct Counter(Cell<i32>);
+//!
+//! #[rusty_winapi::dispatch_server]
+//! impl Counter {
+//!     #[method]
+//!     fn add(&self, x: i32) -> i32 {
+//!         self.0.set(self.0.get() + x);
+//!         self.0.get()
+//!     }
+//!
+//!     #[get("Value")]
+//!     fn value(&self) -> i32 {
+//!         self.0.get()
+//!     }
+//!
+//!     #[put("Value")]
+//!     fn set_value(&self, x: i32) -> Result<(), DispatchError> {
+//!         if x < 0 {
+//!             return Err(DispatchError::exception("negative value"));
+//!         }
+//!         self.0.set(x);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let counter = Counter(Cell::new(0)).into_dispatch();
+//! ```
+//!
+//! [`DispatchServer`]: trait.DispatchServer.html
+//! [`DispatchServer::into_dispatch`]: trait.DispatchServer.html#method.into_dispatch
+//! [`SmartVariant`]: ../../smart_variant/enum.SmartVariant.html
+//! [`ComBox`]: ../com_box/struct.ComBox.html
+//! [`IntoDispatchResult`]: trait.IntoDispatchResult.html
+
+use std::convert::TryFrom;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use winapi::shared::guiddef::{IID, IID_NULL, REFIID};
+use winapi::shared::minwindef::{DWORD, UINT, WORD};
+use winapi::shared::ntdef::{HRESULT, LCID};
+use winapi::shared::winerror;
+use winapi::shared::wtypes::BSTR;
+use winapi::shared::wtypes::{
+    VARENUM, VT_ARRAY, VT_BOOL, VT_BSTR, VT_DATE, VT_DISPATCH, VT_EMPTY, VT_ERROR, VT_I1, VT_I2,
+    VT_I4, VT_I8, VT_INT, VT_R4, VT_R8, VT_UI1, VT_UI2, VT_UI4, VT_UI8, VT_UINT, VT_UNKNOWN,
+};
+use winapi::shared::wtypesbase::LPOLESTR;
+use winapi::um::dispex::{fdexNameEnsure, IDispatchEx, IDispatchExVtbl};
+use winapi::um::oaidl::{
+    IDispatch, IDispatchVtbl, ITypeInfo, DISPID, DISPID_PROPERTYPUT, DISPID_UNKNOWN, DISPPARAMS,
+    EXCEPINFO, VARIANT,
+};
+use winapi::um::oleauto::{
+    SysStringLen, VariantCopyInd, DISPATCH_METHOD, DISPATCH_PROPERTYPUT, DISPATCH_PROPERTYPUTREF,
+};
+use winapi::um::servprov::IServiceProvider;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+
+use crate::auto_bstr::AutoBSTR;
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::DispatchError;
+use crate::error_info::set_error_info;
+use crate::server::com_box::{ComBox, ComInterfaceEntry, ComObject};
+use crate::smart_variant::{AutoVariant, SmartVariant, SmartVariantRef};
+
+/// Automation object implemented in Rust, see [module level documentation].
+///
+/// Object is shared by all its clients, so methods take `&self`, use `Cell`/`RefCell` for mutable state. It's called
+/// from the apartment it was created in, objects of classes registered as `Free` or `Both` must be `Send + Sync` and
+/// keep state in atomics or a `Mutex` instead, see [`ClassRegistration::thread_safe`].
+///
+/// [module level documentation]: index.html
+/// [`ClassRegistration::thread_safe`]: ../registration/struct.ClassRegistration.html#method.thread_safe
+pub trait DispatchServer: 'static {
+    /// Returns DISPIDs of a member name (first) and names of its named arguments, `None` for unknown names.
+    ///
+    /// Names should be matched case-insensitively, as automation clients do.
+    fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>>;
+
+    /// Calls member `dispid` with arguments in caller's order, `flags` are `DISPATCH_*` values.
+    ///
+    /// Value of property put is the last argument.
+    fn invoke(
+        &self,
+        dispid: DISPID,
+        flags: WORD,
+        args: Vec<SmartVariant>,
+    ) -> Result<SmartVariant, DispatchError>;
+
+    /// Returns DISPID of a member creating it if it doesn't exist, for IDispatchEx::GetDispID with
+    /// `fdexNameEnsure`. Default implementation doesn't support new members.
+    fn ensure_member(&self, name: &str) -> Option<DISPID> {
+        None
+    }
+
+    /// Returns name of a member, for IDispatchEx::GetMemberName.
+    fn member_name(&self, dispid: DISPID) -> Option<String> {
+        None
+    }
+
+    /// Returns DISPID of the member after `dispid` (after `DISPID_STARTENUM` for the first one), for enumeration of
+    /// members with IDispatchEx::GetNextDispID, e.g. by `for ... in` of JScript.
+    fn next_member(&self, dispid: DISPID) -> Option<DISPID> {
+        None
+    }
+
+    /// Deletes a member, returns `false` if it can't be deleted.
+    fn delete_member(&self, dispid: DISPID) -> bool {
+        false
+    }
+
+    /// Returns `true` if the object implements dispinterface `iid` besides IDispatch, QueryInterface answers it
+    /// with IDispatch, e.g. the source interface of an event sink. Default implementation implements none.
+    fn implements(&self, iid: &IID) -> bool {
+        false
+    }
+
+    /// Moves object into a new COM object and returns its IDispatch.
+    fn into_dispatch(self) -> AutoCOMInterface<IDispatch>
+    where
+        Self: Sized,
+    {
+        ComBox::create_interface::<IDispatch>(Dispatcher(self))
+            .expect("Dispatcher implements IDispatch")
+    }
+}
+
+/// Adapter of a [`DispatchServer`] to IDispatch and IDispatchEx.
+///
+/// [`DispatchServer`]: trait.DispatchServer.html
+pub struct Dispatcher<T: DispatchServer>(pub T);
+
+impl<T: DispatchServer> Dispatcher<T> {
+    const VTBL: IDispatchExVtbl = IDispatchExVtbl {
+        parent: IDispatchVtbl {
+            parent: ComBox::<Self>::IUNKNOWN_VTBL,
+            GetTypeInfoCount: Self::get_type_info_count,
+            GetTypeInfo: Self::get_type_info,
+            GetIDsOfNames: Self::get_ids_of_names,
+            Invoke: Self::invoke,
+        },
+        GetDispID: Self::get_disp_id,
+        InvokeEx: Self::invoke_ex,
+        DeleteMemberByName: Self::delete_member_by_name,
+        DeleteMemberByDispID: Self::delete_member_by_disp_id,
+        GetMemberProperties: Self::get_member_properties,
+        GetMemberName: Self::get_member_name,
+        GetNextDispID: Self::get_next_disp_id,
+        GetNameSpaceParent: Self::get_name_space_parent,
+    };
+
+    const INTERFACES: &'static [ComInterfaceEntry] = &[ComInterfaceEntry::new(
+        &[IDispatch::uuidof, IDispatchEx::uuidof],
+        &Self::VTBL,
+    )];
+
+    unsafe extern "system" fn get_type_info_count(
+        this: *mut IDispatch,
+        pctinfo: *mut UINT,
+    ) -> HRESULT {
+        if pctinfo.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        *pctinfo = 0;
+        winerror::S_OK
+    }
+
+    unsafe extern "system" fn get_type_info(
+        this: *mut IDispatch,
+        iTInfo: UINT,
+        lcid: LCID,
+        ppTInfo: *mut *mut ITypeInfo,
+    ) -> HRESULT {
+        if ppTInfo.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        *ppTInfo = std::ptr::null_mut();
+        winerror::DISP_E_BADINDEX
+    }
+
+    unsafe extern "system" fn get_ids_of_names(
+        this: *mut IDispatch,
+        riid: REFIID,
+        rgszNames: *mut LPOLESTR,
+        cNames: UINT,
+        lcid: LCID,
+        rgDispId: *mut DISPID,
+    ) -> HRESULT {
+        if rgszNames.is_null() || rgDispId.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        let server = &ComBox::<Self>::value_of(this).0;
+        let names: Vec<String> = std::slice::from_raw_parts(rgszNames, cNames as usize)
+            .iter()
+            .map(|&x| {
+                let len = (0..).take_while(|&i| *x.offset(i) != 0).count();
+                String::from_utf16_lossy(std::slice::from_raw_parts(x, len))
+            })
+            .collect();
+        let dispids = std::slice::from_raw_parts_mut(rgDispId, cNames as usize);
+
+        let ids = match catch_unwind(AssertUnwindSafe(|| server.get_ids_of_names(&names))) {
+            Ok(x) => x,
+            Err(_) => return winerror::E_UNEXPECTED,
+        };
+
+        let mut result = winerror::S_OK;
+        for (i, dispid) in dispids.iter_mut().enumerate() {
+            *dispid = match ids.get(i).copied().flatten() {
+                Some(x) => x,
+                None => {
+                    result = winerror::DISP_E_UNKNOWNNAME;
+                    -1 // DISPID_UNKNOWN
+                }
+            };
+        }
+
+        result
+    }
+
+    unsafe extern "system" fn invoke(
+        this: *mut IDispatch,
+        dispIdMember: DISPID,
+        riid: REFIID,
+        lcid: LCID,
+        wFlags: WORD,
+        pDispParams: *mut DISPPARAMS,
+        pVarResult: *mut VARIANT,
+        pExcepInfo: *mut EXCEPINFO,
+        puArgErr: *mut UINT,
+    ) -> HRESULT {
+        if pDispParams.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        let server = &ComBox::<Self>::value_of(this).0;
+        let params = &*pDispParams;
+        let rgvarg: &[VARIANT] = if params.rgvarg.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(params.rgvarg, params.cArgs as usize)
+        };
+
+        // The only named argument supported is the value of property put.
+        let is_put = wFlags & (DISPATCH_PROPERTYPUT | DISPATCH_PROPERTYPUTREF) != 0;
+        let named: &[DISPID] = if params.rgdispidNamedArgs.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(params.rgdispidNamedArgs, params.cNamedArgs as usize)
+        };
+        if !(named.is_empty() || is_put && named == [DISPID_PROPERTYPUT]) {
+            return winerror::DISP_E_NONAMEDARGS;
+        }
+
+        let arg_err = |index: usize| {
+            if !puArgErr.is_null() {
+                *puArgErr = (rgvarg.len() - 1 - index) as UINT;
+            }
+        };
+
+        // rgvarg is in reversed order.
+        let mut args = Vec::with_capacity(rgvarg.len());
+        for (index, x) in rgvarg.iter().rev().enumerate() {
+            match arg_to_smart_variant(x) {
+                Ok(x) => args.push(x),
+                Err(hresult) => {
+                    arg_err(index);
+                    return hresult;
+                }
+            }
+        }
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            server.invoke(dispIdMember, wFlags, args)
+        }))
+        .unwrap_or_else(|x| {
+            let message = x
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| x.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic in automation object".to_string());
+            Err(DispatchError::Exception {
+                scode: winerror::E_UNEXPECTED,
+                source: String::new(),
+                description: message,
+            })
+        });
+
+        match result {
+            Ok(x) if pVarResult.is_null() => winerror::S_OK,
+            // An array of the result is handed over to the client.
+            Ok(x) => match AutoVariant::try_raw_from_ref(SmartVariantRef::Value(&x)) {
+                Ok(x) => {
+                    *pVarResult = x;
+                    winerror::S_OK
+                }
+                Err(_) => winerror::E_OUTOFMEMORY,
+            },
+            Err(e) => {
+                match &e {
+                    DispatchError::Argument { index, .. } if *index < rgvarg.len() => {
+                        arg_err(*index)
+                    }
+                    DispatchError::Exception {
+                        scode,
+                        source,
+                        description,
+                    } if !pExcepInfo.is_null() => {
+                        fill_excep_info(&mut *pExcepInfo, *scode, source, description)
+                    }
+                    // Caller has no EXCEPINFO, description still reaches it through the error object.
+                    DispatchError::Exception {
+                        scode,
+                        source,
+                        description,
+                    } => {
+                        set_error_info(source, description, None, *scode);
+                    }
+                    _ => {}
+                }
+                e.hresult().into()
+            }
+        }
+    }
+}
+
+impl<T: DispatchServer> Dispatcher<T> {
+    unsafe extern "system" fn get_disp_id(
+        this: *mut IDispatchEx,
+        bstrName: BSTR,
+        grfdex: DWORD,
+        pid: *mut DISPID,
+    ) -> HRESULT {
+        if pid.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        let server = &ComBox::<Self>::value_of(this).0;
+        let name = bstr_to_string(bstrName);
+        let dispid = catch_unwind(AssertUnwindSafe(|| {
+            if grfdex & fdexNameEnsure != 0 {
+                server.ensure_member(&name)
+            } else {
+                server.get_ids_of_names(&[name]).first().copied().flatten()
+            }
+        }));
+
+        match dispid {
+            Ok(Some(x)) => {
+                *pid = x;
+                winerror::S_OK
+            }
+            Ok(None) => {
+                *pid = DISPID_UNKNOWN;
+                winerror::DISP_E_UNKNOWNNAME
+            }
+            Err(_) => winerror::E_UNEXPECTED,
+        }
+    }
+
+    unsafe extern "system" fn invoke_ex(
+        this: *mut IDispatchEx,
+        id: DISPID,
+        lcid: LCID,
+        wFlags: WORD,
+        pdp: *mut DISPPARAMS,
+        pvarRes: *mut VARIANT,
+        pei: *mut EXCEPINFO,
+        pspCaller: *mut IServiceProvider,
+    ) -> HRESULT {
+        Self::invoke(
+            this as *mut IDispatch,
+            id,
+            &IID_NULL,
+            lcid,
+            wFlags,
+            pdp,
+            pvarRes,
+            pei,
+            std::ptr::null_mut(),
+        )
+    }
+
+    unsafe extern "system" fn delete_member_by_name(
+        this: *mut IDispatchEx,
+        bstrName: BSTR,
+        grfdex: DWORD,
+    ) -> HRESULT {
+        let mut dispid: DISPID = DISPID_UNKNOWN;
+        match Self::get_disp_id(this, bstrName, grfdex & !fdexNameEnsure, &mut dispid) {
+            winerror::S_OK => Self::delete_member_by_disp_id(this, dispid),
+            _ => winerror::S_FALSE,
+        }
+    }
+
+    unsafe extern "system" fn delete_member_by_disp_id(
+        this: *mut IDispatchEx,
+        id: DISPID,
+    ) -> HRESULT {
+        let server = &ComBox::<Self>::value_of(this).0;
+        match catch_unwind(AssertUnwindSafe(|| server.delete_member(id))) {
+            Ok(true) => winerror::S_OK,
+            Ok(false) => winerror::S_FALSE,
+            Err(_) => winerror::E_UNEXPECTED,
+        }
+    }
+
+    unsafe extern "system" fn get_member_properties(
+        this: *mut IDispatchEx,
+        id: DISPID,
+        grfdexFetch: DWORD,
+        pgrfdex: *mut DWORD,
+    ) -> HRESULT {
+        if !pgrfdex.is_null() {
+            *pgrfdex = 0;
+        }
+        winerror::E_NOTIMPL
+    }
+
+    unsafe extern "system" fn get_member_name(
+        this: *mut IDispatchEx,
+        id: DISPID,
+        pbstrName: *mut BSTR,
+    ) -> HRESULT {
+        if pbstrName.is_null() {
+            return winerror::E_POINTER;
+        }
+        *pbstrName = std::ptr::null_mut();
+
+        let server = &ComBox::<Self>::value_of(this).0;
+        match catch_unwind(AssertUnwindSafe(|| server.member_name(id))) {
+            Ok(Some(x)) => match AutoBSTR::try_from(x.as_str()) {
+                Ok(x) => {
+                    *pbstrName = x.into();
+                    winerror::S_OK
+                }
+                Err(_) => winerror::E_OUTOFMEMORY,
+            },
+            Ok(None) => winerror::DISP_E_UNKNOWNNAME,
+            Err(_) => winerror::E_UNEXPECTED,
+        }
+    }
+
+    unsafe extern "system" fn get_next_disp_id(
+        this: *mut IDispatchEx,
+        grfdex: DWORD,
+        id: DISPID,
+        pid: *mut DISPID,
+    ) -> HRESULT {
+        if pid.is_null() {
+            return winerror::E_POINTER;
+        }
+
+        let server = &ComBox::<Self>::value_of(this).0;
+        match catch_unwind(AssertUnwindSafe(|| server.next_member(id))) {
+            Ok(Some(x)) => {
+                *pid = x;
+                winerror::S_OK
+            }
+            Ok(None) => {
+                *pid = DISPID_UNKNOWN;
+                winerror::S_FALSE
+            }
+            Err(_) => winerror::E_UNEXPECTED,
+        }
+    }
+
+    unsafe extern "system" fn get_name_space_parent(
+        this: *mut IDispatchEx,
+        ppunk: *mut *mut IUnknown,
+    ) -> HRESULT {
+        if !ppunk.is_null() {
+            *ppunk = std::ptr::null_mut();
+        }
+        winerror::E_NOTIMPL
+    }
+}
+
+impl<T: DispatchServer> ComObject for Dispatcher<T> {
+    fn interfaces() -> &'static [ComInterfaceEntry] {
+        Self::INTERFACES
+    }
+
+    fn interface_of(&self, iid: &IID) -> Option<usize> {
+        match catch_unwind(AssertUnwindSafe(|| self.0.implements(iid))) {
+            Ok(true) => Some(0),
+            _ => None,
+        }
+    }
+}
+
+/// Copies an argument dereferencing VT_BYREF, fails with `DISP_E_TYPEMISMATCH` if there is no matching
+/// `SmartVariant`.
+unsafe fn arg_to_smart_variant(x: &VARIANT) -> Result<SmartVariant, HRESULT> {
+    let mut copy = AutoVariant::new();
+    let hresult = VariantCopyInd(copy.as_mut_ptr(), x);
+    if !winerror::SUCCEEDED(hresult) {
+        return Err(hresult);
+    }
+
+    let vt = copy.vtype();
+    if vt & VT_ARRAY != 0 {
+        let array = *copy.data().parray();
+        *copy.vtype_mut() = VT_EMPTY as u16;
+        return Ok(SmartVariant::Array(array));
+    }
+
+    match vt {
+        VT_EMPTY | VT_I2 | VT_I4 | VT_R4 | VT_R8 | VT_DATE | VT_BSTR | VT_DISPATCH | VT_ERROR
+        | VT_BOOL | VT_UNKNOWN | VT_I1 | VT_UI1 | VT_UI2 | VT_UI4 | VT_I8 | VT_UI8 | VT_INT
+        | VT_UINT => SmartVariant::try_from(copy).map_err(|_| winerror::DISP_E_TYPEMISMATCH),
+        _ => Err(winerror::DISP_E_TYPEMISMATCH),
+    }
+}
+
+/// Copies a BSTR owned by the caller.
+unsafe fn bstr_to_string(x: BSTR) -> String {
+    if x.is_null() {
+        String::new()
+    } else {
+        String::from_utf16_lossy(std::slice::from_raw_parts(x, SysStringLen(x) as usize))
+    }
+}
+
+unsafe fn fill_excep_info(info: &mut EXCEPINFO, scode: HRESULT, source: &str, description: &str) {
+    info.scode = scode;
+    if let Ok(x) = AutoBSTR::try_from(source) {
+        info.bstrSource = x.into();
+    }
+    if let Ok(x) = AutoBSTR::try_from(description) {
+        info.bstrDescription = x.into();
+    }
+}
+
+/// Return value of a member declared with [`#[dispatch_server]`](../../attr.dispatch_server.html), converted into
+/// the result of Invoke.
+pub trait IntoDispatchResult {
+    fn into_dispatch_result(self) -> Result<SmartVariant, DispatchError>;
+}
+
+macro_rules! impl_into_dispatch_result {
+    ($($source:ty => $variant:ident),+) => {
+        $(
+            impl IntoDispatchResult for $source {
+                #[inline]
+                fn into_dispatch_result(self) -> Result<SmartVariant, DispatchError> {
+                    Ok(SmartVariant::$variant(self.into()))
+                }
+            }
+        )+
+    };
+}
+
+impl_into_dispatch_result!(
+    i8 => Int1, u8 => UInt1, i16 => Int2, u16 => UInt2, i32 => Int4, u32 => UInt4, i64 => Int8, u64 => UInt8,
+    f32 => Real4, f64 => Real8, bool => Bool, String => Text, &str => Text
+);
+
+impl IntoDispatchResult for () {
+    #[inline]
+    fn into_dispatch_result(self) -> Result<SmartVariant, DispatchError> {
+        Ok(SmartVariant::Empty)
+    }
+}
+
+impl IntoDispatchResult for SmartVariant {
+    #[inline]
+    fn into_dispatch_result(self) -> Result<SmartVariant, DispatchError> {
+        Ok(self)
+    }
+}
+
+impl<T: IntoDispatchResult> IntoDispatchResult for Result<T, DispatchError> {
+    #[inline]
+    fn into_dispatch_result(self) -> Result<SmartVariant, DispatchError> {
+        self.and_then(T::into_dispatch_result)
+    }
+}
+
+/// DISPID assigned by [`#[dispatch_server]`](../../attr.dispatch_server.html) to member `name`: 1-based index of
+/// the name among distinct (ignoring case) names of `members`.
+pub fn member_dispid(members: &[&str], name: &str) -> Option<DISPID> {
+    let mut dispid: DISPID = 0;
+    for (i, x) in members.iter().enumerate() {
+        if !members[..i].iter().any(|y| y.eq_ignore_ascii_case(x)) {
+            dispid += 1;
+            if x.eq_ignore_ascii_case(name) {
+                return Some(dispid);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{MemberContext, RustyWinapiError};
+    use crate::hresult::HResult;
+    use crate::smart_idispatch::SmartIDispatch;
+    use std::cell::{Cell, RefCell};
+    use winapi::um::oleauto::DISPATCH_PROPERTYGET;
+
+    struct Counter(Cell<i32>);
+
+    impl DispatchServer for Counter {
+        fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+            names
+                .iter()
+                .map(|x| match x.to_ascii_lowercase().as_str() {
+                    "add" => Some(1),
+                    "fail" => Some(2),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn invoke(
+            &self,
+            dispid: DISPID,
+            flags: WORD,
+            args: Vec<SmartVariant>,
+        ) -> Result<SmartVariant, DispatchError> {
+            match (dispid, args.as_slice()) {
+                (1, [x]) => {
+                    let x =
+                        i32::try_from(x.clone()).map_err(|_| DispatchError::type_mismatch(0))?;
+                    self.0.set(self.0.get() + x);
+                    Ok(SmartVariant::Int4(self.0.get()))
+                }
+                (2, _) => Err(DispatchError::exception("failed")),
+                _ => Err(winerror::DISP_E_MEMBERNOTFOUND.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_DispatchServer_into_dispatch() {
+        let mut counter = Counter(Cell::new(1)).into_dispatch();
+        assert_eq!(
+            Ok(SmartVariant::Int4(3)),
+            counter.call("Add", &[SmartVariant::Int4(2)])
+        );
+        assert_eq!(
+            Err(RustyWinapiError::TypeMismatch { argument: Some(0) }),
+            counter
+                .call("Add", &[SmartVariant::Text("x".into())])
+                .map_err(|e| e.root_cause().clone())
+        );
+        match counter
+            .call("Fail", &[])
+            .map_err(|e| e.root_cause().clone())
+        {
+            Err(RustyWinapiError::Dispatch {
+                hresult: winerror::DISP_E_EXCEPTION,
+                info,
+                ..
+            }) => assert_eq!("failed", info.description),
+            x => panic!("DISP_E_EXCEPTION expected, got {:?}", x),
+        }
+        let e = counter.call("Nothing", &[]).unwrap_err();
+        assert_eq!(&RustyWinapiError::UnknownName, e.root_cause());
+        assert_eq!(
+            Some(&MemberContext {
+                interface: None,
+                member: "Nothing".to_string(),
+                dispid: None,
+                ..Default::default()
+            }),
+            e.member()
+        );
+        assert_eq!(
+            &RustyWinapiError::ParamNotFound { argument: Some(1) },
+            counter
+                .call_named(
+                    "Add",
+                    &[SmartVariant::Int4(1)],
+                    &[("Amount", SmartVariant::Int4(2))]
+                )
+                .unwrap_err()
+                .root_cause()
+        );
+        assert_eq!(
+            &RustyWinapiError::UnknownName,
+            counter
+                .call_named("Nothing", &[], &[])
+                .unwrap_err()
+                .root_cause()
+        );
+        assert_eq!(
+            Some(2),
+            counter
+                .call("Fail", &[])
+                .unwrap_err()
+                .member()
+                .and_then(|x| x.dispid)
+        );
+    }
+
+    struct Items(RefCell<Vec<i32>>);
+
+    impl DispatchServer for Items {
+        fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+            names
+                .iter()
+                .map(|x| {
+                    if x.eq_ignore_ascii_case("Item") {
+                        Some(0)
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        }
+
+        fn invoke(
+            &self,
+            dispid: DISPID,
+            flags: WORD,
+            args: Vec<SmartVariant>,
+        ) -> Result<SmartVariant, DispatchError> {
+            let index = |x: &SmartVariant| {
+                usize::try_from(
+                    i32::try_from(x.clone()).map_err(|_| DispatchError::type_mismatch(0))?,
+                )
+                .ok()
+                .filter(|x| *x < self.0.borrow().len())
+                .ok_or(DispatchError::from(winerror::DISP_E_BADINDEX))
+            };
+            match (dispid, flags, args.as_slice()) {
+                (0, flags, [i]) if flags & DISPATCH_PROPERTYGET != 0 => {
+                    Ok(SmartVariant::Int4(self.0.borrow()[index(i)?]))
+                }
+                (0, DISPATCH_PROPERTYPUT, [i, x]) => {
+                    let x =
+                        i32::try_from(x.clone()).map_err(|_| DispatchError::type_mismatch(1))?;
+                    self.0.borrow_mut()[index(i)?] = x;
+                    Ok(SmartVariant::Empty)
+                }
+                _ => Err(winerror::DISP_E_MEMBERNOTFOUND.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_indexed_property() {
+        let mut items = Items(RefCell::new(vec![1, 2, 3])).into_dispatch();
+        assert_eq!(
+            Ok(SmartVariant::Int4(2)),
+            items.get_indexed("Item", &[SmartVariant::Int4(1)])
+        );
+        assert!(items
+            .put_indexed("Item", &[SmartVariant::Int4(1)], SmartVariant::Int4(20))
+            .is_ok());
+        assert_eq!(
+            Ok(SmartVariant::Int4(20)),
+            items.get_indexed("Item", &[SmartVariant::Int4(1)])
+        );
+        assert_eq!(
+            Ok(SmartVariant::Int4(20)),
+            items.call_or_get("Item", &[SmartVariant::Int4(1)])
+        );
+        assert_eq!(
+            Some(HResult::DISP_E_BADINDEX),
+            items
+                .get_indexed("Item", &[SmartVariant::Int4(5)])
+                .err()
+                .map(|x| x.hresult())
+        );
+    }
+
+    #[test]
+    fn test_com_call() {
+        let mut items = Items(RefCell::new(vec![1, 2, 3])).into_dispatch();
+        assert!(crate::com_call!(items.Item(2) = 30).is_ok());
+        assert_eq!(Ok(SmartVariant::Int4(30)), crate::com_call!(items.Item(2)));
+        assert_eq!(
+            Some(HResult::DISP_E_UNKNOWNNAME),
+            crate::com_call!(items.Missing.Item(0))
+                .err()
+                .map(|x| x.hresult())
+        );
+    }
+
+    struct Accumulator(Cell<i32>);
+
+    #[crate::dispatch_server]
+    impl Accumulator {
+        #[method("Add")]
+        fn add(&self, x: i32, y: i32) -> i32 {
+            self.0.set(self.0.get() + x + y);
+            self.0.get()
+        }
+
+        #[get("Value")]
+        fn value(&self) -> i32 {
+            self.0.get()
+        }
+
+        #[put("Value")]
+        fn set_value(&self, x: i32) -> Result<(), DispatchError> {
+            if x < 0 {
+                return Err(DispatchError::exception("negative value"));
+            }
+            self.0.set(x);
+            Ok(())
+        }
+
+        #[method]
+        fn reset(&self) {
+            self.0.set(0);
+        }
+    }
+
+    #[test]
+    fn test_dispatch_server() {
+        assert_eq!(Some(1), member_dispid(&["Add", "Value", "value"], "add"));
+        assert_eq!(Some(2), member_dispid(&["Add", "Value", "value"], "VALUE"));
+        assert_eq!(None, member_dispid(&["Add"], "Value"));
+
+        let mut accumulator = Accumulator(Cell::new(0)).into_dispatch();
+        assert_eq!(
+            Ok(SmartVariant::Int4(3)),
+            accumulator.call("Add", &[SmartVariant::Int4(1), SmartVariant::Int4(2)])
+        );
+        assert_eq!(
+            Err(RustyWinapiError::BadParamCount),
+            accumulator
+                .call("Add", &[])
+                .map_err(|e| e.root_cause().clone())
+        );
+        assert!(accumulator.put("Value", SmartVariant::Int4(10)).is_ok());
+        assert_eq!(Ok(SmartVariant::Int4(10)), accumulator.get("Value"));
+        assert_eq!(Ok(SmartVariant::Empty), accumulator.call("Reset", &[]));
+        assert_eq!(Ok(SmartVariant::Int4(0)), accumulator.get("Value"));
+        assert_eq!(
+            Some(HResult::DISP_E_EXCEPTION),
+            accumulator
+                .put("Value", SmartVariant::Int4(-1))
+                .err()
+                .map(|x| x.hresult())
+        );
+    }
+}
@@ -0,0 +1,312 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Expando automation object with members stored in a map.
+//!
+//! [`DynamicObject`] is a property bag which can be handed to scripting hosts and automation callbacks without
+//! declaring an interface. Members are values or closures (methods), added from Rust or by clients: assignment of
+//! an unknown property through IDispatchEx (JScript `obj.x = 1`) creates it. Clones share members, so a clone kept
+//! on the Rust side sees changes made by clients.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::server::dispatch::DispatchServer;
+//! use rusty_winapi::server::dynamic_object::DynamicObject;
+//! use rusty_winapi::smart_variant::SmartVariant;
+//!
+//! let options = DynamicObject::new()
+//!     .with("Title", SmartVariant::Text("Report".into()))
+//!     .with_method("Log", |args| {
+//!         println!("{:?}", args);
+//!         Ok(SmartVariant::Empty)
+//!     });
+//! let dispatch = options.clone().into_dispatch();
+//! // ... pass `dispatch` to a script ...
+//! let title = options.get("title");
+//! ```
+//!
+//! [`DynamicObject`]: struct.DynamicObject.html
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use winapi::shared::minwindef::WORD;
+use winapi::shared::winerror;
+use winapi::um::dispex::DISPID_STARTENUM;
+use winapi::um::oaidl::DISPID;
+use winapi::um::oleauto::{
+    DISPATCH_METHOD, DISPATCH_PROPERTYGET, DISPATCH_PROPERTYPUT, DISPATCH_PROPERTYPUTREF,
+};
+
+use crate::error::DispatchError;
+use crate::server::dispatch::DispatchServer;
+use crate::smart_variant::SmartVariant;
+
+/// Method of a [`DynamicObject`], called with arguments in caller's order.
+///
+/// [`DynamicObject`]: struct.DynamicObject.html
+pub type DynamicMethod = dyn Fn(Vec<SmartVariant>) -> Result<SmartVariant, DispatchError>;
+
+/// Member of a [`DynamicObject`].
+///
+/// [`DynamicObject`]: struct.DynamicObject.html
+#[derive(Clone)]
+pub enum Member {
+    Value(SmartVariant),
+    Method(Rc<DynamicMethod>),
+}
+
+impl fmt::Debug for Member {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Member::Value(x) => f.debug_tuple("Value").field(x).finish(),
+            Member::Method(_) => f.write_str("Method(..)"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Members {
+    /// Member of DISPID `i + 1`, deleted members leave `None` so DISPIDs are stable.
+    slots: Vec<Option<(String, Member)>>,
+    /// Lowercase name to DISPID.
+    dispids: HashMap<String, DISPID>,
+}
+
+/// Expando automation object, see [module level documentation].
+///
+/// [module level documentation]: index.html
+#[derive(Clone, Default)]
+pub struct DynamicObject(Rc<RefCell<Members>>);
+
+impl DynamicObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value member, builder style.
+    pub fn with(self, name: &str, value: SmartVariant) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Adds a method, builder style.
+    pub fn with_method<F>(self, name: &str, f: F) -> Self
+    where
+        F: Fn(Vec<SmartVariant>) -> Result<SmartVariant, DispatchError> + 'static,
+    {
+        self.set_method(name, f);
+        self
+    }
+
+    /// Sets value of a member (names are case-insensitive), adds it if it doesn't exist.
+    pub fn set(&self, name: &str, value: SmartVariant) {
+        self.set_member(name, Member::Value(value));
+    }
+
+    /// Sets a method, replacing a member of the same name.
+    pub fn set_method<F>(&self, name: &str, f: F)
+    where
+        F: Fn(Vec<SmartVariant>) -> Result<SmartVariant, DispatchError> + 'static,
+    {
+        self.set_member(name, Member::Method(Rc::new(f)));
+    }
+
+    /// Returns value of a member, `None` if there is no such member or it's a method.
+    pub fn get(&self, name: &str) -> Option<SmartVariant> {
+        match self.member(name)? {
+            Member::Value(x) => Some(x),
+            Member::Method(_) => None,
+        }
+    }
+
+    /// Returns a member.
+    pub fn member(&self, name: &str) -> Option<Member> {
+        let members = self.0.borrow();
+        let dispid = *members.dispids.get(&name.to_lowercase())?;
+        members.slot(dispid).map(|(_, x)| x.clone())
+    }
+
+    /// Removes a member, returns `false` if there is no such member.
+    pub fn remove(&self, name: &str) -> bool {
+        let dispid = self.0.borrow().dispids.get(&name.to_lowercase()).copied();
+        dispid.is_some_and(|x| self.delete_member(x))
+    }
+
+    /// Names of members in order of addition.
+    pub fn names(&self) -> Vec<String> {
+        let members = self.0.borrow();
+        members
+            .slots
+            .iter()
+            .flatten()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn set_member(&self, name: &str, member: Member) -> DISPID {
+        let mut members = self.0.borrow_mut();
+        let key = name.to_lowercase();
+        match members.dispids.get(&key).copied() {
+            Some(dispid) => {
+                if let Some(x) = members.slots.get_mut(dispid as usize - 1) {
+                    *x = Some((name.to_string(), member));
+                }
+                dispid
+            }
+            None => {
+                members.slots.push(Some((name.to_string(), member)));
+                let dispid = members.slots.len() as DISPID;
+                members.dispids.insert(key, dispid);
+                dispid
+            }
+        }
+    }
+}
+
+impl Members {
+    fn slot(&self, dispid: DISPID) -> Option<&(String, Member)> {
+        if dispid < 1 {
+            return None;
+        }
+
+        self.slots.get(dispid as usize - 1)?.as_ref()
+    }
+}
+
+impl fmt::Debug for DynamicObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let members = self.0.borrow();
+        f.debug_map()
+            .entries(members.slots.iter().flatten().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+impl DispatchServer for DynamicObject {
+    fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+        let members = self.0.borrow();
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, x)| match i {
+                0 => members.dispids.get(&x.to_lowercase()).copied(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn invoke(
+        &self,
+        dispid: DISPID,
+        flags: WORD,
+        mut args: Vec<SmartVariant>,
+    ) -> Result<SmartVariant, DispatchError> {
+        let (name, member) = self
+            .0
+            .borrow()
+            .slot(dispid)
+            .cloned()
+            .ok_or(DispatchError::Failed(winerror::DISP_E_MEMBERNOTFOUND))?;
+
+        if flags & (DISPATCH_PROPERTYPUT | DISPATCH_PROPERTYPUTREF) != 0 {
+            return match args.pop() {
+                Some(x) if args.is_empty() => {
+                    self.set(&name, x);
+                    Ok(SmartVariant::Empty)
+                }
+                _ => Err(DispatchError::Failed(winerror::DISP_E_BADPARAMCOUNT)),
+            };
+        }
+
+        // Method is called (dropping the borrow first, it may be reentrant) by DISPATCH_METHOD, value is read by
+        // DISPATCH_PROPERTYGET or by parameterless method call like `obj.Value()` of VBScript.
+        match member {
+            Member::Method(f) if flags & DISPATCH_METHOD != 0 => f(args),
+            Member::Value(x) if flags & DISPATCH_PROPERTYGET != 0 || args.is_empty() => Ok(x),
+            Member::Value(_) => Err(DispatchError::Failed(winerror::DISP_E_BADPARAMCOUNT)),
+            Member::Method(_) => Err(DispatchError::Failed(winerror::DISP_E_MEMBERNOTFOUND)),
+        }
+    }
+
+    fn ensure_member(&self, name: &str) -> Option<DISPID> {
+        if let Some(x) = self.0.borrow().dispids.get(&name.to_lowercase()) {
+            return Some(*x);
+        }
+
+        Some(self.set_member(name, Member::Value(SmartVariant::Empty)))
+    }
+
+    fn member_name(&self, dispid: DISPID) -> Option<String> {
+        self.0.borrow().slot(dispid).map(|(name, _)| name.clone())
+    }
+
+    fn next_member(&self, dispid: DISPID) -> Option<DISPID> {
+        let members = self.0.borrow();
+        let start = if dispid == DISPID_STARTENUM {
+            0
+        } else {
+            dispid.max(0) as usize
+        };
+
+        members
+            .slots
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, x)| x.is_some())
+            .map(|(i, _)| i as DISPID + 1)
+    }
+
+    fn delete_member(&self, dispid: DISPID) -> bool {
+        let mut members = self.0.borrow_mut();
+        let name = match members.slot(dispid) {
+            Some((name, _)) => name.to_lowercase(),
+            None => return false,
+        };
+
+        members.dispids.remove(&name);
+        members.slots[dispid as usize - 1] = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smart_idispatch::SmartIDispatch;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_DynamicObject_members() {
+        let object = DynamicObject::new()
+            .with("Title", SmartVariant::Text("Report".into()))
+            .with_method("Twice", |args| match args.as_slice() {
+                [x] => Ok(SmartVariant::Int4(
+                    2 * i32::try_from(x.clone()).map_err(|_| DispatchError::type_mismatch(0))?,
+                )),
+                _ => Err(DispatchError::Failed(winerror::DISP_E_BADPARAMCOUNT)),
+            });
+
+        let mut dispatch = object.clone().into_dispatch();
+        assert_eq!(
+            Ok(SmartVariant::Text("Report".into())),
+            dispatch.get("title")
+        );
+        assert_eq!(
+            Ok(SmartVariant::Int4(42)),
+            dispatch.call("Twice", &[SmartVariant::Int4(21)])
+        );
+        assert!(dispatch.put("Title", SmartVariant::Int4(1)).is_ok());
+        assert_eq!(Some(SmartVariant::Int4(1)), object.get("TITLE"));
+
+        assert_eq!(Some(1), object.next_member(DISPID_STARTENUM));
+        assert_eq!(Some(2), object.next_member(1));
+        assert!(object.remove("Title"));
+        assert_eq!(Some(2), object.next_member(DISPID_STARTENUM));
+        assert_eq!(Some(3), object.ensure_member("New"));
+        assert_eq!(vec!["Twice".to_string(), "New".to_string()], object.names());
+    }
+}
@@ -0,0 +1,393 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Sinks of dispinterface events (outgoing interfaces of Excel, 1C, browsers) routed to Rust closures.
+//!
+//! [`EventSink::builder`] registers handlers by DISPID or by event name, names are resolved by the type info of
+//! the source interface, given explicitly or found by IProvideClassInfo of the source object (its default source
+//! interface). The sink is an IDispatch answering QueryInterface for the source interface, arguments of an event
+//! come to its handler as `Vec<SmartVariant>` in declaration order. Events without a handler are ignored.
+//!
+//! Methods of a Rust type become typed handlers with [`#[event_sink]`](../../attr.event_sink.html), see
+//! [`EventHandlers`].
+//!
+//! Handlers take `&self` semantics of [`DispatchServer`]: an event raised from a handler must not need a mutable
+//! borrow held by the handler, use `Cell`/`RefCell` for state.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::prelude::*;
+//! use rusty_winapi::server::event_sink::EventSink;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let mut excel = Activate::<IDispatch>::new().progid("Excel.Application").create()?;
+//! let workbook: AutoCOMInterface<IDispatch> = excel.get_path("Workbooks.Add")?.try_into()?;
+//! let subscription = EventSink::builder()
+//!     .source(&workbook)
+//!     .on("SheetActivate", |args| println!("activated {:?}", args))
+//!     .advise(&workbook)?;
+//! # Ok::<(), RustyWinapiError>(())
+//! ```
+//!
+//! [`EventSink::builder`]: struct.EventSink.html#method.builder
+//! [`DispatchServer`]: ../dispatch/trait.DispatchServer.html
+//! [`EventHandlers`]: trait.EventHandlers.html
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use winapi::shared::guiddef::{IsEqualGUID, IID};
+use winapi::shared::minwindef::{UINT, WORD};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::oaidl::{
+    IDispatch, ITypeInfo, DISPID, IMPLTYPEFLAG_FDEFAULT, IMPLTYPEFLAG_FSOURCE, TKIND_COCLASS,
+};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::{Interface, RIDL};
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::{ComResult, DispatchError, RustyWinapiError};
+use crate::server::dispatch::DispatchServer;
+use crate::smart_iconnectionpoint::{
+    AdviseGuard, IConnectionPointContainer, SmartIConnectionPointContainer, CONNECT_E_NOCONNECTION,
+};
+use crate::smart_idispatch::OutParam;
+use crate::smart_itypeinfo::SmartITypeInfo;
+use crate::smart_iunknown::SmartIUnknown;
+use crate::smart_variant::SmartVariant;
+
+RIDL! {#[uuid(0xb196b283, 0xbab4, 0x101a, 0xb6, 0x9c, 0x00, 0xaa, 0x00, 0x34, 0x1d, 0x07)]
+interface IProvideClassInfo(IProvideClassInfoVtbl): IUnknown(IUnknownVtbl) {
+    fn GetClassInfo(
+        ppTI: *mut *mut ITypeInfo,
+    ) -> HRESULT,
+}}
+
+crate::impl_interface_vtbl!(IProvideClassInfo => IProvideClassInfoVtbl);
+
+/// Handler of an event, called with arguments in declaration order.
+pub type EventHandler = dyn Fn(Vec<SmartVariant>);
+
+/// Typed event handlers registered on an [`EventSinkBuilder`] at once, implemented by
+/// [`#[event_sink]`](../../attr.event_sink.html) for methods of an impl block.
+///
+/// Methods marked `#[event]` (an event of the method name in PascalCase), `#[event("Name")]` or
+/// `#[event(dispid = ...)]` take `&self` and parameters converted from event arguments like out-parameters
+/// ([`OutParam`]): `SmartVariant` as is, an interface or a [`VariantType`] coerced. Event which arguments don't
+/// convert isn't handled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::cell::Cell;
+///
+/// use rusty_winapi::event_sink;
+/// use rusty_winapi::prelude::*;
+/// use rusty_winapi::server::event_sink::EventSink;
+/// use winapi::um::oaidl::IDispatch;
+///
+/// #[derive(Default)]
+/// struct WorkbookEvents {
+///     changes: Cell<u32>,
+/// }
+///
+/// #[event_sink]
+/// impl WorkbookEvents {
+///     #[event]
+///     fn sheet_change(&self, sheet: AutoCOMInterface<IDispatch>, range: AutoCOMInterface<IDispatch>) {
+///         self.changes.set(self.changes.get() + 1);
+///     }
+///
+///     #[event(dispid = 0x61d)]
+///     fn sheet_activate(&self, sheet: SmartVariant) {}
+/// }
+///
+/// # fn workbook() -> AutoCOMInterface<IDispatch> { unimplemented!() }
+/// let workbook = workbook();
+/// let subscription = EventSink::builder()
+///     .source(&workbook)
+///     .handlers(WorkbookEvents::default())
+///     .advise(&workbook)?;
+/// # Ok::<(), RustyWinapiError>(())
+/// ```
+///
+/// [`EventSinkBuilder`]: struct.EventSinkBuilder.html
+/// [`OutParam`]: ../../smart_idispatch/trait.OutParam.html
+/// [`VariantType`]: ../../smart_variant/trait.VariantType.html
+pub trait EventHandlers: 'static {
+    /// Registers handlers of `self` on `builder`.
+    fn register(self: Rc<Self>, builder: EventSinkBuilder) -> EventSinkBuilder;
+}
+
+/// Shared handlers, the caller keeps access to their state.
+impl<H: EventHandlers> EventHandlers for Rc<H> {
+    fn register(self: Rc<Self>, builder: EventSinkBuilder) -> EventSinkBuilder {
+        H::register(Rc::clone(&self), builder)
+    }
+}
+
+/// Converts an argument of an event for `#[event_sink]` handlers, `None` if it doesn't convert.
+#[doc(hidden)]
+pub fn event_arg<T: OutParam>(x: Option<SmartVariant>) -> Option<T> {
+    T::from_out_param(x.unwrap_or(SmartVariant::Empty)).ok()
+}
+
+/// Sink of events of a source dispinterface, see [module level documentation].
+///
+/// [module level documentation]: index.html
+pub struct EventSink {
+    iid: IID,
+    handlers: HashMap<DISPID, Box<EventHandler>>,
+}
+
+/// Builder of an [`EventSink`].
+///
+/// Errors (of source lookup or name resolution) are reported by [`build`] or [`advise`].
+///
+/// [`EventSink`]: struct.EventSink.html
+/// [`build`]: #method.build
+/// [`advise`]: #method.advise
+#[derive(Default)]
+pub struct EventSinkBuilder {
+    source: Option<ComResult<AutoCOMInterface<ITypeInfo>>>,
+    iid: Option<IID>,
+    by_dispid: Vec<(DISPID, Box<EventHandler>)>,
+    by_name: Vec<(String, Box<EventHandler>)>,
+}
+
+impl EventSink {
+    pub fn builder() -> EventSinkBuilder {
+        EventSinkBuilder::default()
+    }
+
+    /// IID of the source interface.
+    #[inline]
+    pub fn iid(&self) -> &IID {
+        &self.iid
+    }
+}
+
+impl DispatchServer for EventSink {
+    fn get_ids_of_names(&self, names: &[String]) -> Vec<Option<DISPID>> {
+        vec![None; names.len()]
+    }
+
+    fn invoke(
+        &self,
+        dispid: DISPID,
+        flags: WORD,
+        args: Vec<SmartVariant>,
+    ) -> Result<SmartVariant, DispatchError> {
+        if let Some(handler) = self.handlers.get(&dispid) {
+            handler(args);
+        }
+        Ok(SmartVariant::Empty)
+    }
+
+    fn implements(&self, iid: &IID) -> bool {
+        IsEqualGUID(iid, &self.iid)
+    }
+}
+
+impl EventSinkBuilder {
+    /// Uses the default source interface of `object`, found by IProvideClassInfo.
+    pub fn source<T: Interface>(mut self, object: &AutoCOMInterface<T>) -> Self {
+        self.source = Some(default_source(object));
+        self
+    }
+
+    /// Uses the source interface described by `type_info`.
+    pub fn source_type_info(mut self, type_info: AutoCOMInterface<ITypeInfo>) -> Self {
+        self.source = Some(Ok(type_info));
+        self
+    }
+
+    /// Sets IID of the source interface, needed without type info, overrides IID of the type info otherwise.
+    pub fn iid(mut self, iid: &IID) -> Self {
+        self.iid = Some(*iid);
+        self
+    }
+
+    /// Handles event `name` (case-insensitive), requires type info of the source interface.
+    pub fn on<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(Vec<SmartVariant>) + 'static,
+    {
+        self.by_name.push((name.to_string(), Box::new(handler)));
+        self
+    }
+
+    /// Registers typed handlers, see [`EventHandlers`].
+    ///
+    /// [`EventHandlers`]: trait.EventHandlers.html
+    pub fn handlers<H: EventHandlers>(self, handlers: H) -> Self {
+        H::register(Rc::new(handlers), self)
+    }
+
+    /// Handles event `dispid`.
+    pub fn on_dispid<F>(mut self, dispid: DISPID, handler: F) -> Self
+    where
+        F: Fn(Vec<SmartVariant>) + 'static,
+    {
+        self.by_dispid.push((dispid, Box::new(handler)));
+        self
+    }
+
+    /// Resolves event names and builds the sink.
+    ///
+    /// # Errors
+    ///
+    /// * Failure of the source lookup.
+    /// * `E_INVALIDARG` if the IID of the source interface is unknown.
+    /// * `UnknownName` if the source interface has no event of a handled name.
+    pub fn build(self) -> ComResult<EventSink> {
+        let type_info = self.source.transpose()?;
+        let iid = match (self.iid, &type_info) {
+            (Some(iid), _) => iid,
+            (None, Some(x)) => x.type_attr()?.guid,
+            (None, None) => return Err(winerror::E_INVALIDARG.into()),
+        };
+
+        let mut handlers: HashMap<DISPID, Box<EventHandler>> = self.by_dispid.into_iter().collect();
+        if !self.by_name.is_empty() {
+            let type_info = type_info.ok_or(RustyWinapiError::UnknownName)?;
+            let events = event_names(&type_info)?;
+            for (name, handler) in self.by_name {
+                let dispid = events
+                    .iter()
+                    .find(|(x, _)| x.eq_ignore_ascii_case(&name))
+                    .map(|(_, dispid)| *dispid)
+                    .ok_or(RustyWinapiError::UnknownName)?;
+                handlers.insert(dispid, handler);
+            }
+        }
+
+        Ok(EventSink { iid, handlers })
+    }
+
+    /// Builds the sink and subscribes it to events of `source`, the subscription lasts until the returned guard is
+    /// dropped.
+    pub fn advise<T: Interface>(self, source: &AutoCOMInterface<T>) -> ComResult<AdviseGuard> {
+        let sink = self.build()?;
+        let iid = sink.iid;
+        let container = source.query_interface::<IConnectionPointContainer>()?;
+        container.advise(&iid, &sink.into_dispatch())
+    }
+}
+
+/// Type info of the default source interface of the class of `object`.
+fn default_source<T: Interface>(
+    object: &AutoCOMInterface<T>,
+) -> ComResult<AutoCOMInterface<ITypeInfo>> {
+    let provider = object.query_interface::<IProvideClassInfo>()?;
+    let mut ptinfo: *mut ITypeInfo = std::ptr::null_mut();
+    let hresult = unsafe { provider.GetClassInfo(&mut ptinfo) };
+    if winerror::FAILED(hresult) {
+        return Err(hresult.into());
+    }
+    let class: AutoCOMInterface<ITypeInfo> = unsafe { AutoCOMInterface::from_raw(ptinfo) };
+
+    let attr = class.type_attr()?;
+    if attr.typekind != TKIND_COCLASS {
+        return Err(winerror::E_UNEXPECTED.into());
+    }
+    for i in 0..UINT::from(attr.cImplTypes) {
+        let mut flags = 0;
+        let hresult = unsafe { class.GetImplTypeFlags(i, &mut flags) };
+        let source = (IMPLTYPEFLAG_FDEFAULT | IMPLTYPEFLAG_FSOURCE) as i32;
+        if winerror::SUCCEEDED(hresult) && flags & source == source {
+            return class.impl_type_info(i);
+        }
+    }
+
+    Err(CONNECT_E_NOCONNECTION.into())
+}
+
+/// Names and DISPIDs of functions of a dispinterface.
+fn event_names(type_info: &AutoCOMInterface<ITypeInfo>) -> ComResult<Vec<(String, DISPID)>> {
+    let mut result = Vec::new();
+    for i in 0..type_info.type_attr()?.cFuncs {
+        let memid = type_info.func_desc(i.into())?.memid;
+        if let Some(name) = type_info.names(memid)?.into_iter().next() {
+            result.push((name, memid));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use winapi::um::oleauto::DISPATCH_METHOD;
+
+    #[test]
+    fn test_EventSink() {
+        let iid = crate::com_interface::guid("00024413-0000-0000-C000-000000000046");
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let sink = {
+            let received = received.clone();
+            EventSink::builder()
+                .iid(&iid)
+                .on_dispid(0x61d, move |args| received.borrow_mut().extend(args))
+                .build()
+                .unwrap()
+        };
+        assert!(sink.implements(&iid));
+
+        sink.invoke(0x61d, DISPATCH_METHOD, vec![SmartVariant::Int4(1)])
+            .unwrap();
+        sink.invoke(0x61e, DISPATCH_METHOD, vec![SmartVariant::Int4(2)])
+            .unwrap();
+        assert_eq!(vec![SmartVariant::Int4(1)], *received.borrow());
+
+        let dispatch = sink.into_dispatch();
+        let mut source = std::ptr::null_mut();
+        let hresult = unsafe { dispatch.as_iunknown().QueryInterface(&iid, &mut source) };
+        assert_eq!(winerror::S_OK, hresult);
+        let source = unsafe { AutoCOMInterface::from_raw(source as *mut IUnknown) };
+    }
+
+    #[derive(Default)]
+    struct Events {
+        received: RefCell<Vec<(i32, String)>>,
+    }
+
+    #[crate::event_sink]
+    impl Events {
+        #[event(dispid = 1)]
+        fn changed(&self, count: i32, name: String) {
+            self.received.borrow_mut().push((count, name));
+        }
+
+        fn unrelated(&self) {}
+    }
+
+    #[test]
+    fn test_EventSink_handlers() {
+        let events = Rc::new(Events::default());
+        let sink = EventSink::builder()
+            .iid(&IDispatch::uuidof())
+            .handlers(Rc::clone(&events))
+            .build()
+            .unwrap();
+
+        let args = vec![SmartVariant::Int2(2), SmartVariant::Text("A1".into())];
+        sink.invoke(1, DISPATCH_METHOD, args).unwrap();
+        sink.invoke(1, DISPATCH_METHOD, vec![SmartVariant::Empty])
+            .unwrap();
+        assert_eq!(vec![(2, String::from("A1"))], *events.received.borrow());
+    }
+
+    #[test]
+    fn test_EventSink_requires_source() {
+        assert!(EventSink::builder().build().is_err());
+        assert!(EventSink::builder()
+            .iid(&IDispatch::uuidof())
+            .on("Event", |_| ())
+            .build()
+            .is_err());
+    }
+}
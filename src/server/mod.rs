@@ -0,0 +1,331 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Scaffolding of in-process COM servers (DLLs) written in Rust.
+//!
+//! Server registers a class factory constructor per CLSID with [`register_class`], and objects hold a
+//! [`ModuleLock`] while alive, so the module knows when it can be unloaded. [`dll_get_class_object`],
+//! [`dll_can_unload_now`], [`dll_register_server`] and [`dll_unregister_server`] implement the DLL entry points COM
+//! calls, [`export_dll_server!`] exports them under their well-known names.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::auto_com_interface::AutoCOMInterface;
+//! use rusty_winapi::error::ComResult;
+//! use rusty_winapi::safe::guid::Guid;
+//! use rusty_winapi::server;
+//! use winapi::um::unknwnbase::IUnknown;
+//!
+//! const CLSID_MY_CLASS: Guid = Guid::from_u128(0x8d3ac3e2_5c43_4b7a_9d5e_1f0e6c2d7a91);
+//!
+//! # fn my_class_factory() -> ComResult<AutoCOMInterface<IUnknown>> { unimplemented!() }
+//! fn init() {
+//!     server::register_class(CLSID_MY_CLASS.as_guid(), my_class_factory);
+//! }
+//!
+//! rusty_winapi::export_dll_server!(init);
+//! ```
+//!
+//! [`register_class`]: fn.register_class.html
+//! [`ModuleLock`]: struct.ModuleLock.html
+//! [`dll_get_class_object`]: fn.dll_get_class_object.html
+//! [`dll_can_unload_now`]: fn.dll_can_unload_now.html
+//! [`dll_register_server`]: fn.dll_register_server.html
+//! [`dll_unregister_server`]: fn.dll_unregister_server.html
+//! [`export_dll_server!`]: ../macro.export_dll_server.html
+
+pub mod class_factory;
+pub mod com_box;
+pub mod dispatch;
+pub mod dynamic_object;
+pub mod event_sink;
+pub mod registration;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use winapi::shared::guiddef::{CLSID, REFCLSID, REFIID};
+use winapi::shared::minwindef::LPVOID;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::unknwnbase::IUnknown;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+use crate::safe::guid::Guid;
+
+/// Constructor of a class object (usually IClassFactory) of a registered class.
+pub type ClassFactoryFn = dyn Fn() -> ComResult<AutoCOMInterface<IUnknown>> + Send + Sync;
+
+/// Registration and unregistration callbacks of the server, see [`set_registrar`].
+///
+/// [`set_registrar`]: fn.set_registrar.html
+pub type RegistrarFn = fn() -> ComResult<()>;
+
+static LOCK_COUNT: AtomicUsize = AtomicUsize::new(0);
+static CLASSES: Mutex<Vec<(Guid, Arc<ClassFactoryFn>)>> = Mutex::new(Vec::new());
+static REGISTRAR: Mutex<Option<(RegistrarFn, RegistrarFn)>> = Mutex::new(None);
+
+/// Increments the module lock count, returns the new count.
+///
+/// Call it for every live object and every `IClassFactory::LockServer(TRUE)`, or hold a [`ModuleLock`].
+///
+/// [`ModuleLock`]: struct.ModuleLock.html
+pub fn lock_module() -> usize {
+    LOCK_COUNT.fetch_add(1, Ordering::AcqRel) + 1
+}
+
+/// Decrements the module lock count, returns the new count.
+pub fn unlock_module() -> usize {
+    let previous = LOCK_COUNT
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |x| x.checked_sub(1))
+        .unwrap_or(0);
+    previous.saturating_sub(1)
+}
+
+/// Current module lock count.
+pub fn lock_count() -> usize {
+    LOCK_COUNT.load(Ordering::Acquire)
+}
+
+/// Module lock held while alive, keeps the DLL loaded.
+///
+/// Embed one into every object implemented by the server.
+#[derive(Debug)]
+pub struct ModuleLock(());
+
+impl ModuleLock {
+    pub fn new() -> Self {
+        lock_module();
+        ModuleLock(())
+    }
+}
+
+impl Default for ModuleLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for ModuleLock {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ModuleLock {
+    fn drop(&mut self) {
+        unlock_module();
+    }
+}
+
+/// Registers constructor of the class object of `clsid`, replacing previous registration of the same CLSID.
+///
+/// Constructor is called for every DllGetClassObject request of the class.
+pub fn register_class<F>(clsid: &CLSID, factory: F)
+where
+    F: Fn() -> ComResult<AutoCOMInterface<IUnknown>> + Send + Sync + 'static,
+{
+    let clsid = Guid::from(*clsid);
+    let mut classes = CLASSES.lock().unwrap_or_else(|x| x.into_inner());
+    classes.retain(|(x, _)| *x != clsid);
+    classes.push((clsid, Arc::new(factory)));
+}
+
+/// Unregisters class of `clsid`, returns `false` if it wasn't registered.
+pub fn unregister_class(clsid: &CLSID) -> bool {
+    let clsid = Guid::from(*clsid);
+    let mut classes = CLASSES.lock().unwrap_or_else(|x| x.into_inner());
+    let len = classes.len();
+    classes.retain(|(x, _)| *x != clsid);
+    classes.len() != len
+}
+
+/// CLSIDs of registered classes in order of registration.
+pub fn registered_classes() -> Vec<CLSID> {
+    let classes = CLASSES.lock().unwrap_or_else(|x| x.into_inner());
+    classes.iter().map(|(x, _)| *x.as_guid()).collect()
+}
+
+/// Sets callbacks of [`dll_register_server`] and [`dll_unregister_server`] writing and removing registry keys of
+/// the server's classes.
+///
+/// [`dll_register_server`]: fn.dll_register_server.html
+/// [`dll_unregister_server`]: fn.dll_unregister_server.html
+pub fn set_registrar(register: RegistrarFn, unregister: RegistrarFn) {
+    *REGISTRAR.lock().unwrap_or_else(|x| x.into_inner()) = Some((register, unregister));
+}
+
+/// Returns interface `riid` of the class object of `rclsid`, implementation of DllGetClassObject.
+///
+/// # Errors
+///
+/// * If class isn't registered, returns `CLASS_E_CLASSNOTAVAILABLE`.
+/// * If the class object constructor panics, returns `E_UNEXPECTED`.
+/// * Otherwise returns HRESULT of the class object constructor or of QueryInterface.
+///
+/// # Safety
+///
+/// `rclsid` and `riid` must point to valid GUIDs, `ppv` must be NULL or valid for writes.
+pub unsafe fn dll_get_class_object(rclsid: REFCLSID, riid: REFIID, ppv: *mut LPVOID) -> HRESULT {
+    if ppv.is_null() {
+        return winerror::E_POINTER;
+    }
+    *ppv = std::ptr::null_mut();
+
+    if rclsid.is_null() || riid.is_null() {
+        return winerror::E_INVALIDARG;
+    }
+
+    let clsid = Guid::from(*rclsid);
+    let factory = {
+        let classes = CLASSES.lock().unwrap_or_else(|x| x.into_inner());
+        match classes.iter().find(|(x, _)| *x == clsid) {
+            Some((_, x)) => x.clone(),
+            None => return winerror::CLASS_E_CLASSNOTAVAILABLE,
+        }
+    };
+
+    // Panic must not unwind into COM.
+    match catch_unwind(AssertUnwindSafe(|| factory())) {
+        Ok(Ok(x)) => match x.try_as_iunknown() {
+            Some(unknown) => unknown.QueryInterface(riid, ppv),
+            None => winerror::E_POINTER,
+        },
+        Ok(Err(x)) => x.hresult().0,
+        Err(_) => winerror::E_UNEXPECTED,
+    }
+}
+
+/// Returns `S_OK` if module isn't locked and can be unloaded, otherwise `S_FALSE`, implementation of
+/// DllCanUnloadNow.
+pub fn dll_can_unload_now() -> HRESULT {
+    if lock_count() == 0 {
+        winerror::S_OK
+    } else {
+        winerror::S_FALSE
+    }
+}
+
+/// Calls registration callback set by [`set_registrar`], implementation of DllRegisterServer.
+///
+/// Returns `SELFREG_E_CLASS` if there is no callback, `E_UNEXPECTED` if it panics.
+///
+/// [`set_registrar`]: fn.set_registrar.html
+pub fn dll_register_server() -> HRESULT {
+    call_registrar(|(register, _)| register())
+}
+
+/// Calls unregistration callback set by [`set_registrar`], implementation of DllUnregisterServer.
+///
+/// Returns `SELFREG_E_CLASS` if there is no callback, `E_UNEXPECTED` if it panics.
+///
+/// [`set_registrar`]: fn.set_registrar.html
+pub fn dll_unregister_server() -> HRESULT {
+    call_registrar(|(_, unregister)| unregister())
+}
+
+/// `SELFREG_E_CLASS` of olectl.h, class registration failed.
+pub const SELFREG_E_CLASS: HRESULT = 0x8004_0201_u32 as HRESULT;
+
+fn call_registrar<F>(f: F) -> HRESULT
+where
+    F: FnOnce((RegistrarFn, RegistrarFn)) -> ComResult<()>,
+{
+    let registrar = *REGISTRAR.lock().unwrap_or_else(|x| x.into_inner());
+    match registrar.map(|x| catch_unwind(AssertUnwindSafe(|| f(x)))) {
+        Some(Ok(Ok(()))) => winerror::S_OK,
+        Some(Ok(Err(x))) => x.hresult().0,
+        Some(Err(_)) => winerror::E_UNEXPECTED,
+        None => SELFREG_E_CLASS,
+    }
+}
+
+/// Exports `DllGetClassObject`, `DllCanUnloadNow`, `DllRegisterServer` and `DllUnregisterServer` of an in-process
+/// server implemented by [`server`] module functions.
+///
+/// Optional `init` function is called once before the first request, register classes there.
+///
+/// [`server`]: server/index.html
+#[macro_export]
+macro_rules! export_dll_server {
+    () => {
+        $crate::export_dll_server!(|| ());
+    };
+    ($init:expr) => {
+        fn __rusty_winapi_server_init() {
+            static INIT: ::std::sync::Once = ::std::sync::Once::new();
+            INIT.call_once(|| ($init)());
+        }
+
+        #[no_mangle]
+        pub unsafe extern "system" fn DllGetClassObject(
+            rclsid: ::winapi::shared::guiddef::REFCLSID,
+            riid: ::winapi::shared::guiddef::REFIID,
+            ppv: *mut ::winapi::shared::minwindef::LPVOID,
+        ) -> ::winapi::shared::ntdef::HRESULT {
+            __rusty_winapi_server_init();
+            $crate::server::dll_get_class_object(rclsid, riid, ppv)
+        }
+
+        #[no_mangle]
+        pub extern "system" fn DllCanUnloadNow() -> ::winapi::shared::ntdef::HRESULT {
+            $crate::server::dll_can_unload_now()
+        }
+
+        #[no_mangle]
+        pub extern "system" fn DllRegisterServer() -> ::winapi::shared::ntdef::HRESULT {
+            __rusty_winapi_server_init();
+            $crate::server::dll_register_server()
+        }
+
+        #[no_mangle]
+        pub extern "system" fn DllUnregisterServer() -> ::winapi::shared::ntdef::HRESULT {
+            __rusty_winapi_server_init();
+            $crate::server::dll_unregister_server()
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_apartment::ComApartment;
+    use winapi::um::objidl::IRunningObjectTable;
+    use winapi::Interface;
+
+    #[test]
+    fn test_server_dll_get_class_object() {
+        let _com = ComApartment::init_mta().unwrap();
+        let clsid: CLSID = Guid::generate().unwrap().into();
+
+        let mut pvoid: LPVOID = std::ptr::null_mut();
+        assert_eq!(winerror::CLASS_E_CLASSNOTAVAILABLE, unsafe {
+            dll_get_class_object(&clsid, &IUnknown::uuidof(), &mut pvoid)
+        });
+
+        register_class(&clsid, || {
+            crate::running_object::running_object_table().map(|x| x.to_iunknown())
+        });
+        assert!(registered_classes().iter().any(|x| Guid::from(*x) == clsid));
+        assert_eq!(winerror::S_OK, unsafe {
+            dll_get_class_object(&clsid, &IRunningObjectTable::uuidof(), &mut pvoid)
+        });
+        drop(unsafe { AutoCOMInterface::from_raw(pvoid as *mut IRunningObjectTable) });
+
+        assert!(unregister_class(&clsid));
+        assert!(!unregister_class(&clsid));
+
+        register_class(&clsid, || panic!("class object failed"));
+        assert_eq!(winerror::E_UNEXPECTED, unsafe {
+            dll_get_class_object(&clsid, &IUnknown::uuidof(), &mut pvoid)
+        });
+        assert!(pvoid.is_null());
+        assert!(unregister_class(&clsid));
+
+        let lock = ModuleLock::new();
+        assert_eq!(winerror::S_FALSE, dll_can_unload_now());
+        drop(lock);
+    }
+}
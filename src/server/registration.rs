@@ -0,0 +1,451 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Registry keys of COM server classes.
+//!
+//! [`ClassRegistration`] describes a class: its CLSID, ProgIDs, description, server (an in-process DLL, by default
+//! the module containing this code, or a local server command line) and threading model. [`register`] writes
+//! `CLSID\{...}` with `InprocServer32` or `LocalServer32` and the ProgID keys under `Software\Classes` of the
+//! current user or of the machine (which requires elevation), [`unregister`] removes them. Together with
+//! [`set_registrar`] it implements `DllRegisterServer` in a few lines.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::error::ComResult;
+//! use rusty_winapi::safe::guid::Guid;
+//! use rusty_winapi::server;
+//! use rusty_winapi::server::registration::{ClassRegistration, RegistryScope, ThreadingModel};
+//!
+//! const CLSID_COUNTER: Guid = Guid::from_u128(0x3f2a8c51_7d0e_4b6f_a1c9_5e8d2b7f0a34);
+//!
+//! fn classes() -> Vec<ClassRegistration> {
+//!     vec![ClassRegistration::new(CLSID_COUNTER.as_guid())
+//!         .progid("Sample.Counter.1")
+//!         .version_independent_progid("Sample.Counter")
+//!         .description("Sample counter")
+//!         .threading_model(ThreadingModel::Apartment)]
+//! }
+//!
+//! fn register() -> ComResult<()> {
+//!     server::registration::register(&classes(), RegistryScope::PerUser)
+//! }
+//!
+//! fn unregister() -> ComResult<()> {
+//!     server::registration::unregister(&classes(), RegistryScope::PerUser)
+//! }
+//!
+//! fn init() {
+//!     server::set_registrar(register, unregister);
+//! }
+//!
+//! rusty_winapi::export_dll_server!(init);
+//! ```
+//!
+//! [`ClassRegistration`]: struct.ClassRegistration.html
+//! [`register`]: fn.register.html
+//! [`unregister`]: fn.unregister.html
+//! [`set_registrar`]: ../fn.set_registrar.html
+
+use std::path::{Path, PathBuf};
+
+use winapi::shared::guiddef::CLSID;
+use winapi::shared::minwindef::{DWORD, HKEY, HMODULE, MAX_PATH};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::libloaderapi::{
+    GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+    GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+};
+use winapi::um::winnt::{KEY_WRITE, REG_OPTION_NON_VOLATILE, REG_SZ};
+use winapi::um::winreg::{
+    RegCloseKey, RegCreateKeyExW, RegDeleteTreeW, RegSetValueExW, HKEY_CURRENT_USER,
+    HKEY_LOCAL_MACHINE,
+};
+
+use crate::error::{ComResult, RustyWinapiError};
+use crate::safe::clsid::to_wide;
+use crate::safe::guid::Guid;
+
+/// Registry hive classes are registered in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistryScope {
+    /// `HKEY_CURRENT_USER\Software\Classes`, doesn't require elevation.
+    PerUser,
+    /// `HKEY_LOCAL_MACHINE\Software\Classes`, requires elevation.
+    PerMachine,
+}
+
+/// `ThreadingModel` value of `InprocServer32`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThreadingModel {
+    #[default]
+    Apartment,
+    Free,
+    Both,
+    Neutral,
+}
+
+impl ThreadingModel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThreadingModel::Apartment => "Apartment",
+            ThreadingModel::Free => "Free",
+            ThreadingModel::Both => "Both",
+            ThreadingModel::Neutral => "Neutral",
+        }
+    }
+}
+
+/// Server of a registered class.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerLocation {
+    /// `InprocServer32`, path of the DLL.
+    InProc(PathBuf),
+    /// `LocalServer32`, command line of the executable.
+    Local(String),
+}
+
+/// Registry entries of a class, see [module level documentation].
+///
+/// [module level documentation]: index.html
+#[derive(Clone, Debug)]
+pub struct ClassRegistration {
+    clsid: Guid,
+    progid: Option<String>,
+    version_independent_progid: Option<String>,
+    description: Option<String>,
+    server: Option<ServerLocation>,
+    threading_model: ThreadingModel,
+    thread_safe: bool,
+}
+
+impl ClassRegistration {
+    /// Creates registration of `clsid` served by the current module in the `Apartment` threading model.
+    pub fn new(clsid: &CLSID) -> Self {
+        ClassRegistration {
+            clsid: Guid::from(*clsid),
+            progid: None,
+            version_independent_progid: None,
+            description: None,
+            server: None,
+            threading_model: ThreadingModel::default(),
+            thread_safe: false,
+        }
+    }
+
+    /// Sets ProgID, e.g. `"Sample.Counter.1"`.
+    pub fn progid(mut self, progid: &str) -> Self {
+        self.progid = Some(progid.to_string());
+        self
+    }
+
+    /// Sets version independent ProgID, e.g. `"Sample.Counter"`, pointing to the ProgID with `CurVer`.
+    pub fn version_independent_progid(mut self, progid: &str) -> Self {
+        self.version_independent_progid = Some(progid.to_string());
+        self
+    }
+
+    /// Sets description, the default value of the CLSID and ProgID keys.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Sets threading model of an in-process server.
+    ///
+    /// COM calls objects of `Free`, `Both` and `Neutral` classes from several threads at once, so a class served by
+    /// the current module must declare its objects `Send + Sync` with [`thread_safe`] to be registered with them.
+    ///
+    /// [`thread_safe`]: #method.thread_safe
+    pub fn threading_model(mut self, threading_model: ThreadingModel) -> Self {
+        self.threading_model = threading_model;
+        self
+    }
+
+    /// Declares objects of the class served by the current module as `T`, which allows threading models other
+    /// than `Apartment`.
+    pub fn thread_safe<T: Send + Sync>(mut self) -> Self {
+        self.thread_safe = true;
+        self
+    }
+
+    /// Registers class as served by the DLL at `path` instead of the current module.
+    pub fn inproc_server<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.server = Some(ServerLocation::InProc(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Registers class as served by a local server started with `command_line`, e.g. the quoted path of
+    /// `std::env::current_exe()` followed by `-Embedding`.
+    pub fn local_server(mut self, command_line: &str) -> Self {
+        self.server = Some(ServerLocation::Local(command_line.to_string()));
+        self
+    }
+
+    pub fn clsid(&self) -> &CLSID {
+        self.clsid.as_guid()
+    }
+
+    /// Server of the class, `None` means the current module.
+    pub fn server(&self) -> Option<&ServerLocation> {
+        self.server.as_ref()
+    }
+
+    /// Writes registry keys of the class.
+    ///
+    /// # Errors
+    ///
+    /// * If class is served by the current module in a threading model other than `Apartment` without
+    ///   [`thread_safe`], returns `RustyWinapiError::Com(E_INVALIDARG)`.
+    /// * Otherwise returns HRESULT of the failed registry call as `RustyWinapiError`.
+    ///
+    /// [`thread_safe`]: #method.thread_safe
+    pub fn register(&self, scope: RegistryScope) -> ComResult<()> {
+        if self.server.is_none()
+            && self.threading_model != ThreadingModel::Apartment
+            && !self.thread_safe
+        {
+            return Err(winerror::E_INVALIDARG.into());
+        }
+
+        let clsid = self.clsid.to_string();
+        let description = self.description.as_deref();
+        let clsid_key = format!("CLSID\\{}", clsid);
+
+        set_value(scope, &clsid_key, None, description)?;
+        match self.server.clone() {
+            Some(ServerLocation::Local(command_line)) => {
+                set_value(
+                    scope,
+                    &format!("{}\\LocalServer32", clsid_key),
+                    None,
+                    Some(&command_line),
+                )?;
+            }
+            x => {
+                let path = match x {
+                    Some(ServerLocation::InProc(path)) => path,
+                    _ => current_module_path()?,
+                };
+                let key = format!("{}\\InprocServer32", clsid_key);
+                set_value(scope, &key, None, Some(&path.to_string_lossy()))?;
+                set_value(
+                    scope,
+                    &key,
+                    Some("ThreadingModel"),
+                    Some(self.threading_model.as_str()),
+                )?;
+            }
+        }
+
+        if let Some(progid) = &self.progid {
+            set_value(scope, &format!("{}\\ProgID", clsid_key), None, Some(progid))?;
+            set_value(scope, progid, None, description)?;
+            set_value(scope, &format!("{}\\CLSID", progid), None, Some(&clsid))?;
+        }
+
+        if let Some(progid) = &self.version_independent_progid {
+            set_value(
+                scope,
+                &format!("{}\\VersionIndependentProgID", clsid_key),
+                None,
+                Some(progid),
+            )?;
+            set_value(scope, progid, None, description)?;
+            set_value(scope, &format!("{}\\CLSID", progid), None, Some(&clsid))?;
+            if let Some(current) = &self.progid {
+                set_value(scope, &format!("{}\\CurVer", progid), None, Some(current))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes registry keys of the class, keys which don't exist are skipped.
+    pub fn unregister(&self, scope: RegistryScope) -> ComResult<()> {
+        let keys = [
+            Some(format!("CLSID\\{}", self.clsid)),
+            self.progid.clone(),
+            self.version_independent_progid.clone(),
+        ];
+
+        keys.iter()
+            .flatten()
+            .try_for_each(|x| delete_tree(scope, x))
+            .map_err(RustyWinapiError::from)
+    }
+}
+
+/// Registers `classes`, on failure removes keys of the classes already registered.
+///
+/// # Errors
+///
+/// * If `scope` is `PerMachine` and process isn't elevated, returns `RustyWinapiError::Com(E_ACCESSDENIED)`.
+/// * Otherwise returns HRESULT of the failed registry call as `RustyWinapiError`.
+pub fn register(classes: &[ClassRegistration], scope: RegistryScope) -> ComResult<()> {
+    for (i, x) in classes.iter().enumerate() {
+        if let Err(e) = x.register(scope) {
+            classes[..=i].iter().for_each(|x| {
+                let _ = x.unregister(scope);
+            });
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes registry keys of `classes`, continuing past failures, returns the first error.
+pub fn unregister(classes: &[ClassRegistration], scope: RegistryScope) -> ComResult<()> {
+    let mut result = Ok(());
+    for x in classes {
+        if let Err(e) = x.unregister(scope) {
+            result = result.and(Err(e));
+        }
+    }
+
+    result
+}
+
+/// Path of the module (DLL or EXE) containing this code.
+pub fn current_module_path() -> ComResult<PathBuf> {
+    let mut module: HMODULE = std::ptr::null_mut();
+    let anchor = current_module_path as *const u16;
+    let succeeded = unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            anchor,
+            &mut module,
+        )
+    };
+    if succeeded == 0 {
+        return Err(last_error().into());
+    }
+
+    let mut buffer = vec![0u16; MAX_PATH];
+    loop {
+        let len = unsafe { GetModuleFileNameW(module, buffer.as_mut_ptr(), buffer.len() as DWORD) }
+            as usize;
+        if len == 0 {
+            return Err(last_error().into());
+        }
+        if len < buffer.len() {
+            buffer.truncate(len);
+            return Ok(PathBuf::from(String::from_utf16_lossy(&buffer)));
+        }
+
+        let size = buffer.len() * 2;
+        buffer.resize(size, 0);
+    }
+}
+
+/// Opened registry key, closed on drop.
+struct Key(HKEY);
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe { RegCloseKey(self.0) };
+    }
+}
+
+fn classes_path(scope: RegistryScope, subkey: &str) -> (HKEY, Vec<u16>) {
+    let root = match scope {
+        RegistryScope::PerUser => HKEY_CURRENT_USER,
+        RegistryScope::PerMachine => HKEY_LOCAL_MACHINE,
+    };
+
+    (root, to_wide(&format!("Software\\Classes\\{}", subkey)))
+}
+
+fn set_value(
+    scope: RegistryScope,
+    subkey: &str,
+    name: Option<&str>,
+    value: Option<&str>,
+) -> Result<(), HRESULT> {
+    let (root, subkey) = classes_path(scope, subkey);
+    let mut hkey: HKEY = std::ptr::null_mut();
+    check(unsafe {
+        RegCreateKeyExW(
+            root,
+            subkey.as_ptr(),
+            0,
+            std::ptr::null_mut(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            std::ptr::null_mut(),
+            &mut hkey,
+            std::ptr::null_mut(),
+        )
+    })?;
+    let key = Key(hkey);
+
+    let value = match value {
+        Some(x) => to_wide(x),
+        None => return Ok(()),
+    };
+    let name = name.map(to_wide);
+    check(unsafe {
+        RegSetValueExW(
+            key.0,
+            name.as_ref().map_or(std::ptr::null(), |x| x.as_ptr()),
+            0,
+            REG_SZ,
+            value.as_ptr() as *const u8,
+            (value.len() * 2) as DWORD,
+        )
+    })
+}
+
+fn delete_tree(scope: RegistryScope, subkey: &str) -> Result<(), HRESULT> {
+    let (root, subkey) = classes_path(scope, subkey);
+    match check(unsafe { RegDeleteTreeW(root, subkey.as_ptr()) }) {
+        Err(x) if x == winerror::HRESULT_FROM_WIN32(winerror::ERROR_FILE_NOT_FOUND) => Ok(()),
+        x => x,
+    }
+}
+
+fn check(status: i32) -> Result<(), HRESULT> {
+    if status == winerror::ERROR_SUCCESS as i32 {
+        Ok(())
+    } else {
+        Err(winerror::HRESULT_FROM_WIN32(status as u32))
+    }
+}
+
+fn last_error() -> HRESULT {
+    winerror::HRESULT_FROM_WIN32(unsafe { GetLastError() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::safe::clsid::CLSIDFromProgID;
+
+    #[test]
+    fn test_registration_per_user() {
+        let clsid: CLSID = Guid::generate().unwrap().into();
+        let progid = format!("RustyWinapi.Test{}.1", Guid::from(clsid).to_u128() as u32);
+        let class = ClassRegistration::new(&clsid)
+            .progid(&progid)
+            .description("rusty_winapi registration test")
+            .threading_model(ThreadingModel::Both);
+        assert!(current_module_path().unwrap().is_absolute());
+        assert_eq!(
+            Err(RustyWinapiError::Com(winerror::E_INVALIDARG)),
+            class.register(RegistryScope::PerUser)
+        );
+        let class = class.thread_safe::<std::sync::atomic::AtomicI32>();
+
+        register(std::slice::from_ref(&class), RegistryScope::PerUser).unwrap();
+        assert_eq!(
+            Guid::from(clsid),
+            Guid::from(CLSIDFromProgID(&progid).unwrap())
+        );
+
+        unregister(std::slice::from_ref(&class), RegistryScope::PerUser).unwrap();
+        assert!(CLSIDFromProgID(&progid).is_err());
+        assert_eq!(Ok(()), class.unregister(RegistryScope::PerUser));
+    }
+}
@@ -25,6 +25,8 @@ use winapi::um::winnt::{LOCALE_USER_DEFAULT, LONG, LPCSTR, LPSTR, WCHAR};
 use winapi::{Class, Interface, RIDL};
 
 use crate::auto_com_interface::*;
+use crate::error::ComResult;
+use crate::hresult::HResult;
 use crate::smart_iunknown::*;
 use crate::smart_variant::*;
 
@@ -35,7 +37,7 @@ pub trait SmartIClassFactory: SmartIUnknown {
     fn create_instance<U: Interface>(
         &self,
         unk_outer: LPUNKNOWN,
-    ) -> Result<AutoCOMInterface<U>, HRESULT> {
+    ) -> ComResult<AutoCOMInterface<U>> {
         let mut pvoid: LPVOID = std::ptr::null_mut();
         let hresult = unsafe {
             self.as_iclass_factory().CreateInstance(
@@ -48,15 +50,15 @@ pub trait SmartIClassFactory: SmartIUnknown {
         if winerror::SUCCEEDED(hresult) {
             Ok((pvoid as *mut U).try_into().unwrap())
         } else {
-            Err(hresult)
+            Err(hresult.into())
         }
     }
 
-    fn lock_server(&mut self, fLock: bool) -> HRESULT {
-        unsafe {
+    fn lock_server(&mut self, fLock: bool) -> HResult {
+        HResult(unsafe {
             self.as_iclass_factory_mut()
                 .LockServer(if fLock { -1 } else { 0 })
-        }
+        })
     }
 }
 
@@ -0,0 +1,281 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Smart & safe rustified WinAPI IConnectionPointContainer and IConnectionPoint counterparts.
+//!
+//! Event sources expose an outgoing (source) interface through a connection point, a sink implementing it is
+//! subscribed with Advise. [`AdviseGuard`] keeps the cookie of a subscription and Unadvises it on drop.
+//!
+//! See also: [IConnectionPoint] at MSDN.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::prelude::*;
+//! use rusty_winapi::smart_iconnectionpoint::{IConnectionPointContainer, SmartIConnectionPointContainer};
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! # fn sink() -> AutoCOMInterface<IDispatch> { unimplemented!() }
+//! # let events_iid = unsafe { std::mem::zeroed() };
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create()?;
+//! let container = excel.query_interface::<IConnectionPointContainer>()?;
+//! let subscription = container.advise(&events_iid, &sink())?;
+//! // Events are delivered to the sink until `subscription` is dropped.
+//! # Ok::<(), RustyWinapiError>(())
+//! ```
+//!
+//! [`AdviseGuard`]: struct.AdviseGuard.html
+//! [IConnectionPoint]: https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iconnectionpoint
+
+use winapi::shared::guiddef::{IID, REFIID};
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::{HRESULT, ULONG};
+use winapi::shared::winerror;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::{Interface, RIDL};
+
+use crate::auto_com_interface::*;
+use crate::enumerator::EnumConnectionPoints;
+use crate::error::ComResult;
+use crate::smart_iunknown::*;
+
+/// Object doesn't source the requested outgoing interface.
+pub const CONNECT_E_NOCONNECTION: HRESULT = 0x8004_0200_u32 as HRESULT;
+/// Connection point doesn't accept more sinks.
+pub const CONNECT_E_ADVISELIMIT: HRESULT = 0x8004_0201_u32 as HRESULT;
+/// Sink doesn't implement the outgoing interface.
+pub const CONNECT_E_CANNOTCONNECT: HRESULT = 0x8004_0202_u32 as HRESULT;
+
+RIDL! {#[uuid(0xb196b284, 0xbab4, 0x101a, 0xb6, 0x9c, 0x00, 0xaa, 0x00, 0x34, 0x1d, 0x07)]
+interface IConnectionPointContainer(IConnectionPointContainerVtbl): IUnknown(IUnknownVtbl) {
+    fn EnumConnectionPoints(
+        ppEnum: *mut *mut IEnumConnectionPoints,
+    ) -> HRESULT,
+    fn FindConnectionPoint(
+        riid: REFIID,
+        ppCP: *mut *mut IConnectionPoint,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xb196b286, 0xbab4, 0x101a, 0xb6, 0x9c, 0x00, 0xaa, 0x00, 0x34, 0x1d, 0x07)]
+interface IConnectionPoint(IConnectionPointVtbl): IUnknown(IUnknownVtbl) {
+    fn GetConnectionInterface(
+        pIID: *mut IID,
+    ) -> HRESULT,
+    fn GetConnectionPointContainer(
+        ppCPC: *mut *mut IConnectionPointContainer,
+    ) -> HRESULT,
+    fn Advise(
+        pUnkSink: *mut IUnknown,
+        pdwCookie: *mut DWORD,
+    ) -> HRESULT,
+    fn Unadvise(
+        dwCookie: DWORD,
+    ) -> HRESULT,
+    // IEnumConnections isn't wrapped.
+    fn EnumConnections(
+        ppEnum: *mut *mut IUnknown,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xb196b285, 0xbab4, 0x101a, 0xb6, 0x9c, 0x00, 0xaa, 0x00, 0x34, 0x1d, 0x07)]
+interface IEnumConnectionPoints(IEnumConnectionPointsVtbl): IUnknown(IUnknownVtbl) {
+    fn Next(
+        cConnections: ULONG,
+        ppCP: *mut *mut IConnectionPoint,
+        pcFetched: *mut ULONG,
+    ) -> HRESULT,
+    fn Skip(
+        cConnections: ULONG,
+    ) -> HRESULT,
+    fn Reset() -> HRESULT,
+    fn Clone(
+        ppEnum: *mut *mut IEnumConnectionPoints,
+    ) -> HRESULT,
+}}
+
+crate::impl_interface_vtbl!(IConnectionPointContainer => IConnectionPointContainerVtbl);
+crate::impl_interface_vtbl!(IConnectionPoint => IConnectionPointVtbl);
+crate::impl_interface_vtbl!(IEnumConnectionPoints => IEnumConnectionPointsVtbl);
+
+pub trait SmartIConnectionPointContainer: SmartIUnknown {
+    fn as_iconnection_point_container(&self) -> &IConnectionPointContainer;
+
+    /// Returns connection point of the outgoing interface `iid`, fails with `CONNECT_E_NOCONNECTION` if the object
+    /// doesn't source it.
+    fn find_connection_point(&self, iid: &IID) -> ComResult<AutoCOMInterface<IConnectionPoint>> {
+        let mut point = std::ptr::null_mut();
+        let hresult = unsafe {
+            self.as_iconnection_point_container()
+                .FindConnectionPoint(iid, &mut point)
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(unsafe { AutoCOMInterface::from_raw(point) })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Enumerates all connection points of the object.
+    fn connection_points(&self) -> ComResult<EnumConnectionPoints> {
+        let mut points = std::ptr::null_mut();
+        let hresult = unsafe {
+            self.as_iconnection_point_container()
+                .EnumConnectionPoints(&mut points)
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(EnumConnectionPoints::new(unsafe {
+                AutoCOMInterface::from_raw(points)
+            }))
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Subscribes `sink` to the outgoing interface `iid`, see [`SmartIConnectionPoint::advise`].
+    ///
+    /// [`SmartIConnectionPoint::advise`]: trait.SmartIConnectionPoint.html#method.advise
+    fn advise<T: Interface>(
+        &self,
+        iid: &IID,
+        sink: &AutoCOMInterface<T>,
+    ) -> ComResult<AdviseGuard> {
+        self.find_connection_point(iid)?.advise(sink)
+    }
+}
+
+pub trait SmartIConnectionPoint: SmartIUnknown {
+    fn as_iconnection_point(&self) -> &IConnectionPoint;
+
+    /// Returns IID of the outgoing interface of the connection point.
+    fn connection_interface(&self) -> ComResult<IID> {
+        let mut iid: IID = unsafe { std::mem::zeroed() };
+        let hresult = unsafe { self.as_iconnection_point().GetConnectionInterface(&mut iid) };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(iid)
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Subscribes `sink` to events, the subscription lasts until the returned guard is dropped.
+    ///
+    /// Fails with `CONNECT_E_CANNOTCONNECT` if `sink` doesn't implement the outgoing interface and with
+    /// `CONNECT_E_ADVISELIMIT` if the source doesn't accept more sinks.
+    fn advise<T: Interface>(&self, sink: &AutoCOMInterface<T>) -> ComResult<AdviseGuard> {
+        if sink.is_null() {
+            return Err(winerror::E_POINTER.into());
+        }
+
+        let mut cookie: DWORD = 0;
+        let point = self.as_iconnection_point();
+        let hresult = unsafe { point.Advise(sink.as_iunknown_ptr(), &mut cookie) };
+
+        if winerror::SUCCEEDED(hresult) {
+            unsafe { point.AddRef() };
+            Ok(AdviseGuard {
+                point: unsafe {
+                    AutoCOMInterface::from_raw(
+                        point as *const IConnectionPoint as *mut IConnectionPoint,
+                    )
+                },
+                cookie,
+            })
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+/// Subscription of a sink to a connection point, Unadvised on drop.
+///
+/// Guard keeps the connection point alive, drop it in the apartment which made the subscription.
+pub struct AdviseGuard {
+    point: AutoCOMInterface<IConnectionPoint>,
+    cookie: DWORD,
+}
+
+impl AdviseGuard {
+    /// Cookie of the subscription returned by Advise.
+    #[inline]
+    pub fn cookie(&self) -> DWORD {
+        self.cookie
+    }
+
+    /// Connection point of the subscription.
+    #[inline]
+    pub fn connection_point(&self) -> &AutoCOMInterface<IConnectionPoint> {
+        &self.point
+    }
+
+    /// Unsubscribes the sink, unlike drop reports failure.
+    pub fn unadvise(mut self) -> ComResult<()> {
+        let hresult = self.unadvise_inner();
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    fn unadvise_inner(&mut self) -> HRESULT {
+        match self.point.try_as_inner() {
+            Some(point) => {
+                let hresult = unsafe { point.Unadvise(self.cookie) };
+                self.point = AutoCOMInterface::default();
+                hresult
+            }
+            None => winerror::S_OK,
+        }
+    }
+}
+
+impl Drop for AdviseGuard {
+    fn drop(&mut self) {
+        self.unadvise_inner();
+    }
+}
+
+impl SmartIConnectionPointContainer for IConnectionPointContainer {
+    fn as_iconnection_point_container(&self) -> &IConnectionPointContainer {
+        self
+    }
+}
+
+impl SmartIConnectionPointContainer for AutoCOMInterface<IConnectionPointContainer> {
+    fn as_iconnection_point_container(&self) -> &IConnectionPointContainer {
+        self.as_inner()
+    }
+}
+
+impl SmartIConnectionPoint for IConnectionPoint {
+    fn as_iconnection_point(&self) -> &IConnectionPoint {
+        self
+    }
+}
+
+impl SmartIConnectionPoint for AutoCOMInterface<IConnectionPoint> {
+    fn as_iconnection_point(&self) -> &IConnectionPoint {
+        self.as_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::dispatch::DispatchServer;
+    use crate::server::dynamic_object::DynamicObject;
+    use winapi::um::oaidl::IDispatch;
+
+    #[test]
+    fn test_no_connection_points() {
+        let object = DynamicObject::new().into_dispatch();
+        assert!(object
+            .query_interface::<IConnectionPointContainer>()
+            .is_err());
+        assert_eq!(0xb196b284, IConnectionPointContainer::uuidof().Data1);
+    }
+}
@@ -0,0 +1,119 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Smart & safe rustified WinAPI IObjectSafety counterpart.
+//!
+//! Some hardened hosts require controls to be marked safe for scripting before invoking them.
+//!
+//! See also: [IObjectSafety] at MSDN.
+//!
+//! [IObjectSafety]: https://docs.microsoft.com/en-us/previous-versions/windows/internet-explorer/ie-developer/platform-apis/aa768224(v=vs.85)
+
+use winapi::shared::guiddef::REFIID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::{Interface, RIDL};
+
+use crate::auto_com_interface::*;
+use crate::error::ComResult;
+use crate::smart_iunknown::*;
+
+/// Caller of the interface may be untrusted.
+pub const INTERFACESAFE_FOR_UNTRUSTED_CALLER: DWORD = 0x0000_0001;
+/// Data passed into the interface may be untrusted.
+pub const INTERFACESAFE_FOR_UNTRUSTED_DATA: DWORD = 0x0000_0002;
+/// Object knows to use IDispatchEx.
+pub const INTERFACE_USES_DISPEX: DWORD = 0x0000_0004;
+/// Object knows to use IInternetHostSecurityManager.
+pub const INTERFACE_USES_SECURITY_MANAGER: DWORD = 0x0000_0008;
+
+RIDL! {#[uuid(0xcb5bdc81, 0x93c1, 0x11cf, 0x8f, 0x20, 0x00, 0x80, 0x5f, 0x2c, 0xd0, 0x64)]
+interface IObjectSafety(IObjectSafetyVtbl): IUnknown(IUnknownVtbl) {
+    fn GetInterfaceSafetyOptions(
+        riid: REFIID,
+        pdwSupportedOptions: *mut DWORD,
+        pdwEnabledOptions: *mut DWORD,
+    ) -> HRESULT,
+    fn SetInterfaceSafetyOptions(
+        riid: REFIID,
+        dwOptionSetMask: DWORD,
+        dwEnabledOptions: DWORD,
+    ) -> HRESULT,
+}}
+pub type LPOBJECTSAFETY = *mut IObjectSafety;
+
+crate::impl_interface_vtbl!(IObjectSafety => IObjectSafetyVtbl);
+
+pub trait SmartIObjectSafety: SmartIUnknown {
+    fn as_iobject_safety(&self) -> &IObjectSafety;
+    fn as_iobject_safety_mut(&mut self) -> &mut IObjectSafety;
+
+    /// Returns `(supported, enabled)` safety options of the interface `I`.
+    fn get_interface_safety_options<I: Interface>(&self) -> ComResult<(DWORD, DWORD)> {
+        let mut supported: DWORD = 0;
+        let mut enabled: DWORD = 0;
+        let hresult = unsafe {
+            self.as_iobject_safety().GetInterfaceSafetyOptions(
+                &<I as Interface>::uuidof(),
+                &mut supported,
+                &mut enabled,
+            )
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok((supported, enabled))
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Changes safety options selected by `mask` of the interface `I` to `options`.
+    fn set_interface_safety_options<I: Interface>(
+        &mut self,
+        mask: DWORD,
+        options: DWORD,
+    ) -> ComResult<()> {
+        let hresult = unsafe {
+            self.as_iobject_safety_mut().SetInterfaceSafetyOptions(
+                &<I as Interface>::uuidof(),
+                mask,
+                options,
+            )
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Marks the interface `I` safe for untrusted callers and data, the usual requirement of scripting hosts.
+    fn set_safe_for_scripting<I: Interface>(&mut self) -> ComResult<()> {
+        const SAFE_FOR_SCRIPTING: DWORD =
+            INTERFACESAFE_FOR_UNTRUSTED_CALLER | INTERFACESAFE_FOR_UNTRUSTED_DATA;
+
+        self.set_interface_safety_options::<I>(SAFE_FOR_SCRIPTING, SAFE_FOR_SCRIPTING)
+    }
+}
+
+impl SmartIObjectSafety for IObjectSafety {
+    fn as_iobject_safety(&self) -> &IObjectSafety {
+        self
+    }
+
+    fn as_iobject_safety_mut(&mut self) -> &mut IObjectSafety {
+        self
+    }
+}
+
+impl SmartIObjectSafety for AutoCOMInterface<IObjectSafety> {
+    fn as_iobject_safety(&self) -> &IObjectSafety {
+        self.as_inner()
+    }
+
+    fn as_iobject_safety_mut(&mut self) -> &mut IObjectSafety {
+        self.as_inner_mut()
+    }
+}
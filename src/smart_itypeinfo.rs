@@ -0,0 +1,388 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Smart & safe rustified WinAPI ITypeInfo counterpart.
+//!
+//! [`SmartITypeInfo`] walks type descriptions of automation objects and type libraries: TYPEATTR, FUNCDESC and
+//! VARDESC are borrowed as [`TypeAttr`], [`FuncDesc`] and [`VarDesc`] guards releasing the descriptors when
+//! dropped, element types are mapped into [`ElementType`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::prelude::*;
+//! use rusty_winapi::smart_itypeinfo::SmartITypeInfo;
+//! # use winapi::um::oaidl::IDispatch;
+//! # fn list(object: &AutoCOMInterface<IDispatch>) -> ComResult<()> {
+//!
+//! let type_info = object.get_type_info(0, 0)?;
+//! for i in 0..type_info.type_attr()?.cFuncs {
+//!     let func = type_info.func_desc(i.into())?;
+//!     println!("{:?} {:?} -> {:?}", type_info.names(func.memid)?, func.params(), func.result());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`SmartITypeInfo`]: trait.SmartITypeInfo.html
+//! [`TypeAttr`]: struct.TypeAttr.html
+//! [`FuncDesc`]: struct.FuncDesc.html
+//! [`VarDesc`]: struct.VarDesc.html
+//! [`ElementType`]: enum.ElementType.html
+
+use std::convert::TryInto;
+use std::ops::Deref;
+
+use winapi::shared::minwindef::{DWORD, UINT};
+use winapi::shared::winerror;
+use winapi::shared::wtypes::{BSTR, VARTYPE, VT_CARRAY, VT_PTR, VT_SAFEARRAY, VT_USERDEFINED};
+use winapi::um::oaidl::{
+    ITypeInfo, ELEMDESC, FUNCDESC, HREFTYPE, MEMBERID, SAFEARRAYBOUND, TYPEATTR, TYPEDESC, VARDESC,
+};
+
+use crate::auto_bstr::AutoBSTR;
+use crate::auto_com_interface::*;
+use crate::error::ComResult;
+use crate::smart_idispatch::MemberKind;
+use crate::smart_iunknown::*;
+
+pub trait SmartITypeInfo: SmartIUnknown {
+    fn as_itypeinfo(&self) -> &ITypeInfo;
+
+    /// Attributes of the type, released when the guard is dropped.
+    fn type_attr(&self) -> ComResult<TypeAttr<'_>> {
+        let type_info = self.as_itypeinfo();
+        let mut attr: *mut TYPEATTR = std::ptr::null_mut();
+        let hresult = unsafe { type_info.GetTypeAttr(&mut attr) };
+        if winerror::SUCCEEDED(hresult) {
+            Ok(TypeAttr { type_info, attr })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Description of the function at `index` (`0..cFuncs` of [`type_attr`]), released when the guard is dropped.
+    ///
+    /// [`type_attr`]: #method.type_attr
+    fn func_desc(&self, index: UINT) -> ComResult<FuncDesc<'_>> {
+        let type_info = self.as_itypeinfo();
+        let mut desc: *mut FUNCDESC = std::ptr::null_mut();
+        let hresult = unsafe { type_info.GetFuncDesc(index, &mut desc) };
+        if winerror::SUCCEEDED(hresult) {
+            Ok(FuncDesc { type_info, desc })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Description of the variable at `index` (`0..cVars` of [`type_attr`]), released when the guard is dropped.
+    ///
+    /// [`type_attr`]: #method.type_attr
+    fn var_desc(&self, index: UINT) -> ComResult<VarDesc<'_>> {
+        let type_info = self.as_itypeinfo();
+        let mut desc: *mut VARDESC = std::ptr::null_mut();
+        let hresult = unsafe { type_info.GetVarDesc(index, &mut desc) };
+        if winerror::SUCCEEDED(hresult) {
+            Ok(VarDesc { type_info, desc })
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Name of the member followed by names of its parameters.
+    fn names(&self, memid: MEMBERID) -> ComResult<Vec<String>> {
+        let mut capacity: UINT = 16;
+        loop {
+            let mut names: Vec<BSTR> = vec![std::ptr::null_mut(); capacity as usize];
+            let mut count: UINT = 0;
+            let hresult = unsafe {
+                self.as_itypeinfo()
+                    .GetNames(memid, names.as_mut_ptr(), capacity, &mut count)
+            };
+            let names: Vec<String> = names
+                .into_iter()
+                .take(count as usize)
+                .map(|x| String::from(AutoBSTR::from(x)))
+                .collect();
+
+            if winerror::FAILED(hresult) {
+                return Err(hresult.into());
+            } else if count < capacity {
+                return Ok(names);
+            }
+            capacity *= 2;
+        }
+    }
+
+    /// Documentation of the member, of the type itself for `DISPID_UNKNOWN` (MEMBERID_NIL).
+    fn documentation(&self, memid: MEMBERID) -> ComResult<Documentation> {
+        let mut name: BSTR = std::ptr::null_mut();
+        let mut doc_string: BSTR = std::ptr::null_mut();
+        let mut help_context: DWORD = 0;
+        let mut help_file: BSTR = std::ptr::null_mut();
+        let hresult = unsafe {
+            self.as_itypeinfo().GetDocumentation(
+                memid,
+                &mut name,
+                &mut doc_string,
+                &mut help_context,
+                &mut help_file,
+            )
+        };
+        let documentation = Documentation {
+            name: String::from(AutoBSTR::from(name)),
+            doc_string: String::from(AutoBSTR::from(doc_string)),
+            help_context,
+            help_file: String::from(AutoBSTR::from(help_file)),
+        };
+
+        if winerror::SUCCEEDED(hresult) {
+            Ok(documentation)
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Type info referenced by `href`, e.g. by [`ElementType::UserDefined`] or an implemented interface.
+    ///
+    /// [`ElementType::UserDefined`]: enum.ElementType.html#variant.UserDefined
+    fn ref_type_info(&self, href: HREFTYPE) -> ComResult<AutoCOMInterface<ITypeInfo>> {
+        let mut ptinfo: *mut ITypeInfo = std::ptr::null_mut();
+        let hresult = unsafe { self.as_itypeinfo().GetRefTypeInfo(href, &mut ptinfo) };
+        if winerror::SUCCEEDED(hresult) {
+            Ok(ptinfo.try_into().unwrap())
+        } else {
+            Err(hresult.into())
+        }
+    }
+
+    /// Type info of the implemented (inherited) interface at `index`, of the vtable interface of a dual
+    /// dispinterface for `index` -1 (`UINT::MAX`).
+    fn impl_type_info(&self, index: UINT) -> ComResult<AutoCOMInterface<ITypeInfo>> {
+        let mut href: HREFTYPE = 0;
+        let hresult = unsafe { self.as_itypeinfo().GetRefTypeOfImplType(index, &mut href) };
+        if winerror::SUCCEEDED(hresult) {
+            self.ref_type_info(href)
+        } else {
+            Err(hresult.into())
+        }
+    }
+}
+
+impl SmartITypeInfo for ITypeInfo {
+    fn as_itypeinfo(&self) -> &ITypeInfo {
+        self
+    }
+}
+
+impl SmartITypeInfo for AutoCOMInterface<ITypeInfo> {
+    fn as_itypeinfo(&self) -> &ITypeInfo {
+        self.as_inner()
+    }
+}
+
+/// Result of [`SmartITypeInfo::documentation`].
+///
+/// [`SmartITypeInfo::documentation`]: trait.SmartITypeInfo.html#method.documentation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Documentation {
+    pub name: String,
+    pub doc_string: String,
+    pub help_context: DWORD,
+    pub help_file: String,
+}
+
+/// TYPEATTR borrowed from ITypeInfo, released with ITypeInfo::ReleaseTypeAttr when dropped.
+pub struct TypeAttr<'a> {
+    type_info: &'a ITypeInfo,
+    attr: *mut TYPEATTR,
+}
+
+impl TypeAttr<'_> {
+    /// Aliased type of `TKIND_ALIAS` type.
+    pub fn alias(&self) -> ElementType {
+        unsafe { ElementType::from_typedesc(&self.tdescAlias) }
+    }
+}
+
+impl Deref for TypeAttr<'_> {
+    type Target = TYPEATTR;
+
+    fn deref(&self) -> &TYPEATTR {
+        unsafe { &*self.attr }
+    }
+}
+
+impl Drop for TypeAttr<'_> {
+    fn drop(&mut self) {
+        unsafe { self.type_info.ReleaseTypeAttr(self.attr) };
+    }
+}
+
+/// FUNCDESC borrowed from ITypeInfo, released with ITypeInfo::ReleaseFuncDesc when dropped.
+pub struct FuncDesc<'a> {
+    type_info: &'a ITypeInfo,
+    desc: *mut FUNCDESC,
+}
+
+impl FuncDesc<'_> {
+    /// Descriptions of the parameters.
+    pub fn param_descs(&self) -> &[ELEMDESC] {
+        match self.cParams {
+            x if x > 0 => unsafe { std::slice::from_raw_parts(self.lprgelemdescParam, x as usize) },
+            _ => &[],
+        }
+    }
+
+    /// Types of the parameters.
+    pub fn params(&self) -> Vec<ElementType> {
+        self.param_descs()
+            .iter()
+            .map(|x| unsafe { ElementType::from_typedesc(&x.tdesc) })
+            .collect()
+    }
+
+    /// Type of the result.
+    pub fn result(&self) -> ElementType {
+        unsafe { ElementType::from_typedesc(&self.elemdescFunc.tdesc) }
+    }
+
+    /// Kind of the member by `invkind`.
+    pub fn kind(&self) -> MemberKind {
+        MemberKind::from_invoke_kind(self.invkind)
+    }
+}
+
+impl Deref for FuncDesc<'_> {
+    type Target = FUNCDESC;
+
+    fn deref(&self) -> &FUNCDESC {
+        unsafe { &*self.desc }
+    }
+}
+
+impl Drop for FuncDesc<'_> {
+    fn drop(&mut self) {
+        unsafe { self.type_info.ReleaseFuncDesc(self.desc) };
+    }
+}
+
+/// VARDESC borrowed from ITypeInfo, released with ITypeInfo::ReleaseVarDesc when dropped.
+pub struct VarDesc<'a> {
+    type_info: &'a ITypeInfo,
+    desc: *mut VARDESC,
+}
+
+impl VarDesc<'_> {
+    /// Type of the variable.
+    pub fn element_type(&self) -> ElementType {
+        unsafe { ElementType::from_typedesc(&self.elemdescVar.tdesc) }
+    }
+}
+
+impl Deref for VarDesc<'_> {
+    type Target = VARDESC;
+
+    fn deref(&self) -> &VARDESC {
+        unsafe { &*self.desc }
+    }
+}
+
+impl Drop for VarDesc<'_> {
+    fn drop(&mut self) {
+        unsafe { self.type_info.ReleaseVarDesc(self.desc) };
+    }
+}
+
+/// Element type of TYPEDESC: parameter, result or variable type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementType {
+    /// Type which is its VARTYPE, e.g. `VT_I4`, `VT_BSTR`, `VT_VARIANT`, `VT_DISPATCH`, `VT_VOID` or `VT_HRESULT`.
+    Base(VARTYPE),
+    /// Pointer, e.g. to an out-parameter.
+    Ptr(Box<ElementType>),
+    /// SAFEARRAY of the elements.
+    SafeArray(Box<ElementType>),
+    /// C-style array with bounds (lower bound, number of elements) of its dimensions.
+    CArray {
+        element: Box<ElementType>,
+        bounds: Vec<(i32, u32)>,
+    },
+    /// Type described by another type info (interface, enum, record or alias), see
+    /// [`SmartITypeInfo::ref_type_info`].
+    ///
+    /// [`SmartITypeInfo::ref_type_info`]: trait.SmartITypeInfo.html#method.ref_type_info
+    UserDefined(HREFTYPE),
+}
+
+impl ElementType {
+    /// Maps TYPEDESC following its pointers.
+    ///
+    /// # Safety
+    ///
+    /// `x` must be a valid TYPEDESC borrowed from a type info.
+    unsafe fn from_typedesc(x: &TYPEDESC) -> Self {
+        match x.vt as u32 {
+            VT_PTR => ElementType::Ptr(Box::new(Self::from_typedesc(&**x.u.lptdesc()))),
+            VT_SAFEARRAY => ElementType::SafeArray(Box::new(Self::from_typedesc(&**x.u.lptdesc()))),
+            VT_CARRAY => {
+                let desc = &**x.u.lpadesc();
+                let bounds: &[SAFEARRAYBOUND] =
+                    std::slice::from_raw_parts(desc.rgbounds.as_ptr(), desc.cDims as usize);
+                ElementType::CArray {
+                    element: Box::new(Self::from_typedesc(&desc.tdescElem)),
+                    bounds: bounds.iter().map(|x| (x.lLbound, x.cElements)).collect(),
+                }
+            }
+            VT_USERDEFINED => ElementType::UserDefined(*x.u.hreftype()),
+            _ => ElementType::Base(x.vt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::safe::clsid::to_wide;
+    use winapi::shared::wtypes::{VT_HRESULT, VT_UINT};
+    use winapi::um::oaidl::{IDispatch, ITypeLib, DISPID_UNKNOWN};
+    use winapi::um::oleauto::{LoadTypeLibEx, REGKIND_NONE};
+    use winapi::Interface;
+
+    #[test]
+    fn test_SmartITypeInfo_stdole() {
+        let mut ptlib: *mut ITypeLib = std::ptr::null_mut();
+        let path = to_wide("stdole2.tlb");
+        assert!(winerror::SUCCEEDED(unsafe {
+            LoadTypeLibEx(path.as_ptr(), REGKIND_NONE, &mut ptlib)
+        }));
+        let type_lib: AutoCOMInterface<ITypeLib> = ptlib.try_into().unwrap();
+
+        let mut ptinfo: *mut ITypeInfo = std::ptr::null_mut();
+        assert!(winerror::SUCCEEDED(unsafe {
+            type_lib
+                .as_inner()
+                .GetTypeInfoOfGuid(&IDispatch::uuidof(), &mut ptinfo)
+        }));
+        let type_info: AutoCOMInterface<ITypeInfo> = ptinfo.try_into().unwrap();
+
+        assert_eq!(
+            "IDispatch",
+            type_info.documentation(DISPID_UNKNOWN).unwrap().name
+        );
+        assert_eq!(4, type_info.type_attr().unwrap().cFuncs);
+
+        let func = type_info.func_desc(0).unwrap();
+        assert_eq!(MemberKind::Method, func.kind());
+        assert_eq!(ElementType::Base(VT_HRESULT as VARTYPE), func.result());
+        assert_eq!(
+            vec![ElementType::Ptr(Box::new(ElementType::Base(
+                VT_UINT as VARTYPE
+            )))],
+            func.params()
+        );
+        assert_eq!(
+            vec!["GetTypeInfoCount", "pctinfo"],
+            type_info.names(func.memid).unwrap()
+        );
+    }
+}
@@ -26,26 +26,46 @@ use winapi::um::winnt::{LOCALE_USER_DEFAULT, LONG, LPCSTR, LPSTR, WCHAR};
 use winapi::{Class, Interface, RIDL};
 
 use crate::auto_com_interface::*;
+use crate::error::{ComResult, RustyWinapiError};
 use crate::smart_variant::*;
 
 pub trait SmartIUnknown {
     fn as_iunknown(&self) -> &IUnknown;
     fn as_iunknown_mut(&mut self) -> &mut IUnknown;
 
-    fn query_interface<T: Interface>(&self) -> Result<AutoCOMInterface<T>, HRESULT> {
+    fn query_interface<T: Interface>(&self) -> ComResult<AutoCOMInterface<T>> {
         let mut pvoid: LPVOID = std::ptr::null_mut();
+        let start = crate::trace::start();
         let hresult = unsafe {
             self.as_iunknown()
                 .QueryInterface(&<T as winapi::Interface>::uuidof(), &mut pvoid)
         };
+        crate::trace::query_interface(&<T as winapi::Interface>::uuidof(), hresult, start);
 
         if winerror::SUCCEEDED(hresult) {
             match (pvoid as *mut T).try_into() {
                 Ok(x) => Ok(x),
-                Err(_) => Err(winerror::E_POINTER),
+                Err(_) => Err(winerror::E_POINTER.into()),
             }
         } else {
-            Err(hresult)
+            Err(RustyWinapiError::query_interface(
+                &<T as winapi::Interface>::uuidof(),
+                hresult,
+            ))
+        }
+    }
+
+    /// Returns `true` if both interfaces belong to the same COM object.
+    ///
+    /// Compares identity IUnknown pointers obtained via QueryInterface, as COM rules require,
+    /// since different interfaces of the same object may have different pointers.
+    fn is_same_object<U: SmartIUnknown + ?Sized>(&self, other: &U) -> bool {
+        match (
+            self.query_interface::<IUnknown>(),
+            other.query_interface::<IUnknown>(),
+        ) {
+            (Ok(x), Ok(y)) => x.as_iunknown_ptr() == y.as_iunknown_ptr(),
+            _ => false,
         }
     }
 
@@ -101,6 +121,7 @@ impl<T: Interface> SmartIUnknown for AutoCOMInterface<T> {
 mod tests {
     use super::*;
     use crate::auto_bstr::*;
+    use crate::com_apartment::ComApartment;
     use std::convert::TryInto;
 
     // 1C ComConnector (comcntr.dll) class
@@ -120,13 +141,7 @@ mod tests {
 
     //#[test]
     fn test_AutoCOMInterface_create_instance() {
-        let hr = unsafe {
-            winapi::um::combaseapi::CoInitializeEx(
-                winapi::shared::ntdef::NULL,
-                winapi::um::objbase::COINIT_MULTITHREADED,
-            )
-        };
-        assert!(winerror::SUCCEEDED(hr));
+        let _com = ComApartment::init_mta().unwrap();
 
         let v8cc = AutoCOMInterface::<IV8COMConnector>::create_instance(
             &<V8COMConnectorClass as Class>::uuidof(),
@@ -151,7 +166,5 @@ mod tests {
             unsafe { &mut *(conn1Cdb as *mut IDispatch as *mut IUnknown) };
 
         conn1Cdb.add_ref();
-
-        unsafe { winapi::um::combaseapi::CoUninitialize() };
     }
 }
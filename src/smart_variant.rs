@@ -14,15 +14,25 @@
 use std::any::Any;
 use std::cell::Cell;
 use std::convert::{AsMut, AsRef, TryFrom};
+use std::sync::Arc;
 
 use winapi::shared::minwindef::UINT;
 use winapi::shared::ntdef::*;
+use winapi::shared::winerror;
 use winapi::shared::wtypes::*;
 use winapi::shared::wtypesbase::*;
 use winapi::um::oaidl::*;
+use winapi::um::oleauto::VariantChangeTypeEx;
 use winapi::um::unknwnbase::*;
 
-use crate::auto_bstr::AutoBSTR;
+use crate::auto_bstr::{AutoBSTR, BStr};
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::config::Config;
+use crate::debug_dump::debug_dump_vartype;
+use crate::error::ConversionError;
+use crate::ffi::SafeArrayCopy;
+use crate::hresult::HResult;
+use crate::safe::bstr::{SysAllocError, SysAllocStringFromStr, SysAllocStringLen};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum SmartVariant {
@@ -33,17 +43,20 @@ pub enum SmartVariant {
     Real8(f64),
     //Currency(CY),
     Date(f64),
-    Text(String),
-    IDispatch(LPDISPATCH),
+    Text(Arc<str>), // Shared, so cloning params with large strings doesn't copy them.
+    Text16(Arc<[u16]>), // BSTR which isn't a valid UTF-16 (e.g. unpaired surrogates), kept as is.
+    IDispatch(AutoCOMInterface<IDispatch>), // Owned reference, released when the value is dropped.
     ErrorCode(i32), // SCODE
     Bool(bool),
     Variant(LPVARIANT),
-    IUnknown(LPUNKNOWN),
+    IUnknown(AutoCOMInterface<IUnknown>), // Owned reference, released when the value is dropped.
     //Decimal(i128),
     Int1(i8),
     UInt1(u8),
     UInt2(u16),
     UInt4(u32),
+    Int8(i64),
+    UInt8(u64),
     Int(i32),
     UInt(u32),
     //Record(LPRECORD),
@@ -51,6 +64,290 @@ pub enum SmartVariant {
     ByRef(PVOID), // mask value?
 }
 
+impl SmartVariant {
+    /// Converts into `T`, coercing a scalar or string value of another type with OLE Automation rules
+    /// (VariantChangeTypeEx using LCID from global [`Config`]), e.g. `Text("42")` into `i32`.
+    ///
+    /// Interfaces, arrays and references aren't coerced. With [`Config::strict_string_coercion`] strings aren't
+    /// coerced into other types and other types into strings, e.g. `Text("42")` into `i32` fails.
+    ///
+    /// [`Config`]: ../config/struct.Config.html
+    /// [`Config::strict_string_coercion`]: ../config/struct.Config.html#method.strict_string_coercion
+    pub fn coerce<T: VariantType>(self) -> Result<T, ConversionError> {
+        self.coerce_with(&Config::global())
+    }
+
+    fn coerce_with<T: VariantType>(self, config: &Config) -> Result<T, ConversionError> {
+        let is_text = matches!(self, SmartVariant::Text(_) | SmartVariant::Text16(_));
+        match self {
+            SmartVariant::IDispatch(_)
+            | SmartVariant::IUnknown(_)
+            | SmartVariant::Array(_)
+            | SmartVariant::ByRef(_)
+            | SmartVariant::Variant(_) => T::try_from(self),
+            x if config.strict_string_coercion() && is_text != (T::VARTYPE == VT_BSTR) => {
+                T::try_from(x)
+            }
+            x => T::try_from(x.clone()).or_else(|e| {
+                AutoVariant::try_from_smart(x)
+                    .ok()
+                    .and_then(|x| x.change_type(T::VARTYPE, config.lcid()).ok())
+                    .and_then(|x| SmartVariant::try_from(x).ok())
+                    .and_then(|x| T::try_from(x).ok())
+                    .ok_or(e)
+            }),
+        }
+    }
+
+    /// Takes the owned IDispatch out of the value, querying it from an IUnknown value.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use rusty_winapi::prelude::*;
+    /// # use winapi::um::oaidl::IDispatch;
+    /// # fn sheet(excel: &mut AutoCOMInterface<IDispatch>) -> ComResult<()> {
+    /// let mut sheet = excel.get("ActiveSheet")?.into_dispatch()?;
+    /// sheet.put("Name", "Report")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_dispatch(self) -> Result<AutoCOMInterface<IDispatch>, ConversionError> {
+        match self {
+            SmartVariant::IDispatch(x) if !x.is_null() => Ok(x),
+            SmartVariant::IUnknown(ref x) if !x.is_null() => x
+                .cast::<IDispatch>()
+                .map_err(|_| ConversionError::new(self.summary(), "AutoCOMInterface<IDispatch>")),
+            x => Err(ConversionError::new(
+                x.summary(),
+                "AutoCOMInterface<IDispatch>",
+            )),
+        }
+    }
+
+    /// Returns VARIANT type tag corresponding to this value.
+    pub fn vtype(&self) -> VARENUM {
+        match self {
+            SmartVariant::Empty => VT_EMPTY,
+            SmartVariant::Int2(_) => VT_I2,
+            SmartVariant::Int4(_) => VT_I4,
+            SmartVariant::Real4(_) => VT_R4,
+            SmartVariant::Real8(_) => VT_R8,
+            SmartVariant::Date(_) => VT_DATE,
+            SmartVariant::Text(_) => VT_BSTR,
+            SmartVariant::Text16(_) => VT_BSTR,
+            SmartVariant::IDispatch(_) => VT_DISPATCH,
+            SmartVariant::ErrorCode(_) => VT_ERROR,
+            SmartVariant::Bool(_) => VT_BOOL,
+            SmartVariant::Variant(_) => VT_VARIANT,
+            SmartVariant::IUnknown(_) => VT_UNKNOWN,
+            SmartVariant::Int1(_) => VT_I1,
+            SmartVariant::UInt1(_) => VT_UI1,
+            SmartVariant::UInt2(_) => VT_UI2,
+            SmartVariant::UInt4(_) => VT_UI4,
+            SmartVariant::Int8(_) => VT_I8,
+            SmartVariant::UInt8(_) => VT_UI8,
+            SmartVariant::Int(_) => VT_INT,
+            SmartVariant::UInt(_) => VT_UINT,
+            SmartVariant::Array(_) => VT_ARRAY,
+            SmartVariant::ByRef(_) => VT_BYREF,
+        }
+    }
+
+    /// Short human readable summary of the value for diagnostics, e.g. `VT_BSTR "abc"` or `VT_I4 42`.
+    ///
+    /// Long strings are truncated.
+    pub fn summary(&self) -> String {
+        const MAX_TEXT_CHARS: usize = 32;
+
+        let name = vt_name(self.vtype());
+        match self {
+            SmartVariant::Empty => name.into(),
+            SmartVariant::Int2(x) => format!("{} {}", name, x),
+            SmartVariant::Int4(x) => format!("{} {}", name, x),
+            SmartVariant::Real4(x) => format!("{} {}", name, x),
+            SmartVariant::Real8(x) => format!("{} {}", name, x),
+            SmartVariant::Date(x) => format!("{} {}", name, x),
+            SmartVariant::Text(x) if x.chars().count() > MAX_TEXT_CHARS => {
+                let head: String = x.chars().take(MAX_TEXT_CHARS).collect();
+                format!("{} {:?}...", name, head)
+            }
+            SmartVariant::Text(x) => format!("{} {:?}", name, x),
+            SmartVariant::Text16(x) => {
+                SmartVariant::Text(String::from_utf16_lossy(x).into()).summary()
+            }
+            SmartVariant::IDispatch(x) => format!("{} {:p}", name, x.as_raw()),
+            SmartVariant::ErrorCode(x) => format!("{} 0x{:08X}", name, x),
+            SmartVariant::Bool(x) => format!("{} {}", name, x),
+            SmartVariant::Variant(x) => format!("{} {:p}", name, *x),
+            SmartVariant::IUnknown(x) => format!("{} {:p}", name, x.as_raw()),
+            SmartVariant::Int1(x) => format!("{} {}", name, x),
+            SmartVariant::UInt1(x) => format!("{} {}", name, x),
+            SmartVariant::UInt2(x) => format!("{} {}", name, x),
+            SmartVariant::UInt4(x) => format!("{} {}", name, x),
+            SmartVariant::Int8(x) => format!("{} {}", name, x),
+            SmartVariant::UInt8(x) => format!("{} {}", name, x),
+            SmartVariant::Int(x) => format!("{} {}", name, x),
+            SmartVariant::UInt(x) => format!("{} {}", name, x),
+            SmartVariant::Array(x) => format!("{} {:p}", name, *x),
+            SmartVariant::ByRef(x) => format!("{} {:p}", name, *x),
+        }
+    }
+}
+
+/// Returns symbolic name of a VARIANT type tag (without VT_ARRAY/VT_BYREF modifiers), e.g. `"VT_BSTR"`.
+pub fn vt_name(vt: VARENUM) -> &'static str {
+    match vt {
+        VT_EMPTY => "VT_EMPTY",
+        VT_NULL => "VT_NULL",
+        VT_I2 => "VT_I2",
+        VT_I4 => "VT_I4",
+        VT_R4 => "VT_R4",
+        VT_R8 => "VT_R8",
+        VT_CY => "VT_CY",
+        VT_DATE => "VT_DATE",
+        VT_BSTR => "VT_BSTR",
+        VT_DISPATCH => "VT_DISPATCH",
+        VT_ERROR => "VT_ERROR",
+        VT_BOOL => "VT_BOOL",
+        VT_VARIANT => "VT_VARIANT",
+        VT_UNKNOWN => "VT_UNKNOWN",
+        VT_DECIMAL => "VT_DECIMAL",
+        VT_I1 => "VT_I1",
+        VT_UI1 => "VT_UI1",
+        VT_UI2 => "VT_UI2",
+        VT_UI4 => "VT_UI4",
+        VT_I8 => "VT_I8",
+        VT_UI8 => "VT_UI8",
+        VT_INT => "VT_INT",
+        VT_UINT => "VT_UINT",
+        VT_VOID => "VT_VOID",
+        VT_HRESULT => "VT_HRESULT",
+        VT_PTR => "VT_PTR",
+        VT_SAFEARRAY => "VT_SAFEARRAY",
+        VT_CARRAY => "VT_CARRAY",
+        VT_USERDEFINED => "VT_USERDEFINED",
+        VT_LPSTR => "VT_LPSTR",
+        VT_LPWSTR => "VT_LPWSTR",
+        VT_RECORD => "VT_RECORD",
+        VT_ARRAY => "VT_ARRAY",
+        VT_BYREF => "VT_BYREF",
+        _ => "VT_UNKNOWN_TYPE",
+    }
+}
+
+macro_rules! impl_try_from_smart_variant {
+    ($target:ty, $name:expr, $($variant:ident),+) => {
+        impl TryFrom<SmartVariant> for $target {
+            type Error = ConversionError;
+
+            #[inline]
+            fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
+                match x {
+                    $(SmartVariant::$variant(x) => Ok(x),)+
+                    x => Err(ConversionError::new(x.summary(), $name)),
+                }
+            }
+        }
+    };
+}
+
+impl_try_from_smart_variant!(i8, "i8", Int1);
+impl_try_from_smart_variant!(u8, "u8", UInt1);
+impl_try_from_smart_variant!(i16, "i16", Int2);
+impl_try_from_smart_variant!(u16, "u16", UInt2);
+impl_try_from_smart_variant!(i32, "i32", Int4, Int);
+impl_try_from_smart_variant!(u32, "u32", UInt4, UInt);
+impl_try_from_smart_variant!(i64, "i64", Int8);
+impl_try_from_smart_variant!(u64, "u64", UInt8);
+impl_try_from_smart_variant!(f32, "f32", Real4);
+impl_try_from_smart_variant!(f64, "f64", Real8);
+impl_try_from_smart_variant!(bool, "bool", Bool);
+
+macro_rules! impl_from_for_smart_variant {
+    ($($source:ty => $variant:ident),+) => {
+        $(
+            impl From<$source> for SmartVariant {
+                #[inline]
+                fn from(x: $source) -> Self {
+                    SmartVariant::$variant(x.into())
+                }
+            }
+        )+
+    };
+}
+
+impl_from_for_smart_variant!(
+    i8 => Int1, u8 => UInt1, i16 => Int2, u16 => UInt2, i32 => Int4, u32 => UInt4, i64 => Int8, u64 => UInt8,
+    f32 => Real4, f64 => Real8, bool => Bool, String => Text, &str => Text
+);
+
+impl From<AutoCOMInterface<IDispatch>> for SmartVariant {
+    #[inline]
+    fn from(x: AutoCOMInterface<IDispatch>) -> Self {
+        SmartVariant::IDispatch(x)
+    }
+}
+
+impl From<AutoCOMInterface<IUnknown>> for SmartVariant {
+    #[inline]
+    fn from(x: AutoCOMInterface<IUnknown>) -> Self {
+        SmartVariant::IUnknown(x)
+    }
+}
+
+impl TryFrom<SmartVariant> for String {
+    type Error = ConversionError;
+
+    #[inline]
+    fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
+        match x {
+            SmartVariant::Text(x) => Ok(x.as_ref().into()),
+            SmartVariant::Text16(ref y) => {
+                String::from_utf16(y).map_err(|_| ConversionError::new(x.summary(), "String"))
+            }
+            x => Err(ConversionError::new(x.summary(), "String")),
+        }
+    }
+}
+
+impl TryFrom<SmartVariant> for Vec<u16> {
+    type Error = ConversionError;
+
+    /// Converts string value into UTF-16 code units, exactly as they are stored in BSTR.
+    #[inline]
+    fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
+        match x {
+            SmartVariant::Text(x) => Ok(x.encode_utf16().collect()),
+            SmartVariant::Text16(x) => Ok(x.to_vec()),
+            x => Err(ConversionError::new(x.summary(), "Vec<u16>")),
+        }
+    }
+}
+
+/// Rust type with a VARIANT counterpart, target of [`SmartVariant::coerce`].
+///
+/// [`SmartVariant::coerce`]: enum.SmartVariant.html#method.coerce
+pub trait VariantType: TryFrom<SmartVariant, Error = ConversionError> {
+    /// VARIANT type values are coerced to.
+    const VARTYPE: VARENUM;
+}
+
+macro_rules! impl_variant_type {
+    ($($target:ty => $vartype:ident),+) => {
+        $(
+            impl VariantType for $target {
+                const VARTYPE: VARENUM = $vartype;
+            }
+        )+
+    };
+}
+
+impl_variant_type!(
+    i8 => VT_I1, u8 => VT_UI1, i16 => VT_I2, u16 => VT_UI2, i32 => VT_I4, u32 => VT_UI4, i64 => VT_I8, u64 => VT_UI8,
+    f32 => VT_R4, f64 => VT_R8, bool => VT_BOOL, String => VT_BSTR
+);
+
 pub struct AutoVariant(Cell<VARIANT>);
 
 impl AutoVariant {
@@ -60,19 +357,46 @@ impl AutoVariant {
     }
 
     #[inline]
-    pub fn clear(&mut self) -> HRESULT {
+    pub fn clear(&mut self) -> HResult {
         unsafe {
             if self.vtype() != VT_EMPTY {
                 let hresult = winapi::um::oleauto::VariantClear(self.0.get_mut());
                 *self.vtype_mut() = VT_EMPTY as u16;
 
-                hresult
+                HResult(hresult)
             } else {
-                0
+                HResult::S_OK
             }
         }
     }
 
+    /// Converts into a new VARIANT of `vartype` with VariantChangeTypeEx.
+    pub fn change_type(&self, vartype: VARENUM, lcid: LCID) -> Result<AutoVariant, HResult> {
+        let mut result = AutoVariant::new();
+        let hresult = unsafe {
+            VariantChangeTypeEx(
+                result.as_mut_ptr(),
+                self.as_ptr(),
+                lcid,
+                0,
+                vartype as VARTYPE,
+            )
+        };
+        HResult(hresult).ok().map(|_| result)
+    }
+
+    /// Converts ref to AutoVariant into pointer to VARIANT.
+    #[inline]
+    pub fn as_ptr(&self) -> *const VARIANT {
+        self.0.as_ptr() as *const VARIANT
+    }
+
+    /// Converts mutable ref to AutoVariant into mutable pointer to VARIANT.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut VARIANT {
+        self.0.as_ptr()
+    }
+
     #[inline]
     pub fn vtype(&self) -> VARENUM {
         unsafe { self.0.get().n1.n2().vt as VARENUM }
@@ -113,6 +437,8 @@ impl AutoVariant {
                 VT_UI1 => self.data().bVal(),     // An unsigned character. (u8)
                 VT_UI2 => self.data().uiVal(),    // An unsigned short. (u16)
                 VT_UI4 => self.data().ulVal(),    // An unsigned long.  (u32)
+                VT_I8 => self.data().llVal(),     // An 8-byte integer. (i64)
+                VT_UI8 => self.data().ullVal(),   // An 8-byte unsigned integer. (u64)
                 VT_INT => self.data().intVal(),   // An integer. (i32)
                 VT_UINT => self.data().uintVal(), // An unsigned integer. (u32)
                 VT_RECORD => self.data().n4(),    // A user-defined type.
@@ -143,6 +469,8 @@ impl AutoVariant {
                 VT_UI1 => self.data_mut().bVal_mut(),     // An unsigned character. (u8)
                 VT_UI2 => self.data_mut().uiVal_mut(),    // An unsigned short. (u16)
                 VT_UI4 => self.data_mut().ulVal_mut(),    // An unsigned long.  (u32)
+                VT_I8 => self.data_mut().llVal_mut(),     // An 8-byte integer. (i64)
+                VT_UI8 => self.data_mut().ullVal_mut(),   // An 8-byte unsigned integer. (u64)
                 VT_INT => self.data_mut().intVal_mut(),   // An integer. (i32)
                 VT_UINT => self.data_mut().uintVal_mut(), // An unsigned integer. (u32)
                 VT_RECORD => self.data_mut().n4_mut(),    // A user-defined type.
@@ -243,6 +571,16 @@ impl AutoVariant {
                 *self.vtype_mut() = VT_UI4 as u16;
                 *self.data_mut().ulVal_mut() = n_u32;
             }
+        } else if let Some(&n_i64) = value.downcast_ref::<i64>() {
+            unsafe {
+                *self.vtype_mut() = VT_I8 as u16;
+                *self.data_mut().llVal_mut() = n_i64;
+            }
+        } else if let Some(&n_u64) = value.downcast_ref::<u64>() {
+            unsafe {
+                *self.vtype_mut() = VT_UI8 as u16;
+                *self.data_mut().ullVal_mut() = n_u64;
+            }
         } else if let Some(&n_i32) = value.downcast_ref::<INT>() {
             unsafe {
                 *self.vtype_mut() = VT_INT as u16;
@@ -303,14 +641,17 @@ impl From<VARIANT> for AutoVariant {
     }
 }
 
-impl From<AutoVariant> for SmartVariant {
+/// Fails on types `SmartVariant` can't hold (`VT_NULL`, `VT_CY`, `VT_DECIMAL`, typed arrays...), the value is
+/// released then.
+impl TryFrom<AutoVariant> for SmartVariant {
+    type Error = ConversionError;
+
     #[inline]
-    fn from(x: AutoVariant) -> Self {
+    fn try_from(x: AutoVariant) -> Result<Self, Self::Error> {
         let vtype = x.vtype();
 
         unsafe {
-            (*x.0.as_ptr()).n1.n2_mut().vt = VT_EMPTY as u16;
-            match vtype {
+            let result = match vtype {
                 VT_EMPTY => SmartVariant::Empty,
                 VT_I2 => SmartVariant::Int2(*x.data().iVal()), // A 2-byte integer.
                 VT_I4 => SmartVariant::Int4(*x.data().lVal()), // A 4-byte integer.
@@ -318,41 +659,76 @@ impl From<AutoVariant> for SmartVariant {
                 VT_R8 => SmartVariant::Real8(*x.data().dblVal()), // An 8-byte real.
                 //VT_CY => SmartVariant::Currency(*x.data().cyVal()), // Currency. (i64)
                 VT_DATE => SmartVariant::Date(*x.data().date()), // A date. (f64)
-                VT_BSTR => SmartVariant::Text(AutoBSTR::from(*x.data().bstrVal()).into()), // A string.
-                VT_DISPATCH => SmartVariant::IDispatch(*x.data().pdispVal()), //An IDispatch pointer.
+                VT_BSTR => bstr_to_smart_variant(AutoBSTR::from(*x.data().bstrVal())), // A string.
+                VT_DISPATCH => {
+                    SmartVariant::IDispatch(AutoCOMInterface::from_raw(*x.data().pdispVal()))
+                } //An IDispatch pointer.
                 VT_ERROR => SmartVariant::ErrorCode(*x.data().scode()), // An SCODE value. (i32)
                 VT_BOOL => SmartVariant::Bool(*x.data().boolVal() == -1), //A Boolean value. True is -1 and false is 0. (i16)
                 VT_VARIANT => SmartVariant::Variant(*x.data().pvarVal()), // A variant pointer.
-                VT_UNKNOWN => SmartVariant::IUnknown(*x.data().punkVal()), // An IUnknown pointer.
+                VT_UNKNOWN => {
+                    SmartVariant::IUnknown(AutoCOMInterface::from_raw(*x.data().punkVal()))
+                } // An IUnknown pointer.
                 //VT_DECIMAL => SmartVariant::Decimal(*x.data().pdecVal()), // A 16-byte fixed-pointer value.
                 VT_I1 => SmartVariant::Int1(*x.data().cVal()), // A character. (i8)
                 VT_UI1 => SmartVariant::UInt1(*x.data().bVal()), // An unsigned character. (u8)
                 VT_UI2 => SmartVariant::UInt2(*x.data().uiVal()), // An unsigned short. (u16)
                 VT_UI4 => SmartVariant::UInt4(*x.data().ulVal()), // An unsigned long.  (u32)
-                VT_INT => SmartVariant::Int(*x.data().intVal()), // An integer. (i32)
+                VT_I8 => SmartVariant::Int8(*x.data().llVal()), // An 8-byte integer. (i64)
+                VT_UI8 => SmartVariant::UInt8(*x.data().ullVal()), // An 8-byte unsigned integer. (u64)
+                VT_INT => SmartVariant::Int(*x.data().intVal()),   // An integer. (i32)
                 VT_UINT => SmartVariant::UInt(*x.data().uintVal()), // An unsigned integer. (u32)
                 //VT_RECORD => SmartVariant::Record(*x.data().n4()), // A user-defined type.
                 VT_ARRAY => SmartVariant::Array(*x.data().parray()), // A SAFEARRAY pointer.
                 VT_BYREF => SmartVariant::ByRef(*x.data().byref()), // A void pointer for local use.
-                _ => panic!("Unsupported type for VARIANT"),
-            }
+                _ => {
+                    return Err(ConversionError::new(
+                        debug_dump_vartype(vtype as VARTYPE),
+                        "SmartVariant",
+                    ))
+                }
+            };
+            // The value is moved out, e.g. BSTR is owned by the result now.
+            (*x.0.as_ptr()).n1.n2_mut().vt = VT_EMPTY as u16;
+
+            Ok(result)
         }
     }
 }
 
-impl From<VARIANT> for SmartVariant {
+/// Converts BSTR into `Text` if it's a valid UTF-16, or into `Text16` keeping code units as is.
+fn bstr_to_smart_variant(x: AutoBSTR) -> SmartVariant {
+    match x.as_u16_slice() {
+        Some(utf16) => match String::from_utf16(utf16) {
+            Ok(text) => SmartVariant::Text(text.into()),
+            Err(_) => SmartVariant::Text16(utf16.into()),
+        },
+        None => SmartVariant::Text("".into()),
+    }
+}
+
+impl TryFrom<VARIANT> for SmartVariant {
+    type Error = ConversionError;
+
     #[inline]
-    fn from(x: VARIANT) -> Self {
-        AutoVariant::from(x).into()
+    fn try_from(x: VARIANT) -> Result<Self, Self::Error> {
+        SmartVariant::try_from(AutoVariant::from(x))
     }
 }
 
-impl From<SmartVariant> for AutoVariant {
+impl AutoVariant {
+    /// Converts `x`, failing with `SysAllocError` instead of panicking if BSTR of a string can't be allocated.
     #[inline]
-    fn from(x: SmartVariant) -> Self {
+    pub fn try_from_smart(x: SmartVariant) -> Result<AutoVariant, SysAllocError> {
+        AutoVariant::try_from_borrowed(&x)
+    }
+
+    /// Converts `x` without cloning it, strings are encoded into a new BSTR, interfaces get a new reference, arrays
+    /// are copied by `SafeArrayCopy` as the result destroys its array on drop.
+    fn try_from_borrowed(x: &SmartVariant) -> Result<AutoVariant, SysAllocError> {
         let mut result = AutoVariant::new();
-        unsafe {
-            match x {
+        Ok(unsafe {
+            match *x {
                 SmartVariant::Empty => result,
                 SmartVariant::Int2(x) => {
                     *result.vtype_mut() = VT_I2 as u16;
@@ -380,16 +756,21 @@ impl From<SmartVariant> for AutoVariant {
                     *result.data_mut().date_mut() = x;
                     result
                 } // A date. (f64)
-                SmartVariant::Text(x) => {
+                SmartVariant::Text(ref x) => {
                     *result.vtype_mut() = VT_BSTR as u16;
-                    *result.data_mut().bstrVal_mut() = AutoBSTR::try_from(x).unwrap().into();
+                    *result.data_mut().bstrVal_mut() = SysAllocStringFromStr(x)?;
                     result
                 } // A string.
-                SmartVariant::IDispatch(x) => {
+                SmartVariant::Text16(ref x) => {
+                    *result.vtype_mut() = VT_BSTR as u16;
+                    *result.data_mut().bstrVal_mut() = SysAllocStringLen(x)?;
+                    result
+                } // A string, not a valid UTF-16.
+                SmartVariant::IDispatch(ref x) => {
                     *result.vtype_mut() = VT_DISPATCH as u16;
-                    *result.data_mut().pdispVal_mut() = x;
+                    *result.data_mut().pdispVal_mut() = x.clone().into_raw();
                     result
-                } //An IDispatch pointer.
+                } //An IDispatch pointer, a new reference.
                 SmartVariant::ErrorCode(x) => {
                     *result.vtype_mut() = VT_ERROR as u16;
                     *result.data_mut().scode_mut() = x;
@@ -405,11 +786,11 @@ impl From<SmartVariant> for AutoVariant {
                     *result.data_mut().pvarVal_mut() = x;
                     result
                 } // A variant pointer.
-                SmartVariant::IUnknown(x) => {
+                SmartVariant::IUnknown(ref x) => {
                     *result.vtype_mut() = VT_UNKNOWN as u16;
-                    *result.data_mut().punkVal_mut() = x;
+                    *result.data_mut().punkVal_mut() = x.clone().into_raw();
                     result
-                } // An IUnknown pointer.
+                } // An IUnknown pointer, a new reference.
                 //SmartVariant::Decimal(x) => { *result.vtype_mut() = VT_DECIMAL as u16; *result.data_mut().pdecVal_mut() = x; result }, // A 16-byte fixed-pointer value.
                 SmartVariant::Int1(x) => {
                     *result.vtype_mut() = VT_I1 as u16;
@@ -431,6 +812,16 @@ impl From<SmartVariant> for AutoVariant {
                     *result.data_mut().ulVal_mut() = x;
                     result
                 } // An unsigned long.  (u32)
+                SmartVariant::Int8(x) => {
+                    *result.vtype_mut() = VT_I8 as u16;
+                    *result.data_mut().llVal_mut() = x;
+                    result
+                } // An 8-byte integer. (i64)
+                SmartVariant::UInt8(x) => {
+                    *result.vtype_mut() = VT_UI8 as u16;
+                    *result.data_mut().ullVal_mut() = x;
+                    result
+                } // An 8-byte unsigned integer. (u64)
                 SmartVariant::Int(x) => {
                     *result.vtype_mut() = VT_INT as u16;
                     *result.data_mut().intVal_mut() = x;
@@ -443,24 +834,127 @@ impl From<SmartVariant> for AutoVariant {
                 } // An unsigned integer. (u32)
                 //SmartVariant::Record(x) => { *result.vtype_mut() = VT_RECORD as u16; *result.data_mut().n4_mut() = x; result }, // A user-defined type.
                 SmartVariant::Array(x) => {
+                    let mut copy = std::ptr::null_mut();
+                    if !x.is_null() {
+                        match SafeArrayCopy(x, &mut copy) {
+                            hresult if winerror::SUCCEEDED(hresult) => {}
+                            winerror::E_OUTOFMEMORY => {
+                                return Err(SysAllocError::BStrAllocationError)
+                            }
+                            _ => return Err(SysAllocError::InvalidPointerError),
+                        }
+                    }
                     *result.vtype_mut() = VT_ARRAY as u16;
-                    *result.data_mut().parray_mut() = x;
+                    *result.data_mut().parray_mut() = copy;
                     result
-                } // A SAFEARRAY pointer.
+                } // A SAFEARRAY pointer, a copy.
                 SmartVariant::ByRef(x) => {
                     *result.vtype_mut() = VT_BYREF as u16;
                     *result.data_mut().byref_mut() = x;
                     result
                 } // A void pointer for local use.
             }
-        }
+        })
+    }
+}
+
+/// Fails with `SysAllocError` if BSTR of a string can't be allocated, same as [`AutoVariant::try_from_smart`].
+///
+/// [`AutoVariant::try_from_smart`]: struct.AutoVariant.html#method.try_from_smart
+impl TryFrom<SmartVariant> for AutoVariant {
+    type Error = SysAllocError;
+
+    #[inline]
+    fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
+        AutoVariant::try_from_smart(x)
+    }
+}
+
+/// Borrowed argument of a call, strings are encoded into BSTR once when the call marshals arguments, without
+/// intermediate `String` or `Vec<u16>` copies.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rusty_winapi::prelude::*;
+/// # use rusty_winapi::smart_variant::SmartVariantRef;
+/// # use winapi::um::oaidl::IDispatch;
+/// # fn find(sheet: &mut AutoCOMInterface<IDispatch>, wide: &[u16]) -> ComResult<SmartVariant> {
+/// let after = SmartVariant::Empty;
+/// sheet.call_ref("Find", &[SmartVariantRef::TextWide(wide), (&after).into()])
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmartVariantRef<'a> {
+    /// Any value, borrowed.
+    Value(&'a SmartVariant),
+    /// String, encoded into UTF-16 directly in the BSTR.
+    Text(&'a str),
+    /// UTF-16 string, copied into the BSTR as is.
+    TextWide(&'a [u16]),
+}
+
+impl<'a> From<&'a SmartVariant> for SmartVariantRef<'a> {
+    #[inline]
+    fn from(x: &'a SmartVariant) -> Self {
+        SmartVariantRef::Value(x)
     }
 }
 
-impl From<SmartVariant> for VARIANT {
+impl<'a> From<&'a str> for SmartVariantRef<'a> {
     #[inline]
-    fn from(x: SmartVariant) -> Self {
-        AutoVariant::from(x).into()
+    fn from(x: &'a str) -> Self {
+        SmartVariantRef::Text(x)
+    }
+}
+
+impl<'a> From<&'a [u16]> for SmartVariantRef<'a> {
+    #[inline]
+    fn from(x: &'a [u16]) -> Self {
+        SmartVariantRef::TextWide(x)
+    }
+}
+
+impl<'a> From<BStr<'a>> for SmartVariantRef<'a> {
+    #[inline]
+    fn from(x: BStr<'a>) -> Self {
+        SmartVariantRef::TextWide(x.as_slice())
+    }
+}
+
+impl AutoVariant {
+    /// Converts borrowed `x` without cloning it, failing with `SysAllocError` if BSTR of a string can't be
+    /// allocated.
+    pub fn try_from_ref(x: SmartVariantRef<'_>) -> Result<AutoVariant, SysAllocError> {
+        let bstr = match x {
+            SmartVariantRef::Value(x) => return AutoVariant::try_from_borrowed(x),
+            SmartVariantRef::Text(x) => SysAllocStringFromStr(x)?,
+            SmartVariantRef::TextWide(x) => SysAllocStringLen(x)?,
+        };
+        let mut result = AutoVariant::new();
+        unsafe {
+            *result.vtype_mut() = VT_BSTR as u16;
+            *result.data_mut().bstrVal_mut() = bstr;
+        }
+
+        Ok(result)
+    }
+
+    /// Converts `x` into a raw VARIANT, same as [`try_from_ref`] but an array pointer is put as is, without a copy.
+    /// An argument buffer borrows such array from the caller, a server result hands it over to the client.
+    ///
+    /// [`try_from_ref`]: #method.try_from_ref
+    pub(crate) fn try_raw_from_ref(x: SmartVariantRef<'_>) -> Result<VARIANT, SysAllocError> {
+        if let SmartVariantRef::Value(SmartVariant::Array(x)) = x {
+            let mut result = AutoVariant::new();
+            unsafe {
+                *result.vtype_mut() = VT_ARRAY as u16;
+                *result.data_mut().parray_mut() = *x;
+            }
+            return Ok(VARIANT::from(result));
+        }
+
+        Ok(VARIANT::from(AutoVariant::try_from_ref(x)?))
     }
 }
 
@@ -478,4 +972,182 @@ mod tests {
 
     #[test]
     fn test1() {}
+
+    #[test]
+    fn test_try_from_SmartVariant() {
+        assert_eq!(Ok(42), i32::try_from(SmartVariant::Int4(42)));
+        assert_eq!(Ok(42), i32::try_from(SmartVariant::Int(42)));
+        assert_eq!(
+            Ok(String::from("abc")),
+            String::try_from(SmartVariant::Text("abc".into()))
+        );
+
+        let e = i32::try_from(SmartVariant::Text("abc".into())).unwrap_err();
+        assert_eq!(r#"cannot convert VT_BSTR "abc" to i32"#, e.to_string());
+
+        let e = bool::try_from(SmartVariant::Empty).unwrap_err();
+        assert_eq!("cannot convert VT_EMPTY to bool", e.to_string());
+    }
+
+    #[test]
+    fn test_Int8_UInt8() {
+        let variant: VARIANT = AutoVariant::try_from(SmartVariant::Int8(-0x1_0000_0000))
+            .unwrap()
+            .into();
+        assert_eq!(
+            SmartVariant::Int8(-0x1_0000_0000),
+            SmartVariant::try_from(variant).unwrap()
+        );
+
+        let variant: VARIANT = AutoVariant::try_from(SmartVariant::UInt8(std::u64::MAX))
+            .unwrap()
+            .into();
+        assert_eq!(
+            SmartVariant::UInt8(std::u64::MAX),
+            SmartVariant::try_from(variant).unwrap()
+        );
+
+        let auto_variant = AutoVariant::new().value_set(&42i64);
+        assert_eq!(VT_I8, auto_variant.vtype());
+        assert_eq!(Some(&42i64), auto_variant.value().downcast_ref::<i64>());
+    }
+
+    #[test]
+    fn test_Text16_round_trip() {
+        // Embedded NUL and unpaired surrogate.
+        let utf16: Vec<u16> = vec![0x0041, 0x0000, 0xD800, 0x0042];
+
+        let variant: VARIANT = AutoVariant::try_from(SmartVariant::Text16(utf16.as_slice().into()))
+            .unwrap()
+            .into();
+        let smart_variant = SmartVariant::try_from(variant).unwrap();
+        assert_eq!(SmartVariant::Text16(utf16.as_slice().into()), smart_variant);
+        assert_eq!(Ok(utf16), Vec::<u16>::try_from(smart_variant.clone()));
+        assert!(String::try_from(smart_variant).is_err());
+
+        let variant: VARIANT = AutoVariant::try_from(SmartVariant::Text("A\u{0000}B".into()))
+            .unwrap()
+            .into();
+        assert_eq!(
+            SmartVariant::Text("A\u{0000}B".into()),
+            SmartVariant::try_from(variant).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_SmartVariant_try_from_unsupported() {
+        let mut variant = AutoVariant::new();
+        unsafe { *variant.vtype_mut() = VT_NULL as u16 };
+        let e = SmartVariant::try_from(variant).unwrap_err();
+        assert_eq!("VT_NULL", e.source_summary());
+        assert_eq!("SmartVariant", e.target());
+
+        let mut variant = AutoVariant::new();
+        unsafe { *variant.vtype_mut() = (VT_ARRAY | VT_I4) as u16 };
+        let e = SmartVariant::try_from(VARIANT::from(variant)).unwrap_err();
+        assert_eq!("VT_ARRAY|VT_I4", e.source_summary());
+    }
+
+    #[test]
+    fn test_Text_clone_shares_buffer() {
+        let text = SmartVariant::Text("Test line.".into());
+        match (&text, &text.clone()) {
+            (SmartVariant::Text(a), SmartVariant::Text(b)) => assert!(Arc::ptr_eq(a, b)),
+            _ => unreachable!(),
+        }
+    }
+    #[test]
+    fn test_AutoVariant_try_from_smart() {
+        let text = SmartVariant::Text("Test line.".into());
+        let variant = AutoVariant::try_from_smart(text.clone()).unwrap();
+        assert_eq!(VT_BSTR, variant.vtype());
+        assert_eq!(text, SmartVariant::try_from(variant).unwrap());
+        assert_eq!(
+            SmartVariant::Int4(42),
+            SmartVariant::try_from(AutoVariant::try_from_smart(SmartVariant::Int4(42)).unwrap())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_AutoVariant_try_from_smart_array() {
+        use crate::ffi::{SafeArrayCreate, SafeArrayGetDim};
+        use winapi::um::oleauto::SafeArrayDestroy;
+
+        let mut bounds = [SAFEARRAYBOUND {
+            cElements: 3,
+            lLbound: 0,
+        }];
+        let psa = unsafe { SafeArrayCreate(VT_I4 as VARTYPE, 1, bounds.as_mut_ptr()) };
+        assert!(!psa.is_null());
+
+        // The owning variant destroys its copy, the caller's array stays valid.
+        let variant = AutoVariant::try_from_smart(SmartVariant::Array(psa)).unwrap();
+        assert_eq!(VT_ARRAY, variant.vtype());
+        assert_ne!(psa, unsafe { *variant.data().parray() });
+        drop(variant);
+        assert_eq!(1, unsafe { SafeArrayGetDim(psa) });
+
+        let arg = AutoVariant::try_raw_from_ref(SmartVariantRef::Value(&SmartVariant::Array(psa)))
+            .unwrap();
+        assert_eq!(psa, unsafe { *arg.n1.n2().n3.parray() });
+        unsafe { SafeArrayDestroy(psa) };
+    }
+
+    #[test]
+    fn test_AutoVariant_try_from_ref() {
+        let text: Arc<str> = "Тестовая строка 🌍".into();
+        let value = SmartVariant::Text(text.clone());
+        let variant = AutoVariant::try_from_ref((&value).into()).unwrap();
+        assert_eq!(2, Arc::strong_count(&text));
+        assert_eq!(value, SmartVariant::try_from(variant).unwrap());
+
+        let wide: Vec<u16> = text.encode_utf16().collect();
+        let variant = AutoVariant::try_from_ref(wide.as_slice().into()).unwrap();
+        assert_eq!(value, SmartVariant::try_from(variant).unwrap());
+        let variant = AutoVariant::try_from_ref(SmartVariantRef::Text(&text)).unwrap();
+        assert_eq!(value, SmartVariant::try_from(variant).unwrap());
+    }
+    #[test]
+    fn test_SmartVariant_coerce() {
+        assert_eq!(Ok(42), SmartVariant::Int4(42).coerce::<i32>());
+        assert_eq!(Ok(42), SmartVariant::Text("42".into()).coerce::<i32>());
+        assert_eq!(Ok(42.0), SmartVariant::Int2(42).coerce::<f64>());
+        assert_eq!(
+            Ok(String::from("7")),
+            SmartVariant::UInt1(7).coerce::<String>()
+        );
+        assert_eq!(
+            Err(ConversionError::new(r#"VT_BSTR "abc""#, "i32")),
+            SmartVariant::Text("abc".into()).coerce::<i32>()
+        );
+    }
+
+    #[test]
+    fn test_SmartVariant_coerce_strict_string() {
+        let lenient = Config::builder().strict_string_coercion(false).build();
+        assert_eq!(
+            Ok(42),
+            SmartVariant::Text("42".into()).coerce_with::<i32>(&lenient)
+        );
+        assert_eq!(
+            Ok(String::from("7")),
+            SmartVariant::UInt1(7).coerce_with::<String>(&lenient)
+        );
+
+        let strict = Config::builder().strict_string_coercion(true).build();
+        assert_eq!(
+            Err(ConversionError::new(r#"VT_BSTR "42""#, "i32")),
+            SmartVariant::Text("42".into()).coerce_with::<i32>(&strict)
+        );
+        assert_eq!(
+            Err(ConversionError::new("VT_UI1 7", "String")),
+            SmartVariant::UInt1(7).coerce_with::<String>(&strict)
+        );
+        assert_eq!(
+            Ok(String::from("abc")),
+            SmartVariant::Text("abc".into()).coerce_with::<String>(&strict)
+        );
+        assert_eq!(Ok(42.0), SmartVariant::Int2(42).coerce_with::<f64>(&strict));
+    }
 }
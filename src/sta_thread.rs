@@ -0,0 +1,230 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Dedicated single-threaded apartment worker.
+//!
+//! Apartment-threaded servers (Office, most ActiveX controls) must be called from the thread which created them,
+//! and that thread must pump window messages. [`StaThread`] owns such a thread: it initializes an STA, pumps
+//! messages and runs closures submitted from any other thread, returning their results through receivers.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rusty_winapi::activate::Activate;
+//! use rusty_winapi::smart_idispatch::SmartIDispatch;
+//! use rusty_winapi::sta_thread::StaThread;
+//! use winapi::um::oaidl::IDispatch;
+//!
+//! let sta = StaThread::new().unwrap();
+//! let version = sta
+//!     .call(|| {
+//!         let mut excel = Activate::<IDispatch>::new().progid("Excel.Application").create()?;
+//!         excel.get("Version").map(|x| x.summary())
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! [`StaThread`]: struct.StaThread.html
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::winerror;
+use winapi::um::winuser::{
+    DispatchMessageW, GetMessageW, PeekMessageW, PostThreadMessageW, TranslateMessage, MSG,
+    PM_NOREMOVE, WM_APP, WM_QUIT,
+};
+
+use crate::cancel::current_thread_id;
+use crate::com_apartment::ComApartment;
+use crate::error::ComResult;
+
+/// Thread message telling worker that a job is queued.
+const WM_STA_JOB: u32 = WM_APP + 0x5354;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Receiver of a result of a closure submitted to [`StaThread`] or [`MtaPool`].
+///
+/// [`StaThread`]: struct.StaThread.html
+/// [`MtaPool`]: ../mta_pool/struct.MtaPool.html
+pub struct StaReceiver<R>(Receiver<R>);
+
+impl<R> From<Receiver<R>> for StaReceiver<R> {
+    fn from(x: Receiver<R>) -> Self {
+        StaReceiver(x)
+    }
+}
+
+impl<R> StaReceiver<R> {
+    /// Blocks until the result is available.
+    ///
+    /// # Errors
+    ///
+    /// If closure panicked or worker thread has gone, returns `RustyWinapiError::Com(RPC_E_DISCONNECTED)`.
+    pub fn wait(self) -> ComResult<R> {
+        self.0
+            .recv()
+            .map_err(|_| winerror::RPC_E_DISCONNECTED.into())
+    }
+
+    /// Returns the result if it's available already, `Ok(None)` otherwise.
+    pub fn try_wait(&self) -> ComResult<Option<R>> {
+        match self.0.try_recv() {
+            Ok(x) => Ok(Some(x)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(winerror::RPC_E_DISCONNECTED.into()),
+        }
+    }
+}
+
+/// Worker thread initialized as a single-threaded apartment, running submitted closures in order.
+///
+/// COM objects created by closures live in the worker apartment, keep them inside the worker (e.g. in thread-local
+/// storage) or marshal them out. Dropping `StaThread` stops the worker after queued closures are run.
+pub struct StaThread {
+    jobs: Sender<Job>,
+    thread_id: DWORD,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StaThread {
+    /// Starts a new worker thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns error of failed COM initialization of the worker.
+    pub fn new() -> ComResult<Self> {
+        let (jobs, queue) = channel::<Job>();
+        let (started, start) = channel::<ComResult<DWORD>>();
+
+        let thread = std::thread::Builder::new()
+            .name("rusty_winapi STA".into())
+            .spawn(move || {
+                let _com = match ComApartment::init_sta() {
+                    Ok(x) => x,
+                    Err(x) => {
+                        let _ = started.send(Err(x));
+                        return;
+                    }
+                };
+
+                // Message queue of the thread must exist before anybody posts to it.
+                let mut msg: MSG = unsafe { std::mem::zeroed() };
+                unsafe { PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_NOREMOVE) };
+                let _ = started.send(Ok(current_thread_id()));
+
+                run_message_loop(&queue);
+            })
+            .map_err(|_| winerror::E_OUTOFMEMORY)?;
+
+        match start.recv() {
+            Ok(Ok(thread_id)) => Ok(StaThread {
+                jobs,
+                thread_id,
+                thread: Some(thread),
+            }),
+            Ok(Err(x)) => {
+                let _ = thread.join();
+                Err(x)
+            }
+            Err(_) => Err(winerror::RPC_E_DISCONNECTED.into()),
+        }
+    }
+
+    /// Id of the worker thread, e.g. for [`cancel_call`].
+    ///
+    /// [`cancel_call`]: ../cancel/fn.cancel_call.html
+    #[inline]
+    pub fn thread_id(&self) -> DWORD {
+        self.thread_id
+    }
+
+    /// Submits a closure to be run on the worker thread, returns receiver of its result.
+    pub fn spawn<F, R>(&self, f: F) -> StaReceiver<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (result, receiver) = channel();
+        let job: Job = Box::new(move || {
+            let _ = result.send(f());
+        });
+
+        if self.jobs.send(job).is_ok() {
+            unsafe { PostThreadMessageW(self.thread_id, WM_STA_JOB, 0, 0) };
+        }
+
+        StaReceiver(receiver)
+    }
+
+    /// Runs a closure on the worker thread and waits for its result.
+    ///
+    /// # Errors
+    ///
+    /// * If closure panicked or worker thread has gone, returns `RustyWinapiError::Com(RPC_E_DISCONNECTED)`.
+    /// * Otherwise returns error of the closure.
+    pub fn call<F, R>(&self, f: F) -> ComResult<R>
+    where
+        F: FnOnce() -> ComResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn(f).wait()?
+    }
+}
+
+impl Drop for StaThread {
+    fn drop(&mut self) {
+        unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+        if let Some(x) = self.thread.take() {
+            let _ = x.join();
+        }
+    }
+}
+
+fn run_message_loop(queue: &Receiver<Job>) {
+    let mut msg: MSG = unsafe { std::mem::zeroed() };
+
+    loop {
+        let result = unsafe { GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) };
+        if result == 0 || result == -1 {
+            break;
+        }
+
+        if msg.hwnd.is_null() && msg.message == WM_STA_JOB {
+            run_jobs(queue);
+        } else {
+            unsafe {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    // Jobs queued before shutdown are run anyway.
+    run_jobs(queue);
+}
+
+fn run_jobs(queue: &Receiver<Job>) {
+    while let Ok(job) = queue.try_recv() {
+        // Panicking job drops its result sender, so its receiver gets RPC_E_DISCONNECTED.
+        let _ = catch_unwind(AssertUnwindSafe(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RustyWinapiError;
+
+    #[test]
+    fn test_StaReceiver_disconnected() {
+        let (result, receiver) = channel::<i32>();
+        let receiver = StaReceiver(receiver);
+        drop(result);
+        let disconnected = RustyWinapiError::Com(winerror::RPC_E_DISCONNECTED);
+        assert_eq!(Err(disconnected.clone()), receiver.try_wait());
+        assert_eq!(Err(disconnected), receiver.wait());
+    }
+}
@@ -0,0 +1,59 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Builder of COM objects, on non-Windows targets creation always fails with `RustyWinapiError::NotSupported`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::prelude::*;
+//!
+//! let excel = Activate::<IDispatch>::new().progid("Excel.Application").create();
+//! assert_eq!(Some(RustyWinapiError::NotSupported), excel.err());
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::{ComResult, RustyWinapiError};
+
+/// Builder of a new COM object instance, can't create one on non-Windows targets.
+pub struct Activate<T> {
+    progid: Option<String>,
+    server: Option<String>,
+    _interface: PhantomData<*mut T>,
+}
+
+impl<T> Activate<T> {
+    /// Starts a new activation.
+    pub fn new() -> Self {
+        Activate {
+            progid: None,
+            server: None,
+            _interface: PhantomData,
+        }
+    }
+
+    /// Class to activate by ProgID, e.g. `"Excel.Application"`.
+    pub fn progid(mut self, progid: &str) -> Self {
+        self.progid = Some(progid.into());
+        self
+    }
+
+    /// Remote machine name to activate object on.
+    pub fn server(mut self, server: &str) -> Self {
+        self.server = Some(server.into());
+        self
+    }
+
+    /// Fails with `RustyWinapiError::NotSupported`.
+    #[inline]
+    pub fn create(self) -> ComResult<AutoCOMInterface<T>> {
+        Err(RustyWinapiError::NotSupported)
+    }
+}
+
+impl<T> Default for Activate<T> {
+    fn default() -> Self {
+        Activate::new()
+    }
+}
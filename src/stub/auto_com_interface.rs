@@ -0,0 +1,74 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Owning wrapper of COM interface pointers, on non-Windows targets it's always NULL.
+//!
+//! [`IUnknown`] and [`IDispatch`] stand for the `winapi` interfaces there, so signatures naming
+//! `AutoCOMInterface<IDispatch>` compile on every target. They're re-exported by the prelude on Windows too.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::prelude::*;
+//!
+//! let object = AutoCOMInterface::<IDispatch>::default();
+//! assert!(object.is_null());
+//! ```
+//!
+//! [`IUnknown`]: enum.IUnknown.html
+//! [`IDispatch`]: enum.IDispatch.html
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::error::{ComResult, RustyWinapiError};
+
+/// Placeholder of `winapi::um::unknwnbase::IUnknown`, no value of it exists.
+pub enum IUnknown {}
+
+/// Placeholder of `winapi::um::oaidl::IDispatch`, no value of it exists.
+pub enum IDispatch {}
+
+/// Interface wrapper, can't hold an interface on non-Windows targets.
+pub struct AutoCOMInterface<T>(PhantomData<*mut T>);
+
+impl<T> AutoCOMInterface<T> {
+    /// Always `true`.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        true
+    }
+
+    /// Fails with `RustyWinapiError::NotSupported`.
+    #[inline]
+    pub fn cast<U>(&self) -> ComResult<AutoCOMInterface<U>> {
+        Err(RustyWinapiError::NotSupported)
+    }
+}
+
+impl<T> Default for AutoCOMInterface<T> {
+    #[inline]
+    fn default() -> Self {
+        AutoCOMInterface(PhantomData)
+    }
+}
+
+impl<T> Clone for AutoCOMInterface<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        AutoCOMInterface::default()
+    }
+}
+
+impl<T, U> PartialEq<AutoCOMInterface<U>> for AutoCOMInterface<T> {
+    /// Empty wrappers are equal to each other.
+    #[inline]
+    fn eq(&self, other: &AutoCOMInterface<U>) -> bool {
+        true
+    }
+}
+
+impl<T> fmt::Debug for AutoCOMInterface<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AutoCOMInterface<{}>(0x0)", std::any::type_name::<T>())
+    }
+}
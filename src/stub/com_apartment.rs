@@ -0,0 +1,68 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! COM initialization of the current thread, on non-Windows targets it always fails with
+//! `RustyWinapiError::NotSupported`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::com_apartment::ComApartment;
+//!
+//! match ComApartment::init_sta() {
+//!     Ok(_com) => println!("COM is initialized"),
+//!     Err(e) => println!("COM isn't available: {}", e),
+//! }
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::error::{ComResult, RustyWinapiError};
+
+/// Guard of COM initialization of the current thread, can't be created on non-Windows targets.
+pub struct ComApartment {
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl ComApartment {
+    /// Fails with `RustyWinapiError::NotSupported`.
+    #[inline]
+    pub fn init_sta() -> ComResult<Self> {
+        Err(RustyWinapiError::NotSupported)
+    }
+
+    /// Fails with `RustyWinapiError::NotSupported`.
+    #[inline]
+    pub fn init_mta() -> ComResult<Self> {
+        Err(RustyWinapiError::NotSupported)
+    }
+
+    /// Fails with `RustyWinapiError::NotSupported`.
+    #[inline]
+    pub fn init_default() -> ComResult<Self> {
+        Err(RustyWinapiError::NotSupported)
+    }
+
+    /// Always `false`, the guard can't exist.
+    #[inline]
+    pub fn already_initialized(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ComApartment_not_supported() {
+        assert!(!crate::is_supported());
+        assert_eq!(
+            Some(RustyWinapiError::NotSupported),
+            ComApartment::init_sta().err()
+        );
+        assert_eq!(
+            Some(RustyWinapiError::NotSupported),
+            ComApartment::init_mta().err()
+        );
+    }
+}
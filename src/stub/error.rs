@@ -0,0 +1,95 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Errors of the crate on non-Windows targets, where no COM call can be made.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::com_apartment::ComApartment;
+//! use rusty_winapi::error::RustyWinapiError;
+//!
+//! if !rusty_winapi::is_supported() {
+//!     assert!(ComApartment::init_sta().is_err());
+//!     println!("{}", RustyWinapiError::NotSupported);
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+
+/// Error of a failed `TryFrom` conversion, carrying both the source value summary and the requested target type.
+///
+/// Displays as `cannot convert VT_BSTR "abc" to i32`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConversionError {
+    source: String,
+    target: &'static str,
+}
+
+impl ConversionError {
+    /// Creates a new error from a source value summary and a target type name.
+    pub fn new<S: Into<String>>(source: S, target: &'static str) -> Self {
+        ConversionError {
+            source: source.into(),
+            target,
+        }
+    }
+
+    /// Summary of the value which failed to convert (VT name and value, when printable).
+    #[inline]
+    pub fn source_summary(&self) -> &str {
+        &self.source
+    }
+
+    /// Name of the requested target type.
+    #[inline]
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot convert {} to {}", self.source, self.target)
+    }
+}
+
+impl Error for ConversionError {}
+
+/// Crate-wide error, on non-Windows targets every operation fails with `NotSupported`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RustyWinapiError {
+    /// COM isn't available on the target.
+    NotSupported,
+    /// Value conversion failed.
+    Conversion(ConversionError),
+}
+
+/// Result of COM calls of the crate, see [`RustyWinapiError`].
+///
+/// [`RustyWinapiError`]: enum.RustyWinapiError.html
+pub type ComResult<T> = Result<T, RustyWinapiError>;
+
+impl fmt::Display for RustyWinapiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RustyWinapiError::NotSupported => write!(f, "COM is supported on Windows only"),
+            RustyWinapiError::Conversion(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl Error for RustyWinapiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RustyWinapiError::Conversion(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConversionError> for RustyWinapiError {
+    fn from(x: ConversionError) -> Self {
+        RustyWinapiError::Conversion(x)
+    }
+}
@@ -0,0 +1,13 @@
+//! Commonly used traits and types, glob-import them with `use rusty_winapi::prelude::*;`.
+//!
+//! On non-Windows targets only the portable part of the API is available, every COM operation fails with
+//! `RustyWinapiError::NotSupported`.
+
+pub use std::convert::{TryFrom, TryInto};
+
+pub use crate::activate::Activate;
+pub use crate::auto_com_interface::{AutoCOMInterface, IDispatch, IUnknown};
+pub use crate::com_apartment::ComApartment;
+pub use crate::error::{ComResult, ConversionError, RustyWinapiError};
+pub use crate::smart_idispatch::SmartIDispatch;
+pub use crate::smart_variant::SmartVariant;
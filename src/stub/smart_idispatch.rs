@@ -0,0 +1,117 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Late-bound automation calls, on non-Windows targets every call fails with `RustyWinapiError::NotSupported`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::prelude::*;
+//!
+//! let mut workbooks = AutoCOMInterface::<IDispatch>::default();
+//! let result = workbooks.call("Open", ("C:\\report.xlsx", 0, true));
+//! assert_eq!(Some(RustyWinapiError::NotSupported), result.err());
+//! ```
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::{ComResult, RustyWinapiError};
+use crate::smart_variant::SmartVariant;
+
+/// Name-based helpers of IDispatch, see [module level documentation].
+///
+/// [module level documentation]: index.html
+pub trait SmartIDispatch {
+    /// Fails with `RustyWinapiError::NotSupported`.
+    fn call(&mut self, method: &str, params: impl IntoParams) -> ComResult<SmartVariant>
+    where
+        Self: Sized,
+    {
+        Err(RustyWinapiError::NotSupported)
+    }
+
+    /// Fails with `RustyWinapiError::NotSupported`.
+    fn get(&mut self, property: &str) -> ComResult<SmartVariant> {
+        Err(RustyWinapiError::NotSupported)
+    }
+
+    /// Fails with `RustyWinapiError::NotSupported`.
+    fn put(&mut self, property: &str, value: impl Into<SmartVariant>) -> ComResult<SmartVariant>
+    where
+        Self: Sized,
+    {
+        Err(RustyWinapiError::NotSupported)
+    }
+}
+
+impl<T> SmartIDispatch for AutoCOMInterface<T> {}
+
+/// Arguments of [`SmartIDispatch::call`], a slice of `SmartVariant` or plain values in an array, a `Vec` or a tuple.
+///
+/// [`SmartIDispatch::call`]: trait.SmartIDispatch.html#method.call
+pub trait IntoParams {
+    /// Calls `f` with the arguments, borrowed slices are passed without copies.
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R;
+}
+
+impl IntoParams for &[SmartVariant] {
+    #[inline]
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+        f(self)
+    }
+}
+
+impl<const N: usize> IntoParams for &[SmartVariant; N] {
+    #[inline]
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+        f(self)
+    }
+}
+
+impl IntoParams for &Vec<SmartVariant> {
+    #[inline]
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+        f(self)
+    }
+}
+
+impl<T: Into<SmartVariant>, const N: usize> IntoParams for [T; N] {
+    #[inline]
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+        f(&self.map(Into::into))
+    }
+}
+
+impl<T: Into<SmartVariant>> IntoParams for Vec<T> {
+    #[inline]
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+        f(&self.into_iter().map(Into::into).collect::<Vec<_>>())
+    }
+}
+
+/// No arguments.
+impl IntoParams for () {
+    #[inline]
+    fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+        f(&[])
+    }
+}
+
+macro_rules! impl_into_params {
+    ($($name:ident),+) => {
+        impl<$($name: Into<SmartVariant>),+> IntoParams for ($($name,)+) {
+            #[inline]
+            fn with_params<R>(self, f: impl FnOnce(&[SmartVariant]) -> R) -> R {
+                let ($($name,)+) = self;
+                f(&[$($name.into()),+])
+            }
+        }
+    };
+}
+
+impl_into_params!(A);
+impl_into_params!(A, B);
+impl_into_params!(A, B, C);
+impl_into_params!(A, B, C, D);
+impl_into_params!(A, B, C, D, E);
+impl_into_params!(A, B, C, D, E, F);
+impl_into_params!(A, B, C, D, E, F, G);
+impl_into_params!(A, B, C, D, E, F, G, H);
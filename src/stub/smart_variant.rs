@@ -0,0 +1,141 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Values of automation calls, on non-Windows targets without the variants holding raw VARIANT, SAFEARRAY and
+//! by-reference pointers.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::prelude::*;
+//!
+//! let value = SmartVariant::from(42);
+//! assert_eq!(Ok(42), i32::try_from(value));
+//! ```
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::auto_com_interface::{AutoCOMInterface, IDispatch, IUnknown};
+use crate::error::ConversionError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SmartVariant {
+    Empty,
+    Int2(i16),
+    Int4(i32),
+    Real4(f32),
+    Real8(f64),
+    Date(f64),
+    Text(Arc<str>),
+    Text16(Arc<[u16]>),
+    IDispatch(AutoCOMInterface<IDispatch>),
+    ErrorCode(i32),
+    Bool(bool),
+    IUnknown(AutoCOMInterface<IUnknown>),
+    Int1(i8),
+    UInt1(u8),
+    UInt2(u16),
+    UInt4(u32),
+    Int8(i64),
+    UInt8(u64),
+    Int(i32),
+    UInt(u32),
+}
+
+impl SmartVariant {
+    /// Takes the owned IDispatch out of the value, interfaces are always NULL on non-Windows targets.
+    pub fn into_dispatch(self) -> Result<AutoCOMInterface<IDispatch>, ConversionError> {
+        Err(ConversionError::new(
+            self.summary(),
+            "AutoCOMInterface<IDispatch>",
+        ))
+    }
+
+    /// Short human readable summary of the value for diagnostics, e.g. `VT_BSTR "abc"` or `VT_I4 42`.
+    pub fn summary(&self) -> String {
+        match self {
+            SmartVariant::Empty => "VT_EMPTY".into(),
+            SmartVariant::Int2(x) => format!("VT_I2 {}", x),
+            SmartVariant::Int4(x) => format!("VT_I4 {}", x),
+            SmartVariant::Real4(x) => format!("VT_R4 {}", x),
+            SmartVariant::Real8(x) => format!("VT_R8 {}", x),
+            SmartVariant::Date(x) => format!("VT_DATE {}", x),
+            SmartVariant::Text(x) => format!("VT_BSTR {:?}", x),
+            SmartVariant::Text16(x) => format!("VT_BSTR {:?}", String::from_utf16_lossy(x)),
+            SmartVariant::IDispatch(_) => "VT_DISPATCH 0x0".into(),
+            SmartVariant::ErrorCode(x) => format!("VT_ERROR 0x{:08X}", x),
+            SmartVariant::Bool(x) => format!("VT_BOOL {}", x),
+            SmartVariant::IUnknown(_) => "VT_UNKNOWN 0x0".into(),
+            SmartVariant::Int1(x) => format!("VT_I1 {}", x),
+            SmartVariant::UInt1(x) => format!("VT_UI1 {}", x),
+            SmartVariant::UInt2(x) => format!("VT_UI2 {}", x),
+            SmartVariant::UInt4(x) => format!("VT_UI4 {}", x),
+            SmartVariant::Int8(x) => format!("VT_I8 {}", x),
+            SmartVariant::UInt8(x) => format!("VT_UI8 {}", x),
+            SmartVariant::Int(x) => format!("VT_INT {}", x),
+            SmartVariant::UInt(x) => format!("VT_UINT {}", x),
+        }
+    }
+}
+
+macro_rules! impl_try_from_smart_variant {
+    ($target:ty, $name:expr, $($variant:ident),+) => {
+        impl TryFrom<SmartVariant> for $target {
+            type Error = ConversionError;
+
+            #[inline]
+            fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
+                match x {
+                    $(SmartVariant::$variant(x) => Ok(x),)+
+                    x => Err(ConversionError::new(x.summary(), $name)),
+                }
+            }
+        }
+    };
+}
+
+impl_try_from_smart_variant!(i8, "i8", Int1);
+impl_try_from_smart_variant!(u8, "u8", UInt1);
+impl_try_from_smart_variant!(i16, "i16", Int2);
+impl_try_from_smart_variant!(u16, "u16", UInt2);
+impl_try_from_smart_variant!(i32, "i32", Int4, Int);
+impl_try_from_smart_variant!(u32, "u32", UInt4, UInt);
+impl_try_from_smart_variant!(i64, "i64", Int8);
+impl_try_from_smart_variant!(u64, "u64", UInt8);
+impl_try_from_smart_variant!(f32, "f32", Real4);
+impl_try_from_smart_variant!(f64, "f64", Real8);
+impl_try_from_smart_variant!(bool, "bool", Bool);
+
+macro_rules! impl_from_for_smart_variant {
+    ($($source:ty => $variant:ident),+) => {
+        $(
+            impl From<$source> for SmartVariant {
+                #[inline]
+                fn from(x: $source) -> Self {
+                    SmartVariant::$variant(x.into())
+                }
+            }
+        )+
+    };
+}
+
+impl_from_for_smart_variant!(
+    i8 => Int1, u8 => UInt1, i16 => Int2, u16 => UInt2, i32 => Int4, u32 => UInt4, i64 => Int8, u64 => UInt8,
+    f32 => Real4, f64 => Real8, bool => Bool, String => Text, &str => Text,
+    AutoCOMInterface<IDispatch> => IDispatch, AutoCOMInterface<IUnknown> => IUnknown
+);
+
+impl TryFrom<SmartVariant> for String {
+    type Error = ConversionError;
+
+    #[inline]
+    fn try_from(x: SmartVariant) -> Result<Self, Self::Error> {
+        match x {
+            SmartVariant::Text(x) => Ok(x.as_ref().into()),
+            SmartVariant::Text16(ref y) => {
+                String::from_utf16(y).map_err(|_| ConversionError::new(x.summary(), "String"))
+            }
+            x => Err(ConversionError::new(x.summary(), "String")),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Diagnostic records of outgoing COM calls, emitted through the [log] crate when the `log` feature is enabled.
+//!
+//! Object creation, QueryInterface, IDispatch::GetIDsOfNames and IDispatch::Invoke calls made by the crate are
+//! reported under the `rusty_winapi::trace` target as `key=value` records with member names, DISPIDs, argument
+//! VARTYPEs, HRESULT and duration of the call. Succeeded calls are logged at `Trace` level and failed ones at `Debug`
+//! level, so failures can be collected in production without recording every call. QueryInterface failing with
+//! `E_NOINTERFACE` is a normal probe and is logged at `Trace` level.
+//!
+//! Invoke records are followed by the [`debug_dump`] of their DISPPARAMS when [`Config::trace_level`] is
+//! `TraceLevel::Calls`, or `TraceLevel::Errors` and the call failed.
+//!
+//! Without the feature nothing is formatted or emitted.
+//!
+//! # Examples
+//!
+//! With `env_logger` and `RUST_LOG=rusty_winapi::trace=trace`:
+//!
+//! ```text
+//! TRACE rusty_winapi::trace] GetIDsOfNames names=["Range"] dispids=[197] hresult=0x00000000 elapsed=41.2µs
+//! TRACE rusty_winapi::trace] Invoke dispid=197 flags=0x2 args=[VT_BSTR] hresult=0x00000000 elapsed=312.5µs
+//! DEBUG rusty_winapi::trace] GetIDsOfNames names=["Rnage"] dispids=[-1] hresult=0x80020006 elapsed=38.9µs
+//! ```
+//!
+//! [log]: https://docs.rs/log
+//! [`debug_dump`]: ../debug_dump/fn.debug_dump.html
+//! [`Config::trace_level`]: ../config/struct.Config.html#method.trace_level
+
+use std::time::Instant;
+
+use winapi::shared::guiddef::{IID, REFCLSID};
+use winapi::shared::minwindef::WORD;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror;
+use winapi::um::oaidl::{DISPID, DISPPARAMS};
+
+use crate::config::{Config, TraceLevel};
+use crate::debug_dump::{debug_dump, debug_dump_vartype};
+use crate::safe::guid::Guid;
+
+/// Target of the emitted records.
+pub const TARGET: &str = "rusty_winapi::trace";
+
+/// Starts timing of a call.
+#[inline]
+pub(crate) fn start() -> Instant {
+    Instant::now()
+}
+
+/// Reports creation of an instance of `clsid` requesting `iid`.
+pub(crate) fn create_instance(clsid: REFCLSID, iid: &IID, hresult: HRESULT, start: Instant) {
+    emit(!winerror::SUCCEEDED(hresult), || {
+        format!(
+            "CreateInstance clsid={} iid={} hresult=0x{:08X} elapsed={:?}",
+            unsafe { clsid.as_ref() }
+                .map(|x| Guid::from(*x))
+                .unwrap_or_default(),
+            Guid::from(*iid),
+            hresult,
+            start.elapsed()
+        )
+    });
+}
+
+/// Reports QueryInterface for `iid`.
+pub(crate) fn query_interface(iid: &IID, hresult: HRESULT, start: Instant) {
+    emit(
+        !winerror::SUCCEEDED(hresult) && hresult != winerror::E_NOINTERFACE,
+        || {
+            format!(
+                "QueryInterface iid={} hresult=0x{:08X} elapsed={:?}",
+                Guid::from(*iid),
+                hresult,
+                start.elapsed()
+            )
+        },
+    );
+}
+
+/// Reports resolution of `names` into `dispids`.
+pub(crate) fn get_ids_of_names(
+    names: &[&str],
+    dispids: &[DISPID],
+    hresult: HRESULT,
+    start: Instant,
+) {
+    emit(!winerror::SUCCEEDED(hresult), || {
+        format!(
+            "GetIDsOfNames names={:?} dispids={:?} hresult=0x{:08X} elapsed={:?}",
+            names,
+            dispids,
+            hresult,
+            start.elapsed()
+        )
+    });
+}
+
+/// Reports invocation of `dispid`, argument types are listed in caller's order and the arguments are dumped if
+/// requested by the trace level of global [`Config`].
+///
+/// [`Config`]: ../config/struct.Config.html
+///
+/// # Safety
+///
+/// `params` must point to `cArgs` valid VARIANTs and `cNamedArgs` DISPIDs.
+pub(crate) unsafe fn invoke(
+    dispid: DISPID,
+    flags: WORD,
+    params: &DISPPARAMS,
+    hresult: HRESULT,
+    start: Instant,
+) {
+    emit(!winerror::SUCCEEDED(hresult), || {
+        let args: Vec<String> = (0..params.cArgs as usize)
+            .rev()
+            .map(|i| debug_dump_vartype((*params.rgvarg.add(i)).n1.n2().vt))
+            .collect();
+        let mut result = format!(
+            "Invoke dispid={} flags=0x{:X} args=[{}]",
+            dispid,
+            flags,
+            args.join(", ")
+        );
+        if params.cNamedArgs > 0 {
+            let named =
+                std::slice::from_raw_parts(params.rgdispidNamedArgs, params.cNamedArgs as usize);
+            result += &format!(" named={:?}", named);
+        }
+        result += &format!(" hresult=0x{:08X} elapsed={:?}", hresult, start.elapsed());
+        let trace_level = Config::global().trace_level();
+        if trace_level >= TraceLevel::Calls
+            || (trace_level >= TraceLevel::Errors && !winerror::SUCCEEDED(hresult))
+        {
+            result += "\n";
+            result += &debug_dump(params);
+        }
+        result
+    });
+}
+
+#[cfg(feature = "log")]
+fn emit(failed: bool, message: impl FnOnce() -> String) {
+    let level = if failed {
+        log::Level::Debug
+    } else {
+        log::Level::Trace
+    };
+    if log::log_enabled!(target: TARGET, level) {
+        log::log!(target: TARGET, level, "{}", message());
+    }
+}
+
+#[cfg(not(feature = "log"))]
+#[inline(always)]
+fn emit(failed: bool, message: impl FnOnce() -> String) {}
@@ -0,0 +1,607 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Rust bindings generated from type libraries.
+//!
+//! [`generate_bindings`] reads a type library (.tlb, or .dll/.exe/.ocx with an embedded one) and emits Rust source
+//! with:
+//!
+//! * `RIDL!` declarations of vtable interfaces, including vtable parts of dual interfaces;
+//! * `RIDL!` classes of coclasses, so their CLSIDs are available as `<Name as winapi::Class>::uuidof()`;
+//! * type aliases and constants of enums and aliases;
+//! * early-bound wrappers of dispinterfaces, `Auto<Name>` structs with a method per member invoking it by
+//!   [`LazyDispId`], resolved by name or pinned to DISPIDs of the type library, see [`DispIdMode`].
+//!
+//! Records, unions and modules aren't supported, interfaces passing records by value are skipped. Generated code
+//! brings its imports and is meant to be included into a dedicated module, e.g. from a build script.
+//!
+//! Each wrapper method keeps its DISPID in a `static`, resolved with the first object it's called on and then
+//! used for every object of the process. So a wrapper fits objects of one server version with fixed members: for
+//! expando (IDispatchEx) objects, or objects of different server versions in one process, call members by name
+//! with [`SmartIDispatch`] helpers, which resolve them per object.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // build.rs
+//! use rusty_winapi::typelib::{generate_bindings, DispIdMode};
+//!
+//! let path = "C:\\Program Files\\1cv8\\bin\\comcntr.dll";
+//! let bindings = generate_bindings(path, DispIdMode::Lazy).unwrap();
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! std::fs::write(std::path::Path::new(&out_dir).join("comcntr.rs"), bindings).unwrap();
+//! ```
+//!
+//! ```ignore
+//! // lib.rs
+//! mod comcntr {
+//!     include!(concat!(env!("OUT_DIR"), "/comcntr.rs"));
+//! }
+//! ```
+//!
+//! [`generate_bindings`]: fn.generate_bindings.html
+//! [`DispIdMode`]: enum.DispIdMode.html
+//! [`SmartIDispatch`]: ../smart_idispatch/trait.SmartIDispatch.html
+//! [`LazyDispId`]: ../smart_idispatch/struct.LazyDispId.html
+
+use std::collections::HashSet;
+use std::convert::{TryFrom, TryInto};
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::minwindef::{UINT, WORD};
+use winapi::shared::winerror;
+use winapi::shared::wtypes::*;
+use winapi::um::oaidl::{
+    ITypeInfo, ITypeLib, DISPID, DISPID_UNKNOWN, FUNCFLAG_FRESTRICTED, TKIND_ALIAS, TKIND_COCLASS,
+    TKIND_DISPATCH, TKIND_ENUM, TKIND_INTERFACE, TKIND_RECORD, TYPEFLAG_FDUAL, VARFLAG_FREADONLY,
+    VARFLAG_FRESTRICTED, VAR_CONST,
+};
+use winapi::um::oleauto::{LoadTypeLibEx, VariantCopy, REGKIND_NONE};
+
+use crate::auto_com_interface::AutoCOMInterface;
+use crate::error::ComResult;
+use crate::safe::clsid::to_wide;
+use crate::smart_idispatch::MemberKind;
+use crate::smart_itypeinfo::{ElementType, SmartITypeInfo};
+use crate::smart_variant::{AutoVariant, SmartVariant};
+
+/// Loads type library from `path` without registering it.
+pub fn load_type_lib(path: &str) -> ComResult<AutoCOMInterface<ITypeLib>> {
+    let path = to_wide(path);
+    let mut ptlib: *mut ITypeLib = std::ptr::null_mut();
+    let hresult = unsafe { LoadTypeLibEx(path.as_ptr(), REGKIND_NONE, &mut ptlib) };
+    if winerror::SUCCEEDED(hresult) {
+        Ok(ptlib.try_into().unwrap())
+    } else {
+        Err(hresult.into())
+    }
+}
+
+/// How early-bound wrappers get DISPIDs of members.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DispIdMode {
+    /// Resolved by name at first use, DISPID of the type library is the fallback, see [`LazyDispId::new`]. The
+    /// DISPID is resolved once per process, not per object.
+    ///
+    /// [`LazyDispId::new`]: ../smart_idispatch/struct.LazyDispId.html#method.new
+    Lazy,
+    /// DISPIDs of the type library are used as is (strict mode), see [`LazyDispId::pinned`].
+    ///
+    /// [`LazyDispId::pinned`]: ../smart_idispatch/struct.LazyDispId.html#method.pinned
+    Pinned,
+}
+
+/// Generates bindings of the type library at `path`, see [module documentation](index.html).
+pub fn generate_bindings(path: &str, dispids: DispIdMode) -> ComResult<String> {
+    bindings_of(&load_type_lib(path)?, dispids)
+}
+
+/// Generates bindings of a loaded type library, see [`generate_bindings`].
+///
+/// [`generate_bindings`]: fn.generate_bindings.html
+pub fn bindings_of(
+    type_lib: &AutoCOMInterface<ITypeLib>,
+    dispids: DispIdMode,
+) -> ComResult<String> {
+    let type_lib = type_lib.as_inner();
+    let count = unsafe { type_lib.GetTypeInfoCount() };
+    let mut type_infos = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let mut ptinfo: *mut ITypeInfo = std::ptr::null_mut();
+        let hresult = unsafe { type_lib.GetTypeInfo(i, &mut ptinfo) };
+        if winerror::FAILED(hresult) {
+            return Err(hresult.into());
+        }
+        let type_info: AutoCOMInterface<ITypeInfo> = ptinfo.try_into().unwrap();
+        type_infos.push((type_info.documentation(DISPID_UNKNOWN)?.name, type_info));
+    }
+
+    let mut generator = Generator {
+        known: type_infos.iter().map(|(name, _)| name.clone()).collect(),
+        dispids,
+        out: String::from(HEADER),
+    };
+    for (name, type_info) in &type_infos {
+        if !WELL_KNOWN.contains(&name.as_str()) {
+            generator.type_info(name, type_info)?;
+        }
+    }
+
+    Ok(generator.out)
+}
+
+const HEADER: &str = "// Generated by rusty_winapi::typelib, do not edit.
+
+#[allow(unused_imports)]
+use rusty_winapi::auto_com_interface::AutoCOMInterface;
+#[allow(unused_imports)]
+use rusty_winapi::error::ComResult;
+#[allow(unused_imports)]
+use rusty_winapi::smart_idispatch::{LazyDispId, SmartIDispatch};
+#[allow(unused_imports)]
+use rusty_winapi::smart_variant::SmartVariant;
+#[allow(unused_imports)]
+use winapi::ctypes::c_void;
+#[allow(unused_imports)]
+use winapi::shared::ntdef::HRESULT;
+#[allow(unused_imports)]
+use winapi::shared::wtypes::{BSTR, CY, DATE, DECIMAL, VARIANT_BOOL};
+#[allow(unused_imports)]
+use winapi::um::oaidl::{IDispatch, IDispatchVtbl, SAFEARRAY, VARIANT};
+#[allow(unused_imports)]
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+#[allow(unused_imports)]
+use winapi::um::winnt::{LPSTR, LPWSTR};
+#[allow(unused_imports)]
+use winapi::RIDL;
+";
+
+/// Types declared by the generated code's imports, not generated even if the type library describes them.
+const WELL_KNOWN: &[&str] = &["IUnknown", "IDispatch"];
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+    "type", "unsafe", "use", "where", "while", "yield",
+];
+
+struct Generator {
+    /// Names of types of the type library.
+    known: HashSet<String>,
+    dispids: DispIdMode,
+    out: String,
+}
+
+impl Generator {
+    fn type_info(&mut self, name: &str, type_info: &AutoCOMInterface<ITypeInfo>) -> ComResult<()> {
+        let (typekind, flags, guid) = {
+            let attr = type_info.type_attr()?;
+            (attr.typekind, attr.wTypeFlags, attr.guid)
+        };
+
+        match typekind {
+            TKIND_ENUM => self.enumeration(name, type_info),
+            TKIND_ALIAS => {
+                let alias = type_info.type_attr()?.alias();
+                match self.rust_type(type_info, &alias) {
+                    Some(x) => self.line(&format!("pub type {} = {};\n", name, x)),
+                    None => self.unsupported(name, "aliased type"),
+                }
+                Ok(())
+            }
+            TKIND_COCLASS => {
+                self.line(&format!("RIDL! {{{}\nclass {};\n}}\n", uuid(&guid), name));
+                Ok(())
+            }
+            TKIND_INTERFACE => self.interface(name, &guid, type_info),
+            TKIND_DISPATCH => {
+                if flags & TYPEFLAG_FDUAL as WORD != 0 {
+                    let vtable = type_info.impl_type_info(UINT::MAX)?;
+                    self.interface(name, &guid, &vtable)?;
+                }
+                self.wrapper(name, type_info)
+            }
+            _ => {
+                self.unsupported(name, "records, unions and modules");
+                Ok(())
+            }
+        }
+    }
+
+    fn enumeration(
+        &mut self,
+        name: &str,
+        type_info: &AutoCOMInterface<ITypeInfo>,
+    ) -> ComResult<()> {
+        self.line(&format!("pub type {} = i32;", name));
+        for i in 0..type_info.type_attr()?.cVars {
+            let var = type_info.var_desc(i.into())?;
+            if var.varkind != VAR_CONST {
+                continue;
+            }
+
+            let mut value = AutoVariant::new();
+            let hresult = unsafe { VariantCopy(value.as_mut_ptr(), *var.u.lpvarValue()) };
+            if winerror::FAILED(hresult) {
+                return Err(hresult.into());
+            }
+            let value = value.change_type(VT_I4, 0).ok();
+            if let Some(Ok(SmartVariant::Int4(x))) = value.map(SmartVariant::try_from) {
+                let constant = identifier(&type_info.documentation(var.memid)?.name);
+                self.line(&format!("pub const {}: {} = {};", constant, name, x));
+            }
+        }
+        self.line("");
+        Ok(())
+    }
+
+    /// Declares vtable interface, or its vtable part if it's dual.
+    fn interface(
+        &mut self,
+        name: &str,
+        guid: &GUID,
+        type_info: &AutoCOMInterface<ITypeInfo>,
+    ) -> ComResult<()> {
+        let parent = match type_info.impl_type_info(0) {
+            Ok(x) => x.documentation(DISPID_UNKNOWN)?.name,
+            Err(_) => {
+                self.unsupported(name, "interface without a base interface");
+                return Ok(());
+            }
+        };
+
+        let mut methods = String::new();
+        for i in 0..type_info.type_attr()?.cFuncs {
+            let func = type_info.func_desc(i.into())?;
+            let names = type_info.names(func.memid)?;
+            let prefix = match func.kind() {
+                MemberKind::PropertyGet => "get_",
+                MemberKind::PropertyPut => "put_",
+                MemberKind::PropertyPutRef => "putref_",
+                _ => "",
+            };
+
+            methods.push_str(&format!("    fn {}{}(\n", prefix, names[0]));
+            for (j, param) in func.params().iter().enumerate() {
+                let param = match self.rust_type(type_info, param) {
+                    Some(x) => x,
+                    None => {
+                        self.unsupported(name, "parameters passed by value");
+                        return Ok(());
+                    }
+                };
+                methods.push_str(&format!("        {}: {},\n", param_name(&names, j), param));
+            }
+            let result = match self.rust_type(type_info, &func.result()) {
+                Some(x) => x,
+                None => {
+                    self.unsupported(name, "result returned by value");
+                    return Ok(());
+                }
+            };
+            methods.push_str(&format!("    ) -> {},\n", result));
+        }
+
+        self.line(&format!(
+            "RIDL! {{{}\ninterface {}({}Vtbl): {}({}Vtbl) {{\n{}}}}}\n",
+            uuid(guid),
+            name,
+            name,
+            parent,
+            parent,
+            methods
+        ));
+        Ok(())
+    }
+
+    /// Early-bound wrapper of dispinterface members.
+    fn wrapper(&mut self, name: &str, type_info: &AutoCOMInterface<ITypeInfo>) -> ComResult<()> {
+        let wrapper = format!("Auto{}", name.trim_start_matches('_'));
+        let doc = type_info.documentation(DISPID_UNKNOWN)?.doc_string;
+        let (funcs, vars) = {
+            let attr = type_info.type_attr()?;
+            (attr.cFuncs, attr.cVars)
+        };
+
+        let mut methods = String::new();
+        let mut emitted = HashSet::new();
+        for i in 0..funcs {
+            let func = type_info.func_desc(i.into())?;
+            if func.wFuncFlags & FUNCFLAG_FRESTRICTED as WORD != 0 {
+                continue;
+            }
+            let names = type_info.names(func.memid)?;
+            let params = (0..func.cParams.max(0) as usize)
+                .map(|j| param_name(&names, j))
+                .collect::<Vec<_>>();
+            let optional = func.cParamsOpt.max(0) as usize;
+            let member = WrapperMember {
+                name: &names[0],
+                dispid: func.memid,
+                mode: self.dispids,
+                kind: func.kind(),
+                params: &params,
+                optional: optional.min(params.len()),
+            };
+            if emitted.insert(member.fn_name()) {
+                methods.push_str(&member.render());
+            }
+        }
+
+        for i in 0..vars {
+            let var = type_info.var_desc(i.into())?;
+            if var.wVarFlags & VARFLAG_FRESTRICTED as WORD != 0 {
+                continue;
+            }
+            let var_name = type_info.documentation(var.memid)?.name;
+            let mut kinds = vec![MemberKind::PropertyGet];
+            if var.wVarFlags & VARFLAG_FREADONLY as WORD == 0 {
+                kinds.push(MemberKind::PropertyPut);
+            }
+            for kind in kinds {
+                let params = match kind {
+                    MemberKind::PropertyPut => vec![String::from("value")],
+                    _ => Vec::new(),
+                };
+                let member = WrapperMember {
+                    name: &var_name,
+                    dispid: var.memid,
+                    mode: self.dispids,
+                    kind,
+                    params: &params,
+                    optional: 0,
+                };
+                if emitted.insert(member.fn_name()) {
+                    methods.push_str(&member.render());
+                }
+            }
+        }
+
+        if !doc.is_empty() {
+            self.line(&format!("/// {}", doc));
+        }
+        self.line(&format!(
+            "pub struct {}(pub AutoCOMInterface<IDispatch>);\n\n#[allow(non_snake_case)]\nimpl {} {{\n{}}}\n",
+            wrapper, wrapper, methods
+        ));
+        Ok(())
+    }
+
+    /// Rust type of an element, `None` if it can't be passed as is (records and unions by value).
+    fn rust_type(
+        &self,
+        type_info: &AutoCOMInterface<ITypeInfo>,
+        x: &ElementType,
+    ) -> Option<String> {
+        match x {
+            ElementType::Ptr(x) => Some(format!(
+                "*mut {}",
+                self.rust_type(type_info, x)
+                    .unwrap_or_else(|| String::from("c_void"))
+            )),
+            ElementType::SafeArray(_) => Some(String::from("*mut SAFEARRAY")),
+            ElementType::CArray { element, .. } => {
+                self.rust_type(type_info, &ElementType::Ptr(element.clone()))
+            }
+            ElementType::UserDefined(href) => {
+                let referenced = type_info.ref_type_info(*href).ok()?;
+                let name = referenced.documentation(DISPID_UNKNOWN).ok()?.name;
+                let (typekind, alias) = {
+                    let attr = referenced.type_attr().ok()?;
+                    (attr.typekind, attr.alias())
+                };
+                match typekind {
+                    TKIND_ENUM => Some(String::from("i32")),
+                    TKIND_ALIAS => self.rust_type(&referenced, &alias),
+                    TKIND_INTERFACE | TKIND_DISPATCH | TKIND_COCLASS
+                        if WELL_KNOWN.contains(&name.as_str()) || self.known.contains(&name) =>
+                    {
+                        Some(name)
+                    }
+                    TKIND_INTERFACE | TKIND_DISPATCH | TKIND_COCLASS => {
+                        Some(String::from("IUnknown"))
+                    }
+                    _ => None,
+                }
+            }
+            ElementType::Base(vt) => base_type(*vt).map(String::from),
+        }
+    }
+
+    fn unsupported(&mut self, name: &str, what: &str) {
+        self.line(&format!(
+            "// {} is skipped: {} aren't supported.\n",
+            name, what
+        ));
+    }
+
+    fn line(&mut self, x: &str) {
+        self.out.push_str(x);
+        self.out.push('\n');
+    }
+}
+
+/// Member of an early-bound wrapper.
+struct WrapperMember<'a> {
+    name: &'a str,
+    dispid: DISPID,
+    mode: DispIdMode,
+    kind: MemberKind,
+    params: &'a [String],
+    /// Number of optional parameters at the end of `params`.
+    optional: usize,
+}
+
+impl WrapperMember<'_> {
+    fn fn_name(&self) -> String {
+        match self.kind {
+            MemberKind::PropertyPut => format!("set_{}", self.name),
+            MemberKind::PropertyPutRef => format!("set_ref_{}", self.name),
+            _ => identifier(self.name),
+        }
+    }
+
+    fn render(&self) -> String {
+        let flags = match self.kind {
+            MemberKind::PropertyGet => "DISPATCH_PROPERTYGET",
+            MemberKind::PropertyPut => "DISPATCH_PROPERTYPUT",
+            MemberKind::PropertyPutRef => "DISPATCH_PROPERTYPUTREF",
+            _ => "DISPATCH_METHOD",
+        };
+        let required = self.params.len() - self.optional;
+        let signature: Vec<String> = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                if i < required {
+                    format!(", {}: impl Into<SmartVariant>", x)
+                } else {
+                    format!(", {}: Option<SmartVariant>", x)
+                }
+            })
+            .collect();
+        let args: Vec<String> = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                if i < required {
+                    format!("{}.into()", x)
+                } else {
+                    // Omitted optional argument, as VBA passes it.
+                    format!(
+                        "{}.unwrap_or(SmartVariant::ErrorCode(::winapi::shared::winerror::DISP_E_PARAMNOTFOUND))",
+                        x
+                    )
+                }
+            })
+            .collect();
+
+        format!(
+            "    pub fn {}(&mut self{}) -> ComResult<SmartVariant> {{
+        static DISPID: LazyDispId = LazyDispId::{}({:?}, {});
+        let dispid = DISPID.resolve(&self.0);
+        let lcid = self.0.lcid();
+        self.0.invoke(dispid, lcid, ::winapi::um::oleauto::{}, &[{}])
+    }}
+
+",
+            self.fn_name(),
+            signature.concat(),
+            match self.mode {
+                DispIdMode::Lazy => "new",
+                DispIdMode::Pinned => "pinned",
+            },
+            self.name,
+            self.dispid,
+            flags,
+            args.join(", ")
+        )
+    }
+}
+
+/// Rust type of a VARTYPE which is passed as is.
+fn base_type(vt: VARTYPE) -> Option<&'static str> {
+    Some(match vt as u32 {
+        VT_I1 => "i8",
+        VT_UI1 => "u8",
+        VT_I2 => "i16",
+        VT_UI2 => "u16",
+        VT_I4 | VT_INT | VT_ERROR => "i32",
+        VT_UI4 | VT_UINT => "u32",
+        VT_I8 => "i64",
+        VT_UI8 => "u64",
+        VT_R4 => "f32",
+        VT_R8 => "f64",
+        VT_CY => "CY",
+        VT_DATE => "DATE",
+        VT_BSTR => "BSTR",
+        VT_BOOL => "VARIANT_BOOL",
+        VT_VARIANT => "VARIANT",
+        VT_DECIMAL => "DECIMAL",
+        VT_DISPATCH => "*mut IDispatch",
+        VT_UNKNOWN => "*mut IUnknown",
+        VT_HRESULT => "HRESULT",
+        VT_LPSTR => "LPSTR",
+        VT_LPWSTR => "LPWSTR",
+        VT_VOID => "c_void",
+        _ => return None,
+    })
+}
+
+/// Name of parameter `index` given names of the member and its parameters, a property put value has no name.
+fn param_name(names: &[String], index: usize) -> String {
+    match names.get(index + 1) {
+        Some(x) => identifier(x),
+        None if index + 1 == names.len() => String::from("value"),
+        None => format!("arg{}", index),
+    }
+}
+
+/// Name made a valid Rust identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `#[uuid(...)]` attribute of RIDL!.
+fn uuid(x: &GUID) -> String {
+    format!(
+        "#[uuid(0x{:08x}, 0x{:04x}, 0x{:04x}, {})]",
+        x.Data1,
+        x.Data2,
+        x.Data3,
+        x.Data4
+            .iter()
+            .map(|x| format!("0x{:02x}", x))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_bindings_stdole() {
+        let bindings = generate_bindings("stdole2.tlb", DispIdMode::Lazy).unwrap();
+
+        assert!(bindings.contains("class StdFont;"));
+        assert!(bindings.contains("pub type OLE_TRISTATE = i32;"));
+        assert!(bindings.contains("pub const Checked: OLE_TRISTATE = 1;"));
+        assert!(bindings.contains("pub struct AutoFont(pub AutoCOMInterface<IDispatch>);"));
+        assert!(bindings.contains("LazyDispId::new(\"Name\", 0)"));
+        assert!(!bindings.contains("interface IDispatch("));
+
+        let bindings = generate_bindings("stdole2.tlb", DispIdMode::Pinned).unwrap();
+        assert!(bindings.contains("LazyDispId::pinned(\"Name\", 0)"));
+        assert!(!bindings.contains("LazyDispId::new("));
+    }
+
+    #[test]
+    fn test_uuid() {
+        let guid = GUID {
+            Data1: 0x181E893D,
+            Data2: 0x73A4,
+            Data3: 0x4722,
+            Data4: [0xB6, 0x1D, 0xD6, 0x04, 0xB3, 0xD6, 0x7D, 0x47],
+        };
+        assert_eq!(
+            "#[uuid(0x181e893d, 0x73a4, 0x4722, 0xb6, 0x1d, 0xd6, 0x04, 0xb3, 0xd6, 0x7d, 0x47)]",
+            uuid(&guid)
+        );
+    }
+
+    #[test]
+    fn test_param_name() {
+        let names = vec![String::from("Item"), String::from("type")];
+        assert_eq!("type_", param_name(&names, 0));
+        assert_eq!("value", param_name(&names, 1));
+        assert_eq!("arg2", param_name(&names, 2));
+    }
+}
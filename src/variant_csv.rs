@@ -0,0 +1,255 @@
+#![allow(non_camel_case_types, non_snake_case, unused)]
+
+//! Conversion between 2D VARIANT arrays (or rows of SmartVariant values) and CSV, behind `csv` feature.
+//!
+//! Non-text values are formatted with OLE Automation rules (VariantChangeTypeEx to VT_BSTR) using LCID from
+//! global [`Config`], so numbers and dates look like Excel/1C would show them in that locale.
+//!
+//! # Examples
+//!
+//! ```
+//! use rusty_winapi::smart_variant::SmartVariant;
+//! use rusty_winapi::variant_csv::write_rows;
+//!
+//! let rows = vec![
+//!     vec![SmartVariant::Text("Name".into()), SmartVariant::Text("Count".into())],
+//!     vec![SmartVariant::Text("Apples, green".into()), SmartVariant::Int4(42)],
+//! ];
+//!
+//! let mut writer = csv::Writer::from_writer(vec![]);
+//! write_rows(&mut writer, rows).unwrap();
+//! let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+//! assert_eq!("Name,Count\n\"Apples, green\",42\n", csv);
+//! ```
+//!
+//! [`Config`]: ../config/struct.Config.html
+
+use std::convert::TryFrom;
+use std::io;
+
+use winapi::shared::minwindef::UINT;
+use winapi::shared::ntdef::{HRESULT, LONG};
+use winapi::shared::winerror;
+use winapi::shared::wtypes::{VARTYPE, VT_BSTR, VT_VARIANT};
+use winapi::um::oaidl::{LPSAFEARRAY, SAFEARRAYBOUND, VARIANT};
+use winapi::um::oleauto::{
+    SafeArrayDestroy, SafeArrayGetLBound, SafeArrayGetUBound, VariantChangeTypeEx,
+};
+
+use crate::config::Config;
+use crate::ffi::{SafeArrayCreate, SafeArrayGetDim, SafeArrayGetElement, SafeArrayPutElement};
+use crate::smart_variant::{AutoVariant, SmartVariant};
+
+/// Formats a value as CSV field text.
+///
+/// `Empty` becomes an empty field, strings are kept as is, other values are converted to text with
+/// VariantChangeTypeEx using LCID from global [`Config`].
+///
+/// [`Config`]: ../config/struct.Config.html
+pub fn format_field(value: &SmartVariant) -> Result<String, HRESULT> {
+    match value {
+        SmartVariant::Empty => Ok(String::new()),
+        SmartVariant::Text(x) => Ok(x.as_ref().into()),
+        SmartVariant::Text16(x) => Ok(String::from_utf16_lossy(x)),
+        x => {
+            let src = AutoVariant::try_from(x.clone()).map_err(|_| winerror::E_OUTOFMEMORY)?;
+            let mut dst = AutoVariant::new();
+            let hresult = unsafe {
+                VariantChangeTypeEx(
+                    dst.as_mut_ptr(),
+                    src.as_ptr(),
+                    Config::global().lcid(),
+                    0,
+                    VT_BSTR as VARTYPE,
+                )
+            };
+
+            if winerror::SUCCEEDED(hresult) {
+                match SmartVariant::try_from(dst) {
+                    Ok(SmartVariant::Text(x)) => Ok(x.as_ref().into()),
+                    Ok(SmartVariant::Text16(x)) => Ok(String::from_utf16_lossy(&x)),
+                    _ => Err(winerror::DISP_E_TYPEMISMATCH),
+                }
+            } else {
+                Err(hresult)
+            }
+        }
+    }
+}
+
+/// Writes rows of values as CSV records.
+///
+/// Values which can't be formatted as text produce an `io::Error` with the failed HRESULT.
+pub fn write_rows<W, I, R>(writer: &mut csv::Writer<W>, rows: I) -> csv::Result<()>
+where
+    W: io::Write,
+    I: IntoIterator<Item = R>,
+    R: IntoIterator<Item = SmartVariant>,
+{
+    for row in rows {
+        let mut record = Vec::new();
+        for value in row {
+            record.push(format_field(&value).map_err(|x| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("can't format {} as CSV field: 0x{:08X}", value.summary(), x),
+                )
+            })?);
+        }
+        writer.write_record(&record)?;
+    }
+
+    Ok(())
+}
+
+/// Reads all CSV records as rows of `SmartVariant::Text` values.
+pub fn read_rows<R: io::Read>(reader: &mut csv::Reader<R>) -> csv::Result<Vec<Vec<SmartVariant>>> {
+    let mut result = Vec::new();
+    for record in reader.records() {
+        result.push(
+            record?
+                .iter()
+                .map(|x| SmartVariant::Text(x.into()))
+                .collect(),
+        );
+    }
+
+    Ok(result)
+}
+
+/// Reads a 2D SAFEARRAY of VARIANTs (e.g. Excel `Range.Value`) into rows of values.
+///
+/// # Safety
+///
+/// `psa` must be a valid SAFEARRAY of VT_VARIANT elements.
+///
+/// # Errors
+///
+/// * If array isn't two-dimensional, returns `DISP_E_BADINDEX`.
+/// * If an element has a type `SmartVariant` can't hold (e.g. VT_NULL), returns `DISP_E_TYPEMISMATCH`.
+/// * Otherwise returns HRESULT of a failed SafeArray call.
+pub unsafe fn safearray_to_rows(psa: LPSAFEARRAY) -> Result<Vec<Vec<SmartVariant>>, HRESULT> {
+    if psa.is_null() || SafeArrayGetDim(psa) != 2 {
+        return Err(winerror::DISP_E_BADINDEX);
+    }
+
+    let (row_lbound, row_ubound) = bounds(psa, 1)?;
+    let (col_lbound, col_ubound) = bounds(psa, 2)?;
+
+    let mut result = Vec::new();
+    for row in row_lbound..=row_ubound {
+        let mut values = Vec::new();
+        for col in col_lbound..=col_ubound {
+            let indices = [row, col];
+            let mut value = VARIANT::default();
+            let hresult = SafeArrayGetElement(psa, indices.as_ptr(), &mut value);
+            if !winerror::SUCCEEDED(hresult) {
+                return Err(hresult);
+            }
+            values.push(SmartVariant::try_from(value).map_err(|_| winerror::DISP_E_TYPEMISMATCH)?);
+        }
+        result.push(values);
+    }
+
+    Ok(result)
+}
+
+/// Creates a new 2D SAFEARRAY of VARIANTs (1-based like Excel ranges) from rows of values.
+///
+/// Short rows are padded with `Empty`. Caller is responsible to destroy the array with `SafeArrayDestroy`, a call
+/// with it inside `SmartVariant::Array` only borrows it.
+pub fn rows_to_safearray(rows: &[Vec<SmartVariant>]) -> Result<LPSAFEARRAY, HRESULT> {
+    let cols = rows.iter().map(|x| x.len()).max().unwrap_or(0);
+    let mut bounds = [
+        SAFEARRAYBOUND {
+            cElements: rows.len() as u32,
+            lLbound: 1,
+        },
+        SAFEARRAYBOUND {
+            cElements: cols as u32,
+            lLbound: 1,
+        },
+    ];
+
+    unsafe {
+        let psa = SafeArrayCreate(VT_VARIANT as VARTYPE, 2, bounds.as_mut_ptr());
+        if psa.is_null() {
+            return Err(winerror::E_OUTOFMEMORY);
+        }
+
+        for (row, values) in rows.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                let indices = [row as LONG + 1, col as LONG + 1];
+                let value = match AutoVariant::try_from(value.clone()) {
+                    Ok(x) => x,
+                    Err(_) => {
+                        SafeArrayDestroy(psa);
+                        return Err(winerror::E_OUTOFMEMORY);
+                    }
+                };
+                // SafeArrayPutElement copies the value.
+                let hresult = SafeArrayPutElement(psa, indices.as_ptr(), value.as_ptr());
+                if !winerror::SUCCEEDED(hresult) {
+                    SafeArrayDestroy(psa);
+                    return Err(hresult);
+                }
+            }
+        }
+
+        Ok(psa)
+    }
+}
+
+unsafe fn bounds(psa: LPSAFEARRAY, dim: UINT) -> Result<(LONG, LONG), HRESULT> {
+    let mut lbound: LONG = 0;
+    let mut ubound: LONG = 0;
+
+    let hresult = SafeArrayGetLBound(psa, dim, &mut lbound);
+    if !winerror::SUCCEEDED(hresult) {
+        return Err(hresult);
+    }
+    let hresult = SafeArrayGetUBound(psa, dim, &mut ubound);
+    if !winerror::SUCCEEDED(hresult) {
+        return Err(hresult);
+    }
+
+    Ok((lbound, ubound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_round_trip() {
+        let rows = vec![
+            vec![
+                SmartVariant::Text("a".into()),
+                SmartVariant::Text("b, c".into()),
+            ],
+            vec![SmartVariant::Text("\"d\"".into())],
+        ];
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        write_rows(&mut writer, rows.clone()).unwrap();
+        let data = writer.into_inner().unwrap();
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(data.as_slice());
+        assert_eq!(rows, read_rows(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_safearray_round_trip() {
+        let rows = vec![
+            vec![SmartVariant::Text("a".into()), SmartVariant::Int4(1)],
+            vec![SmartVariant::Text("b".into()), SmartVariant::Int4(2)],
+        ];
+
+        let psa = rows_to_safearray(&rows).unwrap();
+        assert_eq!(rows, unsafe { safearray_to_rows(psa) }.unwrap());
+        unsafe { SafeArrayDestroy(psa) };
+    }
+}